@@ -63,3 +63,7 @@ pub fn get_data_path() -> PathBuf {
 
     dir
 }
+
+pub fn get_profile_cache_path() -> PathBuf {
+    get_data_path().join("profiles")
+}
@@ -4,13 +4,11 @@ use std::thread;
 use std::time::Duration;
 
 use chrono::Utc;
-use plume_core::{
-    AnisetteConfiguration, CertificateIdentity, MobileProvision, developer::DeveloperSession,
-};
-use plume_store::{AccountStore, RefreshDevice};
+use plume_core::{CertificateIdentity, MobileProvision, developer::DeveloperSession};
+use plume_store::{AccountStore, ProfileCache, RefreshDevice};
 use plume_utils::{Bundle, Device, Signer, SignerMode, SignerOptions};
 
-use crate::defaults::get_data_path;
+use crate::defaults::{get_data_path, get_profile_cache_path};
 
 pub type ConnectedDevices = Arc<Mutex<HashMap<String, Device>>>;
 
@@ -79,6 +77,19 @@ impl RefreshDaemon {
         let now = Utc::now();
 
         for (udid, refresh_device) in store.refreshes() {
+            // retrying every check would only fail again until the user signs in
+            if store
+                .get_account(&refresh_device.account)
+                .is_some_and(|a| a.needs_relogin())
+            {
+                log::warn!(
+                    "Skipping refreshes for {}, {} has to sign in again",
+                    udid,
+                    refresh_device.account
+                );
+                continue;
+            }
+
             for app in &refresh_device.apps {
                 if app.scheduled_refresh <= now {
                     // We check for active tasks here to prevent the background loop
@@ -181,41 +192,39 @@ impl RefreshDaemon {
             .get_account(&refresh_device.account)
             .ok_or_else(|| format!("Account {} not found", refresh_device.account))?;
 
-        let session = DeveloperSession::new(
-            account.adsid().clone(),
-            account.xcode_gs_token().clone(),
-            AnisetteConfiguration::default().set_configuration_path(get_data_path()),
-        )
-        .await
-        .map_err(|e| format!("Failed to create session: {}", e))?;
+        let session = crate::subscriptions::restore_session(account)
+            .await
+            .map_err(|e| format!("Failed to create session: {}", e))?;
 
         let teams_response = session
             .qh_list_teams()
             .await
             .map_err(|e| format!("Failed to list teams: {}", e))?;
 
-        if teams_response.teams.is_empty() {
-            return Err("No teams available for this account".to_string());
-        }
-
-        let team_id = if account.team_id().is_empty() {
-            &teams_response.teams[0].team_id
-        } else {
-            account.team_id()
-        };
-
-        let identity_is_new = {
-            let identity = CertificateIdentity::new_with_session(
-                &session,
-                get_data_path(),
-                None,
-                team_id,
-                false,
-            )
-            .await
-            .map_err(|e| format!("Failed to create identity: {}", e))?;
-            identity.new
-        };
+        let team_id = &crate::subscriptions::resolve_team_id(
+            &teams_response.teams,
+            app.team_id.as_deref(),
+            account,
+        )?;
+        let free_team = teams_response
+            .teams
+            .iter()
+            .any(|t| t.team_id == *team_id && t.is_free());
+
+        // the same identity signs below, so a new one also clears the team's cached profiles
+        let keys = plume_store::key_store(&get_data_path())
+            .map_err(|e| format!("Failed to open key store: {}", e))?;
+        let identity = CertificateIdentity::new_with_session(
+            &session,
+            &keys,
+            None,
+            team_id,
+            false,
+            !account.never_revoke(),
+        )
+        .await
+        .map_err(|e| format!("Failed to create identity: {}", e))?;
+        let identity_is_new = identity.new;
 
         let is_installed = if let Some(bundle_id) = app.bundle_id.as_deref() {
             device
@@ -234,13 +243,13 @@ impl RefreshDaemon {
         let needs_reinstall = device.is_mac || identity_is_new || !is_installed;
 
         if needs_reinstall {
-            self.resign_and_reinstall(app, device, &session, team_id)
+            self.resign_and_reinstall(app, device, &session, identity, team_id, free_team)
                 .await?;
         } else {
             log::info!(
                 "Certificate exists and app is installed, updating provisioning profiles..."
             );
-            self.update_provisioning_profiles(app, device, &session, team_id)
+            self.update_provisioning_profiles(app, device, &session, identity, team_id, free_team)
                 .await?;
         }
 
@@ -267,7 +276,9 @@ impl RefreshDaemon {
         app: &plume_store::RefreshApp,
         device: &Device,
         session: &DeveloperSession,
+        signing_identity: CertificateIdentity,
         team_id: &str,
+        free_team: bool,
     ) -> Result<(), String> {
         let team_id_string = team_id.to_string();
         session
@@ -280,21 +291,16 @@ impl RefreshDaemon {
 
         let options = SignerOptions {
             mode: SignerMode::Pem,
+            team_id: Some(team_id.to_string()),
             ..Default::default()
         };
 
-        let team_id_string = team_id.to_string();
-        let signing_identity = CertificateIdentity::new_with_session(
-            session,
-            get_data_path(),
-            None,
-            &team_id_string,
-            false,
-        )
-        .await
-        .map_err(|e| format!("Failed to create signing identity: {}", e))?;
-
-        let mut signer = Signer::new(Some(signing_identity), options);
+        let mut signer = Signer::new(Some(signing_identity), options)
+            .with_profile_cache(
+                ProfileCache::new(get_profile_cache_path()),
+                (!device.is_mac).then(|| device.udid.clone()),
+            )
+            .with_free_team(free_team);
 
         signer
             .register_bundle(&bundle, session, &team_id.to_string(), true)
@@ -325,17 +331,25 @@ impl RefreshDaemon {
         app: &plume_store::RefreshApp,
         device: &Device,
         session: &DeveloperSession,
+        signing_identity: CertificateIdentity,
         team_id: &str,
+        free_team: bool,
     ) -> Result<(), String> {
         let bundle =
             Bundle::new(app.path.clone()).map_err(|e| format!("Failed to create bundle: {}", e))?;
 
         let options = SignerOptions {
             mode: SignerMode::Pem,
+            team_id: Some(team_id.to_string()),
             ..Default::default()
         };
 
-        let mut signer = Signer::new(None, options);
+        let mut signer = Signer::new(Some(signing_identity), options)
+            .with_profile_cache(
+                ProfileCache::new(get_profile_cache_path()),
+                (!device.is_mac).then(|| device.udid.clone()),
+            )
+            .with_free_team(free_team);
 
         signer
             .register_bundle(&bundle, session, &team_id.to_string(), true)
@@ -373,15 +387,19 @@ impl RefreshDaemon {
             .unwrap_or_else(|_| Utc::now() + chrono::Duration::days(6));
         let scheduled_refresh = scheduled_refresh - chrono::Duration::days(1);
 
+        // only touch this app, the device entry may have changed since the store was loaded
         let mut store = store.clone();
-        let mut updated_device = refresh_device.clone();
-
-        if let Some(existing_app) = updated_device.apps.iter_mut().find(|a| a.path == app.path) {
-            existing_app.scheduled_refresh = scheduled_refresh;
-        }
-
         store
-            .add_or_update_refresh_device_sync(updated_device)
+            .update_sync(|s| {
+                if let Some(existing_app) = s
+                    .refreshes_mut()
+                    .get_mut(&refresh_device.udid)
+                    .and_then(|d| d.apps.iter_mut().find(|a| a.path == app.path))
+                {
+                    existing_app.scheduled_refresh = scheduled_refresh;
+                }
+                Ok(())
+            })
             .map_err(|e| format!("Failed to update refresh schedule: {}", e))?;
 
         log::info!("Next refresh scheduled for: {}", scheduled_refresh);
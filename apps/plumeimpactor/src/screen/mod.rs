@@ -134,7 +134,23 @@ impl Impactor {
 
     fn init_account_store_sync() -> AccountStore {
         let path = defaults::get_data_path().join("accounts.json");
-        AccountStore::load_sync(&Some(path)).unwrap_or_default()
+        match AccountStore::load_sync(&Some(path)) {
+            Ok(store) => store,
+            // e.g. no keyring and no vault passphrase, nothing is saved until that's sorted out
+            Err(e) => {
+                log::error!("Failed to load accounts: {e}");
+                let description = e.to_string();
+                std::thread::spawn(move || {
+                    rfd::MessageDialog::new()
+                        .set_level(rfd::MessageLevel::Error)
+                        .set_title("Accounts Unavailable")
+                        .set_description(&description)
+                        .set_buttons(rfd::MessageButtons::Ok)
+                        .show();
+                });
+                AccountStore::default()
+            }
+        }
     }
 
     pub fn update(&mut self, message: Message) -> Task<Message> {
@@ -361,6 +377,12 @@ impl Impactor {
                         self.current_screen = ImpactorScreen::Installer(
                             package::PackageScreen::new(Some(package), options),
                         );
+
+                        return Task::batch(vec![
+                            task,
+                            self.app_id_quota_task(None),
+                            self.installer_teams_task(),
+                        ]);
                     } else if let general::Message::NavigateToUtilities = msg {
                         self.current_screen = ImpactorScreen::Utilities(
                             utilties::UtilitiesScreen::new(self.selected_device.clone()),
@@ -391,6 +413,12 @@ impl Impactor {
                             self.login_windows.insert(id, login_window);
                             task.map(move |msg| Message::LoginWindowMessage(id, msg))
                         }
+                        settings::Message::Relogin(email) => {
+                            let (login_window, task) = login_window::LoginWindow::with_email(email);
+                            let id = login_window.window_id().unwrap();
+                            self.login_windows.insert(id, login_window);
+                            task.map(move |msg| Message::LoginWindowMessage(id, msg))
+                        }
                         settings::Message::SelectAccount(index) => {
                             if let Some(store) = &mut self.account_store {
                                 let mut emails: Vec<_> = store.accounts().keys().cloned().collect();
@@ -436,6 +464,16 @@ impl Impactor {
                             }
                             Task::none()
                         }
+                        settings::Message::ToggleNeverRevoke(ref email, enabled) => {
+                            if let Some(store) = &mut self.account_store {
+                                if let Err(e) =
+                                    store.update_account_never_revoke_sync(email, enabled)
+                                {
+                                    log::error!("Failed to update account: {:?}", e);
+                                }
+                            }
+                            Task::none()
+                        }
                         settings::Message::FetchTeams(ref email) => {
                             if let Some(account_store) = &self.account_store {
                                 if let Some(account) = account_store.accounts().get(email) {
@@ -474,6 +512,50 @@ impl Impactor {
                             }
                             screen.update(msg).map(Message::SettingsScreen)
                         }
+                        settings::Message::FetchAccountInfo(ref email) => {
+                            let Some(account) = self
+                                .account_store
+                                .as_ref()
+                                .and_then(|s| s.accounts().get(email))
+                                .cloned()
+                            else {
+                                return Task::none();
+                            };
+                            let email_clone = email.clone();
+
+                            let task = screen.update(msg).map(Message::SettingsScreen);
+                            return Task::batch(vec![
+                                task,
+                                Task::perform(
+                                    async move {
+                                        let (tx, rx) = std::sync::mpsc::channel();
+
+                                        std::thread::spawn(move || {
+                                            let rt = tokio::runtime::Runtime::new().unwrap();
+                                            let result = rt.block_on(async move {
+                                                crate::subscriptions::fetch_account_info(&account)
+                                                    .await
+                                                    .map_err(|e| {
+                                                        log::error!(
+                                                            "Failed to fetch account info: {}",
+                                                            e
+                                                        );
+                                                    })
+                                                    .ok()
+                                            });
+                                            let _ = tx.send(result);
+                                        });
+
+                                        rx.recv().ok().flatten()
+                                    },
+                                    move |info| {
+                                        Message::SettingsScreen(
+                                            settings::Message::AccountInfoLoaded(email_clone, info),
+                                        )
+                                    },
+                                ),
+                            ]);
+                        }
                         settings::Message::SelectTeam(ref email, ref team_id) => {
                             if let Some(store) = &mut self.account_store {
                                 if let Err(e) =
@@ -486,6 +568,102 @@ impl Impactor {
                             }
                             screen.update(msg).map(Message::SettingsScreen)
                         }
+                        settings::Message::AddAnisetteProvider(ref provider) => {
+                            if let Some(store) = &mut self.account_store {
+                                let mut providers = store.anisette_providers().to_vec();
+                                providers.retain(|p| p != provider);
+                                providers.push(provider.clone());
+                                if let Err(e) = store.set_anisette_providers_sync(providers) {
+                                    log::error!("Failed to save anisette providers: {:?}", e);
+                                }
+                            }
+                            screen.update(msg).map(Message::SettingsScreen)
+                        }
+                        settings::Message::RemoveAnisetteProvider(ref provider) => {
+                            if let Some(store) = &mut self.account_store {
+                                let mut providers = store.anisette_providers().to_vec();
+                                providers.retain(|p| p != provider);
+                                if let Err(e) = store.set_anisette_providers_sync(providers) {
+                                    log::error!("Failed to save anisette providers: {:?}", e);
+                                }
+                            }
+                            screen.update(msg).map(Message::SettingsScreen)
+                        }
+                        settings::Message::CheckAnisetteProviders => {
+                            let providers = self
+                                .account_store
+                                .as_ref()
+                                .map(|s| s.anisette_providers().to_vec())
+                                .unwrap_or_default();
+
+                            let check = Task::perform(
+                                async move {
+                                    let (tx, rx) = std::sync::mpsc::channel();
+
+                                    std::thread::spawn(move || {
+                                        let rt = tokio::runtime::Runtime::new().unwrap();
+                                        let results = rt.block_on(
+                                            crate::subscriptions::check_anisette_providers(
+                                                providers,
+                                            ),
+                                        );
+                                        let _ = tx.send(results);
+                                    });
+
+                                    rx.recv().unwrap_or_default()
+                                },
+                                |results| {
+                                    Message::SettingsScreen(settings::Message::AnisetteChecked(
+                                        results,
+                                    ))
+                                },
+                            );
+
+                            Task::batch([check, screen.update(msg).map(Message::SettingsScreen)])
+                        }
+                        settings::Message::CreateBackup | settings::Message::RestoreBackup => {
+                            let passphrase = screen.backup_passphrase().to_string();
+                            let restore = matches!(msg, settings::Message::RestoreBackup);
+
+                            let backup = Task::perform(
+                                async move {
+                                    let (tx, rx) = std::sync::mpsc::channel();
+
+                                    std::thread::spawn(move || {
+                                        let rt = tokio::runtime::Runtime::new().unwrap();
+                                        let result = rt.block_on(async move {
+                                            if restore {
+                                                crate::subscriptions::restore_backup(passphrase)
+                                                    .await
+                                            } else {
+                                                crate::subscriptions::create_backup(passphrase)
+                                                    .await
+                                            }
+                                        });
+                                        let _ = tx.send(result);
+                                    });
+
+                                    rx.recv()
+                                        .unwrap_or_else(|_| Err("Backup was interrupted".into()))
+                                },
+                                |result| {
+                                    Message::SettingsScreen(settings::Message::BackupFinished(
+                                        result,
+                                    ))
+                                },
+                            );
+
+                            Task::batch([backup, screen.update(msg).map(Message::SettingsScreen)])
+                        }
+                        settings::Message::BackupFinished(Ok(Some(_))) => {
+                            // a restore brings in accounts and refresh entries
+                            let store = Self::init_account_store_sync();
+                            if let Some(tray) = &mut self.tray {
+                                tray.update_refresh_apps(&store);
+                            }
+                            self.account_store = Some(store);
+                            screen.update(msg).map(Message::SettingsScreen)
+                        }
                         _ => screen.update(msg).map(Message::SettingsScreen),
                     }
                 } else {
@@ -521,6 +699,11 @@ impl Impactor {
 
                             self.start_installation_task()
                         }
+                        package::Message::SelectTeam(ref team) => {
+                            let team_id = team.id.clone();
+                            let task = screen.update(msg).map(Message::InstallerScreen);
+                            Task::batch(vec![task, self.app_id_quota_task(Some(team_id))])
+                        }
                         _ => screen.update(msg).map(Message::InstallerScreen),
                     }
                 } else {
@@ -616,7 +799,7 @@ impl Impactor {
             }
             Message::ForgetApp { udid, app_path } => {
                 if let Some(store) = &mut self.account_store {
-                    if let Some(mut refresh_device) = store.get_refresh_device(&udid).cloned() {
+                    if let Some(refresh_device) = store.get_refresh_device(&udid).cloned() {
                         if let Some(app) = refresh_device
                             .apps
                             .iter()
@@ -638,15 +821,19 @@ impl Impactor {
                             });
                         }
 
-                        refresh_device
-                            .apps
-                            .retain(|a| a.path.to_string_lossy() != app_path);
+                        let _ = store.update_sync(|s| {
+                            let refreshes = s.refreshes_mut();
+                            if let Some(refresh_device) = refreshes.get_mut(&udid) {
+                                refresh_device
+                                    .apps
+                                    .retain(|a| a.path.to_string_lossy() != app_path);
 
-                        if refresh_device.apps.is_empty() {
-                            let _ = store.remove_refresh_device_sync(&udid);
-                        } else {
-                            let _ = store.add_or_update_refresh_device_sync(refresh_device);
-                        }
+                                if refresh_device.apps.is_empty() {
+                                    refreshes.remove(&udid);
+                                }
+                            }
+                            Ok(())
+                        });
 
                         self.account_store = Some(Self::init_account_store_sync());
                     }
@@ -808,6 +995,71 @@ impl Impactor {
         }
     }
 
+    /// App ID usage of `team_id`, or of the selected account's stored team.
+    fn app_id_quota_task(&self, team_id: Option<String>) -> Task<Message> {
+        let Some(account) = self
+            .account_store
+            .as_ref()
+            .and_then(|s| s.selected_account().cloned())
+        else {
+            return Task::none();
+        };
+
+        Task::perform(
+            async move {
+                let (tx, rx) = std::sync::mpsc::channel();
+
+                std::thread::spawn(move || {
+                    let rt = tokio::runtime::Runtime::new().unwrap();
+                    let result = rt.block_on(async move {
+                        crate::subscriptions::fetch_app_id_quota(&account, team_id)
+                            .await
+                            .map_err(|e| {
+                                log::error!("Failed to fetch App ID quota: {}", e);
+                            })
+                            .ok()
+                    });
+                    let _ = tx.send(result);
+                });
+
+                rx.recv().ok().flatten()
+            },
+            |quota| Message::InstallerScreen(package::Message::AppIdQuotaLoaded(quota)),
+        )
+    }
+
+    fn installer_teams_task(&self) -> Task<Message> {
+        let Some(account) = self
+            .account_store
+            .as_ref()
+            .and_then(|s| s.selected_account().cloned())
+        else {
+            return Task::none();
+        };
+
+        Task::perform(
+            async move {
+                let (tx, rx) = std::sync::mpsc::channel();
+
+                std::thread::spawn(move || {
+                    let rt = tokio::runtime::Runtime::new().unwrap();
+                    let result = rt.block_on(async move {
+                        crate::subscriptions::fetch_teams(&account)
+                            .await
+                            .unwrap_or_else(|e| {
+                                log::error!("Failed to fetch teams: {}", e);
+                                Vec::new()
+                            })
+                    });
+                    let _ = tx.send(result);
+                });
+
+                rx.recv().unwrap_or_default()
+            },
+            |teams| Message::InstallerScreen(package::Message::TeamsLoaded(teams)),
+        )
+    }
+
     fn start_installation_task(&mut self) -> Task<Message> {
         if let ImpactorScreen::Installer(installer) = &self.current_screen {
             let Some(package) = installer.selected_package.clone() else {
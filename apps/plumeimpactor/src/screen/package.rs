@@ -2,9 +2,14 @@ use iced::widget::{
     button, checkbox, column, container, pick_list, row, scrollable, text, text_input,
 };
 use iced::{Alignment, Center, Element, Fill, Task};
-use plume_utils::{Package, PlistInfoTrait, SignerInstallMode, SignerMode, SignerOptions};
+use plume_core::developer::qh::quota::AppIdQuota;
+use plume_utils::{
+    InstalledTweak, Package, PlistInfoTrait, SignerHookingRuntime, SignerInstallMode, SignerMode,
+    SignerOptions,
+};
 
 use crate::appearance;
+use crate::screen::settings::Team;
 
 #[derive(Debug, Clone)]
 pub enum Message {
@@ -20,11 +25,17 @@ pub enum Message {
     ToggleLiquidGlass(bool),
     ToggleRefresh(bool),
     ToggleElleKit(bool),
+    ToggleAllowTweakConflicts(bool),
+    UpdateHookingRuntime(SignerHookingRuntime),
     UpdateSignerMode(SignerMode),
     UpdateInstallMode(SignerInstallMode),
     AddTweak,
     AddBundle,
     RemoveTweak(usize),
+    RemoveInstalledTweak(String),
+    AppIdQuotaLoaded(Option<AppIdQuota>),
+    TeamsLoaded(Vec<Team>),
+    SelectTeam(Team),
     Back,
     RequestInstallation,
 }
@@ -33,13 +44,27 @@ pub enum Message {
 pub struct PackageScreen {
     pub selected_package: Option<Package>,
     pub options: SignerOptions,
+    /// Tweaks already injected into the package by a previous signing.
+    pub installed_tweaks: Vec<InstalledTweak>,
+    /// App ID usage of the selected account's team, fetched in the background.
+    pub app_id_quota: Option<AppIdQuota>,
+    /// Teams of the selected account, to sign with one other than its stored team.
+    pub teams: Vec<Team>,
 }
 
 impl PackageScreen {
     pub fn new(package: Option<Package>, options: SignerOptions) -> Self {
+        let installed_tweaks = package
+            .as_ref()
+            .map(|p| p.installed_tweaks())
+            .unwrap_or_default();
+
         Self {
             selected_package: package,
             options,
+            installed_tweaks,
+            app_id_quota: None,
+            teams: Vec::new(),
         }
     }
 
@@ -123,6 +148,26 @@ impl PackageScreen {
                 self.options.features.support_ellekit = value;
                 Task::none()
             }
+            Message::ToggleAllowTweakConflicts(value) => {
+                self.options.allow_tweak_conflicts = value;
+                Task::none()
+            }
+            Message::UpdateHookingRuntime(runtime) => {
+                match runtime {
+                    SignerHookingRuntime::Custom(path) if path.as_os_str().is_empty() => {
+                        let path = rfd::FileDialog::new()
+                            .add_filter("Hooking runtime", &["deb"])
+                            .set_title("Select Hooking Runtime")
+                            .pick_file();
+
+                        if let Some(path) = path {
+                            self.options.hooking_runtime = SignerHookingRuntime::Custom(path);
+                        }
+                    }
+                    runtime => self.options.hooking_runtime = runtime,
+                }
+                Task::none()
+            }
             Message::UpdateSignerMode(mode) => {
                 self.options.mode = mode;
                 Task::none()
@@ -172,6 +217,25 @@ impl PackageScreen {
                 }
                 Task::none()
             }
+            Message::RemoveInstalledTweak(id) => {
+                if !self.options.remove_tweaks.contains(&id) {
+                    self.options.remove_tweaks.push(id);
+                }
+                Task::none()
+            }
+            Message::AppIdQuotaLoaded(quota) => {
+                self.app_id_quota = quota;
+                Task::none()
+            }
+            Message::TeamsLoaded(teams) => {
+                self.teams = teams;
+                Task::none()
+            }
+            Message::SelectTeam(team) => {
+                self.options.team_id = Some(team.id);
+                self.app_id_quota = None;
+                Task::none()
+            }
             _ => Task::none(),
         }
     }
@@ -189,12 +253,57 @@ impl PackageScreen {
             .spacing(appearance::THEME_PADDING),
         );
 
-        column![
-            container(content).width(Fill).height(Fill),
-            self.view_buttons(has_device)
-        ]
-        .spacing(appearance::THEME_PADDING)
-        .into()
+        let mut layout =
+            column![container(content).width(Fill).height(Fill)].spacing(appearance::THEME_PADDING);
+
+        if let Some(warning) = self.app_id_quota_warning(pkg) {
+            layout = layout.push(text(warning).size(12).style(text::danger));
+        }
+
+        layout.push(self.view_buttons(has_device)).into()
+    }
+
+    fn app_id_quota_warning(&self, pkg: &Package) -> Option<String> {
+        if self.options.mode != SignerMode::Pem {
+            return None;
+        }
+
+        let quota = self.app_id_quota.as_ref()?;
+        let remaining = quota.remaining()?;
+        let needed = if self.options.embedding.single_profile {
+            1
+        } else {
+            pkg.app_id_count()
+        };
+
+        // App IDs this app registered before are reused
+        let registered = quota
+            .active()
+            .filter(|a| {
+                pkg.get_bundle_identifier()
+                    .is_some_and(|id| a.identifier.starts_with(&id))
+            })
+            .count();
+
+        if needed.saturating_sub(registered) <= remaining {
+            return None;
+        }
+
+        let mut warning = format!(
+            "This app needs {} App IDs, but only {} of {} are left this week.",
+            needed,
+            remaining,
+            quota.limit.unwrap_or_default()
+        );
+
+        if let Some(next) = quota.active().filter_map(|a| a.expires).min() {
+            warning.push_str(&format!(
+                " The next one frees up {}. Enable \"Only Register Main Bundle\" or remove unused App IDs with plumesign.",
+                chrono::DateTime::<chrono::Local>::from(next).format("%Y-%m-%d %H:%M")
+            ));
+        }
+
+        Some(warning)
     }
 
     fn view_no_package(&self) -> Element<'_, Message> {
@@ -235,6 +344,7 @@ impl PackageScreen {
             .on_input(Message::UpdateCustomVersion)
             .padding(8),
             text("Tweaks:").size(12),
+            self.view_installed_tweaks(),
             self.view_tweaks(),
             row![
                 button(appearance::icon_text(appearance::PLUS, "Add Tweak", None))
@@ -252,7 +362,7 @@ impl PackageScreen {
     }
 
     fn view_options_column(&self) -> Element<'_, Message> {
-        column![
+        let options = column![
             text("General:").size(12),
             checkbox(self.options.features.support_minimum_os_version)
                 .label("Support older versions (7+)")
@@ -277,8 +387,22 @@ impl PackageScreen {
                 .label("Force Liquid Glass (26+)")
                 .on_toggle(Message::ToggleLiquidGlass),
             checkbox(self.options.features.support_ellekit)
-                .label("Replace Substrate with ElleKit")
+                .label("Replace Substrate with Hooking Runtime")
                 .on_toggle(Message::ToggleElleKit),
+            checkbox(self.options.allow_tweak_conflicts)
+                .label("Allow Conflicting Tweaks")
+                .on_toggle(Message::ToggleAllowTweakConflicts),
+            pick_list(
+                vec![
+                    SignerHookingRuntime::ElleKit,
+                    SignerHookingRuntime::Custom(Default::default()),
+                    SignerHookingRuntime::None,
+                ],
+                Some(self.options.hooking_runtime.clone()),
+                Message::UpdateHookingRuntime
+            )
+            .style(appearance::s_pick_list)
+            .placeholder("Select hooking runtime"),
             checkbox(self.options.refresh)
                 .label("Auto Refresh [BETA]")
                 .on_toggle(Message::ToggleRefresh),
@@ -300,8 +424,27 @@ impl PackageScreen {
             .placeholder("Select signing method"),
         ]
         .spacing(8)
-        .width(Fill)
-        .into()
+        .width(Fill);
+
+        if self.options.mode != SignerMode::Pem || self.teams.len() < 2 {
+            return options.into();
+        }
+
+        let selected_team = self
+            .options
+            .team_id
+            .as_ref()
+            .and_then(|id| self.teams.iter().find(|t| t.id == *id))
+            .cloned();
+
+        options
+            .push(text("Team:").size(12))
+            .push(
+                pick_list(self.teams.clone(), selected_team, Message::SelectTeam)
+                    .style(appearance::s_pick_list)
+                    .placeholder("Account's team"),
+            )
+            .into()
     }
 
     fn view_buttons(&self, has_device: bool) -> Element<'_, Message> {
@@ -335,6 +478,35 @@ impl PackageScreen {
         .into()
     }
 
+    fn view_installed_tweaks(&self) -> Element<'_, Message> {
+        let mut tweak_list = column![].spacing(4);
+
+        for tweak in &self.installed_tweaks {
+            if self.options.remove_tweaks.contains(&tweak.id) {
+                continue;
+            }
+
+            let label = match &tweak.version {
+                Some(version) => format!("{} {} (injected)", tweak.name, version),
+                None => format!("{} (injected)", tweak.name),
+            };
+
+            let tweak_row = row![
+                text(label).size(12).width(Fill),
+                button(appearance::icon(appearance::MINUS))
+                    .on_press(Message::RemoveInstalledTweak(tweak.id.clone()))
+                    .style(appearance::s_button)
+                    .padding(6)
+            ]
+            .spacing(8)
+            .align_y(Alignment::Center);
+
+            tweak_list = tweak_list.push(tweak_row);
+        }
+
+        tweak_list.into()
+    }
+
     fn view_tweaks(&self) -> Element<'_, Message> {
         let tweaks = self.options.tweaks.as_ref();
 
@@ -1,7 +1,10 @@
 use std::collections::HashMap;
 
-use iced::widget::{button, checkbox, column, container, pick_list, row, scrollable, text};
+use iced::widget::{
+    button, checkbox, column, container, pick_list, row, scrollable, text, text_input,
+};
 use iced::{Alignment, Element, Fill, Task};
+use plume_core::auth::anisette_provider::{AnisetteHealth, AnisetteProvider};
 use plume_store::AccountStore;
 
 use crate::appearance;
@@ -10,30 +13,73 @@ use crate::appearance;
 pub struct Team {
     pub name: String,
     pub id: String,
+    pub kind: String,
+    pub status: String,
+    /// The account's roles on the team, comma separated.
+    pub roles: String,
+    pub free_only: bool,
+    pub membership_expires: Option<std::time::SystemTime>,
 }
 
 impl std::fmt::Display for Team {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{} ({})", self.name, self.id)
+        write!(
+            f,
+            "{} ({}) - {}, {}",
+            self.name, self.id, self.kind, self.status
+        )?;
+        if !self.roles.is_empty() {
+            write!(f, ", {}", self.roles)?;
+        }
+        Ok(())
     }
 }
 
+/// What the developer portal knows about an account, shown under its entry.
+#[derive(Debug, Clone)]
+pub struct AccountInfo {
+    pub developer_name: String,
+    pub developer_status: String,
+    pub teams: Vec<Team>,
+}
+
 #[derive(Debug, Clone)]
 pub enum Message {
     ShowLogin,
+    Relogin(String),
     SelectAccount(usize),
     RemoveAccount(usize),
     ExportP12,
     SelectTeam(String, String),
     FetchTeams(String),
     TeamsLoaded(String, Vec<Team>),
+    FetchAccountInfo(String),
+    AccountInfoLoaded(String, Option<AccountInfo>),
     ToggleAutoStart(bool),
+    ToggleNeverRevoke(String, bool),
+    AnisetteInputChanged(String),
+    AddAnisetteProvider(AnisetteProvider),
+    RemoveAnisetteProvider(AnisetteProvider),
+    CheckAnisetteProviders,
+    AnisetteChecked(Vec<AnisetteHealth>),
+    BackupPassphraseChanged(String),
+    CreateBackup,
+    RestoreBackup,
+    BackupFinished(Result<Option<String>, String>),
 }
 
 #[derive(Debug)]
 pub struct SettingsScreen {
     teams: HashMap<String, Vec<Team>>,
     loading_teams: Option<String>,
+    account_info: HashMap<String, AccountInfo>,
+    loading_account_info: Option<String>,
+    anisette_input: String,
+    anisette_health: HashMap<AnisetteProvider, AnisetteHealth>,
+    checking_anisette: bool,
+    backup_passphrase: String,
+    backup_running: bool,
+    backup_status: Option<Result<String, String>>,
 }
 
 impl SettingsScreen {
@@ -41,9 +87,21 @@ impl SettingsScreen {
         Self {
             teams: HashMap::new(),
             loading_teams: None,
+            account_info: HashMap::new(),
+            loading_account_info: None,
+            anisette_input: String::new(),
+            anisette_health: HashMap::new(),
+            checking_anisette: false,
+            backup_passphrase: String::new(),
+            backup_running: false,
+            backup_status: None,
         }
     }
 
+    pub fn backup_passphrase(&self) -> &str {
+        &self.backup_passphrase
+    }
+
     pub fn update(&mut self, message: Message) -> Task<Message> {
         match message {
             Message::FetchTeams(ref email) => {
@@ -55,7 +113,57 @@ impl SettingsScreen {
                 self.loading_teams = None;
                 Task::none()
             }
+            Message::FetchAccountInfo(email) => {
+                self.loading_account_info = Some(email);
+                Task::none()
+            }
+            Message::AccountInfoLoaded(email, info) => {
+                if let Some(info) = info {
+                    self.teams.insert(email.clone(), info.teams.clone());
+                    self.account_info.insert(email, info);
+                }
+                self.loading_account_info = None;
+                Task::none()
+            }
+            Message::AnisetteInputChanged(input) => {
+                self.anisette_input = input;
+                Task::none()
+            }
+            Message::AddAnisetteProvider(_) => {
+                self.anisette_input.clear();
+                Task::none()
+            }
+            Message::CheckAnisetteProviders => {
+                self.checking_anisette = true;
+                Task::none()
+            }
+            Message::AnisetteChecked(results) => {
+                self.anisette_health = results
+                    .into_iter()
+                    .map(|health| (health.provider.clone(), health))
+                    .collect();
+                self.checking_anisette = false;
+                Task::none()
+            }
+            Message::BackupPassphraseChanged(passphrase) => {
+                self.backup_passphrase = passphrase;
+                Task::none()
+            }
+            Message::CreateBackup | Message::RestoreBackup => {
+                self.backup_running = true;
+                self.backup_status = None;
+                Task::none()
+            }
+            Message::BackupFinished(result) => {
+                self.backup_running = false;
+                self.backup_status = result.transpose();
+                if self.backup_status.is_some() {
+                    self.backup_passphrase.clear();
+                }
+                Task::none()
+            }
             Message::ToggleAutoStart(_) => Task::none(),
+            Message::ToggleNeverRevoke(_, _) => Task::none(),
             Message::SelectTeam(_, _) => Task::none(),
             _ => Task::none(),
         }
@@ -93,8 +201,14 @@ impl SettingsScreen {
                         appearance::s_button
                     };
 
+                    let status = if account.needs_relogin() {
+                        " (session expired)"
+                    } else {
+                        ""
+                    };
+
                     let account_button = button(
-                        text(format!("{}{}", marker, account.email()))
+                        text(format!("{}{}{}", marker, account.email(), status))
                             .size(appearance::THEME_FONT_SIZE)
                             .align_x(Alignment::Start),
                     )
@@ -155,7 +269,22 @@ impl SettingsScreen {
 
         let auto_start_enabled = crate::startup::auto_start_enabled();
         content = content.push(self.view_auto_start_toggle(auto_start_enabled));
-        content = content.push(self.view_account_buttons(selected_index));
+        if let Some(account) = store.selected_account() {
+            let email = account.email().clone();
+            content = content.push(
+                checkbox(account.never_revoke())
+                    .label("Never Revoke Certificates")
+                    .on_toggle(move |enabled| Message::ToggleNeverRevoke(email.clone(), enabled)),
+            );
+            content = content.push(self.view_account_info(account.email()));
+        }
+        content = content.push(self.view_anisette_providers(store.anisette_providers()));
+        content = content.push(self.view_backup());
+        let relogin = store
+            .selected_account()
+            .filter(|a| a.needs_relogin())
+            .map(|a| a.email().clone());
+        content = content.push(self.view_account_buttons(selected_index, relogin));
 
         content.into()
     }
@@ -167,7 +296,169 @@ impl SettingsScreen {
             .into()
     }
 
-    fn view_account_buttons(&self, selected_index: Option<usize>) -> Element<'_, Message> {
+    fn view_account_info(&self, email: &String) -> Element<'_, Message> {
+        let Some(info) = self.account_info.get(email) else {
+            let loading = self.loading_account_info.as_ref() == Some(email);
+            return button(appearance::icon_text(
+                appearance::WRENCH,
+                if loading {
+                    "Loading Details..."
+                } else {
+                    "Show Account Details"
+                },
+                None,
+            ))
+            .on_press_maybe((!loading).then(|| Message::FetchAccountInfo(email.clone())))
+            .style(appearance::s_button)
+            .into();
+        };
+
+        let mut details = column![
+            text(format!(
+                "{} ({})",
+                info.developer_name, info.developer_status
+            ))
+            .size(appearance::THEME_FONT_SIZE)
+        ]
+        .spacing(4);
+
+        for team in &info.teams {
+            // free teams get 7 day profiles and none of the paid capabilities
+            let membership = if team.free_only {
+                "free, apps expire after 7 days".to_string()
+            } else {
+                match team.membership_expires {
+                    Some(expires) => format!(
+                        "membership expires {}",
+                        chrono::DateTime::<chrono::Local>::from(expires).format("%Y-%m-%d")
+                    ),
+                    None => "paid".to_string(),
+                }
+            };
+
+            details = details.push(
+                text(format!(
+                    "{} ({}): {}, {}, {}",
+                    team.name, team.id, team.kind, team.status, membership
+                ))
+                .size(12),
+            );
+        }
+
+        details.into()
+    }
+
+    fn view_anisette_providers<'a>(
+        &'a self,
+        providers: &'a [AnisetteProvider],
+    ) -> Element<'a, Message> {
+        let mut list = column![text("Anisette Providers").size(appearance::THEME_FONT_SIZE)]
+            .spacing(appearance::THEME_PADDING);
+
+        if providers.is_empty() {
+            list = list.push(text("Using the default provider").size(appearance::THEME_FONT_SIZE));
+        }
+
+        for provider in providers {
+            let status = match self.anisette_health.get(provider) {
+                Some(health) if health.is_ok() => {
+                    format!(" (ok, {} ms)", health.latency.as_millis())
+                }
+                Some(health) => {
+                    format!(" (failed: {})", health.error.as_deref().unwrap_or_default())
+                }
+                None => String::new(),
+            };
+
+            list = list.push(
+                row![
+                    text(format!("{}{}", provider, status))
+                        .size(appearance::THEME_FONT_SIZE)
+                        .width(Fill),
+                    button(appearance::icon_text(appearance::MINUS, "Remove", None))
+                        .on_press(Message::RemoveAnisetteProvider(provider.clone()))
+                        .style(appearance::s_button),
+                ]
+                .spacing(appearance::THEME_PADDING)
+                .align_y(Alignment::Center),
+            );
+        }
+
+        let parsed = self
+            .anisette_input
+            .parse::<AnisetteProvider>()
+            .ok()
+            .map(Message::AddAnisetteProvider);
+
+        list.push(
+            row![
+                text_input(
+                    "local or https://anisette.example.com",
+                    &self.anisette_input
+                )
+                .on_input(Message::AnisetteInputChanged)
+                .on_submit_maybe(parsed.clone())
+                .padding(8),
+                button(appearance::icon_text(appearance::PLUS, "Add", None))
+                    .on_press_maybe(parsed)
+                    .style(appearance::s_button),
+                button(appearance::icon_text(
+                    appearance::WRENCH,
+                    if self.checking_anisette {
+                        "Checking..."
+                    } else {
+                        "Check"
+                    },
+                    None,
+                ))
+                .on_press_maybe(
+                    (!self.checking_anisette && !providers.is_empty())
+                        .then_some(Message::CheckAnisetteProviders),
+                )
+                .style(appearance::s_button),
+            ]
+            .spacing(appearance::THEME_PADDING)
+            .align_y(Alignment::Center),
+        )
+        .into()
+    }
+
+    fn view_backup(&self) -> Element<'_, Message> {
+        let ready = !self.backup_running && !self.backup_passphrase.is_empty();
+
+        let mut section = column![
+            text("Backup").size(appearance::THEME_FONT_SIZE),
+            row![
+                text_input("Backup passphrase", &self.backup_passphrase)
+                    .secure(true)
+                    .on_input(Message::BackupPassphraseChanged)
+                    .padding(8),
+                button(appearance::icon_text(appearance::SHARE, "Create", None))
+                    .on_press_maybe(ready.then_some(Message::CreateBackup))
+                    .style(appearance::s_button),
+                button(appearance::icon_text(appearance::DOWNLOAD, "Restore", None))
+                    .on_press_maybe(ready.then_some(Message::RestoreBackup))
+                    .style(appearance::s_button),
+            ]
+            .spacing(appearance::THEME_PADDING)
+            .align_y(Alignment::Center),
+        ]
+        .spacing(appearance::THEME_PADDING);
+
+        match &self.backup_status {
+            Some(Ok(summary)) => section = section.push(text(summary).size(12)),
+            Some(Err(e)) => section = section.push(text(e).size(12).style(text::danger)),
+            None => {}
+        }
+
+        section.into()
+    }
+
+    fn view_account_buttons(
+        &self,
+        selected_index: Option<usize>,
+        relogin: Option<String>,
+    ) -> Element<'_, Message> {
         let mut buttons = row![
             button(appearance::icon_text(appearance::PLUS, "Add Account", None))
                 .on_press(Message::ShowLogin)
@@ -175,6 +466,18 @@ impl SettingsScreen {
         ]
         .spacing(appearance::THEME_PADDING);
 
+        if let Some(email) = relogin {
+            buttons = buttons.push(
+                button(appearance::icon_text(
+                    appearance::WRENCH,
+                    "Sign In Again",
+                    None,
+                ))
+                .on_press(Message::Relogin(email))
+                .style(appearance::p_button),
+            );
+        }
+
         if let Some(index) = selected_index {
             buttons = buttons
                 .push(
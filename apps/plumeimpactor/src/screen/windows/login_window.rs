@@ -1,7 +1,7 @@
 use iced::futures::SinkExt;
 use iced::widget::{button, column, container, row, text, text_input};
 use iced::{Alignment, Element, Fill, Task, window};
-use plume_core::{AnisetteConfiguration, auth::Account};
+use plume_core::auth::Account;
 use plume_store::{AccountStore, GsaAccount};
 use std::sync::mpsc as std_mpsc;
 
@@ -35,6 +35,11 @@ pub struct LoginWindow {
 
 impl LoginWindow {
     pub fn new() -> (Self, Task<Message>) {
+        Self::with_email(String::new())
+    }
+
+    /// For signing in again to an account whose session expired.
+    pub fn with_email(email: String) -> (Self, Task<Message>) {
         let (id, task) = window::open(window::Settings {
             size: iced::Size::new(400.0, 300.0),
             position: window::Position::Centered,
@@ -46,7 +51,7 @@ impl LoginWindow {
         (
             Self {
                 window_id: Some(id),
-                email: String::new(),
+                email,
                 password: String::new(),
                 two_factor_code: String::new(),
                 login_error: None,
@@ -299,21 +304,24 @@ impl LoginWindow {
                         .build()
                         .unwrap();
 
-                    let anisette_config = AnisetteConfiguration::default()
-                        .set_configuration_path(crate::defaults::get_data_path());
-
-                    let account_result = rt.block_on(Account::login(
-                        || Ok((email_clone.clone(), password.clone())),
-                        || {
-                            let _ = bridge_tx.send(Message::RequestTwoFactor);
-
-                            match two_factor_rx.recv() {
-                                Ok(result) => result,
-                                Err(_) => Err("Two-factor authentication cancelled".to_string()),
-                            }
-                        },
-                        anisette_config,
-                    ));
+                    let account_result = rt
+                        .block_on(crate::subscriptions::anisette_data())
+                        .and_then(|anisette| {
+                            rt.block_on(Account::login_with_anisette(
+                                || Ok((email_clone.clone(), password.clone())),
+                                || {
+                                    let _ = bridge_tx.send(Message::RequestTwoFactor);
+
+                                    match two_factor_rx.recv() {
+                                        Ok(result) => result,
+                                        Err(_) => {
+                                            Err("Two-factor authentication cancelled".to_string())
+                                        }
+                                    }
+                                },
+                                anisette,
+                            ))
+                        });
 
                     let final_msg = match account_result {
                         Ok(account) => {
@@ -7,6 +7,7 @@ use crate::{
     defaults::get_data_path,
     screen::{Message, general},
 };
+use plume_store::ProfileCache;
 use plume_utils::{Bundle, Device, PlistInfoTrait};
 
 pub(crate) fn device_listener() -> Subscription<Message> {
@@ -201,7 +202,7 @@ pub(crate) async fn run_installation(
     mut store: Option<&mut plume_store::AccountStore>,
     tx: &std::sync::mpsc::Sender<(String, i32)>,
 ) -> Result<(), String> {
-    use plume_core::{AnisetteConfiguration, CertificateIdentity, developer::DeveloperSession};
+    use plume_core::CertificateIdentity;
     use plume_utils::{Signer, SignerInstallMode, SignerMode};
 
     let package_file: Bundle;
@@ -220,42 +221,26 @@ pub(crate) async fn run_installation(
 
             send("Ensuring account is valid...".to_string(), 20);
 
-            let session = DeveloperSession::new(
-                account.adsid().clone(),
-                account.xcode_gs_token().clone(),
-                AnisetteConfiguration::default()
-                    .set_configuration_path(crate::defaults::get_data_path()),
-            )
-            .await
-            .map_err(|e| e.to_string())?;
+            let session = restore_session(account).await?;
 
             let teams_response = session.qh_list_teams().await.map_err(|e| e.to_string())?;
-
-            if teams_response.teams.is_empty() {
-                return Err("No teams available for this account".to_string());
-            }
-
-            let team_id = account.team_id();
-
-            if !team_id.is_empty() && !teams_response.teams.iter().any(|t| &t.team_id == team_id) {
-                return Err(format!(
-                    "Stored team ID '{}' not found in available teams. Please update your team selection in Settings.",
-                    team_id
-                ));
-            }
-
-            let team_id = if team_id.is_empty() {
-                &teams_response.teams[0].team_id
-            } else {
-                team_id
-            };
-
+            let team_id =
+                &resolve_team_id(&teams_response.teams, options.team_id.as_deref(), account)?;
+            options.team_id = Some(team_id.clone());
+            let free_team = teams_response
+                .teams
+                .iter()
+                .any(|t| t.team_id == *team_id && t.is_free());
+
+            let keys = plume_store::key_store(&crate::defaults::get_data_path())
+                .map_err(|e| e.to_string())?;
             let identity = CertificateIdentity::new_with_session(
                 &session,
-                crate::defaults::get_data_path(),
+                &keys,
                 None,
                 team_id,
                 false,
+                !account.never_revoke(),
             )
             .await
             .map_err(|e| e.to_string())?;
@@ -271,16 +256,22 @@ pub(crate) async fn run_installation(
 
             send("Extracting package...".to_string(), 50);
 
-            let mut signer = Signer::new(Some(identity), options.clone());
+            let mut signer = Signer::new(Some(identity), options.clone())
+                .with_profile_cache(
+                    ProfileCache::new(crate::defaults::get_profile_cache_path()),
+                    device.filter(|d| !d.is_mac).map(|d| d.udid.clone()),
+                )
+                .with_free_team(free_team);
 
             let bundle = package.get_package_bundle().map_err(|e| e.to_string())?;
 
             send("Signing package...".to_string(), 70);
 
-            signer
+            let warnings = signer
                 .modify_bundle(&bundle, &Some(team_id.clone()))
                 .await
                 .map_err(|e| e.to_string())?;
+            show_tweak_warnings(&warnings);
             signer
                 .register_bundle(&bundle, &session, team_id, false)
                 .await
@@ -302,10 +293,11 @@ pub(crate) async fn run_installation(
 
             send("Signing package...".to_string(), 70);
 
-            signer
+            let warnings = signer
                 .modify_bundle(&bundle, &None)
                 .await
                 .map_err(|e| e.to_string())?;
+            show_tweak_warnings(&warnings);
             signer
                 .sign_bundle(&bundle)
                 .await
@@ -439,33 +431,35 @@ pub(crate) async fn run_installation(
                     let refresh_app = plume_store::RefreshApp {
                         name: package_file.get_name(),
                         bundle_id: package_file.get_bundle_identifier(),
+                        team_id: options.team_id.clone(),
                         path: dest_path.clone(),
                         scheduled_refresh,
                     };
 
-                    let mut refresh_device = store
-                        .get_refresh_device(&dev.udid)
-                        .cloned()
-                        .unwrap_or_else(|| plume_store::RefreshDevice {
-                            udid: dev.udid.clone(),
-                            name: dev.name.clone(),
-                            account: account.email().clone(),
-                            apps: Vec::new(),
-                            is_mac: dev.is_mac,
-                        });
-
-                    if let Some(existing_app) = refresh_device
-                        .apps
-                        .iter_mut()
-                        .find(|a| a.bundle_id == refresh_app.bundle_id)
-                    {
-                        *existing_app = refresh_app;
-                    } else {
-                        refresh_device.apps.push(refresh_app);
-                    }
-
                     store
-                        .add_or_update_refresh_device_sync(refresh_device)
+                        .update_sync(|s| {
+                            let refresh_device = s
+                                .refreshes_mut()
+                                .entry(dev.udid.clone())
+                                .or_insert_with(|| plume_store::RefreshDevice {
+                                    udid: dev.udid.clone(),
+                                    name: dev.name.clone(),
+                                    account: account.email().clone(),
+                                    apps: Vec::new(),
+                                    is_mac: dev.is_mac,
+                                });
+
+                            if let Some(existing_app) = refresh_device
+                                .apps
+                                .iter_mut()
+                                .find(|a| a.bundle_id == refresh_app.bundle_id)
+                            {
+                                *existing_app = refresh_app;
+                            } else {
+                                refresh_device.apps.push(refresh_app);
+                            }
+                            Ok(())
+                        })
                         .map_err(|e| e.to_string())?;
                 }
             }
@@ -479,43 +473,22 @@ pub(crate) async fn run_installation(
 
 #[allow(dead_code)]
 pub(crate) async fn export_certificate(account: plume_store::GsaAccount) -> Result<(), String> {
-    use plume_core::{AnisetteConfiguration, CertificateIdentity, developer::DeveloperSession};
+    use plume_core::CertificateIdentity;
 
-    let session = DeveloperSession::new(
-        account.adsid().clone(),
-        account.xcode_gs_token().clone(),
-        AnisetteConfiguration::default().set_configuration_path(crate::defaults::get_data_path()),
-    )
-    .await
-    .map_err(|e| e.to_string())?;
+    let session = restore_session(&account).await?;
 
     let teams_response = session.qh_list_teams().await.map_err(|e| e.to_string())?;
+    let team_id = &resolve_team_id(&teams_response.teams, None, &account)?;
 
-    if teams_response.teams.is_empty() {
-        return Err("No teams available for this account".to_string());
-    }
-
-    let team_id = account.team_id();
-
-    if !team_id.is_empty() && !teams_response.teams.iter().any(|t| &t.team_id == team_id) {
-        return Err(format!(
-            "Stored team ID '{}' not found in available teams. Please update your team selection in Settings.",
-            team_id
-        ));
-    }
-
-    let team_id = if team_id.is_empty() {
-        &teams_response.teams[0].team_id
-    } else {
-        team_id
-    };
-
+    let keys =
+        plume_store::key_store(&crate::defaults::get_data_path()).map_err(|e| e.to_string())?;
     let identity = CertificateIdentity::new_with_session(
         &session,
-        crate::defaults::get_data_path(),
+        &keys,
         None,
         team_id,
         true,
+        !account.never_revoke(),
     )
     .await
     .map_err(|e| e.to_string())?;
@@ -550,27 +523,240 @@ pub(crate) async fn export_certificate(account: plume_store::GsaAccount) -> Resu
     Ok(())
 }
 
+// installation carries on, the tweaks may just not load
+fn show_tweak_warnings(warnings: &plume_utils::TweakWarnings) {
+    if warnings.is_empty() {
+        return;
+    }
+
+    let description = warnings.messages().join("\n");
+    std::thread::spawn(move || {
+        rfd::MessageDialog::new()
+            .set_level(rfd::MessageLevel::Warning)
+            .set_title("Tweak Warnings")
+            .set_description(&description)
+            .set_buttons(rfd::MessageButtons::Ok)
+            .show();
+    });
+}
+
+/// The team to sign with: the one picked for this operation, else the account's
+/// stored team, else its first team.
+pub(crate) fn resolve_team_id(
+    teams: &[plume_core::developer::qh::teams::Team],
+    requested: Option<&str>,
+    account: &plume_store::GsaAccount,
+) -> Result<String, String> {
+    let Some(first) = teams.first() else {
+        return Err("No teams available for this account".to_string());
+    };
+
+    let (team_id, source) = match requested {
+        Some(team_id) => (team_id, "Selected"),
+        None if !account.team_id().is_empty() => (account.team_id().as_str(), "Stored"),
+        None => return Ok(first.team_id.clone()),
+    };
+
+    if !teams.iter().any(|t| t.team_id == team_id) {
+        return Err(format!(
+            "{} team ID '{}' not found in available teams. Please update your team selection.",
+            source, team_id
+        ));
+    }
+
+    Ok(team_id.to_string())
+}
+
 pub(crate) async fn fetch_teams(
     account: &plume_store::GsaAccount,
 ) -> Result<Vec<crate::screen::settings::Team>, String> {
-    use plume_core::{AnisetteConfiguration, developer::DeveloperSession};
-
-    let session = DeveloperSession::new(
-        account.adsid().clone(),
-        account.xcode_gs_token().clone(),
-        AnisetteConfiguration::default().set_configuration_path(crate::defaults::get_data_path()),
-    )
-    .await
-    .map_err(|e| e.to_string())?;
+    let session = restore_session(account).await?;
 
     let teams_response = session.qh_list_teams().await.map_err(|e| e.to_string())?;
 
     Ok(teams_response
         .teams
         .into_iter()
-        .map(|t| crate::screen::settings::Team {
-            name: t.name,
-            id: t.team_id,
-        })
+        .map(settings_team)
         .collect())
 }
+
+pub(crate) async fn fetch_account_info(
+    account: &plume_store::GsaAccount,
+) -> Result<crate::screen::settings::AccountInfo, String> {
+    let session = restore_session(account).await?;
+
+    let teams = session
+        .qh_list_teams()
+        .await
+        .map_err(|e| e.to_string())?
+        .teams;
+    let team_id = resolve_team_id(&teams, None, account)?;
+
+    let developer = session
+        .qh_get_account_info(&team_id)
+        .await
+        .map_err(|e| e.to_string())?
+        .developer;
+
+    Ok(crate::screen::settings::AccountInfo {
+        developer_name: developer.name(),
+        developer_status: developer.developer_status,
+        teams: teams.into_iter().map(settings_team).collect(),
+    })
+}
+
+fn settings_team(t: plume_core::developer::qh::teams::Team) -> crate::screen::settings::Team {
+    crate::screen::settings::Team {
+        kind: t.team_type().to_string(),
+        status: t.membership_status().to_string(),
+        roles: t.roles().join(", "),
+        free_only: t.is_free(),
+        membership_expires: t.membership_expiry(),
+        name: t.name,
+        id: t.team_id,
+    }
+}
+
+pub(crate) async fn fetch_app_id_quota(
+    account: &plume_store::GsaAccount,
+    team_id: Option<String>,
+) -> Result<plume_core::developer::qh::quota::AppIdQuota, String> {
+    let session = restore_session(account).await?;
+
+    session
+        .qh_app_id_quota(team_id.as_ref().unwrap_or(account.team_id()))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Anisette data from the providers picked in Settings.
+pub(crate) async fn anisette_data()
+-> Result<plume_core::auth::anisette_data::AnisetteData, plume_core::Error> {
+    let path = crate::defaults::get_data_path();
+    plume_store::AccountStore::load(&Some(path.join("accounts.json")))
+        .await?
+        .anisette_data(path)
+        .await
+}
+
+/// Fetches anisette data from each provider, for the health check in Settings.
+pub(crate) async fn check_anisette_providers(
+    providers: Vec<plume_core::auth::anisette_provider::AnisetteProvider>,
+) -> Vec<plume_core::auth::anisette_provider::AnisetteHealth> {
+    let config = plume_core::AnisetteConfiguration::default()
+        .set_configuration_path(crate::defaults::get_data_path());
+
+    let mut results = Vec::new();
+    for provider in providers {
+        results.push(provider.check(&config).await);
+    }
+    results
+}
+
+/// Saves accounts, keys, anisette state and refresh entries to an archive the user picks,
+/// `None` when they cancelled.
+pub(crate) async fn create_backup(passphrase: String) -> Result<Option<String>, String> {
+    let Some(file) = rfd::AsyncFileDialog::new()
+        .set_title("Save Backup As")
+        .set_file_name("PlumeImpactor.plumebackup")
+        .save_file()
+        .await
+    else {
+        return Ok(None);
+    };
+
+    let path = crate::defaults::get_data_path();
+    let summary = plume_store::AccountStore::load(&Some(path.join("accounts.json")))
+        .await
+        .and_then(|store| store.create_backup(&path, file.path(), &passphrase, false))
+        .map_err(|e| e.to_string())?;
+
+    Ok(Some(format!(
+        "Backed up {} account(s), {} refresh device(s) and {} file(s)",
+        summary.accounts, summary.refresh_devices, summary.files
+    )))
+}
+
+/// Restores an archive the user picks into the data path, `None` when they cancelled.
+/// Replacing this machine's keys or anisette state is asked about first.
+pub(crate) async fn restore_backup(passphrase: String) -> Result<Option<String>, String> {
+    let Some(file) = rfd::AsyncFileDialog::new()
+        .set_title("Restore Backup")
+        .add_filter("Plume backup", &["plumebackup"])
+        .pick_file()
+        .await
+    else {
+        return Ok(None);
+    };
+
+    let path = crate::defaults::get_data_path();
+    let mut store = plume_store::AccountStore::load(&Some(path.join("accounts.json")))
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let summary = match store.restore_backup(&path, file.path(), &passphrase, false) {
+        Err(plume_core::Error::BackupWouldReplace(replaced)) => {
+            let answer = rfd::AsyncMessageDialog::new()
+                .set_level(rfd::MessageLevel::Warning)
+                .set_title("Replace Keys?")
+                .set_description(format!(
+                    "The backup has different signing keys or anisette state than this machine ({replaced}). Replacing them signs apps with the backup's keys and makes this machine show up to Apple as the old one. Replace them?"
+                ))
+                .set_buttons(rfd::MessageButtons::YesNo)
+                .show()
+                .await;
+            if !matches!(answer, rfd::MessageDialogResult::Yes) {
+                return Ok(None);
+            }
+            store.restore_backup(&path, file.path(), &passphrase, true)
+        }
+        result => result,
+    }
+    .map_err(|e| e.to_string())?;
+
+    Ok(Some(format!(
+        "Restored {} account(s), {} refresh device(s) and {} file(s)",
+        summary.accounts, summary.refresh_devices, summary.files
+    )))
+}
+
+/// Restores the account's session, flagging the account for signing in again
+/// when its token was rejected so Settings can ask for it.
+pub(crate) async fn restore_session(
+    account: &plume_store::GsaAccount,
+) -> Result<plume_core::developer::DeveloperSession, String> {
+    use plume_core::developer::DeveloperSession;
+
+    let result = match anisette_data().await {
+        Ok(anisette) => {
+            DeveloperSession::new_with_anisette(
+                account.adsid().clone(),
+                account.xcode_gs_token().clone(),
+                std::sync::Arc::new(tokio::sync::Mutex::new(anisette)),
+            )
+            .await
+        }
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok(session) => Ok(session),
+        Err(e) if e.is_session_expired() => {
+            let path = crate::defaults::get_data_path().join("accounts.json");
+            let flagged = match plume_store::AccountStore::load(&Some(path)).await {
+                Ok(mut store) => store.mark_needs_relogin(account.email()).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = flagged {
+                log::error!("Failed to flag account for signing in again: {}", e);
+            }
+
+            Err(format!(
+                "The session for {} has expired, sign in again from Settings.",
+                account.email()
+            ))
+        }
+        Err(e) => Err(e.to_string()),
+    }
+}
@@ -1,12 +1,28 @@
 use std::io::Write;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 use anyhow::{Ok, Result};
 use clap::{Args, Subcommand};
-use dialoguer::Select;
-
-use plume_core::{AnisetteConfiguration, auth::Account, developer::DeveloperSession};
-use plume_store::AccountStore;
+use dialoguer::{Confirm, Select};
+
+use plume_core::{
+    CertificateIdentity,
+    auth::{
+        Account,
+        anisette_data::AnisetteData,
+        two_factor::{
+            CommandTwoFactor, EnvTwoFactor, InteractiveTwoFactor, SmsNumberTwoFactor,
+            TWO_FACTOR_CODE_ENV, TwoFactorProvider,
+        },
+    },
+    developer::{
+        DeveloperSession,
+        qh::devices::{Device, DevicePlatform, DeviceStatus, parse_device_list},
+    },
+};
+use plume_store::{AccountStore, GsaAccount, ProfileCache, key_store};
+use tokio::sync::Mutex;
 
 use crate::get_data_path;
 
@@ -28,14 +44,20 @@ pub enum AccountCommands {
     List,
     /// Switch to a different account
     Switch(SwitchArgs),
-    /// List certificates for a team
+    /// Show the developer account and its teams' memberships
+    Info,
+    /// List and manage certificates for a team
     Certificates(CertificatesArgs),
-    /// List devices registered to the account
+    /// List and manage devices registered to the account
     Devices(DevicesArgs),
     /// Register a new device
     RegisterDevice(RegisterDeviceArgs),
     /// List all app IDs for a team
     AppIds(AppIdsArgs),
+    /// Manage app groups for a team
+    AppGroups(AppGroupsArgs),
+    /// Inspect and prune cached provisioning profiles
+    Profiles(ProfilesArgs),
 }
 
 #[derive(Debug, Args)]
@@ -47,26 +69,143 @@ pub struct LoginArgs {
     /// Password (will prompt if not provided)
     #[arg(short = 'p', long = "password", value_name = "PASSWORD")]
     pub password: Option<String>,
+    /// Sign in again to the selected account (or --username) after its session expired
+    #[arg(long)]
+    pub refresh: bool,
+    /// Read the 2FA code from an environment variable instead of prompting
+    #[arg(
+        long = "2fa-env",
+        value_name = "VAR",
+        num_args = 0..=1,
+        default_missing_value = TWO_FACTOR_CODE_ENV
+    )]
+    pub two_factor_env: Option<String>,
+    /// Run a program that prints the 2FA code (must come last, takes the remaining arguments)
+    #[arg(
+        long = "2fa-command",
+        value_name = "COMMAND",
+        num_args = 1..,
+        allow_hyphen_values = true,
+        conflicts_with = "two_factor_env"
+    )]
+    pub two_factor_command: Option<Vec<String>>,
+    /// Last two digits of the trusted number to text the code to, for accounts without trusted devices
+    #[arg(long = "sms-number", value_name = "DIGITS")]
+    pub sms_number: Option<String>,
 }
 
 #[derive(Debug, Args)]
 pub struct CertificatesArgs {
     /// Team ID to list certificates for
-    #[arg(short = 't', long = "team", value_name = "TEAM_ID")]
+    #[arg(short = 't', long = "team", value_name = "TEAM_ID", global = true)]
     pub team_id: Option<String>,
     /// Filter by certificate type (development, distribution)
     #[arg(long = "type", value_name = "TYPE")]
     pub cert_type: Option<String>,
+    #[command(subcommand)]
+    pub command: Option<CertificatesCommands>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CertificatesCommands {
+    /// Revoke a certificate
+    Revoke(RevokeCertificateArgs),
+    /// Export the signing identity for a team
+    Export(ExportCertificateArgs),
+    /// Use an existing certificate and key for a team
+    Import(ImportCertificateArgs),
+    /// Allow or forbid revoking certificates when a team has too many
+    AutoRevoke(AutoRevokeArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct RevokeCertificateArgs {
+    /// Serial number of the certificate
+    #[arg(value_name = "SERIAL", required = true)]
+    pub serial: String,
+}
+
+#[derive(Debug, Args)]
+pub struct ExportCertificateArgs {
+    /// Where to write the .p12
+    #[arg(long = "p12", value_name = "PATH", required = true)]
+    pub p12: PathBuf,
+}
+
+#[derive(Debug, Args)]
+pub struct ImportCertificateArgs {
+    /// .p12 containing the certificate and its private key
+    #[arg(long = "p12", value_name = "PATH", required = true)]
+    pub p12: PathBuf,
+    /// Password of the .p12
+    #[arg(
+        short = 'p',
+        long = "password",
+        value_name = "PASSWORD",
+        default_value = ""
+    )]
+    pub password: String,
+}
+
+#[derive(Debug, Args)]
+pub struct AutoRevokeArgs {
+    /// Whether certificates may be revoked automatically
+    #[arg(value_name = "ENABLED", action = clap::ArgAction::Set, required = true)]
+    pub enabled: bool,
 }
 
 #[derive(Debug, Args)]
 pub struct DevicesArgs {
     /// Team ID to list devices for
-    #[arg(short = 't', long = "team", value_name = "TEAM_ID")]
+    #[arg(short = 't', long = "team", value_name = "TEAM_ID", global = true)]
     pub team_id: Option<String>,
-    /// Filter by device platform (ios, tvos, watchos)
+    /// Filter by device platform (ios, tvos, watchos, macos)
     #[arg(long = "platform", value_name = "PLATFORM")]
-    pub platform: Option<String>,
+    pub platform: Option<DevicePlatform>,
+    /// Filter by device status (enabled, disabled, processing)
+    #[arg(long = "status", value_name = "STATUS")]
+    pub status: Option<DeviceStatus>,
+    #[command(subcommand)]
+    pub command: Option<DevicesCommands>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum DevicesCommands {
+    /// Rename a device
+    Rename(RenameDeviceArgs),
+    /// Disable devices, they keep counting against the device limit until the membership renews
+    Disable(DeviceUdidsArgs),
+    /// Enable previously disabled devices
+    Enable(DeviceUdidsArgs),
+    /// Register devices from a CSV or Apple's device upload file
+    Import(ImportDevicesArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct RenameDeviceArgs {
+    /// Device UDID
+    #[arg(value_name = "UDID", required = true)]
+    pub udid: String,
+    /// New device name
+    #[arg(value_name = "NAME", required = true)]
+    pub name: String,
+}
+
+#[derive(Debug, Args)]
+pub struct DeviceUdidsArgs {
+    /// Device UDIDs
+    #[arg(value_name = "UDID", required = true)]
+    pub udids: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct ImportDevicesArgs {
+    /// File with one `udid,name[,platform]` per line, tab separated files are also accepted
+    #[arg(value_name = "FILE", required = true)]
+    pub file: PathBuf,
+    /// Show what would be registered without registering anything
+    #[arg(long = "dry-run")]
+    pub dry_run: bool,
 }
 
 #[derive(Debug, Args)]
@@ -87,6 +226,128 @@ pub struct AppIdsArgs {
     /// Team ID to list app IDs for
     #[arg(short = 't', long = "team", value_name = "TEAM_ID")]
     pub team_id: Option<String>,
+    /// Show App ID quota usage and offer to delete unused ones
+    #[arg(long = "quota")]
+    pub quota: bool,
+}
+
+#[derive(Debug, Args)]
+#[command(arg_required_else_help = true)]
+pub struct AppGroupsArgs {
+    /// Team ID to manage app groups for
+    #[arg(short = 't', long = "team", value_name = "TEAM_ID", global = true)]
+    pub team_id: Option<String>,
+    #[command(subcommand)]
+    pub command: AppGroupsCommands,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum AppGroupsCommands {
+    /// List all app groups
+    List,
+    /// Create a new app group
+    Create(CreateAppGroupArgs),
+    /// Set the app groups of an App ID, replacing its current ones
+    Assign(AssignAppGroupArgs),
+    /// Remove the app groups of an App ID
+    Unassign(UnassignAppGroupArgs),
+    /// Delete app groups
+    Delete(DeleteAppGroupArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct CreateAppGroupArgs {
+    /// Group identifier (e.g. group.com.example.app)
+    #[arg(value_name = "GROUP", required = true)]
+    pub identifier: String,
+    /// Display name, defaults to the identifier
+    #[arg(short = 'n', long = "name", value_name = "NAME")]
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct AssignAppGroupArgs {
+    /// Bundle identifier of the App ID
+    #[arg(
+        short = 'a',
+        long = "app-id",
+        value_name = "BUNDLE_ID",
+        required = true
+    )]
+    pub app_id: String,
+    /// Group identifiers to assign
+    #[arg(value_name = "GROUP", required = true)]
+    pub groups: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct UnassignAppGroupArgs {
+    /// Bundle identifier of the App ID
+    #[arg(
+        short = 'a',
+        long = "app-id",
+        value_name = "BUNDLE_ID",
+        required = true
+    )]
+    pub app_id: String,
+    /// Group identifiers the App ID should keep
+    #[arg(long = "keep", value_name = "GROUP")]
+    pub keep: Vec<String>,
+}
+
+#[derive(Debug, Args)]
+pub struct DeleteAppGroupArgs {
+    /// Group identifiers to delete
+    #[arg(value_name = "GROUP", required_unless_present = "stale")]
+    pub groups: Vec<String>,
+    /// Delete every group Impactor created for this team (group.*.TEAM_ID)
+    #[arg(long = "stale")]
+    pub stale: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ProfilesArgs {
+    /// Team ID to manage profiles for
+    #[arg(short = 't', long = "team", value_name = "TEAM_ID", global = true)]
+    pub team_id: Option<String>,
+    #[command(subcommand)]
+    pub command: Option<ProfilesCommands>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ProfilesCommands {
+    /// Remove expired profiles from the cache
+    Prune(PruneProfilesArgs),
+    /// List profiles on the Developer Portal
+    Remote,
+    /// Download a profile from the Developer Portal
+    Download(DownloadProfileArgs),
+    /// Delete profiles from the Developer Portal and the cache
+    Delete(DeleteProfileArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct PruneProfilesArgs {
+    /// Remove every cached profile, not only expired ones
+    #[arg(long = "all")]
+    pub all: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct DownloadProfileArgs {
+    /// Provisioning profile ID, as shown by `profiles remote`
+    #[arg(value_name = "PROFILE_ID", required = true)]
+    pub profile_id: String,
+    /// Where to write the profile, defaults to its file name
+    #[arg(short = 'o', long = "output", value_name = "PATH")]
+    pub output: Option<PathBuf>,
+}
+
+#[derive(Debug, Args)]
+pub struct DeleteProfileArgs {
+    /// Provisioning profile IDs, as shown by `profiles remote`
+    #[arg(value_name = "PROFILE_ID", required = true)]
+    pub profile_ids: Vec<String>,
 }
 
 #[derive(Debug, Args)]
@@ -102,18 +363,27 @@ pub async fn execute(args: AccountArgs) -> Result<()> {
         AccountCommands::Logout => logout().await,
         AccountCommands::List => list_accounts().await,
         AccountCommands::Switch(switch_args) => switch_account(switch_args).await,
+        AccountCommands::Info => account_info().await,
         AccountCommands::Certificates(cert_args) => certificates(cert_args).await,
         AccountCommands::Devices(device_args) => devices(device_args).await,
         AccountCommands::RegisterDevice(register_args) => register_device(register_args).await,
         AccountCommands::AppIds(app_id_args) => app_ids(app_id_args).await,
+        AccountCommands::AppGroups(app_group_args) => app_groups(app_group_args).await,
+        AccountCommands::Profiles(profile_args) => profiles(profile_args).await,
     }
 }
 
-fn get_settings_path() -> PathBuf {
+fn format_time(time: std::time::SystemTime) -> String {
+    chrono::DateTime::<chrono::Local>::from(time)
+        .format("%Y-%m-%d %H:%M")
+        .to_string()
+}
+
+pub fn get_settings_path() -> PathBuf {
     get_data_path().join("accounts.json")
 }
 
-pub async fn get_authenticated_account() -> Result<DeveloperSession> {
+pub async fn get_selected_account() -> Result<GsaAccount> {
     let settings_path = get_settings_path();
     let settings = AccountStore::load(&Some(settings_path.clone())).await?;
 
@@ -126,34 +396,52 @@ pub async fn get_authenticated_account() -> Result<DeveloperSession> {
         })?
         .clone();
 
-    let anisette_config = AnisetteConfiguration::default().set_configuration_path(get_data_path());
+    Ok(gsa_account)
+}
+
+pub async fn get_authenticated_account() -> Result<DeveloperSession> {
+    let gsa_account = get_selected_account().await?;
+    let anisette = anisette_data().await?;
 
     log::info!("Restoring session for {}...", gsa_account.email());
 
-    let session = DeveloperSession::new(
+    let session = match DeveloperSession::new_with_anisette(
         gsa_account.adsid().clone(),
         gsa_account.xcode_gs_token().clone(),
-        anisette_config,
+        Arc::new(Mutex::new(anisette)),
     )
-    .await?;
+    .await
+    {
+        Err(e) if e.is_session_expired() => {
+            let mut settings = AccountStore::load(&Some(get_settings_path())).await?;
+            settings.mark_needs_relogin(gsa_account.email()).await?;
+
+            return Err(anyhow::anyhow!(
+                "{e}. Run 'plumesign account login --refresh' to sign in to {} again.",
+                gsa_account.email()
+            ));
+        }
+        session => session?,
+    };
 
     Ok(session)
 }
 
-async fn login(args: LoginArgs) -> Result<()> {
-    let tfa_closure = || -> std::result::Result<String, String> {
-        log::info!("Enter 2FA code: ");
-        let mut input = String::new();
-        std::io::stdin()
-            .read_line(&mut input)
-            .map_err(|e| e.to_string())?;
-        std::result::Result::Ok(input.trim().to_string())
-    };
+/// Anisette data from the providers configured with `plumesign anisette`.
+async fn anisette_data() -> Result<AnisetteData> {
+    let settings = AccountStore::load(&Some(get_settings_path())).await?;
+    Ok(settings.anisette_data(get_data_path()).await?)
+}
 
-    let anisette_config = AnisetteConfiguration::default().set_configuration_path(get_data_path());
+async fn login(args: LoginArgs) -> Result<()> {
+    let anisette = anisette_data().await?;
 
     let username = if let Some(user) = args.username {
         user
+    } else if args.refresh {
+        let email = get_selected_account().await?.email().clone();
+        log::info!("Signing in again as {}", email);
+        email
     } else {
         log::info!("Enter Apple ID email: ");
         let mut input = String::new();
@@ -177,7 +465,36 @@ async fn login(args: LoginArgs) -> Result<()> {
     };
 
     println!("Logging in...");
-    let account = Account::login(login_closure, tfa_closure, anisette_config).await?;
+    let account = match (args.two_factor_env, args.two_factor_command) {
+        (Some(var), _) => {
+            login_with_provider(
+                login_closure,
+                EnvTwoFactor::new(var),
+                args.sms_number,
+                anisette,
+            )
+            .await?
+        }
+        (None, Some(mut command)) => {
+            let program = command.remove(0);
+            login_with_provider(
+                login_closure,
+                CommandTwoFactor::new(program, command),
+                args.sms_number,
+                anisette,
+            )
+            .await?
+        }
+        (None, None) => {
+            login_with_provider(
+                login_closure,
+                InteractiveTwoFactor,
+                args.sms_number,
+                anisette,
+            )
+            .await?
+        }
+    };
 
     let settings_path = get_settings_path();
     let mut settings = AccountStore::load(&Some(settings_path.clone())).await?;
@@ -190,6 +507,27 @@ async fn login(args: LoginArgs) -> Result<()> {
     Ok(())
 }
 
+async fn login_with_provider(
+    login_closure: impl Fn() -> std::result::Result<(String, String), String>,
+    provider: impl TwoFactorProvider,
+    sms_number: Option<String>,
+    anisette: AnisetteData,
+) -> Result<Account> {
+    let account = match sms_number {
+        Some(digits) => {
+            Account::login_with_anisette(
+                login_closure,
+                SmsNumberTwoFactor::new(digits, provider),
+                anisette,
+            )
+            .await?
+        }
+        None => Account::login_with_anisette(login_closure, provider, anisette).await?,
+    };
+
+    Ok(account)
+}
+
 async fn logout() -> Result<()> {
     let settings_path = get_settings_path();
     let mut settings = AccountStore::load(&Some(settings_path.clone())).await?;
@@ -216,9 +554,99 @@ async fn certificates(args: CertificatesArgs) -> Result<()> {
         args.team_id.unwrap()
     };
 
-    let p = session.qh_list_certs(&team_id).await?.certificates;
-
-    log::info!("{:#?}", p);
+    match args.command {
+        None => {
+            let certs = session.qh_list_certs(&team_id).await?.certificates;
+
+            if certs.is_empty() {
+                log::info!("No certificates found.");
+            }
+
+            for cert in certs {
+                log::info!(
+                    " {} [{}] serial {} on {} ({})",
+                    cert.name,
+                    cert.status,
+                    cert.serial_number,
+                    cert.machine_name.as_deref().unwrap_or("unknown machine"),
+                    format_time(cert.expiration_date.into())
+                );
+            }
+        }
+        Some(CertificatesCommands::Revoke(revoke_args)) => {
+            let confirmed = Confirm::new()
+                .with_prompt(format!(
+                    "Revoke certificate {}? Apps signed with it stop launching",
+                    revoke_args.serial
+                ))
+                .default(false)
+                .interact()?;
+
+            if confirmed {
+                session
+                    .qh_revoke_cert(&team_id, &revoke_args.serial)
+                    .await?;
+                log::info!("Revoked certificate {}", revoke_args.serial);
+            }
+        }
+        Some(CertificatesCommands::Export(export_args)) => {
+            let account = get_selected_account().await?;
+            let identity = CertificateIdentity::new_with_session(
+                &session,
+                &key_store(&get_data_path())?,
+                None,
+                &team_id,
+                true,
+                !account.never_revoke(),
+            )
+            .await?;
+
+            let p12_data = identity
+                .p12_data
+                .ok_or_else(|| anyhow::anyhow!("Failed to create p12"))?;
+            tokio::fs::write(&export_args.p12, p12_data).await?;
+
+            log::info!("Exported certificate to {}", export_args.p12.display());
+        }
+        Some(CertificatesCommands::Import(import_args)) => {
+            let p12_data = tokio::fs::read(&import_args.p12).await?;
+            let cert = CertificateIdentity::import_p12(
+                &session,
+                &key_store(&get_data_path())?,
+                &team_id,
+                &p12_data,
+                &import_args.password,
+            )
+            .await?;
+
+            log::info!(
+                "Imported certificate {} (serial {}), it will be used for team {}",
+                cert.name,
+                cert.serial_number,
+                team_id
+            );
+        }
+        Some(CertificatesCommands::AutoRevoke(auto_revoke_args)) => {
+            let mut settings = AccountStore::load(&Some(get_settings_path())).await?;
+            let email = settings
+                .selected_account()
+                .map(|a| a.email().clone())
+                .ok_or_else(|| anyhow::anyhow!("No account selected"))?;
+            settings
+                .update_account_never_revoke(&email, !auto_revoke_args.enabled)
+                .await?;
+
+            log::info!(
+                "Automatic certificate revocation {} for {}",
+                if auto_revoke_args.enabled {
+                    "enabled"
+                } else {
+                    "disabled"
+                },
+                email
+            );
+        }
+    }
 
     Ok(())
 }
@@ -232,13 +660,125 @@ async fn devices(args: DevicesArgs) -> Result<()> {
         args.team_id.unwrap()
     };
 
-    let p = session.qh_list_devices(&team_id).await?.devices;
-
-    log::info!("{:#?}", p);
+    match args.command {
+        None => {
+            let devices = session
+                .qh_list_devices_filtered(&team_id, args.platform, args.status)
+                .await?;
+
+            if devices.is_empty() {
+                log::info!("No devices found.");
+            }
+
+            for device in &devices {
+                log::info!(
+                    " {} [{}] {} ({}, {})",
+                    device.name,
+                    device.device_number,
+                    device
+                        .platform()
+                        .map(|p| p.to_string())
+                        .unwrap_or_else(|| device.device_class().to_string()),
+                    device.device_class(),
+                    device.status()
+                );
+            }
+
+            let enabled = devices
+                .iter()
+                .filter(|d| d.status() != DeviceStatus::Disabled)
+                .count();
+            log::info!("{} devices, {} enabled", devices.len(), enabled);
+        }
+        Some(DevicesCommands::Rename(rename_args)) => {
+            let device = find_device(&session, &team_id, &rename_args.udid).await?;
+            session
+                .qh_update_device(&team_id, &device, &rename_args.name)
+                .await?;
+            log::info!("Renamed {} to {}", device.name, rename_args.name);
+        }
+        Some(DevicesCommands::Disable(udid_args)) => {
+            let confirmed = Confirm::new()
+                .with_prompt(format!(
+                    "Disable {} device(s)? They keep counting against the device limit",
+                    udid_args.udids.len()
+                ))
+                .default(false)
+                .interact()?;
+
+            if confirmed {
+                for udid in &udid_args.udids {
+                    let device = find_device(&session, &team_id, udid).await?;
+                    session.qh_disable_device(&team_id, &device).await?;
+                    log::info!("Disabled {} ({})", device.name, udid);
+                }
+            }
+        }
+        Some(DevicesCommands::Enable(udid_args)) => {
+            for udid in &udid_args.udids {
+                let device = find_device(&session, &team_id, udid).await?;
+                session.qh_enable_device(&team_id, &device).await?;
+                log::info!("Enabled {} ({})", device.name, udid);
+            }
+        }
+        Some(DevicesCommands::Import(import_args)) => {
+            let contents = tokio::fs::read_to_string(&import_args.file).await?;
+            let entries = parse_device_list(&contents).map_err(|_| {
+                anyhow::anyhow!("Invalid device list, expected `udid,name[,platform]` lines")
+            })?;
+
+            let existing = session
+                .qh_list_devices_filtered(&team_id, None, None)
+                .await?;
+
+            for entry in entries {
+                if existing
+                    .iter()
+                    .any(|d| d.device_number.eq_ignore_ascii_case(&entry.udid))
+                {
+                    log::info!(
+                        "Skipping {} ({}), already registered",
+                        entry.name,
+                        entry.udid
+                    );
+                    continue;
+                }
+
+                if import_args.dry_run {
+                    log::info!(
+                        "Would register {} ({}) for {}",
+                        entry.name,
+                        entry.udid,
+                        entry.platform
+                    );
+                    continue;
+                }
+
+                session
+                    .qh_add_device_for_platform(&team_id, &entry.name, &entry.udid, entry.platform)
+                    .await?;
+                log::info!(
+                    "Registered {} ({}) for {}",
+                    entry.name,
+                    entry.udid,
+                    entry.platform
+                );
+            }
+        }
+    }
 
     Ok(())
 }
 
+async fn find_device(session: &DeveloperSession, team_id: &String, udid: &str) -> Result<Device> {
+    session
+        .qh_list_devices_filtered(team_id, None, None)
+        .await?
+        .into_iter()
+        .find(|d| d.device_number.eq_ignore_ascii_case(udid))
+        .ok_or_else(|| anyhow::anyhow!("No device with UDID {} found", udid))
+}
+
 async fn register_device(args: RegisterDeviceArgs) -> Result<()> {
     let session = get_authenticated_account().await?;
 
@@ -267,7 +807,19 @@ pub async fn teams(session: &DeveloperSession) -> Result<String> {
 
     let team_names: Vec<String> = teams
         .iter()
-        .map(|t| format!("{} ({})", t.name, t.team_id))
+        .map(|t| {
+            let mut label = format!(
+                "{} ({}) - {}, {}",
+                t.name,
+                t.team_id,
+                t.team_type(),
+                t.membership_status()
+            );
+            if !t.roles().is_empty() {
+                label.push_str(&format!(", {}", t.roles().join(", ")));
+            }
+            label
+        })
         .collect();
 
     let selection = Select::new().items(&team_names).default(0).interact()?;
@@ -284,6 +836,10 @@ pub async fn app_ids(args: AppIdsArgs) -> Result<()> {
         args.team_id.unwrap()
     };
 
+    if args.quota {
+        return app_id_quota(&session, &team_id).await;
+    }
+
     let p = session.v1_list_app_ids(&team_id).await?.data;
 
     log::info!("{:#?}", p);
@@ -291,6 +847,399 @@ pub async fn app_ids(args: AppIdsArgs) -> Result<()> {
     Ok(())
 }
 
+async fn app_id_quota(session: &DeveloperSession, team_id: &String) -> Result<()> {
+    let quota = session.qh_app_id_quota(team_id).await?;
+
+    match quota.limit {
+        Some(limit) => log::info!("App IDs: {} of {} used", quota.used(), limit),
+        None => log::info!("App IDs: {} used (no limit)", quota.used()),
+    }
+
+    for app_id in quota.active() {
+        log::info!(
+            " {} ({}){}{}",
+            app_id.identifier,
+            app_id.name,
+            app_id
+                .expires
+                .map(|e| format!(", expires {}", format_time(e)))
+                .unwrap_or_default(),
+            if app_id.created_by_plume {
+                ", created by Impactor"
+            } else {
+                ""
+            }
+        );
+    }
+
+    if let Some(next) = quota.next_available() {
+        log::warn!(
+            "No App IDs left, the next one frees up {}",
+            format_time(next)
+        );
+    }
+
+    let unused = quota.unused(&[]);
+    if quota.limit.is_none() || unused.is_empty() {
+        return Ok(());
+    }
+
+    let delete = Confirm::new()
+        .with_prompt(format!(
+            "Delete {} App ID(s) created by Impactor? Apps signed with them stop working once their profile expires",
+            unused.len()
+        ))
+        .default(false)
+        .interact()?;
+
+    if delete {
+        let deleted = session.qh_delete_unused_app_ids(team_id, &[]).await?;
+        log::info!("Deleted {} App ID(s)", deleted.len());
+    }
+
+    Ok(())
+}
+
+async fn app_groups(args: AppGroupsArgs) -> Result<()> {
+    let session = get_authenticated_account().await?;
+
+    let team_id = if args.team_id.is_none() {
+        teams(&session).await?
+    } else {
+        args.team_id.unwrap()
+    };
+
+    match args.command {
+        AppGroupsCommands::List => {
+            let groups = session
+                .qh_list_app_groups(&team_id)
+                .await?
+                .application_group_list;
+
+            if groups.is_empty() {
+                log::info!("No app groups found.");
+            }
+
+            for group in groups {
+                log::info!(" {} ({}) [{}]", group.identifier, group.name, group.status);
+            }
+        }
+        AppGroupsCommands::Create(create_args) => {
+            let name = create_args
+                .name
+                .unwrap_or_else(|| create_args.identifier.clone());
+
+            let group = session
+                .qh_add_app_group(&team_id, &name, &create_args.identifier)
+                .await?
+                .application_group;
+
+            log::info!("Created app group {} ({})", group.identifier, group.name);
+        }
+        AppGroupsCommands::Assign(assign_args) => {
+            let app_id = find_app_id(&session, &team_id, &assign_args.app_id).await?;
+            let group_ids = resolve_app_groups(&session, &team_id, &assign_args.groups).await?;
+
+            session
+                .qh_assign_app_group(&team_id, &app_id, &group_ids)
+                .await?;
+
+            log::info!(
+                "Assigned {} app group(s) to {}",
+                group_ids.len(),
+                assign_args.app_id
+            );
+        }
+        AppGroupsCommands::Unassign(unassign_args) => {
+            let app_id = find_app_id(&session, &team_id, &unassign_args.app_id).await?;
+            let keep_ids = resolve_app_groups(&session, &team_id, &unassign_args.keep).await?;
+
+            session
+                .qh_unassign_app_group(&team_id, &app_id, &keep_ids)
+                .await?;
+
+            log::info!("Updated app groups of {}", unassign_args.app_id);
+        }
+        AppGroupsCommands::Delete(delete_args) => {
+            let groups = session
+                .qh_list_app_groups(&team_id)
+                .await?
+                .application_group_list;
+
+            let suffix = format!(".{}", team_id);
+            let to_delete = groups
+                .iter()
+                .filter(|g| {
+                    delete_args.groups.contains(&g.identifier)
+                        || (delete_args.stale
+                            && g.identifier.starts_with("group.")
+                            && g.identifier.ends_with(&suffix))
+                })
+                .collect::<Vec<_>>();
+
+            if let Some(missing) = delete_args
+                .groups
+                .iter()
+                .find(|id| !groups.iter().any(|g| g.identifier == **id))
+            {
+                return Err(anyhow::anyhow!("App group '{}' not found", missing));
+            }
+
+            if to_delete.is_empty() {
+                log::info!("No app groups to delete.");
+                return Ok(());
+            }
+
+            for group in &to_delete {
+                log::info!(" {}", group.identifier);
+            }
+
+            let confirmed = Confirm::new()
+                .with_prompt(format!(
+                    "Delete {} app group(s)? Apps using them lose access to their shared data",
+                    to_delete.len()
+                ))
+                .default(false)
+                .interact()?;
+
+            if !confirmed {
+                return Ok(());
+            }
+
+            for group in to_delete {
+                session
+                    .qh_delete_app_group(&team_id, &group.application_group)
+                    .await?;
+                log::info!("Deleted app group {}", group.identifier);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn find_app_id(
+    session: &DeveloperSession,
+    team_id: &String,
+    identifier: &String,
+) -> Result<String> {
+    let app_id = session
+        .qh_get_app_id(team_id, identifier)
+        .await?
+        .ok_or_else(|| anyhow::anyhow!("App ID '{}' not found", identifier))?;
+
+    Ok(app_id.app_id_id)
+}
+
+async fn resolve_app_groups(
+    session: &DeveloperSession,
+    team_id: &String,
+    identifiers: &[String],
+) -> Result<Vec<String>> {
+    if identifiers.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let groups = session
+        .qh_list_app_groups(team_id)
+        .await?
+        .application_group_list;
+
+    identifiers
+        .iter()
+        .map(|id| {
+            groups
+                .iter()
+                .find(|g| g.identifier == *id)
+                .map(|g| g.application_group.clone())
+                .ok_or_else(|| anyhow::anyhow!("App group '{}' not found", id))
+        })
+        .collect()
+}
+
+async fn profiles(args: ProfilesArgs) -> Result<()> {
+    let cache = ProfileCache::new(get_data_path().join("profiles"));
+
+    match args.command {
+        None => {
+            let entries = cache.entries().await?;
+
+            if entries.is_empty() {
+                log::info!("No cached profiles.");
+            }
+
+            for cached in entries
+                .iter()
+                .filter(|c| args.team_id.as_ref().is_none_or(|t| *t == c.team_id))
+            {
+                log::info!(
+                    " {} [{}] team {}, {} devices, {} {}",
+                    cached.provision.name().unwrap_or("Unnamed profile"),
+                    cached.provision.bundle_id().unwrap_or_default(),
+                    cached.team_id,
+                    cached.provision.devices().len(),
+                    if cached.is_expired() {
+                        "expired"
+                    } else {
+                        "expires"
+                    },
+                    format_time(cached.expires())
+                );
+            }
+        }
+        Some(ProfilesCommands::Prune(prune_args)) => {
+            let removed = cache.prune(prune_args.all).await?;
+
+            for cached in &removed {
+                log::info!(
+                    "Removed {} ({})",
+                    cached.provision.name().unwrap_or("Unnamed profile"),
+                    cached.path.display()
+                );
+            }
+            log::info!("Removed {} cached profile(s)", removed.len());
+        }
+        Some(ProfilesCommands::Remote) => {
+            let (session, team_id) = session_with_team(args.team_id).await?;
+
+            let profiles = session
+                .qh_list_profiles(&team_id)
+                .await?
+                .provisioning_profiles;
+
+            if profiles.is_empty() {
+                log::info!("No profiles found.");
+            }
+
+            for profile in profiles {
+                log::info!(
+                    " {} [{}] {} ({}), expires {}",
+                    profile.name,
+                    profile.provisioning_profile_id,
+                    profile._type,
+                    profile.status,
+                    format_time(profile.date_expire.into())
+                );
+            }
+        }
+        Some(ProfilesCommands::Download(download_args)) => {
+            let (session, team_id) = session_with_team(args.team_id).await?;
+
+            let profile = session
+                .qh_download_profile(&team_id, &download_args.profile_id)
+                .await?
+                .provisioning_profile;
+
+            let output = download_args
+                .output
+                .unwrap_or_else(|| PathBuf::from(&profile.filename));
+            tokio::fs::write(&output, profile.encoded_profile.as_ref()).await?;
+
+            log::info!("Saved {} to {}", profile.name, output.display());
+        }
+        Some(ProfilesCommands::Delete(delete_args)) => {
+            let (session, team_id) = session_with_team(args.team_id).await?;
+
+            let confirmed = Confirm::new()
+                .with_prompt(format!(
+                    "Delete {} profile(s) from the Developer Portal?",
+                    delete_args.profile_ids.len()
+                ))
+                .default(false)
+                .interact()?;
+
+            if !confirmed {
+                return Ok(());
+            }
+
+            let profiles = session
+                .qh_list_profiles(&team_id)
+                .await?
+                .provisioning_profiles;
+
+            for profile_id in &delete_args.profile_ids {
+                session.qh_delete_profile(&team_id, profile_id).await?;
+
+                if let Some(app_id_id) = profiles
+                    .iter()
+                    .find(|p| p.provisioning_profile_id == *profile_id)
+                    .and_then(|p| p.app_id_id.as_ref())
+                {
+                    cache.remove(&team_id, app_id_id).await?;
+                }
+
+                log::info!("Deleted profile {}", profile_id);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn session_with_team(team_id: Option<String>) -> Result<(DeveloperSession, String)> {
+    let session = get_authenticated_account().await?;
+    let team_id = match team_id {
+        Some(team_id) => team_id,
+        None => teams(&session).await?,
+    };
+
+    Ok((session, team_id))
+}
+
+async fn account_info() -> Result<()> {
+    let session = get_authenticated_account().await?;
+    let teams = session.qh_list_teams().await?.teams;
+
+    let Some(first) = teams.first() else {
+        return Err(anyhow::anyhow!("No teams available for this account"));
+    };
+
+    let developer = session.qh_get_account_info(&first.team_id).await?.developer;
+    log::info!(
+        "{} <{}> ({})",
+        developer.name(),
+        developer.email,
+        developer.developer_status
+    );
+
+    for team in &teams {
+        log::info!("{} ({})", team.name, team.team_id);
+        log::info!("  Type: {}", team.team_type());
+        log::info!(
+            "  Free only: {}",
+            if team.is_free() {
+                "yes, apps expire after 7 days"
+            } else {
+                "no"
+            }
+        );
+        log::info!("  Status: {}", team.membership_status());
+
+        for membership in team.memberships() {
+            log::info!(
+                "  Membership: {} ({}){}{}",
+                membership.name().unwrap_or(membership.product_id()),
+                membership.platform(),
+                membership
+                    .date_expire()
+                    .map(|e| format!(", expires {}", format_time(e)))
+                    .unwrap_or_default(),
+                if membership.in_renewal_window() {
+                    ", up for renewal"
+                } else {
+                    ""
+                }
+            );
+        }
+
+        if !team.roles().is_empty() {
+            log::info!("  Roles: {}", team.roles().join(", "));
+        }
+    }
+
+    Ok(())
+}
+
 async fn list_accounts() -> Result<()> {
     let settings_path = get_settings_path();
     let settings = AccountStore::load(&Some(settings_path)).await?;
@@ -312,9 +1261,29 @@ async fn list_accounts() -> Result<()> {
             ""
         };
 
-        log::info!(" [{}] {} {}", account.first_name(), email, selected);
+        let status = if account.needs_relogin() {
+            "(session expired, run 'account login --refresh')"
+        } else {
+            ""
+        };
+
+        log::info!(
+            " [{}] {} {} {}",
+            account.first_name(),
+            email,
+            selected,
+            status
+        );
     }
 
+    log::info!(
+        "Tokens are stored in: {}",
+        settings
+            .secret_backend()
+            .map(|s| s.name())
+            .unwrap_or("accounts.json (plaintext)")
+    );
+
     Ok(())
 }
 
@@ -0,0 +1,122 @@
+use anyhow::{Ok, Result};
+use clap::{Args, Subcommand};
+
+use plume_core::{AnisetteConfiguration, auth::anisette_provider::AnisetteProvider};
+use plume_store::AccountStore;
+
+use crate::commands::account::get_settings_path;
+use crate::get_data_path;
+
+#[derive(Debug, Args)]
+#[command(arg_required_else_help = true)]
+pub struct AnisetteArgs {
+    #[command(subcommand)]
+    pub command: AnisetteCommands,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum AnisetteCommands {
+    /// List the configured providers, in the order they're tried
+    List,
+    /// Add a provider: `local` or the URL of an anisette v3 server
+    Add(AddProviderArgs),
+    /// Remove a provider
+    Remove(RemoveProviderArgs),
+    /// Fetch anisette data from providers and report how long each took
+    Check(CheckProvidersArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct AddProviderArgs {
+    /// `local` or an anisette v3 server URL
+    #[arg(value_name = "PROVIDER", required = true)]
+    pub provider: AnisetteProvider,
+    /// Try this provider before the others
+    #[arg(long = "first")]
+    pub first: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct RemoveProviderArgs {
+    /// Provider as shown by `anisette list`
+    #[arg(value_name = "PROVIDER", required = true)]
+    pub provider: AnisetteProvider,
+}
+
+#[derive(Debug, Args)]
+pub struct CheckProvidersArgs {
+    /// Providers to check, defaults to the configured ones
+    #[arg(value_name = "PROVIDER")]
+    pub providers: Vec<AnisetteProvider>,
+}
+
+pub async fn execute(args: AnisetteArgs) -> Result<()> {
+    let mut settings = AccountStore::load(&Some(get_settings_path())).await?;
+
+    match args.command {
+        AnisetteCommands::List => {
+            if settings.anisette_providers().is_empty() {
+                log::info!("No providers configured, omnisette's default is used.");
+            }
+
+            for (index, provider) in settings.anisette_providers().iter().enumerate() {
+                log::info!(" {}. {}", index + 1, provider);
+            }
+        }
+        AnisetteCommands::Add(add_args) => {
+            let mut providers = settings.anisette_providers().to_vec();
+            providers.retain(|p| *p != add_args.provider);
+            if add_args.first {
+                providers.insert(0, add_args.provider.clone());
+            } else {
+                providers.push(add_args.provider.clone());
+            }
+
+            settings.set_anisette_providers(providers).await?;
+            log::info!("Added anisette provider {}", add_args.provider);
+        }
+        AnisetteCommands::Remove(remove_args) => {
+            let mut providers = settings.anisette_providers().to_vec();
+            let before = providers.len();
+            providers.retain(|p| *p != remove_args.provider);
+            if providers.len() == before {
+                return Err(anyhow::anyhow!(
+                    "{} is not a configured provider",
+                    remove_args.provider
+                ));
+            }
+
+            settings.set_anisette_providers(providers).await?;
+            log::info!("Removed anisette provider {}", remove_args.provider);
+        }
+        AnisetteCommands::Check(check_args) => {
+            let providers = if check_args.providers.is_empty() {
+                settings.anisette_providers().to_vec()
+            } else {
+                check_args.providers
+            };
+
+            if providers.is_empty() {
+                return Err(anyhow::anyhow!(
+                    "No providers configured, pass the ones to check or add them with 'plumesign anisette add'"
+                ));
+            }
+
+            let config = AnisetteConfiguration::default().set_configuration_path(get_data_path());
+            for provider in providers {
+                let health = provider.check(&config).await;
+                match &health.error {
+                    None => log::info!(" {} ok in {} ms", provider, health.latency.as_millis()),
+                    Some(e) => log::warn!(
+                        " {} failed after {} ms: {}",
+                        provider,
+                        health.latency.as_millis(),
+                        e
+                    ),
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
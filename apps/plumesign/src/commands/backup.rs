@@ -0,0 +1,135 @@
+use std::path::PathBuf;
+
+use anyhow::{Ok, Result};
+use clap::{Args, Subcommand};
+use dialoguer::{Confirm, Password};
+
+use plume_store::AccountStore;
+
+use crate::commands::account::get_settings_path;
+use crate::get_data_path;
+
+/// Passphrase for `backup create|restore`, prompted for when unset.
+const BACKUP_PASSPHRASE_ENV: &str = "PLUME_BACKUP_PASSPHRASE";
+
+#[derive(Debug, Args)]
+#[command(arg_required_else_help = true)]
+pub struct BackupArgs {
+    #[command(subcommand)]
+    pub command: BackupCommands,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum BackupCommands {
+    /// Save accounts, signing keys, anisette state and refresh entries to an encrypted archive
+    Create(CreateBackupArgs),
+    /// Restore an archive made with `backup create`, e.g. on a new machine
+    Restore(RestoreBackupArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct CreateBackupArgs {
+    /// Where to write the archive
+    #[arg(value_name = "ARCHIVE", required = true)]
+    pub archive: PathBuf,
+    /// Include the app copies kept for refreshing, the archive is built in memory
+    #[arg(long = "include-apps")]
+    pub include_apps: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct RestoreBackupArgs {
+    /// Archive to restore
+    #[arg(value_name = "ARCHIVE", required = true)]
+    pub archive: PathBuf,
+    /// Don't ask before replacing accounts and refresh entries
+    #[arg(short = 'y', long = "yes")]
+    pub yes: bool,
+    /// Replace this machine's signing keys and anisette state with the backup's
+    #[arg(long = "force")]
+    pub force: bool,
+}
+
+pub async fn execute(args: BackupArgs) -> Result<()> {
+    let mut settings = AccountStore::load(&Some(get_settings_path())).await?;
+
+    match args.command {
+        BackupCommands::Create(create_args) => {
+            if create_args.archive.exists()
+                && !Confirm::new()
+                    .with_prompt(format!("Overwrite {}?", create_args.archive.display()))
+                    .default(false)
+                    .interact()?
+            {
+                return Ok(());
+            }
+
+            let passphrase = passphrase(true)?;
+            let summary = settings.create_backup(
+                &get_data_path(),
+                &create_args.archive,
+                &passphrase,
+                create_args.include_apps,
+            )?;
+
+            log::info!(
+                "Backed up {} account(s), {} refresh device(s) and {} file(s) to {}",
+                summary.accounts,
+                summary.refresh_devices,
+                summary.files,
+                create_args.archive.display()
+            );
+            log::warn!("The archive holds your sessions and signing keys, keep it somewhere safe.");
+        }
+        BackupCommands::Restore(restore_args) => {
+            if !restore_args.yes
+                && !settings.accounts().is_empty()
+                && !Confirm::new()
+                    .with_prompt(
+                        "Accounts and refresh entries in the backup replace the ones here, continue?",
+                    )
+                    .default(false)
+                    .interact()?
+            {
+                return Ok(());
+            }
+
+            let passphrase = passphrase(false)?;
+            let summary = settings
+                .restore_backup(
+                    &get_data_path(),
+                    &restore_args.archive,
+                    &passphrase,
+                    restore_args.force,
+                )
+                .map_err(|e| match e {
+                    plume_core::Error::BackupWouldReplace(_) => {
+                        anyhow::anyhow!("{e}, pass --force to replace them")
+                    }
+                    e => e.into(),
+                })?;
+
+            log::info!(
+                "Restored {} account(s), {} refresh device(s) and {} file(s)",
+                summary.accounts,
+                summary.refresh_devices,
+                summary.files
+            );
+        }
+    }
+
+    Ok(())
+}
+
+fn passphrase(confirm: bool) -> Result<String> {
+    if let std::result::Result::Ok(passphrase) = std::env::var(BACKUP_PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+
+    let mut prompt = Password::new().with_prompt("Backup passphrase");
+    if confirm {
+        prompt = prompt.with_confirmation("Repeat passphrase", "Passphrases don't match");
+    }
+
+    Ok(prompt.interact()?)
+}
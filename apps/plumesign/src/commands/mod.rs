@@ -1,6 +1,8 @@
 use clap::{Parser, Subcommand};
 
 pub mod account;
+pub mod anisette;
+pub mod backup;
 pub mod device;
 pub mod macho;
 pub mod sign;
@@ -29,4 +31,8 @@ pub enum Commands {
     Account(account::AccountArgs),
     /// Device management commands
     Device(device::DeviceArgs),
+    /// Choose and check anisette providers
+    Anisette(anisette::AnisetteArgs),
+    /// Move accounts, keys and refresh entries to another machine
+    Backup(backup::BackupArgs),
 }
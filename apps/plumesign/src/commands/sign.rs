@@ -3,12 +3,19 @@ use std::path::PathBuf;
 use anyhow::Result;
 use clap::Args;
 
-use plume_core::{CertificateIdentity, MobileProvision};
-use plume_utils::{Bundle, Package, Signer, SignerMode, SignerOptions};
+use plume_core::{
+    CertificateIdentity, MobileProvision,
+    developer::{ApiKey, DeveloperSession, v1::certificates::CertificateKind},
+};
+use plume_store::{ProfileCache, key_store};
+use plume_utils::{
+    Bundle, Package, Signer, SignerDistribution, SignerHookingRuntime, SignerMode, SignerOptions,
+    TweakWarnings,
+};
 
 use crate::{
     commands::{
-        account::{get_authenticated_account, teams},
+        account::{get_authenticated_account, get_selected_account, teams},
         device::select_device,
     },
     get_data_path,
@@ -26,6 +33,31 @@ pub struct SignArgs {
     /// Use Apple ID credentials for signing
     #[arg(long = "apple-id")]
     pub apple_id: bool,
+    /// App Store Connect API key ID, signs with the key instead of an Apple ID
+    #[arg(
+        long = "api-key-id",
+        value_name = "KEY_ID",
+        env = "ASC_KEY_ID",
+        requires_all = ["api_issuer", "api_key", "team"],
+        conflicts_with_all = ["apple_id", "pem_files"]
+    )]
+    pub api_key_id: Option<String>,
+    /// App Store Connect API issuer ID
+    #[arg(long = "api-issuer", value_name = "ISSUER_ID", env = "ASC_ISSUER_ID")]
+    pub api_issuer: Option<String>,
+    /// Path to the App Store Connect API private key (.p8)
+    #[arg(long = "api-key", value_name = "P8", env = "ASC_KEY_PATH")]
+    pub api_key: Option<PathBuf>,
+    /// Team ID to sign with, prompts when the account has several (required with an API key)
+    #[arg(long, value_name = "TEAM_ID")]
+    pub team: Option<String>,
+    /// Never revoke an existing certificate when the team has too many
+    #[arg(long = "no-revoke")]
+    pub no_revoke: bool,
+    /// Provisioning profile to register with --apple-id: `development`, `adhoc` (all of a
+    /// paid team's devices) or `inhouse` (enterprise teams)
+    #[arg(long, value_name = "DISTRIBUTION", default_value = "development")]
+    pub distribution: SignerDistribution,
     /// Provisioning profile files to embed
     #[arg(long = "provision", value_name = "PROVISION")]
     pub provisioning_files: Option<PathBuf>,
@@ -41,6 +73,12 @@ pub struct SignArgs {
     /// Perform ad-hoc signing (no certificate required)
     #[arg(long, short, num_args = 1..)]
     pub tweaks: Option<Vec<PathBuf>>,
+    /// Hooking runtime to inject with tweaks: `ellekit`, `none`, or a path to a .deb/.framework
+    #[arg(long, value_name = "RUNTIME", default_value = "ellekit")]
+    pub hooking_runtime: SignerHookingRuntime,
+    /// Apply tweaks even when they declare conflicts with each other
+    #[arg(long = "allow-tweak-conflicts")]
+    pub allow_tweak_conflicts: bool,
     /// Register device and install after signing
     #[arg(long)]
     pub register_and_install: bool,
@@ -57,7 +95,14 @@ pub struct SignArgs {
 }
 
 pub async fn execute(args: SignArgs) -> Result<()> {
-    if !args.package.is_dir() && !args.apple_id && args.output.is_none() {
+    let api_key = match (&args.api_key_id, &args.api_issuer, &args.api_key) {
+        (Some(key_id), Some(issuer), Some(path)) => {
+            Some(ApiKey::from_file(issuer.clone(), key_id.clone(), path)?)
+        }
+        _ => None,
+    };
+
+    if !args.package.is_dir() && !args.apple_id && api_key.is_none() && args.output.is_none() {
         return Err(anyhow::anyhow!(
             "-o/--output is required when signing an .ipa without --apple-id (ad-hoc mode)."
         ));
@@ -68,6 +113,9 @@ pub async fn execute(args: SignArgs) -> Result<()> {
         custom_name: args.name,
         custom_version: args.version,
         tweaks: args.tweaks,
+        hooking_runtime: args.hooking_runtime,
+        allow_tweak_conflicts: args.allow_tweak_conflicts,
+        distribution: args.distribution,
         ..Default::default()
     };
 
@@ -92,10 +140,61 @@ pub async fn execute(args: SignArgs) -> Result<()> {
         (Signer::new(Some(cert_identity), options), None)
     } else if args.apple_id {
         let session = get_authenticated_account().await?;
-        let team_id = teams(&session).await?;
-        let cert_identity =
-            CertificateIdentity::new_with_session(&session, get_data_path(), None, &team_id, false)
-                .await?;
+        let team_id = match args.team {
+            Some(team_id) => team_id,
+            None => teams(&session).await?,
+        };
+        options.team_id = Some(team_id.clone());
+        let free_team = session
+            .qh_list_teams()
+            .await?
+            .teams
+            .iter()
+            .any(|t| t.team_id == team_id && t.is_free());
+        let cert_identity = if args.distribution == SignerDistribution::Development {
+            let auto_revoke = !args.no_revoke && !get_selected_account().await?.never_revoke();
+            CertificateIdentity::new_with_session(
+                &session,
+                &key_store(&get_data_path())?,
+                None,
+                &team_id,
+                false,
+                auto_revoke,
+            )
+            .await?
+        } else {
+            CertificateIdentity::new_distribution_with_session(
+                &session,
+                &key_store(&get_data_path())?,
+                &team_id,
+                false,
+            )
+            .await?
+        };
+
+        options.mode = SignerMode::Pem;
+        (
+            Signer::new(Some(cert_identity), options).with_free_team(free_team),
+            Some((session, team_id)),
+        )
+    } else if let Some(api_key) = api_key {
+        let team_id = args
+            .team
+            .ok_or_else(|| anyhow::anyhow!("--team is required when signing with an API key."))?;
+        let session = DeveloperSession::using_api_key(api_key).await?;
+        let kind = args
+            .distribution
+            .profile_type()
+            .map(|p| p.certificate_kind())
+            .unwrap_or(CertificateKind::Development);
+        let cert_identity = CertificateIdentity::new_with_v1_session(
+            &session,
+            &key_store(&get_data_path())?,
+            &team_id,
+            kind,
+            false,
+        )
+        .await?;
 
         options.mode = SignerMode::Pem;
         (
@@ -138,15 +237,30 @@ pub async fn execute(args: SignArgs) -> Result<()> {
     };
 
     if let Some((session, team_id)) = team_id_opt {
-        signer
+        signer = signer.with_profile_cache(
+            ProfileCache::new(get_data_path().join("profiles")),
+            device
+                .as_ref()
+                .filter(|d| !d.is_mac)
+                .map(|d| d.udid.clone()),
+        );
+
+        let warnings = signer
             .modify_bundle(&bundle, &Some(team_id.clone()))
             .await?;
+        log_tweak_warnings(&warnings);
 
         if let Some(ref dev) = device {
             log::info!("Registering device: {} ({})", dev.name, dev.udid);
-            session
-                .qh_ensure_device(&team_id, &dev.name, &dev.udid)
-                .await?;
+            if session.uses_api_key() {
+                session
+                    .v1_ensure_device(&team_id, &dev.name, &dev.udid)
+                    .await?;
+            } else {
+                session
+                    .qh_ensure_device(&team_id, &dev.name, &dev.udid)
+                    .await?;
+            }
         }
 
         signer
@@ -177,7 +291,8 @@ pub async fn execute(args: SignArgs) -> Result<()> {
             log::info!("Installation complete!");
         }
     } else {
-        signer.modify_bundle(&bundle, &None).await?;
+        let warnings = signer.modify_bundle(&bundle, &None).await?;
+        log_tweak_warnings(&warnings);
         signer.sign_bundle(&bundle).await?;
 
         if let Some(dev) = device {
@@ -205,3 +320,9 @@ pub async fn execute(args: SignArgs) -> Result<()> {
 
     Ok(())
 }
+
+fn log_tweak_warnings(warnings: &TweakWarnings) {
+    for message in warnings.messages() {
+        log::warn!("{}", message);
+    }
+}
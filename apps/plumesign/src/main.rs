@@ -18,6 +18,8 @@ async fn main() -> anyhow::Result<()> {
         Commands::MachO(args) => commands::macho::execute(args).await?,
         Commands::Account(args) => commands::account::execute(args).await?,
         Commands::Device(args) => commands::device::execute(args).await?,
+        Commands::Anisette(args) => commands::anisette::execute(args).await?,
+        Commands::Backup(args) => commands::backup::execute(args).await?,
     }
 
     Ok(())
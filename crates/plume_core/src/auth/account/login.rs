@@ -9,56 +9,64 @@ use crate::Error;
 
 use crate::auth::account::{check_error, parse_response};
 use crate::auth::anisette_data::AnisetteData;
+use crate::auth::two_factor::{TwoFactorMethod, TwoFactorProvider};
 use crate::auth::{
     Account, ChallengeRequest, ChallengeRequestBody, GSA_ENDPOINT, InitRequest, InitRequestBody,
     LoginState, RequestHeader,
 };
 
+/// Reads a string out of nested plist dictionaries, a missing key or wrong type
+/// is a [`MalformedAuthResponse`](crate::Error::MalformedAuthResponse).
 #[macro_export]
 macro_rules! plist_get_string {
     ($base:expr, $( $path:literal )+, $final_key:literal) => {{
-        let mut current_val = $base;
-        $(
-            current_val = current_val
-                .get($path)
-                .expect(concat!("Missing dictionary key: ", $path))
-                .as_dictionary()
-                .expect(concat!("Key value is not a dictionary: ", $path));
-        )+
-        current_val
-            .get($final_key)
-            .expect(concat!("Missing string key: ", $final_key))
-            .as_string()
-            .expect(concat!("Value is not a string: ", $final_key))
-            .to_string()
+        (|| -> Result<String, $crate::Error> {
+            let current_val = $base;
+            $(
+                let current_val = current_val
+                    .get($path)
+                    .and_then(|v| v.as_dictionary())
+                    .ok_or_else(|| $crate::Error::MalformedAuthResponse(
+                        concat!("missing dictionary '", $path, "'").to_string()
+                    ))?;
+            )+
+            current_val
+                .get($final_key)
+                .and_then(|v| v.as_string())
+                .map(|v| v.to_string())
+                .ok_or_else(|| $crate::Error::MalformedAuthResponse(
+                    concat!("missing string '", $final_key, "'").to_string()
+                ))
+        })()
     }};
 
     ($base:expr, $key:literal) => {{
         $base
             .get($key)
-            .expect(concat!("Missing key: ", $key))
-            .as_string()
-            .expect(concat!("Value is not a string: ", $key))
-            .to_string()
+            .and_then(|v| v.as_string())
+            .map(|v| v.to_string())
+            .ok_or_else(|| $crate::Error::MalformedAuthResponse(
+                concat!("missing string '", $key, "'").to_string()
+            ))
     }};
 }
 
 impl Account {
     pub async fn login(
         appleid_closure: impl Fn() -> Result<(String, String), String>,
-        tfa_closure: impl Fn() -> Result<String, String>,
+        two_factor: impl TwoFactorProvider,
         config: AnisetteConfiguration,
     ) -> Result<Account, Error> {
         let anisette = AnisetteData::new(config).await?;
-        Account::login_with_anisette(appleid_closure, tfa_closure, anisette).await
+        Account::login_with_anisette(appleid_closure, two_factor, anisette).await
     }
 
     pub async fn login_with_anisette<
         F: Fn() -> Result<(String, String), String>,
-        G: Fn() -> Result<String, String>,
+        G: TwoFactorProvider,
     >(
         appleid_closure: F,
-        tfa_closure: G,
+        two_factor: G,
         anisette: AnisetteData,
     ) -> Result<Account, Error> {
         let mut _self = Account::new_with_anisette(anisette)?;
@@ -67,22 +75,47 @@ impl Account {
         })?;
 
         let mut response = _self.login_email_pass(&username, &password).await?;
+        // digits of the number the SMS went to, shown when asking for the code
+        let mut sms_number = String::new();
 
         loop {
             match response {
                 LoginState::NeedsDevice2FA => response = _self.send_2fa_to_devices().await?,
                 LoginState::Needs2FAVerification => {
                     response = _self
-                        .verify_2fa(tfa_closure().map_err(|e| {
-                            Error::AuthSrpWithMessage(0, format!("Failed to get 2FA code: {}", e))
-                        })?)
+                        .verify_2fa(two_factor.code(&TwoFactorMethod::TrustedDevice).map_err(
+                            |e| {
+                                Error::AuthSrpWithMessage(
+                                    0,
+                                    format!("Failed to get 2FA code: {}", e),
+                                )
+                            },
+                        )?)
                         .await?
                 }
-                LoginState::NeedsSMS2FA => response = _self.send_sms_2fa_to_devices(1).await?,
+                LoginState::NeedsSMS2FA => {
+                    let numbers = _self.get_auth_extras().await?.trusted_phone_numbers;
+                    let phone_id = two_factor.select_phone_number(&numbers).map_err(|e| {
+                        Error::AuthSrpWithMessage(
+                            0,
+                            format!("Failed to pick a phone number: {}", e),
+                        )
+                    })?;
+                    sms_number = numbers
+                        .iter()
+                        .find(|n| n.id == phone_id)
+                        .map(|n| n.last_two_digits.clone())
+                        .unwrap_or_default();
+
+                    response = _self.send_sms_2fa_to_devices(phone_id).await?
+                }
                 LoginState::NeedsSMS2FAVerification(body) => {
+                    let method = TwoFactorMethod::Sms {
+                        last_two_digits: sms_number.clone(),
+                    };
                     response = _self
                         .verify_sms_2fa(
-                            tfa_closure().map_err(|e| {
+                            two_factor.code(&method).map_err(|e| {
                                 Error::AuthSrpWithMessage(
                                     0,
                                     format!("Failed to get SMS 2FA code: {}", e),
@@ -117,7 +150,7 @@ impl Account {
         let a: Vec<u8> = (0..32).map(|_| rand::random::<u8>()).collect();
         let a_pub = srp_client.compute_public_ephemeral(&a);
 
-        let anisette = self.get_anisette().await;
+        let anisette = self.get_anisette().await?;
 
         let mut gsa_headers = HeaderMap::new();
         gsa_headers.insert(
@@ -131,7 +164,8 @@ impl Account {
         );
         gsa_headers.insert(
             "X-MMe-Client-Info",
-            HeaderValue::from_str(&anisette.get_header("x-mme-client-info")?).unwrap(),
+            HeaderValue::from_str(&anisette.get_header("x-mme-client-info")?)
+                .map_err(|_| Error::MalformedAnisette("X-Mme-Client-Info".into()))?,
         );
 
         let header = RequestHeader {
@@ -139,7 +173,7 @@ impl Account {
         };
         let init_body = InitRequestBody {
             a_pub: plist::Value::Data(a_pub),
-            cpd: anisette.to_plist(true, false, false),
+            cpd: anisette.to_plist(true, false, false)?,
             operation: "init".to_string(),
             ps: vec!["s2k".to_string(), "s2k_fo".to_string()],
             username: username.to_string(),
@@ -154,8 +188,9 @@ impl Account {
         plist::to_writer_xml(&mut buffer, &init_packet)?;
 
         let res = self
-            .client
-            .post(GSA_ENDPOINT)
+            .transport
+            .client()
+            .post(self.transport.gsa_endpoint(GSA_ENDPOINT))
             .headers(gsa_headers.clone())
             .body(buffer)
             .send()
@@ -164,29 +199,31 @@ impl Account {
         let res = parse_response(res).await?;
         check_error(&res)?;
 
-        let salt = res.get("s").unwrap().as_data().unwrap();
-        let b_pub = res.get("B").unwrap().as_data().unwrap();
-        let iters = res.get("i").unwrap().as_signed_integer().unwrap();
-        let c = res.get("c").unwrap().as_string().unwrap();
+        let salt = response_data(&res, "s")?;
+        let b_pub = response_data(&res, "B")?;
+        let iters = res
+            .get("i")
+            .and_then(Value::as_signed_integer)
+            .and_then(|i| u32::try_from(i).ok())
+            .ok_or_else(|| Error::MalformedAuthResponse("missing iterations 'i'".into()))?;
+        let c = res
+            .get("c")
+            .and_then(Value::as_string)
+            .ok_or_else(|| Error::MalformedAuthResponse("missing 'c'".into()))?;
 
         let hashed_password = Sha256::digest(password.as_bytes());
 
         let mut password_buf = [0u8; 32];
-        pbkdf2::pbkdf2::<hmac::Hmac<Sha256>>(
-            &hashed_password,
-            salt,
-            iters as u32,
-            &mut password_buf,
-        );
+        pbkdf2::pbkdf2::<hmac::Hmac<Sha256>>(&hashed_password, salt, iters, &mut password_buf);
 
         let verifier: SrpClientVerifier<Sha256> = srp_client
             .process_reply(&a, username.as_bytes(), &password_buf, salt, b_pub)
-            .unwrap();
+            .map_err(|_| Error::MalformedAuthResponse("invalid SRP reply".into()))?;
 
         let challenge_body = ChallengeRequestBody {
             m: plist::Value::Data(verifier.proof().to_vec()),
             c: c.to_string(),
-            cpd: anisette.to_plist(true, false, false),
+            cpd: anisette.to_plist(true, false, false)?,
             operation: "complete".to_string(),
             username: username.to_string(),
         };
@@ -200,8 +237,9 @@ impl Account {
         plist::to_writer_xml(&mut buffer, &challenge_packet)?;
 
         let res = self
-            .client
-            .post(GSA_ENDPOINT)
+            .transport
+            .client()
+            .post(self.transport.gsa_endpoint(GSA_ENDPOINT))
             .headers(gsa_headers)
             .body(buffer)
             .send()
@@ -210,12 +248,14 @@ impl Account {
         let res = parse_response(res).await?;
         check_error(&res)?;
 
-        let m2 = res.get("M2").unwrap().as_data().unwrap();
-        verifier.verify_server(m2).unwrap();
+        let m2 = response_data(&res, "M2")?;
+        verifier
+            .verify_server(m2)
+            .map_err(|_| Error::MalformedAuthResponse("server proof didn't verify".into()))?;
 
-        let spd_encrypted = res.get("spd").unwrap().as_data().unwrap();
-        let spd_decrypted = super::decrypt_cbc(&verifier, spd_encrypted);
-        let mut spd: Dictionary = plist::from_bytes(&spd_decrypted).unwrap();
+        let spd_encrypted = response_data(&res, "spd")?;
+        let spd_decrypted = super::decrypt_cbc(&verifier, spd_encrypted)?;
+        let mut spd: Dictionary = plist::from_bytes(&spd_decrypted)?;
 
         if !spd.contains_key("appleId") {
             spd.insert(
@@ -226,7 +266,10 @@ impl Account {
 
         self.spd = Some(spd);
 
-        let status = res.get("Status").unwrap().as_dictionary().unwrap();
+        let status = res
+            .get("Status")
+            .and_then(Value::as_dictionary)
+            .ok_or_else(|| Error::MalformedAuthResponse("missing 'Status'".into()))?;
         if let Some(Value::String(auth_type)) = status.get("au") {
             return match auth_type.as_str() {
                 "trustedDeviceSecondaryAuth" => Ok(LoginState::NeedsDevice2FA),
@@ -239,22 +282,129 @@ impl Account {
     }
 
     pub fn get_pet(&self) -> Option<String> {
-        let base = self.spd.as_ref().unwrap();
+        let base = self.spd.as_ref()?;
         let token = base.get("t")?.as_dictionary()?;
 
-        Some(plist_get_string!(token, "com.apple.gs.idms.pet", "token"))
+        plist_get_string!(token, "com.apple.gs.idms.pet", "token").ok()
     }
 
-    pub fn get_name(&self) -> (String, String) {
-        let base = self.spd.as_ref().unwrap();
-        (plist_get_string!(base, "fn"), plist_get_string!(base, "ln"))
+    pub fn get_name(&self) -> Result<(String, String), Error> {
+        let base = self.spd()?;
+        Ok((
+            plist_get_string!(base, "fn")?,
+            plist_get_string!(base, "ln")?,
+        ))
     }
 
-    pub async fn get_anisette(&self) -> AnisetteData {
+    pub async fn get_anisette(&self) -> Result<AnisetteData, Error> {
         let mut locked = self.anisette.lock().await;
         if locked.needs_refresh() {
-            *locked = locked.refresh().await.unwrap();
+            *locked = locked.refresh().await?;
+        }
+        Ok(locked.clone())
+    }
+}
+
+fn response_data<'a>(res: &'a Dictionary, key: &str) -> Result<&'a [u8], Error> {
+    res.get(key)
+        .and_then(Value::as_data)
+        .ok_or_else(|| Error::MalformedAuthResponse(format!("missing '{key}'")))
+}
+
+#[cfg(test)]
+mod tests {
+    use plist::{Dictionary, Value};
+
+    use crate::Error;
+
+    fn dict(entries: &[(&str, Value)]) -> Dictionary {
+        entries
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn plist_get_string_rejects_malformed_paths() {
+        let token = dict(&[("token", "TOKEN".into())]);
+        let cases = [
+            ("no t", dict(&[])),
+            ("t as a string", dict(&[("t", "t".into())])),
+            ("no pet", dict(&[("t", Value::Dictionary(dict(&[])))])),
+            (
+                "pet as an array",
+                dict(&[(
+                    "t",
+                    Value::Dictionary(dict(&[(
+                        "com.apple.gs.idms.pet",
+                        Value::Array(vec![Value::Dictionary(token.clone())]),
+                    )])),
+                )]),
+            ),
+            (
+                "no token",
+                dict(&[(
+                    "t",
+                    Value::Dictionary(dict(&[(
+                        "com.apple.gs.idms.pet",
+                        Value::Dictionary(dict(&[("expiry", "0".into())])),
+                    )])),
+                )]),
+            ),
+            (
+                "token as data",
+                dict(&[(
+                    "t",
+                    Value::Dictionary(dict(&[(
+                        "com.apple.gs.idms.pet",
+                        Value::Dictionary(dict(&[("token", Value::Data(b"TOKEN".to_vec()))])),
+                    )])),
+                )]),
+            ),
+        ];
+
+        for (name, res) in &cases {
+            assert!(
+                matches!(
+                    plist_get_string!(res, "t" "com.apple.gs.idms.pet", "token"),
+                    Err(Error::MalformedAuthResponse(_))
+                ),
+                "{name}"
+            );
+        }
+
+        let res = dict(&[(
+            "t",
+            Value::Dictionary(dict(&[("com.apple.gs.idms.pet", Value::Dictionary(token))])),
+        )]);
+        assert_eq!(
+            plist_get_string!(res, "t" "com.apple.gs.idms.pet", "token").unwrap(),
+            "TOKEN"
+        );
+    }
+
+    #[test]
+    fn plist_get_string_rejects_malformed_keys() {
+        let cases = [
+            ("no fn", dict(&[("ln", "Appleseed".into())])),
+            ("fn as an integer", dict(&[("fn", 1.into())])),
+            (
+                "fn as a dictionary",
+                dict(&[("fn", Value::Dictionary(dict(&[])))]),
+            ),
+        ];
+
+        for (name, base) in &cases {
+            assert!(
+                matches!(
+                    plist_get_string!(base, "fn"),
+                    Err(Error::MalformedAuthResponse(_))
+                ),
+                "{name}"
+            );
         }
-        locked.clone()
+
+        let base = dict(&[("fn", "John".into())]);
+        assert_eq!(plist_get_string!(base, "fn").unwrap(), "John");
     }
 }
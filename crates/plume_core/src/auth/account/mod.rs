@@ -9,16 +9,41 @@ use sha2::Sha256;
 use srp::client::SrpClientVerifier;
 
 use crate::Error;
+use crate::auth::Account;
+
+impl Account {
+    /// Session data from signing in, an error before the account has signed in.
+    pub(crate) fn spd(&self) -> Result<&plist::Dictionary, Error> {
+        self.spd.as_ref().ok_or(Error::NotSignedIn)
+    }
+
+    pub(crate) fn spd_string(&self, key: &str) -> Result<&str, Error> {
+        self.spd()?
+            .get(key)
+            .and_then(plist::Value::as_string)
+            .ok_or_else(|| Error::MalformedAuthResponse(format!("session data has no '{key}'")))
+    }
+
+    pub(crate) fn spd_data(&self, key: &str) -> Result<&[u8], Error> {
+        self.spd()?
+            .get(key)
+            .and_then(plist::Value::as_data)
+            .ok_or_else(|| Error::MalformedAuthResponse(format!("session data has no '{key}'")))
+    }
+}
 
 pub async fn parse_response(
     res: Result<Response, reqwest::Error>,
 ) -> Result<plist::Dictionary, Error> {
     let res = res?.text().await?;
-    let res: plist::Dictionary = plist::from_bytes(res.as_bytes())?;
-    let res: plist::Value = res.get("Response").unwrap().to_owned();
-    match res {
-        plist::Value::Dictionary(dict) => Ok(dict),
-        _ => Err(crate::Error::Parse),
+    let res: plist::Dictionary = plist::from_bytes(res.as_bytes())
+        .map_err(|e| Error::MalformedAuthResponse(format!("not a plist dictionary: {e}")))?;
+    match res.get("Response") {
+        Some(plist::Value::Dictionary(dict)) => Ok(dict.to_owned()),
+        Some(_) => Err(Error::MalformedAuthResponse(
+            "'Response' is not a dictionary".into(),
+        )),
+        None => Err(Error::MalformedAuthResponse("no 'Response'".into())),
     }
 }
 
@@ -28,25 +53,31 @@ pub fn check_error(res: &plist::Dictionary) -> Result<(), Error> {
         _ => &res,
     };
 
-    if res.get("ec").unwrap().as_signed_integer().unwrap() != 0 {
-        return Err(Error::AuthSrpWithMessage(
-            res.get("ec").unwrap().as_signed_integer().unwrap().into(),
-            res.get("em").unwrap().as_string().unwrap().to_owned(),
-        ));
+    let ec = res
+        .get("ec")
+        .and_then(plist::Value::as_signed_integer)
+        .ok_or_else(|| Error::MalformedAuthResponse("no error code 'ec'".into()))?;
+
+    if ec != 0 {
+        let em = res
+            .get("em")
+            .and_then(plist::Value::as_string)
+            .unwrap_or("Unknown error");
+        return Err(Error::AuthSrpWithMessage(ec, em.to_owned()));
     }
 
     Ok(())
 }
 
-pub fn decrypt_cbc(usr: &SrpClientVerifier<Sha256>, data: &[u8]) -> Vec<u8> {
+pub fn decrypt_cbc(usr: &SrpClientVerifier<Sha256>, data: &[u8]) -> Result<Vec<u8>, Error> {
     let extra_data_key = create_session_key(usr, "extra data key:");
     let extra_data_iv = create_session_key(usr, "extra data iv:");
     let extra_data_iv = &extra_data_iv[..16];
 
     cbc::Decryptor::<aes::Aes256>::new_from_slices(&extra_data_key, extra_data_iv)
-        .unwrap()
-        .decrypt_padded_vec_mut::<Pkcs7>(&data)
-        .unwrap()
+        .map_err(|_| Error::MalformedAuthResponse("invalid session key".into()))?
+        .decrypt_padded_vec_mut::<Pkcs7>(data)
+        .map_err(|_| Error::MalformedAuthResponse("session data failed to decrypt".into()))
 }
 
 pub fn create_session_key(usr: &SrpClientVerifier<Sha256>, name: &str) -> Vec<u8> {
@@ -57,3 +88,142 @@ pub fn create_session_key(usr: &SrpClientVerifier<Sha256>, name: &str) -> Vec<u8
         .into_bytes()
         .to_vec()
 }
+
+#[cfg(test)]
+mod tests {
+    use plist::{Dictionary, Value};
+
+    use super::{check_error, parse_response};
+    use crate::Error;
+    use crate::auth::GSA_ENDPOINT;
+    use crate::testing::{Fixture, MockServer};
+
+    fn dict(entries: &[(&str, Value)]) -> Dictionary {
+        entries
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect()
+    }
+
+    fn xml(value: Value) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        plist::to_writer_xml(&mut buffer, &value).unwrap();
+        buffer
+    }
+
+    #[test]
+    fn check_error_rejects_malformed_statuses() {
+        let cases = [
+            ("no ec", dict(&[])),
+            ("ec as a string", dict(&[("ec", "0".into())])),
+            (
+                "Status without ec",
+                dict(&[("Status", Value::Dictionary(dict(&[("em", "ok".into())])))]),
+            ),
+        ];
+
+        for (name, res) in cases {
+            assert!(
+                matches!(check_error(&res), Err(Error::MalformedAuthResponse(_))),
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn check_error_reads_error_codes() {
+        let cases = [
+            (dict(&[("ec", 0.into())]), None),
+            (
+                dict(&[("ec", (-22406).into()), ("em", "Bad password".into())]),
+                Some((-22406, "Bad password")),
+            ),
+            (dict(&[("ec", 5.into())]), Some((5, "Unknown error"))),
+            // GSA nests the status on some responses
+            (
+                dict(&[(
+                    "Status",
+                    Value::Dictionary(dict(&[("ec", (-20209).into()), ("em", "Locked".into())])),
+                )]),
+                Some((-20209, "Locked")),
+            ),
+        ];
+
+        for (res, expected) in cases {
+            match (check_error(&res), expected) {
+                (Ok(()), None) => {}
+                (Err(Error::AuthSrpWithMessage(ec, em)), Some((code, message))) => {
+                    assert_eq!((ec, em.as_str()), (code, message));
+                }
+                (result, expected) => panic!("{res:?}: got {result:?}, expected {expected:?}"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn parse_response_rejects_malformed_bodies() {
+        let cases: [(&str, Vec<u8>); 5] = [
+            ("empty body", Vec::new()),
+            ("not a plist", b"<html>502 Bad Gateway</html>".to_vec()),
+            ("array root", xml(Value::Array(vec!["Response".into()]))),
+            (
+                "no Response",
+                xml(Value::Dictionary(dict(&[("ec", 0.into())]))),
+            ),
+            (
+                "Response as a string",
+                xml(Value::Dictionary(dict(&[("Response", "ok".into())]))),
+            ),
+        ];
+
+        let server = MockServer::start().await.unwrap();
+        for (_, body) in &cases {
+            server.mock(
+                GSA_ENDPOINT,
+                Fixture::new(200, "text/x-xml-plist", body.clone()),
+            );
+        }
+
+        let transport = server.transport();
+        for (name, _) in cases {
+            let res = transport
+                .client()
+                .post(transport.gsa_endpoint(GSA_ENDPOINT))
+                .send()
+                .await;
+            assert!(
+                matches!(
+                    parse_response(res).await,
+                    Err(Error::MalformedAuthResponse(_))
+                ),
+                "{name}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn parse_response_returns_the_response_dictionary() {
+        let server = MockServer::start().await.unwrap();
+        let response = dict(&[("Status", Value::Dictionary(dict(&[("ec", 0.into())])))]);
+        server.mock(
+            GSA_ENDPOINT,
+            Fixture::new(
+                200,
+                "text/x-xml-plist",
+                xml(Value::Dictionary(dict(&[(
+                    "Response",
+                    Value::Dictionary(response.clone()),
+                )]))),
+            ),
+        );
+
+        let transport = server.transport();
+        let res = transport
+            .client()
+            .post(transport.gsa_endpoint(GSA_ENDPOINT))
+            .send()
+            .await;
+
+        assert_eq!(parse_response(res).await.unwrap(), response);
+    }
+}
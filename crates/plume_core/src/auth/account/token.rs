@@ -20,14 +20,13 @@ type Aes256Gcm16 = AesGcm<Aes256, U16>;
 
 impl Account {
     pub async fn get_app_token(&self, app_name: &str) -> Result<AppToken, Error> {
-        let spd = self.spd.as_ref().unwrap();
-        let dsid = spd.get("adsid").unwrap().as_string().unwrap();
-        let auth_token = spd.get("GsIdmsToken").unwrap().as_string().unwrap();
+        let dsid = self.spd_string("adsid")?;
+        let auth_token = self.spd_string("GsIdmsToken")?;
 
-        let valid_anisette = self.get_anisette().await;
+        let valid_anisette = self.get_anisette().await?;
 
-        let sk = spd.get("sk").unwrap().as_data().unwrap();
-        let c = spd.get("c").unwrap().as_data().unwrap();
+        let sk = self.spd_data("sk")?;
+        let c = self.spd_data("c")?;
 
         let checksum = Self::create_checksum(&sk.to_vec(), dsid, app_name);
 
@@ -43,14 +42,15 @@ impl Account {
         );
         gsa_headers.insert(
             "X-MMe-Client-Info",
-            HeaderValue::from_str(&valid_anisette.get_header("x-mme-client-info")?).unwrap(),
+            HeaderValue::from_str(&valid_anisette.get_header("x-mme-client-info")?)
+                .map_err(|_| Error::MalformedAnisette("X-Mme-Client-Info".into()))?,
         );
 
         let header = RequestHeader {
             version: "1.0.1".to_string(),
         };
         let body = AuthTokenRequestBody {
-            cpd: valid_anisette.to_plist(true, false, false),
+            cpd: valid_anisette.to_plist(true, false, false)?,
             app: vec![app_name.to_string()],
             c: plist::Value::Data(c.to_vec()),
             operation: "apptokens".to_owned(),
@@ -69,26 +69,25 @@ impl Account {
         let buffer = String::from_utf8(buffer).unwrap();
 
         let res = self
-            .client
-            .post(GSA_ENDPOINT)
+            .transport
+            .client()
+            .post(self.transport.gsa_endpoint(GSA_ENDPOINT))
             .headers(gsa_headers.clone())
             .body(buffer)
             .send()
             .await;
         let res = parse_response(res).await?;
-        let err_check = check_error(&res);
-        if err_check.is_err() {
-            return Err(err_check.err().unwrap());
-        }
+        check_error(&res)?;
 
         let encrypted_token = res
             .get("et")
-            .ok_or(Error::Parse)?
-            .as_data()
-            .ok_or(Error::Parse)?;
+            .and_then(plist::Value::as_data)
+            .ok_or_else(|| Error::MalformedAuthResponse("missing encrypted token 'et'".into()))?;
 
         if encrypted_token.len() < 3 + 16 + 16 {
-            return Err(Error::Parse);
+            return Err(Error::MalformedAuthResponse(
+                "encrypted token is too short".into(),
+            ));
         }
         let header = &encrypted_token[0..3];
         if header != b"XYZ" {
@@ -101,10 +100,9 @@ impl Account {
         let ciphertext_and_tag = &encrypted_token[19..];
 
         if sk.len() != 32 {
-            return Err(Error::Parse);
-        }
-        if iv.len() != 16 {
-            return Err(Error::Parse);
+            return Err(Error::MalformedAuthResponse(
+                "session key 'sk' is not 32 bytes".into(),
+            ));
         }
         // TODO: fucking botan
         let cipher = Aes256Gcm16::new(GenericArray::from_slice(sk));
@@ -126,17 +124,19 @@ impl Account {
                 )
             })?;
 
-        let decrypted_token: plist::Dictionary =
-            plist::from_bytes(&decrypted).map_err(|_| Error::Parse)?;
+        let decrypted_token: plist::Dictionary = plist::from_bytes(&decrypted)
+            .map_err(|_| Error::MalformedAuthResponse("decrypted token is not a plist".into()))?;
 
-        let t_val = decrypted_token.get("t").ok_or(Error::Parse)?;
-        let app_tokens = t_val.as_dictionary().ok_or(Error::Parse)?;
-        let app_token_dict = app_tokens.get(app_name).ok_or(Error::Parse)?;
-        let app_token = app_token_dict.as_dictionary().ok_or(Error::Parse)?;
-        let token = app_token
-            .get("token")
-            .and_then(|v| v.as_string())
-            .ok_or(Error::Parse)?;
+        let app_tokens = decrypted_token
+            .get("t")
+            .and_then(plist::Value::as_dictionary)
+            .ok_or_else(|| Error::MalformedAuthResponse("missing app tokens 't'".into()))?;
+        let token = app_tokens
+            .get(app_name)
+            .and_then(plist::Value::as_dictionary)
+            .and_then(|t| t.get("token"))
+            .and_then(plist::Value::as_string)
+            .ok_or_else(|| Error::MalformedAuthResponse(format!("missing token for {app_name}")))?;
 
         Ok(AppToken {
             app_tokens: app_tokens.clone(),
@@ -156,3 +156,181 @@ impl Account {
             .to_vec()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use plist::{Dictionary, Value};
+
+    use super::*;
+    use crate::testing::{Fixture, MockServer};
+
+    const APP: &str = "com.apple.gs.xcode.auth";
+    const SK: [u8; 32] = [7; 32];
+    const IV: [u8; 16] = [9; 16];
+
+    fn dict(entries: &[(&str, Value)]) -> Dictionary {
+        entries
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect()
+    }
+
+    fn spd(sk: &[u8]) -> Dictionary {
+        dict(&[
+            (
+                "adsid",
+                "000000-00-00000000-0000-0000-0000-000000000000".into(),
+            ),
+            ("GsIdmsToken", "mock-idms-token".into()),
+            ("sk", Value::Data(sk.to_vec())),
+            ("c", Value::Data(vec![1; 16])),
+        ])
+    }
+
+    // `XYZ`, the IV, then the token plist sealed with the session key
+    fn encrypted_token(token: Dictionary) -> Vec<u8> {
+        let mut plain = Vec::new();
+        plist::to_writer_xml(&mut plain, &Value::Dictionary(token)).unwrap();
+
+        let sealed = Aes256Gcm16::new(GenericArray::from_slice(&SK))
+            .encrypt(
+                GenericArray::from_slice(&IV),
+                Payload {
+                    msg: &plain,
+                    aad: b"XYZ",
+                },
+            )
+            .unwrap();
+
+        [b"XYZ".as_slice(), &IV, &sealed].concat()
+    }
+
+    fn response(et: Option<Value>) -> Fixture {
+        let mut res = dict(&[("Status", Value::Dictionary(dict(&[("ec", 0.into())])))]);
+        if let Some(et) = et {
+            res.insert("et".into(), et);
+        }
+
+        let mut body = Vec::new();
+        plist::to_writer_xml(
+            &mut body,
+            &Value::Dictionary(dict(&[("Response", Value::Dictionary(res))])),
+        )
+        .unwrap();
+        Fixture::new(200, "text/x-xml-plist", body)
+    }
+
+    async fn get_app_token(sk: &[u8], fixture: Fixture) -> Result<AppToken, Error> {
+        let server = MockServer::start().await.unwrap();
+        server.mock(GSA_ENDPOINT, fixture);
+
+        let account = Account {
+            anisette: MockServer::anisette(),
+            spd: Some(spd(sk)),
+            transport: server.transport(),
+        };
+        account.get_app_token(APP).await
+    }
+
+    #[tokio::test]
+    async fn rejects_malformed_app_tokens() {
+        let app_token = |token: Value| {
+            dict(&[(
+                "t",
+                Value::Dictionary(dict(&[(APP, Value::Dictionary(dict(&[("token", token)])))])),
+            )])
+        };
+
+        let cases = [
+            ("no et", SK.to_vec(), response(None)),
+            ("et as a string", SK.to_vec(), response(Some("et".into()))),
+            (
+                "et too short",
+                SK.to_vec(),
+                response(Some(Value::Data(b"XYZ".to_vec()))),
+            ),
+            (
+                "short session key",
+                vec![7; 16],
+                response(Some(Value::Data(encrypted_token(app_token("T".into()))))),
+            ),
+            (
+                "no t",
+                SK.to_vec(),
+                response(Some(Value::Data(encrypted_token(dict(&[]))))),
+            ),
+            (
+                "no token for the app",
+                SK.to_vec(),
+                response(Some(Value::Data(encrypted_token(dict(&[(
+                    "t",
+                    Value::Dictionary(dict(&[])),
+                )]))))),
+            ),
+            (
+                "token as an integer",
+                SK.to_vec(),
+                response(Some(Value::Data(encrypted_token(app_token(1.into()))))),
+            ),
+            (
+                "not a plist",
+                SK.to_vec(),
+                Fixture::new(200, "text/html", "<html>502 Bad Gateway</html>"),
+            ),
+        ];
+
+        for (name, sk, fixture) in cases {
+            assert!(
+                matches!(
+                    get_app_token(&sk, fixture).await,
+                    Err(Error::MalformedAuthResponse(_))
+                ),
+                "{name}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_session_data_without_keys() {
+        let server = MockServer::start().await.unwrap();
+
+        for key in ["adsid", "GsIdmsToken", "sk", "c"] {
+            let mut spd = spd(&SK);
+            spd.remove(key);
+
+            let account = Account {
+                anisette: MockServer::anisette(),
+                spd: Some(spd),
+                transport: server.transport(),
+            };
+            assert!(
+                matches!(
+                    account.get_app_token(APP).await,
+                    Err(Error::MalformedAuthResponse(_))
+                ),
+                "{key}"
+            );
+        }
+
+        // nothing is sent without the session data
+        assert!(server.requests().is_empty());
+    }
+
+    #[tokio::test]
+    async fn decrypts_app_tokens() {
+        let token = dict(&[(
+            "t",
+            Value::Dictionary(dict(&[(
+                APP,
+                Value::Dictionary(dict(&[("token", "APP-TOKEN".into())])),
+            )])),
+        )]);
+
+        let app_token = get_app_token(&SK, response(Some(Value::Data(encrypted_token(token)))))
+            .await
+            .unwrap();
+
+        assert_eq!(app_token.auth_token, "APP-TOKEN");
+        assert_eq!(app_token.app, APP);
+    }
+}
@@ -8,11 +8,12 @@ use crate::auth::{Account, AuthenticationExtras, LoginState, PhoneNumber, Verify
 
 impl Account {
     pub async fn send_2fa_to_devices(&self) -> Result<LoginState, Error> {
-        let headers = self.build_2fa_headers(false).await;
+        let headers = self.build_2fa_headers(false).await?;
 
         let res = self
-            .client
-            .get("https://gsa.apple.com/auth/verify/trusteddevice")
+            .transport
+            .client()
+            .get(self.transport.gsa_endpoint("/auth/verify/trusteddevice"))
             .headers(headers)
             .send()
             .await?;
@@ -30,7 +31,7 @@ impl Account {
     }
 
     pub async fn send_sms_2fa_to_devices(&self, phone_id: u32) -> Result<LoginState, Error> {
-        let headers = self.build_2fa_headers(true).await;
+        let headers = self.build_2fa_headers(true).await?;
 
         let body = VerifyBody {
             phone_number: PhoneNumber { id: phone_id },
@@ -39,8 +40,9 @@ impl Account {
         };
 
         let res = self
-            .client
-            .put("https://gsa.apple.com/auth/verify/phone")
+            .transport
+            .client()
+            .put(self.transport.gsa_endpoint("/auth/verify/phone"))
             .headers(headers)
             .json(&body)
             .send()
@@ -59,22 +61,22 @@ impl Account {
     }
 
     pub async fn get_auth_extras(&self) -> Result<AuthenticationExtras, Error> {
-        let headers = self.build_2fa_headers(true);
+        let headers = self.build_2fa_headers(true).await?;
 
         let req = self
-            .client
-            .get("https://gsa.apple.com/auth")
-            .headers(headers.await)
+            .transport
+            .client()
+            .get(self.transport.gsa_endpoint("/auth"))
+            .headers(headers)
             .header("Accept", "application/json")
             .send()
             .await?;
         let status = req.status().as_u16();
         let mut new_state = req.json::<AuthenticationExtras>().await?;
-        if status == 201 {
+        // 201 means the code was already texted to the primary number
+        if let (201, Some(first)) = (status, new_state.trusted_phone_numbers.first()) {
             new_state.new_state = Some(LoginState::NeedsSMS2FAVerification(VerifyBody {
-                phone_number: PhoneNumber {
-                    id: new_state.trusted_phone_numbers.first().unwrap().id,
-                },
+                phone_number: PhoneNumber { id: first.id },
                 mode: "sms".to_string(),
                 security_code: None,
             }));
@@ -86,14 +88,18 @@ impl Account {
     pub async fn verify_2fa(&self, code: String) -> Result<LoginState, Error> {
         log::debug!("Verifying SMS 2FA with code: {}", code);
 
-        let headers = self.build_2fa_headers(false);
+        let headers = self.build_2fa_headers(false).await?;
         let res = self
-            .client
-            .get("https://gsa.apple.com/grandslam/GsService2/validate")
-            .headers(headers.await)
+            .transport
+            .client()
+            .get(
+                self.transport
+                    .gsa_endpoint("/grandslam/GsService2/validate"),
+            )
+            .headers(headers)
             .header(
                 HeaderName::from_str("security-code").unwrap(),
-                HeaderValue::from_str(&code).unwrap(),
+                HeaderValue::from_str(&code).map_err(|_| Error::Bad2faCode)?,
             )
             .send()
             .await?;
@@ -112,11 +118,15 @@ impl Account {
     ) -> Result<LoginState, Error> {
         log::debug!("Verifying SMS 2FA with code: {}", code);
 
-        let headers = self.build_2fa_headers(true).await;
+        let headers = self.build_2fa_headers(true).await?;
         body.security_code = Some(VerifyCode { code });
         let res = self
-            .client
-            .post("https://gsa.apple.com/auth/verify/phone/securitycode")
+            .transport
+            .client()
+            .post(
+                self.transport
+                    .gsa_endpoint("/auth/verify/phone/securitycode"),
+            )
             .headers(headers)
             .json(&body)
             .send()
@@ -133,21 +143,14 @@ impl Account {
         Ok(LoginState::NeedsLogin)
     }
 
-    async fn build_2fa_headers(&self, sms: bool) -> HeaderMap {
-        let spd = self.spd.as_ref().unwrap();
-        let dsid = spd.get("adsid").unwrap().as_string().unwrap();
-        let token = spd.get("GsIdmsToken").unwrap().as_string().unwrap();
+    async fn build_2fa_headers(&self, sms: bool) -> Result<HeaderMap, Error> {
+        let dsid = self.spd_string("adsid")?;
+        let token = self.spd_string("GsIdmsToken")?;
 
         let identity_token = general_purpose::STANDARD.encode(format!("{}:{}", dsid, token));
 
-        let mut headers = HeaderMap::new();
-        let valid_anisette = self.get_anisette().await;
-        for (k, v) in valid_anisette.generate_headers(false, true, true) {
-            headers.insert(
-                HeaderName::from_bytes(k.as_bytes()).unwrap(),
-                HeaderValue::from_str(&v).unwrap(),
-            );
-        }
+        let valid_anisette = self.get_anisette().await?;
+        let mut headers = valid_anisette.to_header_map(false, true, true)?;
         if !sms {
             headers.insert("Content-Type", HeaderValue::from_static("text/x-xml-plist"));
             headers.insert("Accept", HeaderValue::from_static("text/x-xml-plist"));
@@ -162,10 +165,14 @@ impl Account {
             HeaderValue::from_str(&identity_token).unwrap(),
         );
 
-        if let Ok(locale) = valid_anisette.get_header("x-apple-locale") {
-            headers.insert("Loc", HeaderValue::from_str(&locale).unwrap());
+        if let Some(locale) = valid_anisette
+            .get_header("x-apple-locale")
+            .ok()
+            .and_then(|l| HeaderValue::from_str(&l).ok())
+        {
+            headers.insert("Loc", locale);
         }
 
-        headers
+        Ok(headers)
     }
 }
@@ -1,41 +1,80 @@
 use std::collections::HashMap;
-use std::time::SystemTime;
+use std::time::{Duration, SystemTime};
 
 use omnisette::{AnisetteConfiguration, AnisetteHeaders};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
 
 use crate::Error;
+use crate::auth::anisette_provider::AnisetteProvider;
 
 #[derive(Debug, Clone)]
 pub struct AnisetteData {
     pub base_headers: HashMap<String, String>,
     pub generated_at: SystemTime,
     pub config: AnisetteConfiguration,
+    /// Tried in order on every refresh, empty leaves the choice to omnisette.
+    pub providers: Vec<AnisetteProvider>,
 }
 
 impl AnisetteData {
     pub async fn new(config: AnisetteConfiguration) -> Result<Self, Error> {
-        let mut b = AnisetteHeaders::get_anisette_headers_provider(config.clone())?;
-        let base_headers = b.provider.get_authentication_headers().await?;
+        Self::with_providers(config, Vec::new()).await
+    }
+
+    /// Anisette data from the first of `providers` that answers.
+    pub async fn with_providers(
+        config: AnisetteConfiguration,
+        providers: Vec<AnisetteProvider>,
+    ) -> Result<Self, Error> {
+        let base_headers = if providers.is_empty() {
+            let mut b = AnisetteHeaders::get_anisette_headers_provider(config.clone())?;
+            b.provider.get_authentication_headers().await?
+        } else {
+            Self::first_available(&config, &providers).await?
+        };
 
         Ok(AnisetteData {
             base_headers,
             generated_at: SystemTime::now(),
             config,
+            providers,
         })
     }
 
+    async fn first_available(
+        config: &AnisetteConfiguration,
+        providers: &[AnisetteProvider],
+    ) -> Result<HashMap<String, String>, Error> {
+        let mut failures = Vec::new();
+
+        for provider in providers {
+            match provider.headers(config).await {
+                Ok(headers) => return Ok(headers),
+                Err(e) => {
+                    log::warn!("Anisette provider {} failed: {}", provider, e);
+                    failures.push(format!("{provider}: {e}"));
+                }
+            }
+        }
+
+        Err(Error::AnisetteUnavailable(failures.join("; ")))
+    }
+
+    // `None` when the clock went backwards since, the data is treated as stale then
+    fn age(&self) -> Option<Duration> {
+        self.generated_at.elapsed().ok()
+    }
+
     pub fn needs_refresh(&self) -> bool {
-        let elapsed = self.generated_at.elapsed().unwrap();
-        elapsed.as_secs() > 60
+        self.age().is_none_or(|age| age.as_secs() > 60)
     }
 
     pub fn is_valid(&self) -> bool {
-        let elapsed = self.generated_at.elapsed().unwrap();
-        elapsed.as_secs() < 90
+        self.age().is_some_and(|age| age.as_secs() < 90)
     }
 
     pub async fn refresh(&self) -> Result<Self, crate::Error> {
-        Self::new(self.config.clone()).await
+        Self::with_providers(self.config.clone(), self.providers.clone()).await
     }
 
     pub fn generate_headers(
@@ -43,9 +82,9 @@ impl AnisetteData {
         cpd: bool,
         client_info: bool,
         app_info: bool,
-    ) -> HashMap<String, String> {
+    ) -> Result<HashMap<String, String>, Error> {
         if !self.is_valid() {
-            panic!("Invalid data!")
+            return Err(Error::AnisetteExpired);
         }
 
         let mut headers = self.base_headers.clone();
@@ -56,13 +95,20 @@ impl AnisetteData {
                 Some(v) => {
                     let temp = v.as_str();
 
+                    // `<device> <os> <client>`, the client part is swapped for Xcode's
+                    let current = temp
+                        .split('<')
+                        .nth(3)
+                        .and_then(|part| part.split('>').next())
+                        .ok_or_else(|| Error::MalformedAnisette("X-Mme-Client-Info".into()))?;
+
                     temp.replace(
-                        temp.split('<').nth(3).unwrap().split('>').nth(0).unwrap(),
+                        current,
                         "com.apple.AuthKit/1 (com.apple.dt.Xcode/3594.4.19)",
                     )
                 }
                 None => {
-                    return headers;
+                    return Ok(headers);
                 }
             };
             headers.insert("X-Mme-Client-Info".to_owned(), client_info.to_owned());
@@ -85,21 +131,46 @@ impl AnisetteData {
             headers.insert("svct".to_owned(), "iCloud".to_owned());
         }
 
-        headers
+        Ok(headers)
     }
 
-    pub fn to_plist(&self, cpd: bool, client_info: bool, app_info: bool) -> plist::Dictionary {
+    /// The generated headers ready to send, a provider returning something that isn't
+    /// a valid header is an error rather than a panic.
+    pub fn to_header_map(
+        &self,
+        cpd: bool,
+        client_info: bool,
+        app_info: bool,
+    ) -> Result<HeaderMap, Error> {
+        let mut headers = HeaderMap::new();
+        for (k, v) in self.generate_headers(cpd, client_info, app_info)? {
+            headers.insert(
+                HeaderName::from_bytes(k.as_bytes())
+                    .map_err(|_| Error::MalformedAnisette(k.clone()))?,
+                HeaderValue::from_str(&v).map_err(|_| Error::MalformedAnisette(k.clone()))?,
+            );
+        }
+
+        Ok(headers)
+    }
+
+    pub fn to_plist(
+        &self,
+        cpd: bool,
+        client_info: bool,
+        app_info: bool,
+    ) -> Result<plist::Dictionary, Error> {
         let mut plist = plist::Dictionary::new();
-        for (key, value) in self.generate_headers(cpd, client_info, app_info).iter() {
+        for (key, value) in self.generate_headers(cpd, client_info, app_info)?.iter() {
             plist.insert(key.to_owned(), plist::Value::String(value.to_owned()));
         }
 
-        plist
+        Ok(plist)
     }
 
     pub fn get_header(&self, header: &str) -> Result<String, Error> {
         let headers = self
-            .generate_headers(true, true, true)
+            .generate_headers(true, true, true)?
             .iter()
             .map(|(k, v)| (k.to_lowercase(), v.to_lowercase()))
             .collect::<HashMap<String, String>>();
@@ -110,3 +181,135 @@ impl AnisetteData {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::MockServer;
+
+    async fn anisette() -> AnisetteData {
+        MockServer::anisette().lock().await.clone()
+    }
+
+    #[tokio::test]
+    async fn rejects_expired_data() {
+        let cases = [
+            (
+                "older than 90s",
+                SystemTime::now() - Duration::from_secs(91),
+            ),
+            (
+                "from the future",
+                SystemTime::now() + Duration::from_secs(3600),
+            ),
+        ];
+
+        for (name, generated_at) in cases {
+            let mut data = anisette().await;
+            data.generated_at = generated_at;
+
+            assert!(
+                matches!(
+                    data.generate_headers(true, true, true),
+                    Err(Error::AnisetteExpired)
+                ),
+                "{name}"
+            );
+            assert!(
+                matches!(
+                    data.to_header_map(true, true, true),
+                    Err(Error::AnisetteExpired)
+                ),
+                "{name}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_malformed_client_info() {
+        for client_info in ["", "MacBookPro13,2", "<MacBookPro13,2> <macOS;13.1;22C65>"] {
+            let mut data = anisette().await;
+            data.base_headers
+                .insert("X-Mme-Client-Info".into(), client_info.into());
+
+            assert!(
+                matches!(
+                    data.generate_headers(false, true, false),
+                    Err(Error::MalformedAnisette(_))
+                ),
+                "{client_info:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_headers() {
+        let cases = [
+            ("X-Apple-I-MD M", "AAAAAA=="),
+            ("X-Apple-I-MD\n", "AAAAAA=="),
+            ("X-Apple-I-MD", "AAAA\r\nAA=="),
+            ("X-Apple-I-MD", "AAAA\0AA=="),
+        ];
+
+        for (name, value) in cases {
+            let mut data = anisette().await;
+            data.base_headers.insert(name.into(), value.into());
+
+            assert!(
+                matches!(
+                    data.to_header_map(true, true, true),
+                    Err(Error::MalformedAnisette(header)) if header == name
+                ),
+                "{name:?}: {value:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn builds_header_maps() {
+        let mut data = anisette().await;
+        data.base_headers.insert(
+            "X-Mme-Client-Info".into(),
+            "<MacBookPro13,2> <macOS;13.1;22C65> <com.apple.akd/1.0 (com.apple.akd/1.0)>".into(),
+        );
+
+        let headers = data.to_header_map(true, true, true).unwrap();
+
+        assert_eq!(
+            headers["X-Mme-Client-Info"],
+            "<MacBookPro13,2> <macOS;13.1;22C65> <com.apple.AuthKit/1 (com.apple.dt.Xcode/3594.4.19)>"
+        );
+        assert_eq!(headers["X-Apple-App-Info"], "com.apple.gs.xcode.auth");
+        assert_eq!(headers["bootstrap"], "true");
+    }
+
+    #[tokio::test]
+    async fn fails_over_and_lists_every_failed_provider() {
+        let server = MockServer::start().await.unwrap();
+        let dir = std::env::temp_dir().join(format!("plume_anisette_{}", uuid::Uuid::new_v4()));
+        let config = AnisetteConfiguration::default().set_configuration_path(dir.clone());
+        // nothing listens on port 1, the mock server has no anisette routes
+        let unreachable = "http://127.0.0.1:1".to_string();
+        let providers = vec![
+            AnisetteProvider::Remote {
+                url: unreachable.clone(),
+            },
+            AnisetteProvider::Remote { url: server.url() },
+        ];
+
+        let result = AnisetteData::with_providers(config, providers).await;
+
+        // the second provider was asked after the first couldn't be reached
+        assert!(!server.requests().is_empty());
+        match result {
+            Err(Error::AnisetteUnavailable(message)) => {
+                let first = message.find(&format!("{unreachable}: ")).unwrap();
+                let second = message.find(&format!("{}: ", server.url())).unwrap();
+                assert!(first < second, "{message}");
+            }
+            result => panic!("expected AnisetteUnavailable, got {result:?}"),
+        }
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
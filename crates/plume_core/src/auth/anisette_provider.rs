@@ -0,0 +1,177 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use omnisette::remote_anisette_v3::RemoteAnisetteProviderV3;
+use omnisette::{AnisetteConfiguration, AnisetteHeadersProvider};
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+/// Somewhere to get anisette data from, see [`AnisetteData::with_providers`](crate::auth::anisette_data::AnisetteData::with_providers).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AnisetteProvider {
+    /// An anisette v3 server. Provisioning state is kept in the configuration path
+    /// and isn't tied to the server, so servers can be swapped freely.
+    Remote { url: String },
+    /// The Mac's own AuthKit, no server involved. Not available on other platforms.
+    Local,
+}
+
+impl AnisetteProvider {
+    pub(crate) async fn headers(
+        &self,
+        config: &AnisetteConfiguration,
+    ) -> Result<HashMap<String, String>, Error> {
+        match self {
+            AnisetteProvider::Remote { url } => {
+                let mut provider = RemoteAnisetteProviderV3::new(
+                    url.clone(),
+                    config.configuration_path().clone(),
+                    "0".to_string(),
+                );
+                Ok(provider.get_authentication_headers().await?)
+            }
+            AnisetteProvider::Local => Self::local_headers(config).await,
+        }
+    }
+
+    // omnisette picks AuthKit by itself on macOS
+    #[cfg(target_os = "macos")]
+    async fn local_headers(
+        config: &AnisetteConfiguration,
+    ) -> Result<HashMap<String, String>, Error> {
+        let mut b = omnisette::AnisetteHeaders::get_anisette_headers_provider(config.clone())?;
+        Ok(b.provider.get_authentication_headers().await?)
+    }
+
+    #[cfg(not(target_os = "macos"))]
+    async fn local_headers(
+        _config: &AnisetteConfiguration,
+    ) -> Result<HashMap<String, String>, Error> {
+        Err(Error::AnisetteUnavailable(
+            "local anisette is only available on macOS".into(),
+        ))
+    }
+
+    /// Fetches anisette data once, timing how long the provider took.
+    pub async fn check(&self, config: &AnisetteConfiguration) -> AnisetteHealth {
+        let started = Instant::now();
+        let result = self.headers(config).await;
+
+        AnisetteHealth {
+            provider: self.clone(),
+            latency: started.elapsed(),
+            error: result.err().map(|e| e.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for AnisetteProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AnisetteProvider::Remote { url } => f.write_str(url),
+            AnisetteProvider::Local => f.write_str("local"),
+        }
+    }
+}
+
+/// `local`, or the URL of an anisette v3 server.
+impl FromStr for AnisetteProvider {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("local") {
+            return Ok(AnisetteProvider::Local);
+        }
+
+        if s.starts_with("https://") || s.starts_with("http://") {
+            Ok(AnisetteProvider::Remote {
+                url: s.trim_end_matches('/').to_string(),
+            })
+        } else {
+            Err(format!(
+                "'{s}' is neither 'local' nor an http(s) URL of an anisette server"
+            ))
+        }
+    }
+}
+
+/// Result of [`AnisetteProvider::check`].
+#[derive(Debug, Clone)]
+pub struct AnisetteHealth {
+    pub provider: AnisetteProvider,
+    pub latency: Duration,
+    pub error: Option<String>,
+}
+
+impl AnisetteHealth {
+    pub fn is_ok(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_providers() {
+        let cases = [
+            ("local", Some(AnisetteProvider::Local)),
+            (" LOCAL ", Some(AnisetteProvider::Local)),
+            (
+                "https://ani.example.com/",
+                Some(AnisetteProvider::Remote {
+                    url: "https://ani.example.com".to_string(),
+                }),
+            ),
+            (
+                "http://127.0.0.1:6969",
+                Some(AnisetteProvider::Remote {
+                    url: "http://127.0.0.1:6969".to_string(),
+                }),
+            ),
+            ("ani.example.com", None),
+            ("ftp://ani.example.com", None),
+            ("", None),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(
+                input.parse::<AnisetteProvider>().ok(),
+                expected,
+                "{input:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn displays_providers_as_they_are_parsed() {
+        for input in ["local", "https://ani.example.com"] {
+            assert_eq!(
+                input.parse::<AnisetteProvider>().unwrap().to_string(),
+                input
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn check_reports_unreachable_providers() {
+        let dir = std::env::temp_dir().join(format!("plume_anisette_{}", uuid::Uuid::new_v4()));
+        let config = AnisetteConfiguration::default().set_configuration_path(dir.clone());
+        // nothing listens on port 1
+        let provider = AnisetteProvider::Remote {
+            url: "http://127.0.0.1:1".to_string(),
+        };
+
+        let health = provider.check(&config).await;
+
+        assert!(!health.is_ok());
+        assert_eq!(health.provider, provider);
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
@@ -1,22 +1,23 @@
 pub mod account;
 pub mod anisette_data;
+pub mod anisette_provider;
+pub mod two_factor;
 
 use omnisette::AnisetteConfiguration;
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
 use crate::auth::anisette_data::AnisetteData;
-use crate::{Error, client};
+use crate::{Error, Transport};
 
-const GSA_ENDPOINT: &str = "https://gsa.apple.com/grandslam/GsService2";
+const GSA_ENDPOINT: &str = "/grandslam/GsService2";
 
 #[derive(Debug, Clone)]
 pub struct Account {
     pub anisette: Arc<Mutex<AnisetteData>>,
     pub spd: Option<plist::Dictionary>,
-    pub client: Client,
+    pub transport: Transport,
 }
 
 impl Account {
@@ -26,12 +27,15 @@ impl Account {
     }
 
     pub fn new_with_anisette(anisette: AnisetteData) -> Result<Self, Error> {
-        let client = client()?;
-        Ok(Account {
+        Ok(Self::new_with_transport(anisette, Transport::apple()?))
+    }
+
+    pub fn new_with_transport(anisette: AnisetteData, transport: Transport) -> Self {
+        Account {
             anisette: Arc::new(Mutex::new(anisette)),
             spd: None,
-            client,
-        })
+            transport,
+        }
     }
 }
 
@@ -0,0 +1,306 @@
+use std::io::Write;
+use std::process::Command;
+
+use crate::auth::TrustedPhoneNumber;
+
+/// Environment variable [`EnvTwoFactor`] reads the code from by default.
+pub const TWO_FACTOR_CODE_ENV: &str = "PLUME_2FA_CODE";
+
+/// Where the code the user has to enter was sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TwoFactorMethod {
+    /// Pushed to the account's trusted devices.
+    TrustedDevice,
+    /// Texted to the trusted number ending in these digits.
+    Sms { last_two_digits: String },
+}
+
+impl TwoFactorMethod {
+    fn as_str(&self) -> &'static str {
+        match self {
+            TwoFactorMethod::TrustedDevice => "device",
+            TwoFactorMethod::Sms { .. } => "sms",
+        }
+    }
+}
+
+/// Supplies 2FA codes to [`Account::login`](crate::auth::Account::login).
+///
+/// Plain `Fn() -> Result<String, String>` closures are providers too.
+pub trait TwoFactorProvider {
+    /// The verification code sent with `method`.
+    fn code(&self, method: &TwoFactorMethod) -> Result<String, String>;
+
+    /// The id of the trusted number to text the code to, when the account has
+    /// no trusted device. Apple lists the primary number first.
+    fn select_phone_number(&self, numbers: &[TrustedPhoneNumber]) -> Result<u32, String> {
+        numbers
+            .first()
+            .map(|n| n.id)
+            .ok_or_else(|| "No trusted phone numbers on this account".to_string())
+    }
+}
+
+impl<F: Fn() -> Result<String, String>> TwoFactorProvider for F {
+    fn code(&self, _method: &TwoFactorMethod) -> Result<String, String> {
+        self()
+    }
+}
+
+/// Prompts on the terminal, letting the user pick the number when there are several.
+#[derive(Debug, Default, Clone)]
+pub struct InteractiveTwoFactor;
+
+impl InteractiveTwoFactor {
+    fn read_line(prompt: &str) -> Result<String, String> {
+        eprint!("{prompt}");
+        std::io::stderr().flush().map_err(|e| e.to_string())?;
+
+        let mut input = String::new();
+        std::io::stdin()
+            .read_line(&mut input)
+            .map_err(|e| e.to_string())?;
+        Ok(input.trim().to_string())
+    }
+}
+
+impl TwoFactorProvider for InteractiveTwoFactor {
+    fn code(&self, method: &TwoFactorMethod) -> Result<String, String> {
+        match method {
+            TwoFactorMethod::TrustedDevice => Self::read_line("Enter 2FA code: "),
+            TwoFactorMethod::Sms { last_two_digits } => Self::read_line(&format!(
+                "Enter the code sent to the number ending in {last_two_digits}: "
+            )),
+        }
+    }
+
+    fn select_phone_number(&self, numbers: &[TrustedPhoneNumber]) -> Result<u32, String> {
+        if numbers.len() <= 1 {
+            return numbers
+                .first()
+                .map(|n| n.id)
+                .ok_or_else(|| "No trusted phone numbers on this account".to_string());
+        }
+
+        for (index, number) in numbers.iter().enumerate() {
+            eprintln!("  [{}] {}", index + 1, number.number_with_dial_code);
+        }
+
+        let choice = Self::read_line("Send the code to: ")?;
+        choice
+            .parse::<usize>()
+            .ok()
+            .and_then(|i| i.checked_sub(1))
+            .and_then(|i| numbers.get(i))
+            .map(|n| n.id)
+            .ok_or_else(|| format!("Invalid choice '{choice}'"))
+    }
+}
+
+/// Reads the code from an environment variable, for logins that were handed the code up front.
+#[derive(Debug, Clone)]
+pub struct EnvTwoFactor {
+    var: String,
+}
+
+impl EnvTwoFactor {
+    pub fn new(var: impl Into<String>) -> Self {
+        Self { var: var.into() }
+    }
+}
+
+impl Default for EnvTwoFactor {
+    fn default() -> Self {
+        Self::new(TWO_FACTOR_CODE_ENV)
+    }
+}
+
+impl TwoFactorProvider for EnvTwoFactor {
+    fn code(&self, _method: &TwoFactorMethod) -> Result<String, String> {
+        std::env::var(&self.var)
+            .map(|code| code.trim().to_string())
+            .map_err(|_| format!("{} is not set", self.var))
+    }
+}
+
+/// Runs a program and takes the code from its first line of output,
+/// e.g. a script that polls an SMS gateway on a build agent.
+///
+/// The program gets `PLUME_2FA_METHOD` (`device` or `sms`) and, for SMS,
+/// `PLUME_2FA_PHONE` with the last two digits of the number.
+#[derive(Debug, Clone)]
+pub struct CommandTwoFactor {
+    program: String,
+    args: Vec<String>,
+}
+
+impl CommandTwoFactor {
+    pub fn new(program: impl Into<String>, args: Vec<String>) -> Self {
+        Self {
+            program: program.into(),
+            args,
+        }
+    }
+}
+
+impl TwoFactorProvider for CommandTwoFactor {
+    fn code(&self, method: &TwoFactorMethod) -> Result<String, String> {
+        let mut command = Command::new(&self.program);
+        command
+            .args(&self.args)
+            .env("PLUME_2FA_METHOD", method.as_str());
+        if let TwoFactorMethod::Sms { last_two_digits } = method {
+            command.env("PLUME_2FA_PHONE", last_two_digits);
+        }
+
+        let output = command
+            .output()
+            .map_err(|e| format!("Failed to run {}: {}", self.program, e))?;
+        if !output.status.success() {
+            return Err(format!("{} exited with {}", self.program, output.status));
+        }
+
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::trim)
+            .find(|l| !l.is_empty())
+            .map(str::to_string)
+            .ok_or_else(|| format!("{} printed no code", self.program))
+    }
+}
+
+/// Texts the code to a chosen trusted number, matched by its last two digits,
+/// and takes the code itself from another provider.
+#[derive(Debug, Clone)]
+pub struct SmsNumberTwoFactor<P> {
+    last_two_digits: String,
+    inner: P,
+}
+
+impl<P: TwoFactorProvider> SmsNumberTwoFactor<P> {
+    pub fn new(last_two_digits: impl Into<String>, inner: P) -> Self {
+        Self {
+            last_two_digits: last_two_digits.into(),
+            inner,
+        }
+    }
+}
+
+impl<P: TwoFactorProvider> TwoFactorProvider for SmsNumberTwoFactor<P> {
+    fn code(&self, method: &TwoFactorMethod) -> Result<String, String> {
+        self.inner.code(method)
+    }
+
+    fn select_phone_number(&self, numbers: &[TrustedPhoneNumber]) -> Result<u32, String> {
+        numbers
+            .iter()
+            .find(|n| n.last_two_digits == self.last_two_digits)
+            .map(|n| n.id)
+            .ok_or_else(|| format!("No trusted phone number ending in {}", self.last_two_digits))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn number(id: u32, last_two_digits: &str) -> TrustedPhoneNumber {
+        TrustedPhoneNumber {
+            number_with_dial_code: format!("+1 (•••) •••-••{last_two_digits}"),
+            last_two_digits: last_two_digits.to_string(),
+            push_mode: "sms".to_string(),
+            id,
+        }
+    }
+
+    fn no_code() -> Result<String, String> {
+        Err("no code".to_string())
+    }
+
+    #[test]
+    fn selects_the_first_number_by_default() {
+        assert_eq!(
+            no_code.select_phone_number(&[number(1, "12"), number(2, "34")]),
+            Ok(1)
+        );
+        assert!(no_code.select_phone_number(&[]).is_err());
+    }
+
+    #[test]
+    fn selects_numbers_by_their_last_two_digits() {
+        let provider = SmsNumberTwoFactor::new("34", no_code);
+
+        assert_eq!(
+            provider.select_phone_number(&[number(1, "12"), number(2, "34")]),
+            Ok(2)
+        );
+        assert_eq!(
+            provider.select_phone_number(&[number(1, "12")]),
+            Err("No trusted phone number ending in 34".to_string())
+        );
+        assert!(provider.select_phone_number(&[]).is_err());
+    }
+
+    #[test]
+    fn reads_codes_from_the_environment() {
+        let var = "PLUME_TEST_2FA_CODE_SET";
+        // SAFETY: only this test reads or writes this variable
+        unsafe { std::env::set_var(var, "  123456\n") };
+
+        assert_eq!(
+            EnvTwoFactor::new(var).code(&TwoFactorMethod::TrustedDevice),
+            Ok("123456".to_string())
+        );
+
+        unsafe { std::env::remove_var(var) };
+    }
+
+    #[test]
+    fn fails_without_the_environment_variable() {
+        let var = "PLUME_TEST_2FA_CODE_UNSET";
+
+        assert_eq!(
+            EnvTwoFactor::new(var).code(&TwoFactorMethod::TrustedDevice),
+            Err(format!("{var} is not set"))
+        );
+    }
+
+    #[cfg(unix)]
+    fn sh(script: &str) -> CommandTwoFactor {
+        CommandTwoFactor::new("sh", vec!["-c".to_string(), script.to_string()])
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn takes_the_first_line_a_command_prints() {
+        let method = TwoFactorMethod::TrustedDevice;
+
+        assert_eq!(
+            sh("printf '\\n  \\n 123456 \\n654321\\n'").code(&method),
+            Ok("123456".to_string())
+        );
+        assert!(sh("exit 3").code(&method).is_err());
+        assert!(sh("echo 123456; exit 1").code(&method).is_err());
+        assert_eq!(
+            sh("printf '\\n\\n'").code(&method),
+            Err("sh printed no code".to_string())
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn tells_the_command_where_the_code_went() {
+        let command = sh("echo \"$PLUME_2FA_METHOD:${PLUME_2FA_PHONE:-none}\"");
+
+        assert_eq!(
+            command.code(&TwoFactorMethod::TrustedDevice),
+            Ok("device:none".to_string())
+        );
+        assert_eq!(
+            command.code(&TwoFactorMethod::Sms {
+                last_two_digits: "34".to_string()
+            }),
+            Ok("sms:34".to_string())
+        );
+    }
+}
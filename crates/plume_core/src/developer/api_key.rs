@@ -0,0 +1,138 @@
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use p256::ecdsa::{Signature, SigningKey, signature::Signer};
+use p256::pkcs8::DecodePrivateKey;
+use serde_json::{Value, json};
+
+use crate::Error;
+use crate::developer::RequestType;
+
+pub const APP_STORE_CONNECT_URL: &str = "https://api.appstoreconnect.apple.com";
+
+// Apple rejects tokens that live longer than 20 minutes
+const TOKEN_LIFETIME: Duration = Duration::from_secs(15 * 60);
+
+/// App Store Connect API key, as created under Users and Access > Integrations.
+///
+/// Sessions using one talk to the public v1 api only, without anisette or an Apple ID.
+#[derive(Clone)]
+pub struct ApiKey {
+    issuer_id: String,
+    key_id: String,
+    signing_key: SigningKey,
+}
+
+impl ApiKey {
+    /// `p8` is the contents of the `AuthKey_<key id>.p8` Apple lets you download once.
+    pub fn new(issuer_id: String, key_id: String, p8: &str) -> Result<Self, Error> {
+        let signing_key = SigningKey::from_pkcs8_pem(p8)
+            .map_err(|e| Error::ApiKey(format!("Invalid .p8 key: {}", e)))?;
+
+        Ok(Self {
+            issuer_id,
+            key_id,
+            signing_key,
+        })
+    }
+
+    pub fn from_file(
+        issuer_id: String,
+        key_id: String,
+        path: impl AsRef<Path>,
+    ) -> Result<Self, Error> {
+        Self::new(issuer_id, key_id, &std::fs::read_to_string(path)?)
+    }
+
+    pub fn issuer_id(&self) -> &str {
+        &self.issuer_id
+    }
+
+    pub fn key_id(&self) -> &str {
+        &self.key_id
+    }
+
+    /// ES256 signed JWT for the `Authorization: Bearer` header.
+    pub fn token(&self) -> Result<String, Error> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(|e| Error::ApiKey(e.to_string()))?;
+
+        let header = json!({
+            "alg": "ES256",
+            "kid": self.key_id,
+            "typ": "JWT",
+        });
+        let claims = json!({
+            "iss": self.issuer_id,
+            "iat": now.as_secs(),
+            "exp": (now + TOKEN_LIFETIME).as_secs(),
+            "aud": "appstoreconnect-v1",
+        });
+
+        let message = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(serde_json::to_vec(&header)?),
+            URL_SAFE_NO_PAD.encode(serde_json::to_vec(&claims)?)
+        );
+        let signature: Signature = self.signing_key.sign(message.as_bytes());
+
+        Ok(format!(
+            "{}.{}",
+            message,
+            URL_SAFE_NO_PAD.encode(signature.to_bytes())
+        ))
+    }
+}
+
+/// Turns a developer services v1 request into its public api equivalent.
+///
+/// The Xcode proxy takes the team and query string in the body, the public api
+/// is scoped to the key's team and uses plain GETs and DELETEs.
+pub(crate) fn to_public_request(
+    url: &str,
+    body: Option<Value>,
+    request_type: Option<RequestType>,
+) -> (String, Option<Value>) {
+    let mut url = url.to_string();
+    let mut body = body;
+
+    if let Some(Value::Object(map)) = &mut body {
+        map.remove("teamId");
+
+        if let Some(Value::String(query)) = map.remove("urlEncodedQueryParams") {
+            url = format!("{}?{}", url, query);
+        }
+
+        if let Some(attributes) = map
+            .get_mut("data")
+            .and_then(|d| d.get_mut("attributes"))
+            .and_then(Value::as_object_mut)
+        {
+            attributes.remove("teamId");
+        }
+
+        if map.is_empty() {
+            body = None;
+        }
+    }
+
+    if matches!(
+        request_type,
+        Some(RequestType::Get) | Some(RequestType::Delete)
+    ) {
+        body = None;
+    }
+
+    (url, body)
+}
+
+impl std::fmt::Debug for ApiKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ApiKey")
+            .field("issuer_id", &self.issuer_id)
+            .field("key_id", &self.key_id)
+            .finish_non_exhaustive()
+    }
+}
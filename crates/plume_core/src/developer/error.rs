@@ -0,0 +1,348 @@
+/// Developer Portal failures we know how to recover from.
+///
+/// Result codes aren't documented by Apple and differ between the QH and v1 apis,
+/// so we also fall back on the server message when the code alone doesn't tell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortalError {
+    TooManyCertificates,
+    AppIdLimitReached,
+    DeviceLimitReached,
+    SessionExpired,
+    BundleIdUnavailable,
+    AgreementNotAccepted,
+}
+
+impl PortalError {
+    pub fn from_response(result_code: i64, http_code: Option<u16>, message: &str) -> Option<Self> {
+        match result_code {
+            7460 => return Some(PortalError::TooManyCertificates),
+            9401 => return Some(PortalError::AppIdLimitReached),
+            9400 | 9465 => return Some(PortalError::BundleIdUnavailable),
+            1100 => return Some(PortalError::SessionExpired),
+            _ => {}
+        }
+
+        let message = message.to_ascii_lowercase();
+
+        if message.contains("agreement") {
+            Some(PortalError::AgreementNotAccepted)
+        } else if message.contains("session has expired") || http_code == Some(401) {
+            Some(PortalError::SessionExpired)
+        } else if message.contains("maximum number of") && message.contains("certificate") {
+            Some(PortalError::TooManyCertificates)
+        } else if message.contains("maximum number of") && message.contains("device") {
+            Some(PortalError::DeviceLimitReached)
+        } else if message.contains("maximum") && message.contains("app id") {
+            Some(PortalError::AppIdLimitReached)
+        } else if message.contains("not available") && message.contains("identifier") {
+            Some(PortalError::BundleIdUnavailable)
+        } else {
+            None
+        }
+    }
+
+    /// What the user can do about it.
+    pub fn hint(&self) -> &'static str {
+        match self {
+            PortalError::TooManyCertificates => {
+                "Revoke an existing development certificate, or let Impactor revoke one for you"
+            }
+            PortalError::AppIdLimitReached => {
+                "Free accounts can only register 10 App IDs every 7 days, delete unused ones or wait for older ones to expire"
+            }
+            PortalError::DeviceLimitReached => {
+                "Remove a registered device from your team, or wait for the yearly device reset"
+            }
+            PortalError::SessionExpired => "Your session has expired, sign in again",
+            PortalError::BundleIdUnavailable => {
+                "The bundle identifier is taken by another team, choose a different one"
+            }
+            PortalError::AgreementNotAccepted => {
+                "Sign in to developer.apple.com and accept the latest Program License Agreement"
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for PortalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PortalError::TooManyCertificates => write!(f, "Too many certificates"),
+            PortalError::AppIdLimitReached => write!(f, "App ID limit reached"),
+            PortalError::DeviceLimitReached => write!(f, "Device limit reached"),
+            PortalError::SessionExpired => write!(f, "Session expired"),
+            PortalError::BundleIdUnavailable => write!(f, "Bundle ID unavailable"),
+            PortalError::AgreementNotAccepted => write!(f, "Agreement not accepted"),
+        }
+    }
+}
+
+pub(crate) fn developer_api_error(
+    url: String,
+    result_code: i64,
+    http_code: Option<u16>,
+    message: String,
+) -> crate::Error {
+    match PortalError::from_response(result_code, http_code, &message) {
+        Some(kind) => crate::Error::DeveloperPortal {
+            kind,
+            url,
+            result_code,
+            message,
+        },
+        None => crate::Error::DeveloperApi {
+            url,
+            result_code,
+            http_code,
+            message,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use plist::{Dictionary, Value};
+    use serde_json::json;
+
+    use super::PortalError;
+    use crate::Error;
+    use crate::developer::qh::QHResponseMeta;
+    use crate::developer::v1::V1ErrorResponse;
+
+    const URL: &str = "https://developerservices2.apple.com/services/QH65B2/ios/addAppId.action";
+
+    fn qh_error(payload: &[(&str, Value)]) -> Error {
+        let mut dict = Dictionary::new();
+        dict.insert("creationTimestamp".into(), "2025-06-02T14:21:07Z".into());
+        dict.insert("userLocale".into(), "en_US".into());
+        dict.insert("protocolVersion".into(), "QH65B2".into());
+        dict.insert(
+            "responseId".into(),
+            "8E1D0C5A-4F1B-4B7A-9C2E-3D5F6A7B8C9D".into(),
+        );
+        for (key, value) in payload {
+            dict.insert(key.to_string(), value.clone());
+        }
+
+        plist::from_value::<QHResponseMeta>(&Value::Dictionary(dict))
+            .unwrap()
+            .to_error(URL.to_string())
+    }
+
+    fn v1_error(payload: serde_json::Value) -> Error {
+        serde_json::from_value::<V1ErrorResponse>(payload)
+            .unwrap()
+            .errors[0]
+            .to_error(URL.to_string())
+    }
+
+    fn kind(error: &Error) -> Option<PortalError> {
+        match error {
+            Error::DeveloperPortal { kind, .. } => Some(*kind),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn maps_qh_errors() {
+        let cases = [
+            (
+                7460,
+                "There is a problem with the request entity - You already have a current Development certificate or a pending certificate request.",
+                Some(PortalError::TooManyCertificates),
+            ),
+            (
+                9401,
+                "There are no App IDs available on this team.",
+                Some(PortalError::AppIdLimitReached),
+            ),
+            (
+                9400,
+                "An App ID with Identifier 'com.example.app' is not available. Please enter a different string.",
+                Some(PortalError::BundleIdUnavailable),
+            ),
+            (
+                9465,
+                "An App ID with Identifier 'com.example.app' is not available.",
+                Some(PortalError::BundleIdUnavailable),
+            ),
+            (
+                1100,
+                "Your session has expired. Please log in.",
+                Some(PortalError::SessionExpired),
+            ),
+            // unknown codes fall back on the message
+            (
+                35,
+                "You have reached the maximum number of registered iPhone devices.",
+                Some(PortalError::DeviceLimitReached),
+            ),
+            (
+                9999,
+                "Maximum App ID limit reached. You may create up to 10 App IDs every 7 days.",
+                Some(PortalError::AppIdLimitReached),
+            ),
+            (
+                3250,
+                "You have reached the maximum number of iOS Development certificates.",
+                Some(PortalError::TooManyCertificates),
+            ),
+            (
+                1170,
+                "The Program License Agreement has been updated and needs to be reviewed.",
+                Some(PortalError::AgreementNotAccepted),
+            ),
+            (
+                1167,
+                "Identifier 'group.com.example.app' is not available, choose another.",
+                Some(PortalError::BundleIdUnavailable),
+            ),
+            (35, "Invalid platform.", None),
+        ];
+
+        for (result_code, message, expected) in cases {
+            let error = qh_error(&[
+                ("resultCode", Value::Integer(result_code.into())),
+                ("userString", message.into()),
+            ]);
+
+            assert_eq!(kind(&error), expected, "{result_code}: {message}");
+            match error {
+                Error::DeveloperPortal {
+                    result_code: code,
+                    message: m,
+                    ..
+                }
+                | Error::DeveloperApi {
+                    result_code: code,
+                    message: m,
+                    ..
+                } => {
+                    assert_eq!(code, result_code);
+                    assert_eq!(m, message);
+                }
+                other => panic!("unexpected error {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn qh_message_falls_back_to_result_string() {
+        let error = qh_error(&[
+            ("resultCode", Value::Integer(9999.into())),
+            (
+                "resultString",
+                "Your session has expired. Please log in.".into(),
+            ),
+        ]);
+        assert_eq!(kind(&error), Some(PortalError::SessionExpired));
+        assert!(error.is_session_expired());
+
+        let error = qh_error(&[
+            ("resultCode", Value::Integer(9999.into())),
+            ("httpCode", Value::Integer(500.into())),
+        ]);
+        assert!(matches!(
+            error,
+            Error::DeveloperApi {
+                result_code: 9999,
+                http_code: Some(500),
+                ref message,
+                ..
+            } if message == "Unknown API error"
+        ));
+    }
+
+    #[test]
+    fn maps_v1_errors() {
+        let cases = [
+            (
+                json!({"errors": [{
+                    "id": "5A1E1C34-9C0E-4A49-8C2B-2B2E6B1B0F3D",
+                    "status": "401",
+                    "code": "NOT_AUTHORIZED",
+                    "title": "Authentication credentials are missing or invalid.",
+                    "detail": "Provide a properly configured and signed bearer token, and make sure that it has not expired."
+                }]}),
+                Some(PortalError::SessionExpired),
+            ),
+            (
+                json!({"errors": [{
+                    "id": "0B2F6F5C-1B3E-4E3A-9F4D-6C7A8B9C0D1E",
+                    "status": "409",
+                    "code": "ENTITY_ERROR.ATTRIBUTE.INVALID",
+                    "title": "An attribute value is invalid.",
+                    "detail": "An App ID with Identifier 'com.example.app' is not available. Please enter a different string.",
+                    "resultCode": 9400
+                }]}),
+                Some(PortalError::BundleIdUnavailable),
+            ),
+            (
+                json!({"errors": [{
+                    "status": "409",
+                    "code": "ENTITY_ERROR",
+                    "title": "There is a problem with the request entity",
+                    "detail": "You already have a current Development certificate or a pending certificate request.",
+                    "resultCode": 7460
+                }]}),
+                Some(PortalError::TooManyCertificates),
+            ),
+            (
+                json!({"errors": [{
+                    "status": "403",
+                    "code": "FORBIDDEN.REQUIRED_AGREEMENTS_MISSING_OR_EXPIRED",
+                    "title": "A required agreement is missing or has expired.",
+                    "detail": "This request requires an in-effect agreement that has not been signed or has expired."
+                }]}),
+                Some(PortalError::AgreementNotAccepted),
+            ),
+            (
+                json!({"errors": [{
+                    "status": "409",
+                    "code": "ENTITY_ERROR.ATTRIBUTE.INVALID",
+                    "title": "There are no devices left",
+                    "detail": "You have reached the maximum number of devices for this product family."
+                }]}),
+                Some(PortalError::DeviceLimitReached),
+            ),
+            // no detail, the title is used instead
+            (
+                json!({"errors": [{
+                    "status": "409",
+                    "code": "ENTITY_ERROR",
+                    "title": "Your session has expired. Please log in."
+                }]}),
+                Some(PortalError::SessionExpired),
+            ),
+            (
+                json!({"errors": [{
+                    "status": "500",
+                    "code": "UNEXPECTED_ERROR",
+                    "title": "An unexpected error occurred.",
+                    "detail": "An unexpected error occurred on the server side."
+                }]}),
+                None,
+            ),
+        ];
+
+        for (payload, expected) in cases {
+            let error = v1_error(payload.clone());
+            assert_eq!(kind(&error), expected, "{payload}");
+        }
+    }
+
+    #[test]
+    fn v1_errors_keep_status_and_fallback_message() {
+        let error = v1_error(json!({"errors": [{"status": "502", "code": "BAD_GATEWAY"}]}));
+
+        assert!(matches!(
+            error,
+            Error::DeveloperApi {
+                result_code: 0,
+                http_code: Some(502),
+                ref message,
+                ..
+            } if message == "Unknown API error"
+        ));
+    }
+}
@@ -1,13 +1,23 @@
+mod api_key;
+mod error;
 pub mod qh;
+mod retry;
 mod session;
 pub mod v1;
 
+pub use api_key::{APP_STORE_CONNECT_URL, ApiKey};
+pub use error::PortalError;
+pub(crate) use error::developer_api_error;
+pub use retry::RetryPolicy;
 pub use session::{DeveloperSession, RequestType};
 
 #[macro_export]
 macro_rules! developer_endpoint {
     ($endpoint:expr) => {
-        format!("https://developerservices2.apple.com/services{}", $endpoint)
+        format!("{}{}", $crate::DEVELOPER_SERVICES_URL, $endpoint)
+    };
+    ($session:expr, $endpoint:expr) => {
+        $session.transport().developer_endpoint($endpoint)
     };
 }
 
@@ -11,7 +11,7 @@ impl DeveloperSession {
         &self,
         team_id: &String,
     ) -> Result<ViewDeveloperResponse, Error> {
-        let endpoint = developer_endpoint!("/QH65B2/viewDeveloper.action");
+        let endpoint = developer_endpoint!(self, "/QH65B2/viewDeveloper.action");
 
         let mut body = Dictionary::new();
         body.insert("teamId".to_string(), Value::String(team_id.clone()));
@@ -47,3 +47,15 @@ pub struct Developer {
     pub email: String,
     pub developer_status: String,
 }
+
+impl Developer {
+    /// Name on the developer account, falling back to the Apple ID's.
+    pub fn name(&self) -> String {
+        let (first, last) = if self.first_name.is_empty() && self.last_name.is_empty() {
+            (&self.ds_first_name, &self.ds_last_name)
+        } else {
+            (&self.first_name, &self.last_name)
+        };
+        format!("{} {}", first, last).trim().to_string()
+    }
+}
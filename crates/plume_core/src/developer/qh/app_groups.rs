@@ -9,7 +9,7 @@ use crate::developer_endpoint;
 
 impl DeveloperSession {
     pub async fn qh_list_app_groups(&self, team_id: &String) -> Result<AppGroupsResponse, Error> {
-        let endpoint = developer_endpoint!("/QH65B2/ios/listApplicationGroups.action");
+        let endpoint = developer_endpoint!(self, "/QH65B2/ios/listApplicationGroups.action");
 
         let mut body = Dictionary::new();
         body.insert("teamId".to_string(), Value::String(team_id.clone()));
@@ -26,7 +26,7 @@ impl DeveloperSession {
         name: &String,
         identifier: &String,
     ) -> Result<AppGroupResponse, Error> {
-        let endpoint = developer_endpoint!("/QH65B2/ios/addApplicationGroup.action");
+        let endpoint = developer_endpoint!(self, "/QH65B2/ios/addApplicationGroup.action");
 
         let mut body = Dictionary::new();
         body.insert("teamId".to_string(), Value::String(team_id.clone()));
@@ -74,7 +74,8 @@ impl DeveloperSession {
         app_id_id: &String,
         app_group_ids: &Vec<String>,
     ) -> Result<QHResponseMeta, Error> {
-        let endpoint = developer_endpoint!("/QH65B2/ios/assignApplicationGroupToAppId.action");
+        let endpoint =
+            developer_endpoint!(self, "/QH65B2/ios/assignApplicationGroupToAppId.action");
 
         let mut body = Dictionary::new();
         body.insert("teamId".to_string(), Value::String(team_id.clone()));
@@ -94,8 +95,55 @@ impl DeveloperSession {
 
         Ok(response_data)
     }
+
+    /// `assignApplicationGroupToAppId` replaces the App ID's groups, so unassigning
+    /// is assigning whatever is left. With nothing left the App Groups feature is
+    /// turned off instead, since an empty assignment is rejected.
+    pub async fn qh_unassign_app_group(
+        &self,
+        team_id: &String,
+        app_id_id: &String,
+        remaining_app_group_ids: &Vec<String>,
+    ) -> Result<QHResponseMeta, Error> {
+        if !remaining_app_group_ids.is_empty() {
+            return self
+                .qh_assign_app_group(team_id, app_id_id, remaining_app_group_ids)
+                .await;
+        }
+
+        let mut features = Dictionary::new();
+        features.insert(APP_GROUPS_FEATURE.to_string(), Value::Boolean(false));
+
+        Ok(self
+            .qh_update_app_id(team_id, app_id_id, features)
+            .await?
+            .meta)
+    }
+
+    pub async fn qh_delete_app_group(
+        &self,
+        team_id: &String,
+        app_group_id: &String,
+    ) -> Result<QHResponseMeta, Error> {
+        let endpoint = developer_endpoint!(self, "/QH65B2/ios/deleteApplicationGroup.action");
+
+        let mut body = Dictionary::new();
+        body.insert("teamId".to_string(), Value::String(team_id.clone()));
+        body.insert(
+            "applicationGroup".to_string(),
+            Value::String(app_group_id.clone()),
+        );
+
+        let response = self.qh_send_request(&endpoint, Some(body)).await?;
+        let response_data: QHResponseMeta = plist::from_value(&Value::Dictionary(response))?;
+
+        Ok(response_data)
+    }
 }
 
+// com.apple.security.application-groups
+const APP_GROUPS_FEATURE: &str = "APG3427HIY";
+
 #[allow(dead_code)]
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
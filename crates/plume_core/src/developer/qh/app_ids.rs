@@ -1,4 +1,4 @@
-use plist::{Dictionary, Integer, Value};
+use plist::{Date, Dictionary, Integer, Value};
 use serde::Deserialize;
 
 use crate::Error;
@@ -9,7 +9,7 @@ use crate::developer_endpoint;
 
 impl DeveloperSession {
     pub async fn qh_list_app_ids(&self, team_id: &String) -> Result<AppIDsResponse, Error> {
-        let endpoint = developer_endpoint!("/QH65B2/ios/listAppIds.action");
+        let endpoint = developer_endpoint!(self, "/QH65B2/ios/listAppIds.action");
 
         let mut body = Dictionary::new();
         body.insert("teamId".to_string(), Value::String(team_id.clone()));
@@ -26,7 +26,7 @@ impl DeveloperSession {
         name: &String,
         identifier: &String,
     ) -> Result<AppIDResponse, Error> {
-        let endpoint = developer_endpoint!("/QH65B2/ios/addAppId.action");
+        let endpoint = developer_endpoint!(self, "/QH65B2/ios/addAppId.action");
 
         let mut body = Dictionary::new();
         body.insert("teamId".to_string(), Value::String(team_id.clone()));
@@ -44,7 +44,7 @@ impl DeveloperSession {
         team_id: &String,
         app_id_id: &String,
     ) -> Result<QHResponseMeta, Error> {
-        let endpoint = developer_endpoint!("/QH65B2/ios/deleteAppId.action");
+        let endpoint = developer_endpoint!(self, "/QH65B2/ios/deleteAppId.action");
 
         let mut body = Dictionary::new();
         body.insert("teamId".to_string(), Value::String(team_id.clone()));
@@ -62,7 +62,7 @@ impl DeveloperSession {
         app_id_id: &String,
         features: Dictionary,
     ) -> Result<AppIDResponse, Error> {
-        let endpoint = developer_endpoint!("/QH65B2/ios/updateAppId.action");
+        let endpoint = developer_endpoint!(self, "/QH65B2/ios/updateAppId.action");
 
         let mut body = Dictionary::new();
         body.insert("teamId".to_string(), Value::String(team_id.clone()));
@@ -131,7 +131,7 @@ pub struct AppIDResponse {
 #[serde(rename_all = "camelCase")]
 pub struct AppID {
     pub app_id_id: String,
-    name: String,
+    pub name: String,
     app_id_platform: String,
     prefix: String,
     pub identifier: String,
@@ -144,6 +144,8 @@ pub struct AppID {
     associated_application_groups_count: Option<Integer>,
     associated_cloud_containers_count: Option<Integer>,
     associated_identifiers_count: Option<Integer>,
+    /// Only set for free teams.
+    pub expiration_date: Option<Date>,
 }
 
 #[allow(dead_code)]
@@ -185,3 +187,105 @@ impl Features {
 
     // }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{Fixture, MockServer};
+
+    const LIST_APP_IDS: &str = "/services/QH65B2/ios/listAppIds.action";
+    const ADD_APP_ID: &str = "/services/QH65B2/ios/addAppId.action";
+
+    #[tokio::test]
+    async fn ensure_app_id_reuses_existing() {
+        let server = MockServer::start().await.unwrap();
+        let session = server.recorded_session().await.unwrap();
+        server.mock(
+            LIST_APP_IDS,
+            Fixture::recorded("qh/listAppIds.plist").unwrap(),
+        );
+
+        let app_id = session
+            .qh_ensure_app_id(
+                &"TEAMID1234".to_string(),
+                &"Existing".to_string(),
+                &"com.example.existing.TEAMID1234".to_string(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(app_id.app_id_id, "EXISTING01");
+        assert!(!server.requests().iter().any(|r| r.path == ADD_APP_ID));
+    }
+
+    #[tokio::test]
+    async fn ensure_app_id_adds_missing() {
+        let server = MockServer::start().await.unwrap();
+        let session = server.recorded_session().await.unwrap();
+        server.mock(
+            LIST_APP_IDS,
+            Fixture::recorded("qh/listAppIds.plist").unwrap(),
+        );
+        server.mock(ADD_APP_ID, Fixture::recorded("qh/addAppId.plist").unwrap());
+
+        let app_id = session
+            .qh_ensure_app_id(
+                &"TEAMID1234".to_string(),
+                &"Example!".to_string(),
+                &"com.example.app".to_string(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(app_id.app_id_id, "NEWAPPID01");
+        assert_eq!(app_id.identifier, "com.example.app");
+
+        let requests = server.requests();
+        let add = requests.iter().find(|r| r.path == ADD_APP_ID).unwrap();
+        let body = add.plist().unwrap();
+        assert_eq!(
+            body.get("identifier").and_then(|v| v.as_string()),
+            Some("com.example.app")
+        );
+        assert_eq!(
+            body.get("teamId").and_then(|v| v.as_string()),
+            Some("TEAMID1234")
+        );
+        // the portal rejects names with punctuation
+        assert_eq!(
+            body.get("name").and_then(|v| v.as_string()),
+            Some("Example")
+        );
+        assert!(add.headers.contains_key("x-apple-gs-token"));
+    }
+
+    #[tokio::test]
+    async fn ensure_app_id_surfaces_portal_errors() {
+        let server = MockServer::start().await.unwrap();
+        let session = server.recorded_session().await.unwrap();
+        server.mock(
+            LIST_APP_IDS,
+            Fixture::recorded("qh/listAppIds.plist").unwrap(),
+        );
+        server.mock(
+            ADD_APP_ID,
+            Fixture::qh_error(9401, "You have reached the maximum number of App IDs"),
+        );
+
+        let err = session
+            .qh_ensure_app_id(
+                &"TEAMID1234".to_string(),
+                &"Example".to_string(),
+                &"com.example.app".to_string(),
+            )
+            .await
+            .unwrap_err();
+
+        assert!(matches!(
+            err,
+            crate::Error::DeveloperPortal {
+                result_code: 9401,
+                ..
+            }
+        ));
+    }
+}
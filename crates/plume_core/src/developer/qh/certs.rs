@@ -9,7 +9,7 @@ use crate::developer_endpoint;
 
 impl DeveloperSession {
     pub async fn qh_list_certs(&self, team_id: &String) -> Result<CertsResponse, Error> {
-        let endpoint = developer_endpoint!("/QH65B2/ios/listAllDevelopmentCerts.action");
+        let endpoint = developer_endpoint!(self, "/QH65B2/ios/listAllDevelopmentCerts.action");
 
         let mut body = Dictionary::new();
         body.insert("teamId".to_string(), Value::String(team_id.clone()));
@@ -25,7 +25,7 @@ impl DeveloperSession {
         team_id: &String,
         serial_number: &String,
     ) -> Result<QHResponseMeta, Error> {
-        let endpoint = developer_endpoint!("/QH65B2/ios/revokeDevelopmentCert.action");
+        let endpoint = developer_endpoint!(self, "/QH65B2/ios/revokeDevelopmentCert.action");
 
         let mut body = Dictionary::new();
         body.insert("teamId".to_string(), Value::String(team_id.clone()));
@@ -46,7 +46,7 @@ impl DeveloperSession {
         csr_data: String,
         machine_name: &String,
     ) -> Result<CsrResponse, Error> {
-        let endpoint = developer_endpoint!("/QH65B2/ios/submitDevelopmentCSR.action");
+        let endpoint = developer_endpoint!(self, "/QH65B2/ios/submitDevelopmentCSR.action");
 
         let mut body = Dictionary::new();
         body.insert("teamId".to_string(), Value::String(team_id.clone()));
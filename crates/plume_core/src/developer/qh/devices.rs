@@ -1,3 +1,6 @@
+use std::fmt;
+use std::str::FromStr;
+
 use plist::{Date, Dictionary, Value};
 use serde::Deserialize;
 
@@ -8,7 +11,7 @@ use crate::developer_endpoint;
 
 impl DeveloperSession {
     pub async fn qh_list_devices(&self, team_id: &String) -> Result<DevicesResponse, Error> {
-        let endpoint = developer_endpoint!("/QH65B2/ios/listDevices.action");
+        let endpoint = developer_endpoint!(self, "/QH65B2/ios/listDevices.action");
 
         let mut body = Dictionary::new();
         body.insert("teamId".to_string(), Value::String(team_id.clone()));
@@ -19,13 +22,59 @@ impl DeveloperSession {
         Ok(response_data)
     }
 
+    /// Lists devices of a platform, including disabled ones, filtered by `status`.
+    pub async fn qh_list_devices_filtered(
+        &self,
+        team_id: &String,
+        platform: Option<DevicePlatform>,
+        status: Option<DeviceStatus>,
+    ) -> Result<Vec<Device>, Error> {
+        let endpoint = developer_endpoint!(
+            self,
+            &format!(
+                "/QH65B2/{}/listDevices.action",
+                platform.unwrap_or_default().qh_path()
+            )
+        );
+
+        let mut body = Dictionary::new();
+        body.insert("teamId".to_string(), Value::String(team_id.clone()));
+        body.insert("includeRemovedDevices".to_string(), Value::Boolean(true));
+
+        let response = self.qh_send_request(&endpoint, Some(body)).await?;
+        let response_data: DevicesResponse = plist::from_value(&Value::Dictionary(response))?;
+
+        let devices = response_data
+            .devices
+            .into_iter()
+            .filter(|dev| platform.is_none_or(|p| dev.platform() == Some(p)))
+            .filter(|dev| status.is_none_or(|s| dev.status() == s))
+            .collect();
+
+        Ok(devices)
+    }
+
     pub async fn qh_add_device(
         &self,
         team_id: &String,
         device_name: &String,
         device_udid: &String,
     ) -> Result<DeviceResponse, Error> {
-        let endpoint = developer_endpoint!("/QH65B2/ios/addDevice.action");
+        self.qh_add_device_for_platform(team_id, device_name, device_udid, DevicePlatform::Ios)
+            .await
+    }
+
+    pub async fn qh_add_device_for_platform(
+        &self,
+        team_id: &String,
+        device_name: &String,
+        device_udid: &String,
+        platform: DevicePlatform,
+    ) -> Result<DeviceResponse, Error> {
+        let endpoint = developer_endpoint!(
+            self,
+            &format!("/QH65B2/{}/addDevice.action", platform.qh_path())
+        );
 
         let mut body = Dictionary::new();
         body.insert("teamId".to_string(), Value::String(team_id.clone()));
@@ -41,6 +90,92 @@ impl DeveloperSession {
         Ok(response_data)
     }
 
+    pub async fn qh_update_device(
+        &self,
+        team_id: &String,
+        device: &Device,
+        device_name: &String,
+    ) -> Result<DeviceResponse, Error> {
+        let endpoint = developer_endpoint!(
+            self,
+            &format!(
+                "/QH65B2/{}/updateDevice.action",
+                device.platform().unwrap_or_default().qh_path()
+            )
+        );
+
+        let mut body = Dictionary::new();
+        body.insert("teamId".to_string(), Value::String(team_id.clone()));
+        body.insert(
+            "deviceId".to_string(),
+            Value::String(device.device_id.clone()),
+        );
+        body.insert(
+            "deviceNumber".to_string(),
+            Value::String(device.device_number.clone()),
+        );
+        body.insert("name".to_string(), Value::String(device_name.clone()));
+
+        let response = self.qh_send_request(&endpoint, Some(body)).await?;
+        let response_data: DeviceResponse = plist::from_value(&Value::Dictionary(response))?;
+
+        Ok(response_data)
+    }
+
+    /// Disabled devices still count against the yearly device limit until the membership renews.
+    pub async fn qh_disable_device(
+        &self,
+        team_id: &String,
+        device: &Device,
+    ) -> Result<QHResponseMeta, Error> {
+        self.qh_set_device_enabled(team_id, device, false).await
+    }
+
+    pub async fn qh_enable_device(
+        &self,
+        team_id: &String,
+        device: &Device,
+    ) -> Result<QHResponseMeta, Error> {
+        self.qh_set_device_enabled(team_id, device, true).await
+    }
+
+    async fn qh_set_device_enabled(
+        &self,
+        team_id: &String,
+        device: &Device,
+        enabled: bool,
+    ) -> Result<QHResponseMeta, Error> {
+        let action = if enabled {
+            "enableDevice"
+        } else {
+            "disableDevice"
+        };
+        let endpoint = developer_endpoint!(
+            self,
+            &format!(
+                "/QH65B2/{}/{}.action",
+                device.platform().unwrap_or_default().qh_path(),
+                action
+            )
+        );
+
+        let mut body = Dictionary::new();
+        body.insert("teamId".to_string(), Value::String(team_id.clone()));
+        body.insert(
+            "deviceId".to_string(),
+            Value::String(device.device_id.clone()),
+        );
+        body.insert(
+            "deviceNumber".to_string(),
+            Value::String(device.device_number.clone()),
+        );
+
+        let response = self.qh_send_request(&endpoint, Some(body)).await?;
+        let response_data: QHResponseMeta = plist::from_value(&Value::Dictionary(response))?;
+
+        Ok(response_data)
+    }
+
     pub async fn qh_get_device(
         &self,
         team_id: &String,
@@ -92,14 +227,171 @@ pub struct DeviceResponse {
 }
 
 #[allow(dead_code)]
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Device {
-    device_id: String,
-    name: String,
-    device_number: String,
+    pub device_id: String,
+    pub name: String,
+    pub device_number: String,
     device_platform: String,
     status: String,
     device_class: String,
     expiration_date: Option<Date>,
 }
+
+impl Device {
+    pub fn platform(&self) -> Option<DevicePlatform> {
+        DevicePlatform::from_device_class(&self.device_platform, &self.device_class)
+    }
+
+    pub fn status(&self) -> DeviceStatus {
+        match self.status.as_str() {
+            "c" => DeviceStatus::Enabled,
+            "r" => DeviceStatus::Disabled,
+            _ => DeviceStatus::Processing,
+        }
+    }
+
+    pub fn device_class(&self) -> &str {
+        &self.device_class
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DevicePlatform {
+    #[default]
+    Ios,
+    Tvos,
+    Watchos,
+    Macos,
+}
+
+impl DevicePlatform {
+    // Apple only splits the QH device endpoints between ios and mac
+    fn qh_path(&self) -> &'static str {
+        match self {
+            DevicePlatform::Macos => "mac",
+            _ => "ios",
+        }
+    }
+
+    fn from_device_class(platform: &str, class: &str) -> Option<Self> {
+        match (
+            platform.to_ascii_lowercase().as_str(),
+            class.to_ascii_lowercase().as_str(),
+        ) {
+            ("mac", _) | (_, "mac") => Some(DevicePlatform::Macos),
+            (_, "tvos") | (_, "appletv") => Some(DevicePlatform::Tvos),
+            (_, "watch") => Some(DevicePlatform::Watchos),
+            ("ios", _) => Some(DevicePlatform::Ios),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for DevicePlatform {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "ios" | "iphone" | "ipad" => Ok(DevicePlatform::Ios),
+            "tvos" | "appletv" => Ok(DevicePlatform::Tvos),
+            "watchos" | "watch" => Ok(DevicePlatform::Watchos),
+            "macos" | "mac" | "osx" => Ok(DevicePlatform::Macos),
+            _ => Err(Error::Parse),
+        }
+    }
+}
+
+impl fmt::Display for DevicePlatform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DevicePlatform::Ios => write!(f, "iOS"),
+            DevicePlatform::Tvos => write!(f, "tvOS"),
+            DevicePlatform::Watchos => write!(f, "watchOS"),
+            DevicePlatform::Macos => write!(f, "macOS"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceStatus {
+    Enabled,
+    Disabled,
+    /// Newly added devices, mostly seen on paid teams.
+    Processing,
+}
+
+impl FromStr for DeviceStatus {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "enabled" | "active" => Ok(DeviceStatus::Enabled),
+            "disabled" | "removed" => Ok(DeviceStatus::Disabled),
+            "processing" => Ok(DeviceStatus::Processing),
+            _ => Err(Error::Parse),
+        }
+    }
+}
+
+impl fmt::Display for DeviceStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeviceStatus::Enabled => write!(f, "enabled"),
+            DeviceStatus::Disabled => write!(f, "disabled"),
+            DeviceStatus::Processing => write!(f, "processing"),
+        }
+    }
+}
+
+/// A line of a device list, as exported from the Developer Portal or written by hand.
+#[derive(Debug, Clone)]
+pub struct DeviceEntry {
+    pub udid: String,
+    pub name: String,
+    pub platform: DevicePlatform,
+}
+
+/// Parses a CSV or Apple's tab separated device upload file.
+///
+/// Columns are `udid, name[, platform]`, a header row and `#` comments are skipped.
+pub fn parse_device_list(contents: &str) -> Result<Vec<DeviceEntry>, Error> {
+    let mut entries = Vec::new();
+
+    for line in contents.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let separator = if line.contains('\t') { '\t' } else { ',' };
+        let columns: Vec<&str> = line
+            .split(separator)
+            .map(|c| c.trim().trim_matches('"'))
+            .collect();
+
+        // Apple's template starts with "Device ID\tDevice Name\tDevice Platform"
+        if columns[0].eq_ignore_ascii_case("device id") || columns[0].eq_ignore_ascii_case("udid") {
+            continue;
+        }
+
+        let (udid, name) = match columns.as_slice() {
+            [udid, name, ..] if !udid.is_empty() && !name.is_empty() => (udid, name),
+            _ => return Err(Error::Parse),
+        };
+
+        let platform = match columns.get(2) {
+            Some(p) if !p.is_empty() => p.parse()?,
+            _ => DevicePlatform::Ios,
+        };
+
+        entries.push(DeviceEntry {
+            udid: udid.to_string(),
+            name: name.to_string(),
+            platform,
+        });
+    }
+
+    Ok(entries)
+}
@@ -4,6 +4,7 @@ pub mod app_ids;
 pub mod certs;
 pub mod devices;
 pub mod profile;
+pub mod quota;
 pub mod teams;
 
 use crate::developer::DeveloperSession;
@@ -36,11 +37,11 @@ impl QHResponseMeta {
             .or(self.result_string)
             .unwrap_or_else(|| "Unknown API error".to_string());
 
-        crate::Error::DeveloperApi {
+        crate::developer::developer_api_error(
             url,
-            result_code: self.result_code.as_signed().unwrap_or(0),
-            http_code: self.http_code.and_then(|c| c.as_signed().map(|v| v as u16)),
+            self.result_code.as_signed().unwrap_or(0),
+            self.http_code.and_then(|c| c.as_signed().map(|v| v as u16)),
             message,
-        }
+        )
     }
 }
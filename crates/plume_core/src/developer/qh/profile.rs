@@ -12,7 +12,8 @@ impl DeveloperSession {
         team_id: &String,
         app_id_id: &String,
     ) -> Result<ProfilesResponse, Error> {
-        let endpoint = developer_endpoint!("/QH65B2/ios/downloadTeamProvisioningProfile.action");
+        let endpoint =
+            developer_endpoint!(self, "/QH65B2/ios/downloadTeamProvisioningProfile.action");
 
         let mut body = Dictionary::new();
         body.insert("teamId".to_string(), Value::String(team_id.clone()));
@@ -23,6 +24,86 @@ impl DeveloperSession {
 
         Ok(response_data)
     }
+
+    pub async fn qh_list_profiles(&self, team_id: &String) -> Result<ProfileListResponse, Error> {
+        let endpoint = developer_endpoint!(self, "/QH65B2/ios/listProvisioningProfiles.action");
+
+        let mut body = Dictionary::new();
+        body.insert("teamId".to_string(), Value::String(team_id.clone()));
+        body.insert("includeInactiveProfiles".to_string(), Value::Boolean(true));
+
+        let response = self.qh_send_request(&endpoint, Some(body)).await?;
+        let response_data: ProfileListResponse = plist::from_value(&Value::Dictionary(response))?;
+
+        Ok(response_data)
+    }
+
+    pub async fn qh_download_profile(
+        &self,
+        team_id: &String,
+        provisioning_profile_id: &String,
+    ) -> Result<ProfilesResponse, Error> {
+        let endpoint = developer_endpoint!(self, "/QH65B2/ios/downloadProvisioningProfile.action");
+
+        let mut body = Dictionary::new();
+        body.insert("teamId".to_string(), Value::String(team_id.clone()));
+        body.insert(
+            "provisioningProfileId".to_string(),
+            Value::String(provisioning_profile_id.clone()),
+        );
+
+        let response = self.qh_send_request(&endpoint, Some(body)).await?;
+        let response_data: ProfilesResponse = plist::from_value(&Value::Dictionary(response))?;
+
+        Ok(response_data)
+    }
+
+    pub async fn qh_delete_profile(
+        &self,
+        team_id: &String,
+        provisioning_profile_id: &String,
+    ) -> Result<QHResponseMeta, Error> {
+        let endpoint = developer_endpoint!(self, "/QH65B2/ios/deleteProvisioningProfile.action");
+
+        let mut body = Dictionary::new();
+        body.insert("teamId".to_string(), Value::String(team_id.clone()));
+        body.insert(
+            "provisioningProfileId".to_string(),
+            Value::String(provisioning_profile_id.clone()),
+        );
+
+        let response = self.qh_send_request(&endpoint, Some(body)).await?;
+        let response_data: QHResponseMeta = plist::from_value(&Value::Dictionary(response))?;
+
+        Ok(response_data)
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileListResponse {
+    pub provisioning_profiles: Vec<ProfileInfo>,
+    #[serde(flatten)]
+    pub meta: QHResponseMeta,
+}
+
+/// A profile as listed, without its contents.
+#[allow(dead_code)]
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileInfo {
+    pub provisioning_profile_id: String,
+    pub name: String,
+    pub status: String,
+    #[serde(rename = "type")]
+    pub _type: String,
+    distribution_method: String,
+    #[serde(rename = "UUID")]
+    pub uuid: String,
+    pub date_expire: Date,
+    pub app_id_id: Option<String>,
+    is_team_profile: Option<bool>,
 }
 
 #[allow(dead_code)]
@@ -38,19 +119,19 @@ pub struct ProfilesResponse {
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
 pub struct Profile {
-    provisioning_profile_id: String,
-    name: String,
-    status: String,
+    pub provisioning_profile_id: String,
+    pub name: String,
+    pub status: String,
     #[serde(rename = "type")]
     _type: String,
     distribution_method: String,
     pro_pro_platorm: Option<String>,
     #[serde(rename = "UUID")]
-    uuid: String,
+    pub uuid: String,
     pub date_expire: Date,
     managing_app: Option<String>,
     // app_id: AppID,
-    app_id_id: String,
+    pub app_id_id: String,
     pub encoded_profile: Data,
     pub filename: String,
     is_template_profile: bool,
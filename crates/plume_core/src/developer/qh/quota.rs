@@ -0,0 +1,147 @@
+use std::time::{Duration, SystemTime};
+
+use crate::Error;
+
+use super::DeveloperSession;
+use super::app_ids::AppID;
+
+/// App IDs a free team can have registered at once.
+pub const FREE_APP_ID_LIMIT: usize = 10;
+/// How long an App ID registered by a free team lasts, and counts against the limit.
+pub const FREE_APP_ID_LIFETIME: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+impl DeveloperSession {
+    pub async fn qh_app_id_quota(&self, team_id: &String) -> Result<AppIdQuota, Error> {
+        let is_free = self
+            .qh_list_teams()
+            .await?
+            .teams
+            .iter()
+            .find(|t| t.team_id == *team_id)
+            .is_some_and(|t| t.is_free());
+
+        self.qh_team_app_id_quota(team_id, is_free).await
+    }
+
+    /// [`DeveloperSession::qh_app_id_quota`] for a team whose membership is already known.
+    pub async fn qh_team_app_id_quota(
+        &self,
+        team_id: &String,
+        is_free: bool,
+    ) -> Result<AppIdQuota, Error> {
+        let app_ids = self.qh_list_app_ids(team_id).await?.app_ids;
+
+        Ok(AppIdQuota::new(team_id, is_free, app_ids))
+    }
+
+    /// Deletes App IDs Impactor registered that aren't in `keep`, returns what was deleted.
+    ///
+    /// Apple may keep counting deleted App IDs until they would have expired.
+    pub async fn qh_delete_unused_app_ids(
+        &self,
+        team_id: &String,
+        keep: &[String],
+    ) -> Result<Vec<AppIdUsage>, Error> {
+        let quota = self.qh_app_id_quota(team_id).await?;
+        let mut deleted = Vec::new();
+
+        for app_id in quota.unused(keep) {
+            self.qh_delete_app_id(team_id, &app_id.app_id_id).await?;
+            log::info!("Deleted App ID {} ({})", app_id.identifier, app_id.name);
+            deleted.push(app_id.clone());
+        }
+
+        Ok(deleted)
+    }
+}
+
+/// App ID usage of a team.
+#[derive(Debug, Clone)]
+pub struct AppIdQuota {
+    /// `None` for paid teams, which aren't limited.
+    pub limit: Option<usize>,
+    pub app_ids: Vec<AppIdUsage>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AppIdUsage {
+    pub app_id_id: String,
+    pub name: String,
+    pub identifier: String,
+    /// Derived from the expiry date, only known for free teams.
+    pub created: Option<SystemTime>,
+    pub expires: Option<SystemTime>,
+    /// Registered by Impactor, which suffixes bundle identifiers with the team id.
+    pub created_by_plume: bool,
+}
+
+impl AppIdQuota {
+    fn new(team_id: &str, is_free: bool, app_ids: Vec<AppID>) -> Self {
+        let app_ids = app_ids
+            .into_iter()
+            .map(|app_id| {
+                let expires = app_id.expiration_date.map(SystemTime::from);
+
+                AppIdUsage {
+                    created_by_plume: app_id.identifier.split('.').any(|s| s == team_id),
+                    created: expires.and_then(|e| e.checked_sub(FREE_APP_ID_LIFETIME)),
+                    expires,
+                    app_id_id: app_id.app_id_id,
+                    name: app_id.name,
+                    identifier: app_id.identifier,
+                }
+            })
+            .collect();
+
+        Self {
+            limit: is_free.then_some(FREE_APP_ID_LIMIT),
+            app_ids,
+        }
+    }
+
+    /// App IDs that haven't expired yet.
+    pub fn active(&self) -> impl Iterator<Item = &AppIdUsage> {
+        let now = SystemTime::now();
+        self.app_ids
+            .iter()
+            .filter(move |a| a.expires.is_none_or(|e| e > now))
+    }
+
+    pub fn used(&self) -> usize {
+        self.active().count()
+    }
+
+    pub fn remaining(&self) -> Option<usize> {
+        self.limit.map(|limit| limit.saturating_sub(self.used()))
+    }
+
+    /// When the next App ID frees up, if none are left.
+    pub fn next_available(&self) -> Option<SystemTime> {
+        if self.remaining() != Some(0) {
+            return None;
+        }
+
+        self.active().filter_map(|a| a.expires).min()
+    }
+
+    /// Identifiers that would need a new App ID.
+    pub fn missing<'a>(&self, identifiers: &'a [String]) -> Vec<&'a String> {
+        identifiers
+            .iter()
+            .filter(|id| !self.active().any(|a| a.identifier == **id))
+            .collect()
+    }
+
+    /// Whether registering `identifiers` stays within the limit.
+    pub fn fits(&self, identifiers: &[String]) -> bool {
+        self.remaining()
+            .is_none_or(|remaining| self.missing(identifiers).len() <= remaining)
+    }
+
+    /// Impactor-registered App IDs not in `keep`.
+    pub fn unused(&self, keep: &[String]) -> Vec<&AppIdUsage> {
+        self.active()
+            .filter(|a| a.created_by_plume && !keep.contains(&a.identifier))
+            .collect()
+    }
+}
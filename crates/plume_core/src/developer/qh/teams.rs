@@ -1,3 +1,5 @@
+use std::time::SystemTime;
+
 use plist::{Date, Integer, Value};
 use serde::Deserialize;
 
@@ -8,7 +10,7 @@ use crate::developer_endpoint;
 
 impl DeveloperSession {
     pub async fn qh_list_teams(&self) -> Result<TeamsResponse, Error> {
-        let endpoint = developer_endpoint!("/QH65B2/listTeams.action");
+        let endpoint = developer_endpoint!(self, "/QH65B2/listTeams.action");
 
         let response = self.qh_send_request(&endpoint, None).await?;
         let response_data: TeamsResponse = plist::from_value(&Value::Dictionary(response))?;
@@ -43,24 +45,121 @@ pub struct Team {
     team_provisioning_settings: TeamProvisionSettings,
 }
 
+impl Team {
+    /// Personal team of an Apple ID without a paid membership.
+    pub fn is_free(&self) -> bool {
+        self.xcode_free_only
+    }
+
+    /// `Individual`, `Company/Organization` or `In-House`, as the portal reports it.
+    pub fn team_type(&self) -> &str {
+        &self._type
+    }
+
+    pub fn memberships(&self) -> &[Membership] {
+        &self.memberships
+    }
+
+    /// Status of the team's program membership, free teams have none and report the team's own.
+    pub fn membership_status(&self) -> &str {
+        self.memberships
+            .first()
+            .map(|m| m.status.as_str())
+            .unwrap_or(&self.status)
+    }
+
+    /// Roles of the signed in Apple ID on this team, e.g. `TEAM_ADMIN`.
+    pub fn roles(&self) -> &[String] {
+        self.current_team_member
+            .roles
+            .as_deref()
+            .unwrap_or_default()
+    }
+
+    /// The signed in Apple ID's membership of this team.
+    pub fn current_member(&self) -> &TeamMember {
+        &self.current_team_member
+    }
+
+    /// Account holder of the team, not reported for free teams.
+    pub fn agent(&self) -> Option<&TeamMember> {
+        self.team_agent.as_ref()
+    }
+
+    pub fn date_created(&self) -> Option<SystemTime> {
+        self.date_created.map(SystemTime::from)
+    }
+
+    pub fn provisioning_settings(&self) -> &TeamProvisionSettings {
+        &self.team_provisioning_settings
+    }
+
+    /// When the paid membership runs out, the latest if the team has several.
+    pub fn membership_expiry(&self) -> Option<SystemTime> {
+        self.memberships
+            .iter()
+            .filter_map(Membership::date_expire)
+            .max()
+    }
+}
+
 #[allow(dead_code)]
 #[derive(Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
-struct Membership {
+pub struct Membership {
     membership_id: String,
     membership_product_id: String,
+    name: Option<String>,
     status: String,
     in_ios_reset_window: Option<bool>,
     in_renewal_window: bool,
     date_start: Option<Date>,
+    date_expire: Option<Date>,
     platform: String,
     delete_devices_on_expiry: bool,
 }
 
+impl Membership {
+    /// Program name, e.g. `Apple Developer Program`.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn product_id(&self) -> &str {
+        &self.membership_product_id
+    }
+
+    pub fn status(&self) -> &str {
+        &self.status
+    }
+
+    pub fn date_start(&self) -> Option<SystemTime> {
+        self.date_start.map(SystemTime::from)
+    }
+
+    pub fn date_expire(&self) -> Option<SystemTime> {
+        self.date_expire.map(SystemTime::from)
+    }
+
+    /// `ios`, `macos`, ... the platform the membership was bought for.
+    pub fn platform(&self) -> &str {
+        &self.platform
+    }
+
+    pub fn in_renewal_window(&self) -> bool {
+        self.in_renewal_window
+    }
+
+    /// Whether Apple removes the team's devices once the membership lapses.
+    pub fn deletes_devices_on_expiry(&self) -> bool {
+        self.delete_devices_on_expiry
+    }
+}
+
 #[allow(dead_code)]
 #[derive(Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
-struct TeamMember {
+pub struct TeamMember {
     team_member_id: String,
     person_id: Integer,
     first_name: String,
@@ -71,11 +170,40 @@ struct TeamMember {
     roles: Option<Vec<String>>,
 }
 
+impl TeamMember {
+    pub fn name(&self) -> String {
+        format!("{} {}", self.first_name, self.last_name)
+    }
+
+    pub fn email(&self) -> &str {
+        &self.email
+    }
+
+    pub fn developer_status(&self) -> Option<&str> {
+        self.developer_status.as_deref()
+    }
+}
+
 #[allow(dead_code)]
 #[derive(Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
-struct TeamProvisionSettings {
+pub struct TeamProvisionSettings {
     can_developer_role_register_devices: bool,
     can_developer_role_add_app_ids: bool,
     can_developer_role_update_app_ids: bool,
 }
+
+impl TeamProvisionSettings {
+    /// Whether members with the Developer role may register devices themselves.
+    pub fn developers_can_register_devices(&self) -> bool {
+        self.can_developer_role_register_devices
+    }
+
+    pub fn developers_can_add_app_ids(&self) -> bool {
+        self.can_developer_role_add_app_ids
+    }
+
+    pub fn developers_can_update_app_ids(&self) -> bool {
+        self.can_developer_role_update_app_ids
+    }
+}
@@ -0,0 +1,301 @@
+use std::time::Duration;
+
+use rand::Rng;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+
+// QH actions that can be sent twice without changing the outcome. Anything that
+// changes the team (`add`, `update`, `assign`, ...) may already have gone through
+// when a request times out, so it's only repeated when the server never got it
+const IDEMPOTENT_QH_ACTIONS: &[&str] = &["list", "view", "download"];
+
+/// How a [`DeveloperSession`](crate::developer::DeveloperSession) deals with transient failures.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Attempts per request, including the first one.
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for every one after.
+    pub base_delay: Duration,
+    /// Upper bound for a single delay, including ones asked for with `Retry-After`.
+    pub max_delay: Duration,
+    /// Requests a session sends at once, the rest wait for a slot.
+    pub max_concurrent_requests: usize,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(30),
+            max_concurrent_requests: 4,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Sends every request once.
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    pub(crate) fn delay(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        if let Some(retry_after) = retry_after {
+            return retry_after.min(self.max_delay);
+        }
+
+        let backoff = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+
+        // full jitter on the upper half, so parallel requests don't retry in lockstep
+        let half = backoff.as_millis() as u64 / 2;
+        Duration::from_millis(half + rand::thread_rng().gen_range(0..=half))
+    }
+}
+
+/// `Retry-After` in seconds, Apple doesn't send the HTTP-date form.
+pub(crate) fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    headers
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .map(Duration::from_secs)
+}
+
+pub(crate) fn is_idempotent_qh(url: &str) -> bool {
+    let action = url
+        .rsplit('/')
+        .next()
+        .unwrap_or_default()
+        .trim_end_matches(".action");
+
+    IDEMPOTENT_QH_ACTIONS
+        .iter()
+        .any(|prefix| action.starts_with(prefix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{Fixture, MockServer};
+
+    const LIST_APP_IDS: &str = "/services/QH65B2/ios/listAppIds.action";
+    const ADD_APP_ID: &str = "/services/QH65B2/ios/addAppId.action";
+
+    #[test]
+    fn classifies_qh_actions() {
+        let cases = [
+            ("listTeams", true),
+            ("ios/listAppIds", true),
+            ("ios/listAllDevelopmentCerts", true),
+            ("viewDeveloper", true),
+            ("ios/downloadTeamProvisioningProfile", true),
+            ("ios/addAppId", false),
+            ("ios/updateAppId", false),
+            ("ios/assignApplicationGroupToAppId", false),
+            ("ios/addApplicationGroup", false),
+            ("ios/deleteAppId", false),
+            ("ios/submitDevelopmentCSR", false),
+            ("ios/revokeDevelopmentCert", false),
+            ("ios/addDevice", false),
+        ];
+
+        for (action, idempotent) in cases {
+            let url =
+                format!("https://developerservices2.apple.com/services/QH65B2/{action}.action");
+            assert_eq!(is_idempotent_qh(&url), idempotent, "{action}");
+        }
+    }
+
+    #[test]
+    fn backs_off_exponentially_with_jitter() {
+        let policy = RetryPolicy {
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_millis(1000),
+            ..RetryPolicy::default()
+        };
+
+        let cases = [
+            (1, 100),
+            (2, 200),
+            (3, 400),
+            (4, 800),
+            (5, 1000),
+            (40, 1000),
+        ];
+        for (attempt, backoff) in cases {
+            for _ in 0..20 {
+                let delay = policy.delay(attempt, None).as_millis() as u64;
+                assert!(
+                    (backoff / 2..=backoff).contains(&delay),
+                    "attempt {attempt}: {delay}ms"
+                );
+            }
+        }
+
+        assert!(policy.delay(u32::MAX, None) <= policy.max_delay);
+    }
+
+    #[test]
+    fn honours_retry_after_up_to_max_delay() {
+        let policy = RetryPolicy::default();
+
+        assert_eq!(
+            policy.delay(1, Some(Duration::from_secs(3))),
+            Duration::from_secs(3)
+        );
+        assert_eq!(
+            policy.delay(1, Some(Duration::from_secs(3600))),
+            policy.max_delay
+        );
+    }
+
+    #[test]
+    fn parses_retry_after_seconds() {
+        let cases = [
+            (Some("5"), Some(Duration::from_secs(5))),
+            (Some(" 12 "), Some(Duration::from_secs(12))),
+            (Some("Wed, 21 Oct 2015 07:28:00 GMT"), None),
+            (Some("-1"), None),
+            (None, None),
+        ];
+
+        for (value, expected) in cases {
+            let mut headers = HeaderMap::new();
+            if let Some(value) = value {
+                headers.insert(RETRY_AFTER, value.parse().unwrap());
+            }
+            assert_eq!(retry_after(&headers), expected, "{value:?}");
+        }
+    }
+
+    async fn session(server: &MockServer) -> crate::developer::DeveloperSession {
+        server
+            .recorded_session()
+            .await
+            .unwrap()
+            .with_retry_policy(RetryPolicy {
+                base_delay: Duration::from_millis(1),
+                max_delay: Duration::from_millis(5),
+                ..RetryPolicy::default()
+            })
+    }
+
+    fn count(server: &MockServer, path: &str) -> usize {
+        server.requests().iter().filter(|r| r.path == path).count()
+    }
+
+    #[tokio::test]
+    async fn retries_server_errors_for_reads() {
+        let server = MockServer::start().await.unwrap();
+        let session = session(&server).await;
+        server.mock(LIST_APP_IDS, Fixture::new(500, "text/plain", "oops"));
+        server.mock(
+            LIST_APP_IDS,
+            Fixture::recorded("qh/listAppIds.plist").unwrap(),
+        );
+
+        let app_ids = session
+            .qh_list_app_ids(&"TEAMID1234".to_string())
+            .await
+            .unwrap();
+
+        assert_eq!(app_ids.app_ids.len(), 1);
+        assert_eq!(count(&server, LIST_APP_IDS), 2);
+    }
+
+    #[tokio::test]
+    async fn sends_changes_once_on_server_errors() {
+        let server = MockServer::start().await.unwrap();
+        let session = session(&server).await;
+        server.mock(ADD_APP_ID, Fixture::new(500, "text/plain", "oops"));
+        server.mock(ADD_APP_ID, Fixture::recorded("qh/addAppId.plist").unwrap());
+
+        let result = session
+            .qh_add_app_id(
+                &"TEAMID1234".to_string(),
+                &"Example".to_string(),
+                &"com.example.app".to_string(),
+            )
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(count(&server, ADD_APP_ID), 1);
+    }
+
+    #[tokio::test]
+    async fn retries_rejected_changes() {
+        let server = MockServer::start().await.unwrap();
+        let session = session(&server).await;
+        // 429 and 503 mean the request was never handled
+        server.mock(
+            ADD_APP_ID,
+            Fixture::new(429, "text/plain", "slow down").with_header("Retry-After", "0"),
+        );
+        server.mock(ADD_APP_ID, Fixture::new(503, "text/plain", "unavailable"));
+        server.mock(ADD_APP_ID, Fixture::recorded("qh/addAppId.plist").unwrap());
+
+        let app_id = session
+            .qh_add_app_id(
+                &"TEAMID1234".to_string(),
+                &"Example".to_string(),
+                &"com.example.app".to_string(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(app_id.app_id.app_id_id, "NEWAPPID01");
+        assert_eq!(count(&server, ADD_APP_ID), 3);
+
+        // the same request id on every attempt
+        let ids = server
+            .requests()
+            .iter()
+            .filter(|r| r.path == ADD_APP_ID)
+            .map(|r| {
+                r.plist()
+                    .unwrap()
+                    .get("requestId")
+                    .and_then(|v| v.as_string().map(str::to_string))
+            })
+            .collect::<Vec<_>>();
+        assert!(ids.iter().all(|id| id.is_some() && *id == ids[0]));
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_attempts() {
+        let server = MockServer::start().await.unwrap();
+        let session = session(&server).await;
+        server.mock(LIST_APP_IDS, Fixture::new(503, "text/plain", "unavailable"));
+
+        let result = session.qh_list_app_ids(&"TEAMID1234".to_string()).await;
+
+        assert!(result.is_err());
+        assert_eq!(
+            count(&server, LIST_APP_IDS),
+            RetryPolicy::default().max_attempts as usize
+        );
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_expired_sessions() {
+        let server = MockServer::start().await.unwrap();
+        let session = session(&server).await;
+        server.mock(
+            LIST_APP_IDS,
+            Fixture::new(401, "text/plain", "unauthorized"),
+        );
+
+        let result = session.qh_list_app_ids(&"TEAMID1234".to_string()).await;
+
+        assert!(matches!(result, Err(crate::Error::SessionExpired)));
+        assert_eq!(count(&server, LIST_APP_IDS), 1);
+    }
+}
@@ -1,47 +1,49 @@
 use omnisette::AnisetteConfiguration;
-use reqwest::header::HeaderName;
+use reqwest::{RequestBuilder, StatusCode};
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use tokio::sync::{Mutex, Semaphore};
 
 use plist::{Dictionary, Value};
-use reqwest::Client;
 use reqwest::header::HeaderMap;
 use reqwest::header::HeaderValue;
 use uuid::Uuid;
 
-use crate::Error;
+use crate::{Error, GSA_URL, Transport};
 
 use crate::auth::Account;
 use crate::auth::anisette_data::AnisetteData;
+use crate::developer::api_key::{self, APP_STORE_CONNECT_URL, ApiKey};
 use crate::developer::qh::QHResponseMeta;
+use crate::developer::retry::{self, RetryPolicy};
 use crate::developer::v1::V1ErrorResponse;
 
 pub struct DeveloperSession {
-    anisette: Arc<Mutex<AnisetteData>>,
-    client: Client,
-    adsid: String,          // from grandslam's SPD "adsid"
-    xcode_gs_token: String, // requested from spd initially // com.apple.gs.xcode.auth
+    anisette: Option<Arc<Mutex<AnisetteData>>>, // `None` for api key sessions
+    api_key: Option<ApiKey>,
+    transport: Transport,
+    retry: RetryPolicy,
+    requests: Arc<Semaphore>, // caps concurrent requests per `retry.max_concurrent_requests`
+    adsid: String,            // from grandslam's SPD "adsid"
+    xcode_gs_token: String,   // requested from spd initially // com.apple.gs.xcode.auth
 }
 
 impl DeveloperSession {
     pub async fn using_account(account: Account) -> Result<Self, Error> {
-        let adsid = account
-            .spd
-            .as_ref()
-            .unwrap()
-            .get("adsid")
-            .unwrap()
-            .as_string()
-            .unwrap();
+        let adsid = account.spd_string("adsid")?.to_string();
         let xcode_gs_token = account
             .get_app_token("com.apple.gs.xcode.auth")
             .await?
             .auth_token;
 
         Ok(DeveloperSession {
-            anisette: account.anisette.clone(),
-            client: account.client.clone(),
-            adsid: adsid.into(),
+            anisette: Some(account.anisette.clone()),
+            api_key: None,
+            transport: account.transport.clone(),
+            retry: RetryPolicy::default(),
+            requests: Arc::new(Semaphore::new(
+                RetryPolicy::default().max_concurrent_requests,
+            )),
+            adsid,
             xcode_gs_token,
         })
     }
@@ -60,22 +62,81 @@ impl DeveloperSession {
         xcode_gs_token: String,
         anisette: Arc<Mutex<AnisetteData>>,
     ) -> Result<Self, Error> {
-        let client = crate::client()?;
+        Self::new_with_transport(adsid, xcode_gs_token, anisette, Transport::apple()?).await
+    }
 
+    pub async fn new_with_transport(
+        adsid: String,
+        xcode_gs_token: String,
+        anisette: Arc<Mutex<AnisetteData>>,
+        transport: Transport,
+    ) -> Result<Self, Error> {
         let s = Self {
-            anisette,
-            client,
+            anisette: Some(anisette),
+            api_key: None,
+            transport,
+            retry: RetryPolicy::default(),
+            requests: Arc::new(Semaphore::new(
+                RetryPolicy::default().max_concurrent_requests,
+            )),
             adsid,
             xcode_gs_token,
         };
 
+        // a token that never made it into the store can't be valid
+        if s.adsid.is_empty() || s.xcode_gs_token.is_empty() {
+            return Err(Error::SessionExpired);
+        }
+
         // we test the session by listing teams
         // if this fails, the session is invalid (obviously)
-        s.qh_list_teams().await?;
+        s.qh_list_teams().await.map_err(|e| {
+            if e.is_session_expired() {
+                Error::SessionExpired
+            } else {
+                e
+            }
+        })?;
 
         Ok(s)
     }
 
+    /// Session for the public App Store Connect api, which only has the `v1_*` endpoints.
+    pub async fn using_api_key(api_key: ApiKey) -> Result<Self, Error> {
+        let transport = Transport::new(crate::client()?, APP_STORE_CONNECT_URL, GSA_URL);
+        Self::using_api_key_with_transport(api_key, transport).await
+    }
+
+    pub async fn using_api_key_with_transport(
+        api_key: ApiKey,
+        transport: Transport,
+    ) -> Result<Self, Error> {
+        let s = Self {
+            anisette: None,
+            api_key: Some(api_key),
+            transport,
+            retry: RetryPolicy::default(),
+            requests: Arc::new(Semaphore::new(
+                RetryPolicy::default().max_concurrent_requests,
+            )),
+            adsid: String::new(),
+            xcode_gs_token: String::new(),
+        };
+
+        // api keys are scoped to a single team, the team id is ignored
+        s.v1_list_app_ids(&String::new()).await?;
+
+        Ok(s)
+    }
+
+    pub fn api_key(&self) -> Option<&ApiKey> {
+        self.api_key.as_ref()
+    }
+
+    pub fn uses_api_key(&self) -> bool {
+        self.api_key.is_some()
+    }
+
     pub fn adsid(&self) -> &String {
         &self.adsid
     }
@@ -83,6 +144,20 @@ impl DeveloperSession {
     pub fn xcode_gs_token(&self) -> &String {
         &self.xcode_gs_token
     }
+
+    pub fn transport(&self) -> &Transport {
+        &self.transport
+    }
+
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.retry
+    }
+
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.requests = Arc::new(Semaphore::new(retry.max_concurrent_requests.max(1)));
+        self.retry = retry;
+        self
+    }
 }
 
 impl DeveloperSession {
@@ -91,28 +166,41 @@ impl DeveloperSession {
         url: &str,
         body: Option<Dictionary>,
     ) -> Result<Dictionary, Error> {
+        if self.uses_api_key() {
+            return Err(Error::UnsupportedWithApiKey("Xcode developer services"));
+        }
+
         let mut headers = HeaderMap::new();
         headers.insert("Content-Type", HeaderValue::from_static("text/x-xml-plist"));
         headers.insert("Accept", HeaderValue::from_static("text/x-xml-plist"));
-        self.insert_identity_headers(&mut headers).await;
-        self.insert_anisette_headers(&mut headers).await;
 
+        // same request id for every attempt, so the server can tell they're retries
         let mut body = body.unwrap_or_default();
         body.insert(
             "requestId".into(),
             Value::String(Uuid::new_v4().to_string().to_uppercase()),
         );
 
-        let request_builder = self.client.post(url).headers(headers);
-
         let mut buffer = Vec::new();
         plist::to_writer_xml(&mut buffer, &body)?;
 
         log::debug!("QH Request to {}: {:?}", url, body);
 
-        let response = request_builder.body(buffer).send().await?;
-        let response_bytes = response.bytes().await?;
-        let response_dict: Dictionary = plist::from_bytes(&response_bytes)?;
+        let response_dict: Dictionary = self
+            .send_with_retry(
+                url,
+                retry::is_idempotent_qh(url),
+                headers,
+                |headers| {
+                    self.transport
+                        .client()
+                        .post(url)
+                        .headers(headers)
+                        .body(buffer.clone())
+                },
+                |bytes| Ok(plist::from_bytes(bytes)?),
+            )
+            .await?;
 
         log::debug!("QH Response from {}: {:?}", url, response_dict);
 
@@ -145,32 +233,72 @@ impl DeveloperSession {
             "X-Requested-With",
             HeaderValue::from_static("XMLHttpRequest"),
         );
-        self.insert_identity_headers(&mut headers).await;
-        if let Some(RequestType::Get) = request_type {
-            headers.insert("X-HTTP-Method-Override", HeaderValue::from_static("GET"));
+        match request_type {
+            Some(RequestType::Get) => {
+                headers.insert("X-HTTP-Method-Override", HeaderValue::from_static("GET"));
+            }
+            Some(RequestType::Delete) => {
+                headers.insert("X-HTTP-Method-Override", HeaderValue::from_static("DELETE"));
+            }
+            _ => {}
         }
-        self.insert_anisette_headers(&mut headers).await;
 
-        let mut request_builder = match request_type {
-            Some(RequestType::Patch) => self.client.patch(url).headers(headers.clone()),
-            Some(RequestType::Post) | _ if body.is_some() => {
-                self.client.post(url).headers(headers.clone())
+        // method overridden gets and patches can be repeated, creating things can't
+        let idempotent = match request_type {
+            Some(RequestType::Get) | Some(RequestType::Patch) | Some(RequestType::Delete) => true,
+            _ => body.is_none(),
+        };
+
+        let (url, body) = match self.api_key {
+            Some(_) => {
+                headers.remove("X-HTTP-Method-Override");
+                headers.insert("Content-Type", HeaderValue::from_static("application/json"));
+                api_key::to_public_request(url, body, request_type)
             }
-            _ => self.client.get(url).headers(headers.clone()),
+            None => (url.to_string(), body),
         };
+        let url = url.as_str();
 
         log::debug!("V1 Request to {}: {:?}", url, &body);
 
-        if let Some(body) = body {
-            request_builder = request_builder.json(&body);
-        }
-
-        let response = request_builder.send().await?;
-        let response_text = response.text().await?;
-
-        log::debug!("V1 Response from {}: {}", url, response_text);
-
-        let response_json: serde_json::Value = serde_json::from_str(&response_text)?;
+        let response_json: serde_json::Value = self
+            .send_with_retry(
+                url,
+                idempotent,
+                headers,
+                |headers| {
+                    let request_builder = match request_type {
+                        Some(RequestType::Patch) => {
+                            self.transport.client().patch(url).headers(headers)
+                        }
+                        Some(RequestType::Delete) if body.is_none() => {
+                            self.transport.client().delete(url).headers(headers)
+                        }
+                        Some(RequestType::Post) | _ if body.is_some() => {
+                            self.transport.client().post(url).headers(headers)
+                        }
+                        _ => self.transport.client().get(url).headers(headers),
+                    };
+
+                    match &body {
+                        Some(body) => request_builder.json(body),
+                        None => request_builder,
+                    }
+                },
+                |bytes| {
+                    log::debug!(
+                        "V1 Response from {}: {}",
+                        url,
+                        String::from_utf8_lossy(bytes)
+                    );
+                    // deletes on the public api answer with an empty 204
+                    if bytes.is_empty() {
+                        return Ok(serde_json::Value::Null);
+                    }
+                    Ok(serde_json::from_slice(bytes)?)
+                },
+            )
+            .await?;
 
         if let Ok(errors) = serde_json::from_value::<V1ErrorResponse>(response_json.clone()) {
             return Err(errors.errors[0].to_error(url.to_string()));
@@ -179,40 +307,136 @@ impl DeveloperSession {
         Ok(response_json)
     }
 
+    /// Sends a request according to the session's [`RetryPolicy`].
+    ///
+    /// Failures where the server never handled the request (connection errors,
+    /// 429 and 503) are always retried, everything else only when `idempotent`.
+    async fn send_with_retry<T>(
+        &self,
+        url: &str,
+        idempotent: bool,
+        headers: HeaderMap,
+        build: impl Fn(HeaderMap) -> RequestBuilder,
+        parse: impl Fn(&[u8]) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let mut attempt = 1;
+
+        loop {
+            let can_retry = attempt < self.retry.max_attempts;
+
+            // anisette headers and api tokens are time sensitive, so they're regenerated every attempt
+            let mut headers = headers.clone();
+            match &self.api_key {
+                Some(api_key) => {
+                    let token = format!("Bearer {}", api_key.token()?);
+                    headers.insert(
+                        "Authorization",
+                        HeaderValue::from_str(&token).map_err(|e| Error::ApiKey(e.to_string()))?,
+                    );
+                }
+                None => {
+                    self.insert_identity_headers(&mut headers).await?;
+                    self.insert_anisette_headers(&mut headers).await?;
+                }
+            }
+
+            let permit = self
+                .requests
+                .acquire()
+                .await
+                .map_err(|_| Error::DeveloperSessionRequestFailed)?;
+            let result = build(headers).send().await;
+            drop(permit);
+
+            let (reason, retry_after) = match result {
+                Err(e) if can_retry && (e.is_connect() || (idempotent && e.is_timeout())) => {
+                    (e.to_string(), None)
+                }
+                Err(e) => return Err(e.into()),
+                Ok(response) => {
+                    let status = response.status();
+                    let retry_after = retry::retry_after(response.headers());
+
+                    let rejected = status == StatusCode::TOO_MANY_REQUESTS
+                        || status == StatusCode::SERVICE_UNAVAILABLE;
+
+                    // the gs token is all there is to authenticate with, retrying won't help
+                    if status == StatusCode::UNAUTHORIZED && self.api_key.is_none() {
+                        return Err(Error::SessionExpired);
+                    }
+
+                    if can_retry && (rejected || (idempotent && status.is_server_error())) {
+                        (format!("HTTP {}", status), retry_after)
+                    } else {
+                        let parsed = match response.bytes().await {
+                            Ok(bytes) => parse(&bytes),
+                            Err(e) => Err(e.into()),
+                        };
+
+                        match parsed {
+                            Err(e) if can_retry && idempotent => (e.to_string(), None),
+                            parsed => return parsed,
+                        }
+                    }
+                }
+            };
+
+            let delay = self.retry.delay(attempt, retry_after);
+            log::warn!(
+                "Request to {} failed ({}), retrying in {:?} (attempt {}/{})",
+                url,
+                reason,
+                delay,
+                attempt,
+                self.retry.max_attempts
+            );
+
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
     // TODO: this can be deduplicated as well, for reuse in `fn build_2fa_headers`
-    async fn insert_identity_headers(&self, headers: &mut HeaderMap) {
+    async fn insert_identity_headers(&self, headers: &mut HeaderMap) -> Result<(), Error> {
         headers.insert("Accept-Language", HeaderValue::from_static("en-us"));
         headers.insert("User-Agent", HeaderValue::from_static("Xcode"));
+        // a token that can't be sent can't be used either, signing in again replaces it
         headers.insert(
             "X-Apple-I-Identity-Id",
-            HeaderValue::from_str(&self.adsid).unwrap(),
+            HeaderValue::from_str(&self.adsid).map_err(|_| Error::SessionExpired)?,
         );
         headers.insert(
             "X-Apple-GS-Token",
-            HeaderValue::from_str(&self.xcode_gs_token).unwrap(),
+            HeaderValue::from_str(&self.xcode_gs_token).map_err(|_| Error::SessionExpired)?,
         );
+        Ok(())
     }
 
-    async fn insert_anisette_headers(&self, headers: &mut HeaderMap) {
-        let valid_anisette = self.get_anisette().await;
-        for (k, v) in valid_anisette.generate_headers(false, true, true) {
-            headers.insert(
-                HeaderName::from_bytes(k.as_bytes()).unwrap(),
-                HeaderValue::from_str(&v).unwrap(),
-            );
-        }
-        if let Ok(locale) = valid_anisette.get_header("x-apple-locale") {
-            headers.insert("X-Apple-Locale", HeaderValue::from_str(&locale).unwrap());
+    async fn insert_anisette_headers(&self, headers: &mut HeaderMap) -> Result<(), Error> {
+        let Some(valid_anisette) = self.get_anisette().await? else {
+            return Ok(());
+        };
+        headers.extend(valid_anisette.to_header_map(false, true, true)?);
+        if let Some(locale) = valid_anisette
+            .get_header("x-apple-locale")
+            .ok()
+            .and_then(|l| HeaderValue::from_str(&l).ok())
+        {
+            headers.insert("X-Apple-Locale", locale);
         }
+        Ok(())
     }
 
     // TODO: deduplicate?
-    pub async fn get_anisette(&self) -> AnisetteData {
-        let mut locked = self.anisette.lock().await;
+    pub async fn get_anisette(&self) -> Result<Option<AnisetteData>, Error> {
+        let Some(anisette) = &self.anisette else {
+            return Ok(None);
+        };
+        let mut locked = anisette.lock().await;
         if locked.needs_refresh() {
-            *locked = locked.refresh().await.unwrap();
+            *locked = locked.refresh().await?;
         }
-        locked.clone()
+        Ok(Some(locked.clone()))
     }
 }
 
@@ -221,4 +445,5 @@ pub enum RequestType {
     Get,
     Post,
     Patch,
+    Delete,
 }
@@ -2,13 +2,14 @@ use serde::Deserialize;
 use serde_json::{Value, json};
 
 use super::{DeveloperSession, RequestType};
+use crate::developer::strip_invalid_chars;
 use crate::developer_endpoint;
 
 use crate::Error;
 
 impl DeveloperSession {
     pub async fn v1_list_app_ids(&self, team: &String) -> Result<AppIDsResponse, Error> {
-        let endpoint = developer_endpoint!("/v1/bundleIds");
+        let endpoint = developer_endpoint!(self, "/v1/bundleIds");
 
         let body = json!({
             "teamId": team,
@@ -38,6 +39,48 @@ impl DeveloperSession {
         Ok(app_id)
     }
 
+    pub async fn v1_create_app_id(
+        &self,
+        team: &String,
+        name: &String,
+        identifier: &String,
+    ) -> Result<AppIDResponse, Error> {
+        let endpoint = developer_endpoint!(self, "/v1/bundleIds");
+
+        let payload = json!({
+            "data": {
+                "type": "bundleIds",
+                "attributes": {
+                    "identifier": identifier,
+                    "teamId": team,
+                    "name": strip_invalid_chars(name),
+                    "platform": "IOS",
+                }
+            }
+        });
+
+        let response = self
+            .v1_send_request(&endpoint, Some(payload), Some(RequestType::Post))
+            .await?;
+        let response_data: AppIDResponse = serde_json::from_value(response)?;
+
+        Ok(response_data)
+    }
+
+    pub async fn v1_ensure_app_id(
+        &self,
+        team: &String,
+        name: &String,
+        identifier: &String,
+    ) -> Result<AppID, Error> {
+        if let Some(app_id) = self.v1_get_app_id(team, identifier).await? {
+            Ok(app_id)
+        } else {
+            let response = self.v1_create_app_id(team, name, identifier).await?;
+            Ok(response.data)
+        }
+    }
+
     pub async fn v1_update_app_id(
         &self,
         team: &String,
@@ -47,7 +90,7 @@ impl DeveloperSession {
         let response_data = self.v1_get_app_id(team, app_id).await?;
         let app_id = response_data.ok_or(Error::DeveloperSessionRequestFailed)?;
 
-        let endpoint = developer_endpoint!(&format!("/v1/bundleIds/{}", app_id.id));
+        let endpoint = developer_endpoint!(self, &format!("/v1/bundleIds/{}", app_id.id));
 
         let bundle_id_capabilities: Vec<Value> = capabilities
             .into_iter()
@@ -126,13 +169,85 @@ pub struct AppID {
 pub struct AppIDAttributes {
     pub identifier: String,
     pub seed_id: String,
+    #[serde(default)]
     pub has_exclusive_managed_capabilities: bool,
     pub name: String,
     // pub entitlement_group_name: Option<String>,
+    #[serde(default)]
     pub bundle_type: String,
     // pub entitlement_types: Option<String>,
     // pub platform: Option<String>,
     // pub deployment_data_notice: Option<String>,
     // pub response_id: Option<String>,
+    #[serde(default)]
     pub wildcard: bool,
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::{Fixture, MockServer};
+
+    const BUNDLE_IDS: &str = "/services/v1/bundleIds";
+
+    #[tokio::test]
+    async fn ensure_app_id_reuses_existing() {
+        let server = MockServer::start().await.unwrap();
+        let session = server.recorded_session().await.unwrap();
+        server.mock(BUNDLE_IDS, Fixture::recorded("v1/bundleIds.json").unwrap());
+
+        let app_id = session
+            .v1_ensure_app_id(
+                &"TEAMID1234".to_string(),
+                &"Existing".to_string(),
+                &"com.example.existing.TEAMID1234".to_string(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(app_id.id, "EXISTING01");
+        assert_eq!(app_id.attributes.seed_id, "TEAMID1234");
+
+        let requests = server.requests();
+        let list = requests
+            .iter()
+            .filter(|r| r.path == BUNDLE_IDS)
+            .collect::<Vec<_>>();
+        assert_eq!(list.len(), 1);
+        assert_eq!(
+            list[0]
+                .headers
+                .get("x-http-method-override")
+                .map(String::as_str),
+            Some("GET")
+        );
+    }
+
+    #[tokio::test]
+    async fn ensure_app_id_creates_missing() {
+        let server = MockServer::start().await.unwrap();
+        let session = server.recorded_session().await.unwrap();
+        server.mock(BUNDLE_IDS, Fixture::recorded("v1/bundleIds.json").unwrap());
+        server.mock(
+            BUNDLE_IDS,
+            Fixture::recorded("v1/bundleId-created.json").unwrap(),
+        );
+
+        let app_id = session
+            .v1_ensure_app_id(
+                &"TEAMID1234".to_string(),
+                &"Example".to_string(),
+                &"com.example.app".to_string(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(app_id.id, "NEWAPPID01");
+
+        let requests = server.requests();
+        let create = requests.iter().rfind(|r| r.path == BUNDLE_IDS).unwrap();
+        let body = create.json().unwrap();
+        assert!(!create.headers.contains_key("x-http-method-override"));
+        assert_eq!(body["data"]["attributes"]["identifier"], "com.example.app");
+        assert_eq!(body["data"]["attributes"]["teamId"], "TEAMID1234");
+    }
+}
@@ -20,9 +20,65 @@ const FREE_DEVELOPER_ACCOUNT_UNALLOWED_CAPABILITIES: &[&str] = &[
     "MDM_MANAGED_ASSOCIATED_DOMAINS",
 ];
 
+// The public api has no capability listing, so entitlements are matched by hand
+const ENTITLEMENT_CAPABILITY_TYPES: &[(&str, &str)] = &[
+    ("com.apple.security.application-groups", "APP_GROUPS"),
+    ("aps-environment", "PUSH_NOTIFICATIONS"),
+    (
+        "com.apple.developer.associated-domains",
+        "ASSOCIATED_DOMAINS",
+    ),
+    ("com.apple.developer.icloud-container-identifiers", "ICLOUD"),
+    ("com.apple.developer.ubiquity-kvstore-identifier", "ICLOUD"),
+    (
+        "com.apple.developer.networking.networkextension",
+        "NETWORK_EXTENSIONS",
+    ),
+    ("com.apple.developer.networking.vpn.api", "PERSONAL_VPN"),
+    (
+        "com.apple.developer.networking.wifi-info",
+        "ACCESS_WIFI_INFORMATION",
+    ),
+    (
+        "com.apple.developer.networking.HotspotConfiguration",
+        "HOT_SPOT",
+    ),
+    ("com.apple.developer.networking.multipath", "MULTIPATH"),
+    ("com.apple.developer.healthkit", "HEALTHKIT"),
+    ("com.apple.developer.homekit", "HOMEKIT"),
+    ("com.apple.developer.siri", "SIRIKIT"),
+    ("com.apple.developer.game-center", "GAME_CENTER"),
+    ("com.apple.developer.in-app-payments", "APPLE_PAY"),
+    ("com.apple.developer.pass-type-identifiers", "WALLET"),
+    ("com.apple.developer.applesignin", "SIGN_IN_WITH_APPLE"),
+    (
+        "com.apple.developer.nfc.readersession.formats",
+        "NFC_TAG_READING",
+    ),
+    (
+        "com.apple.developer.default-data-protection",
+        "DATA_PROTECTION",
+    ),
+    ("com.apple.developer.ClassKit-environment", "CLASSKIT"),
+    ("com.apple.developer.family-controls", "FAMILY_CONTROLS"),
+    ("inter-app-audio", "INTER_APP_AUDIO"),
+    (
+        "com.apple.external-accessory.wireless-configuration",
+        "WIRELESS_ACCESSORY_CONFIGURATION",
+    ),
+    (
+        "com.apple.developer.usernotifications.time-sensitive",
+        "USERNOTIFICATIONS_TIMESENSITIVE",
+    ),
+    (
+        "com.apple.developer.usernotifications.communication",
+        "USERNOTIFICATIONS_COMMUNICATION",
+    ),
+];
+
 impl DeveloperSession {
     pub async fn v1_list_capabilities(&self, team: &String) -> Result<CapabilitiesResponse, Error> {
-        let endpoint = developer_endpoint!("/v1/capabilities");
+        let endpoint = developer_endpoint!(self, "/v1/capabilities");
 
         let body = json!({
             "teamId": team,
@@ -43,6 +99,12 @@ impl DeveloperSession {
         id: &String,
         entitlements: &Dictionary,
     ) -> Result<(), Error> {
+        if self.uses_api_key() {
+            return self
+                .v1_enable_capability_types_for_entitlements(team, id, entitlements)
+                .await;
+        }
+
         let capabilities = self.v1_list_capabilities(team).await?.data;
         let entitlement_keys: HashSet<&str> = entitlements.keys().map(|k| k.as_str()).collect();
 
@@ -65,6 +127,90 @@ impl DeveloperSession {
 
         Ok(())
     }
+
+    async fn v1_enable_capability_types_for_entitlements(
+        &self,
+        team: &String,
+        id: &String,
+        entitlements: &Dictionary,
+    ) -> Result<(), Error> {
+        let app_id = self
+            .v1_get_app_id(team, id)
+            .await?
+            .ok_or(Error::DeveloperSessionRequestFailed)?;
+
+        let endpoint = developer_endpoint!(
+            self,
+            &format!("/v1/bundleIds/{}/bundleIdCapabilities", app_id.id)
+        );
+        let response = self
+            .v1_send_request(
+                &endpoint,
+                Some(json!({ "teamId": team })),
+                Some(RequestType::Get),
+            )
+            .await?;
+        let enabled: BundleIdCapabilitiesResponse = serde_json::from_value(response)?;
+
+        let mut wanted: Vec<&str> = ENTITLEMENT_CAPABILITY_TYPES
+            .iter()
+            .filter(|(key, _)| entitlements.contains_key(*key))
+            .map(|(_, capability_type)| *capability_type)
+            .filter(|t| {
+                !enabled
+                    .data
+                    .iter()
+                    .any(|c| c.attributes.capability_type == *t)
+            })
+            .collect();
+        wanted.sort_unstable();
+        wanted.dedup();
+
+        let endpoint = developer_endpoint!(self, "/v1/bundleIdCapabilities");
+        for capability_type in wanted {
+            let payload = json!({
+                "data": {
+                    "type": "bundleIdCapabilities",
+                    "attributes": {
+                        "teamId": team,
+                        "capabilityType": capability_type,
+                    },
+                    "relationships": {
+                        "bundleId": {
+                            "data": { "type": "bundleIds", "id": app_id.id }
+                        }
+                    }
+                }
+            });
+
+            self.v1_send_request(&endpoint, Some(payload), Some(RequestType::Post))
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct BundleIdCapabilitiesResponse {
+    data: Vec<BundleIdCapability>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct BundleIdCapability {
+    id: String,
+    attributes: BundleIdCapabilityAttributes,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct BundleIdCapabilityAttributes {
+    capability_type: String,
 }
 
 #[allow(dead_code)]
@@ -0,0 +1,122 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use serde::Deserialize;
+use serde_json::json;
+
+use super::{DeveloperSession, RequestType};
+use crate::developer_endpoint;
+
+use crate::Error;
+
+impl DeveloperSession {
+    pub async fn v1_list_certificates(
+        &self,
+        team: &String,
+        kind: Option<CertificateKind>,
+    ) -> Result<CertificatesResponse, Error> {
+        let endpoint = developer_endpoint!(self, "/v1/certificates");
+
+        let mut query = "limit=200".to_string();
+        if let Some(kind) = kind {
+            query.push_str(&format!("&filter[certificateType]={}", kind.as_str()));
+        }
+
+        let body = json!({
+            "teamId": team,
+            "urlEncodedQueryParams": query
+        });
+
+        let response = self
+            .v1_send_request(&endpoint, Some(body), Some(RequestType::Get))
+            .await?;
+        let response_data: CertificatesResponse = serde_json::from_value(response)?;
+
+        Ok(response_data)
+    }
+
+    pub async fn v1_create_certificate(
+        &self,
+        team: &String,
+        csr_content: String,
+        kind: CertificateKind,
+    ) -> Result<CertificateResponse, Error> {
+        let endpoint = developer_endpoint!(self, "/v1/certificates");
+
+        let payload = json!({
+            "data": {
+                "type": "certificates",
+                "attributes": {
+                    "teamId": team,
+                    "certificateType": kind.as_str(),
+                    "csrContent": csr_content,
+                }
+            }
+        });
+
+        let response = self
+            .v1_send_request(&endpoint, Some(payload), Some(RequestType::Post))
+            .await?;
+        let response_data: CertificateResponse = serde_json::from_value(response)?;
+
+        Ok(response_data)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CertificateKind {
+    /// Used for development profiles.
+    Development,
+    /// Used for both ad-hoc and in-house profiles.
+    Distribution,
+}
+
+impl CertificateKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CertificateKind::Development => "DEVELOPMENT",
+            CertificateKind::Distribution => "DISTRIBUTION",
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CertificatesResponse {
+    pub data: Vec<Certificate>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CertificateResponse {
+    pub data: Certificate,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Certificate {
+    pub id: String,
+    pub attributes: CertificateAttributes,
+}
+
+impl Certificate {
+    pub fn content_der(&self) -> Result<Vec<u8>, Error> {
+        STANDARD
+            .decode(&self.attributes.certificate_content)
+            .map_err(|_| Error::CertificatePemMissing)
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CertificateAttributes {
+    pub name: String,
+    pub display_name: Option<String>,
+    pub certificate_type: String,
+    pub serial_number: String,
+    pub platform: Option<String>,
+    pub expiration_date: Option<String>,
+    pub certificate_content: String,
+}
@@ -0,0 +1,124 @@
+use serde::Deserialize;
+use serde_json::json;
+
+use super::{DeveloperSession, RequestType};
+use crate::developer_endpoint;
+
+use crate::Error;
+
+impl DeveloperSession {
+    pub async fn v1_list_devices(&self, team: &String) -> Result<DevicesResponse, Error> {
+        let endpoint = developer_endpoint!(self, "/v1/devices");
+
+        let body = json!({
+            "teamId": team,
+            "urlEncodedQueryParams": "limit=200"
+        });
+
+        let response = self
+            .v1_send_request(&endpoint, Some(body), Some(RequestType::Get))
+            .await?;
+        let response_data: DevicesResponse = serde_json::from_value(response)?;
+
+        Ok(response_data)
+    }
+
+    pub async fn v1_add_device(
+        &self,
+        team: &String,
+        device_name: &String,
+        device_udid: &String,
+    ) -> Result<DeviceResponse, Error> {
+        let endpoint = developer_endpoint!(self, "/v1/devices");
+
+        let payload = json!({
+            "data": {
+                "type": "devices",
+                "attributes": {
+                    "teamId": team,
+                    "name": device_name,
+                    "udid": device_udid,
+                    "platform": "IOS",
+                }
+            }
+        });
+
+        let response = self
+            .v1_send_request(&endpoint, Some(payload), Some(RequestType::Post))
+            .await?;
+        let response_data: DeviceResponse = serde_json::from_value(response)?;
+
+        Ok(response_data)
+    }
+
+    pub async fn v1_ensure_device(
+        &self,
+        team: &String,
+        device_name: &String,
+        device_udid: &String,
+    ) -> Result<Device, Error> {
+        let existing = self
+            .v1_list_devices(team)
+            .await?
+            .data
+            .into_iter()
+            .find(|d| d.attributes.udid.eq_ignore_ascii_case(device_udid));
+
+        match existing {
+            Some(device) => Ok(device),
+            None => Ok(self
+                .v1_add_device(team, device_name, device_udid)
+                .await?
+                .data),
+        }
+    }
+
+    pub async fn v1_list_enabled_devices(&self, team: &String) -> Result<DevicesResponse, Error> {
+        let endpoint = developer_endpoint!(self, "/v1/devices");
+
+        let body = json!({
+            "teamId": team,
+            "urlEncodedQueryParams": "limit=200&filter[status]=ENABLED"
+        });
+
+        let response = self
+            .v1_send_request(&endpoint, Some(body), Some(RequestType::Get))
+            .await?;
+        let response_data: DevicesResponse = serde_json::from_value(response)?;
+
+        Ok(response_data)
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DevicesResponse {
+    pub data: Vec<Device>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceResponse {
+    pub data: Device,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Device {
+    pub id: String,
+    pub attributes: DeviceAttributes,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceAttributes {
+    pub name: String,
+    pub udid: String,
+    pub platform: Option<String>,
+    pub status: Option<String>,
+    pub device_class: Option<String>,
+}
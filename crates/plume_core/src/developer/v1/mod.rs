@@ -1,5 +1,8 @@
 pub mod app_ids;
 pub mod capabilities;
+pub mod certificates;
+pub mod devices;
+pub mod profiles;
 
 use serde::Deserialize;
 
@@ -18,7 +21,9 @@ pub struct V1ErrorResponse {
 pub struct V1ErrorDetail {
     pub code: String,
     pub detail: Option<String>,
-    pub id: String,
+    pub id: Option<String>,
+    // only sent by the Xcode proxy
+    #[serde(default)]
     pub result_code: i64,
     pub status: String,
     pub title: Option<String>,
@@ -32,11 +37,11 @@ impl V1ErrorDetail {
             .or(self.title.clone())
             .unwrap_or_else(|| "Unknown API error".to_string());
 
-        crate::Error::DeveloperApi {
+        crate::developer::developer_api_error(
             url,
-            result_code: self.result_code,
-            http_code: self.status.parse().ok(),
+            self.result_code,
+            self.status.parse().ok(),
             message,
-        }
+        )
     }
 }
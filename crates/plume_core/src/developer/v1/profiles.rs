@@ -0,0 +1,416 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use serde::Deserialize;
+use serde_json::json;
+
+use super::certificates::CertificateKind;
+use super::{DeveloperSession, RequestType};
+use crate::developer::strip_invalid_chars;
+use crate::developer_endpoint;
+
+use crate::Error;
+
+impl DeveloperSession {
+    pub async fn v1_list_profiles(
+        &self,
+        team: &String,
+        profile_type: Option<ProfileType>,
+    ) -> Result<ProfilesResponse, Error> {
+        let endpoint = developer_endpoint!(self, "/v1/profiles");
+
+        // the bundle ID is only linked when it's included
+        let mut query = "limit=200&include=bundleId".to_string();
+        if let Some(profile_type) = profile_type {
+            query.push_str(&format!("&filter[profileType]={}", profile_type.as_str()));
+        }
+
+        let body = json!({
+            "teamId": team,
+            "urlEncodedQueryParams": query
+        });
+
+        let response = self
+            .v1_send_request(&endpoint, Some(body), Some(RequestType::Get))
+            .await?;
+        let response_data: ProfilesResponse = serde_json::from_value(response)?;
+
+        Ok(response_data)
+    }
+
+    pub async fn v1_create_profile(
+        &self,
+        team: &String,
+        name: &str,
+        profile_type: ProfileType,
+        bundle_id_id: &String,
+        certificate_ids: &[String],
+        device_ids: &[String],
+    ) -> Result<ProfileResponse, Error> {
+        let endpoint = developer_endpoint!(self, "/v1/profiles");
+
+        let payload = json!({
+            "data": {
+                "type": "profiles",
+                "attributes": {
+                    "teamId": team,
+                    "name": strip_invalid_chars(name),
+                    "profileType": profile_type.as_str(),
+                },
+                "relationships": {
+                    "bundleId": {
+                        "data": { "type": "bundleIds", "id": bundle_id_id }
+                    },
+                    "certificates": {
+                        "data": certificate_ids
+                            .iter()
+                            .map(|id| json!({ "type": "certificates", "id": id }))
+                            .collect::<Vec<_>>()
+                    },
+                    "devices": {
+                        "data": device_ids
+                            .iter()
+                            .map(|id| json!({ "type": "devices", "id": id }))
+                            .collect::<Vec<_>>()
+                    }
+                }
+            }
+        });
+
+        let response = self
+            .v1_send_request(&endpoint, Some(payload), Some(RequestType::Post))
+            .await?;
+        let response_data: ProfileResponse = serde_json::from_value(response)?;
+
+        Ok(response_data)
+    }
+
+    pub async fn v1_delete_profile(&self, team: &String, profile_id: &String) -> Result<(), Error> {
+        let endpoint = developer_endpoint!(self, &format!("/v1/profiles/{}", profile_id));
+
+        let body = json!({
+            "teamId": team,
+        });
+
+        self.v1_send_request(&endpoint, Some(body), Some(RequestType::Delete))
+            .await?;
+
+        Ok(())
+    }
+
+    /// Creates a profile for `identifier` with the certificate with `certificate_serial`,
+    /// replacing the one Impactor made before.
+    ///
+    /// Profiles made here don't pick up devices registered after they were made,
+    /// so they're recreated every time to cover all of the team's enabled devices.
+    pub async fn v1_ensure_profile(
+        &self,
+        team: &String,
+        identifier: &String,
+        profile_type: ProfileType,
+        certificate_serial: &str,
+    ) -> Result<Profile, Error> {
+        let bundle_id = self
+            .v1_get_app_id(team, identifier)
+            .await?
+            .ok_or(Error::DeveloperSessionRequestFailed)?;
+
+        let certificate = self
+            .v1_list_certificates(team, Some(profile_type.certificate_kind()))
+            .await?
+            .data
+            .into_iter()
+            .find(|c| {
+                c.attributes
+                    .serial_number
+                    .eq_ignore_ascii_case(certificate_serial)
+            })
+            .ok_or_else(|| {
+                Error::Certificate(format!("Certificate {} not found", certificate_serial))
+            })?;
+
+        let device_ids = match profile_type {
+            ProfileType::Development | ProfileType::AdHoc => self
+                .v1_list_enabled_devices(team)
+                .await?
+                .data
+                .into_iter()
+                .map(|d| d.id)
+                .collect(),
+            ProfileType::InHouse => Vec::new(),
+        };
+
+        let name = format!("Plume {} {}", profile_type, identifier);
+
+        // names lose everything but letters, so apps differing in digits or dots share one
+        for profile in self.v1_list_profiles(team, Some(profile_type)).await?.data {
+            if profile.bundle_id_id() == Some(bundle_id.id.as_str())
+                && profile.attributes.profile_type == profile_type.as_str()
+                && profile.attributes.name == strip_invalid_chars(&name)
+            {
+                self.v1_delete_profile(team, &profile.id).await?;
+            }
+        }
+
+        let response = self
+            .v1_create_profile(
+                team,
+                &name,
+                profile_type,
+                &bundle_id.id,
+                &[certificate.id],
+                &device_ids,
+            )
+            .await?;
+
+        Ok(response.data)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileType {
+    Development,
+    AdHoc,
+    /// Enterprise program only.
+    InHouse,
+}
+
+impl ProfileType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProfileType::Development => "IOS_APP_DEVELOPMENT",
+            ProfileType::AdHoc => "IOS_APP_ADHOC",
+            ProfileType::InHouse => "IOS_APP_INHOUSE",
+        }
+    }
+
+    pub fn certificate_kind(&self) -> CertificateKind {
+        match self {
+            ProfileType::Development => CertificateKind::Development,
+            ProfileType::AdHoc | ProfileType::InHouse => CertificateKind::Distribution,
+        }
+    }
+}
+
+impl std::fmt::Display for ProfileType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProfileType::Development => write!(f, "Development"),
+            ProfileType::AdHoc => write!(f, "AdHoc"),
+            ProfileType::InHouse => write!(f, "InHouse"),
+        }
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfilesResponse {
+    pub data: Vec<Profile>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileResponse {
+    pub data: Profile,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Profile {
+    pub id: String,
+    pub attributes: ProfileAttributes,
+    #[serde(default)]
+    pub relationships: Option<ProfileRelationships>,
+}
+
+impl Profile {
+    /// ID of the bundle ID the profile is for, only known when listed with it included.
+    pub fn bundle_id_id(&self) -> Option<&str> {
+        self.relationships
+            .as_ref()?
+            .bundle_id
+            .as_ref()?
+            .data
+            .as_ref()
+            .map(|d| d.id.as_str())
+    }
+
+    pub fn content(&self) -> Result<Vec<u8>, Error> {
+        let content = self
+            .attributes
+            .profile_content
+            .as_ref()
+            .ok_or(Error::ProvisioningEntitlementsUnknown)?;
+
+        STANDARD
+            .decode(content)
+            .map_err(|_| Error::ProvisioningEntitlementsUnknown)
+    }
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileAttributes {
+    pub name: String,
+    pub profile_type: String,
+    pub profile_state: Option<String>,
+    pub profile_content: Option<String>,
+    pub uuid: Option<String>,
+    pub expiration_date: Option<String>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfileRelationships {
+    pub bundle_id: Option<ProfileRelationship>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug, Clone)]
+pub struct ProfileRelationship {
+    pub data: Option<ProfileRelationshipData>,
+}
+
+#[allow(dead_code)]
+#[derive(Deserialize, Debug, Clone)]
+pub struct ProfileRelationshipData {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub id: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::ProfileType;
+    use crate::testing::{Fixture, MockServer};
+
+    const TEAM_ID: &str = "TEAMID1234";
+    const SERIAL: &str = "79E1FFEE8E8A365F";
+    const PROFILES: &str = "/services/v1/profiles";
+
+    fn bundle_id(id: &str, identifier: &str) -> serde_json::Value {
+        json!({
+            "type": "bundleIds",
+            "id": id,
+            "attributes": {
+                "identifier": identifier,
+                "seedId": TEAM_ID,
+                "name": "Example",
+                "platform": "IOS",
+            }
+        })
+    }
+
+    // both apps' profiles get the same name, their identifiers only differ in a digit
+    fn profile(id: &str, bundle_id_id: &str) -> serde_json::Value {
+        json!({
+            "type": "profiles",
+            "id": id,
+            "attributes": {
+                "name": "PlumeDevelopmentcomexampleapp",
+                "profileType": "IOS_APP_DEVELOPMENT",
+                "profileState": "ACTIVE",
+            },
+            "relationships": {
+                "bundleId": {
+                    "data": { "type": "bundleIds", "id": bundle_id_id }
+                }
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn ensure_profile_only_replaces_the_apps_own_profile() {
+        let server = MockServer::start().await.unwrap();
+        let session = server.recorded_session().await.unwrap();
+        server.mock(
+            "/services/v1/bundleIds",
+            Fixture::v1(json!({
+                "data": [
+                    bundle_id("APPID00001", "com.example.app1"),
+                    bundle_id("APPID00002", "com.example.app2"),
+                ]
+            })),
+        );
+        server.mock(
+            "/services/v1/certificates",
+            Fixture::v1(json!({
+                "data": [{
+                    "type": "certificates",
+                    "id": "CERT000001",
+                    "attributes": {
+                        "name": "Apple Development: Test User",
+                        "certificateType": "DEVELOPMENT",
+                        "serialNumber": SERIAL,
+                        "certificateContent": "",
+                    }
+                }]
+            })),
+        );
+        server.mock(
+            "/services/v1/devices",
+            Fixture::v1(json!({
+                "data": [{
+                    "type": "devices",
+                    "id": "DEVICE0001",
+                    "attributes": {
+                        "name": "iPhone",
+                        "udid": "00008110-000000000000001E",
+                        "status": "ENABLED",
+                    }
+                }]
+            })),
+        );
+        server.mock(
+            PROFILES,
+            Fixture::v1(json!({
+                "data": [profile("PROFILE001", "APPID00001"), profile("PROFILE002", "APPID00002")]
+            })),
+        );
+        server.mock(
+            PROFILES,
+            Fixture::v1(json!({ "data": profile("PROFILE003", "APPID00002") })),
+        );
+        server.mock(
+            "/services/v1/profiles/PROFILE002",
+            Fixture::new(204, "application/vnd.api+json", ""),
+        );
+
+        let profile = session
+            .v1_ensure_profile(
+                &TEAM_ID.to_string(),
+                &"com.example.app2".to_string(),
+                ProfileType::Development,
+                SERIAL,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(profile.id, "PROFILE003");
+
+        let requests = server.requests();
+        let deleted = requests
+            .iter()
+            .filter(|r| r.path.starts_with("/services/v1/profiles/"))
+            .map(|r| r.path.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(deleted, vec!["/services/v1/profiles/PROFILE002"]);
+
+        let create = requests
+            .iter()
+            .filter(|r| r.path == PROFILES)
+            .nth(1)
+            .unwrap()
+            .json()
+            .unwrap();
+        assert_eq!(
+            create["data"]["relationships"]["bundleId"]["data"]["id"],
+            "APPID00002"
+        );
+    }
+}
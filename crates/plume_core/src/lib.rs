@@ -1,12 +1,18 @@
 pub mod auth;
 pub mod developer;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+mod transport;
 mod utils;
 
 pub use apple_codesign::{AppleCodesignError, SettingsScope, SigningSettings, UnifiedSigner};
 
 pub use omnisette::AnisetteConfiguration;
 
-pub use utils::{CertificateIdentity, MachO, MachOExt, MobileProvision};
+pub use transport::{DEVELOPER_SERVICES_URL, GSA_URL, Transport};
+pub use utils::{
+    CertificateIdentity, KEY_NAMES, KeyStore, MachO, MachOExt, MobileProvision, SecretBackend,
+};
 
 use thiserror::Error as ThisError;
 #[derive(Debug, ThisError)]
@@ -26,14 +32,41 @@ pub enum Error {
         http_code: Option<u16>,
         message: String,
     },
+    #[error("{kind}: {message}. {} [URL: {url}]", kind.hint())]
+    DeveloperPortal {
+        kind: developer::PortalError,
+        url: String,
+        result_code: i64,
+        message: String,
+    },
+    #[error("Apple ID session expired or is no longer valid, sign in again")]
+    SessionExpired,
+    #[error("Account is not signed in")]
+    NotSignedIn,
+    #[error("Malformed Apple ID response: {0}")]
+    MalformedAuthResponse(String),
     #[error("Request to developer session failed")]
     DeveloperSessionRequestFailed,
+    #[error("App Store Connect API key error: {0}")]
+    ApiKey(String),
+    #[error("Not available when signed in with an App Store Connect API key: {0}")]
+    UnsupportedWithApiKey(&'static str),
     #[error("Authentication SRP error {0}: {1}")]
     AuthSrpWithMessage(i64, String),
     #[error("Authentication extra step required: {0}")]
     ExtraStep(String),
     #[error("Bad 2FA code")]
     Bad2faCode,
+    #[error("Secret storage error: {0}")]
+    SecretStore(String),
+    #[error(
+        "Store was written by a newer version (schema {found}, this version reads up to {supported})"
+    )]
+    StoreSchemaUnsupported { found: u64, supported: u64 },
+    #[error("Backup error: {0}")]
+    Backup(String),
+    #[error("Restoring the backup would replace {0} on this machine")]
+    BackupWouldReplace(String),
     #[error("Failed to parse")]
     Parse, // TODO: better parsing errors
     #[error("I/O error: {0}")]
@@ -52,6 +85,12 @@ pub enum Error {
     Reqwest(#[from] reqwest::Error),
     #[error("Anisette error: {0}")]
     Anisette(#[from] omnisette::AnisetteError),
+    #[error("No anisette provider is available: {0}")]
+    AnisetteUnavailable(String),
+    #[error("Anisette data expired before it was used")]
+    AnisetteExpired,
+    #[error("Malformed anisette data: {0}")]
+    MalformedAnisette(String),
     #[error("Serde JSON error: {0}")]
     SerdeJson(#[from] serde_json::Error),
     #[error("RSA error: {0}")]
@@ -64,6 +103,20 @@ pub enum Error {
     RcGen(#[from] rcgen::RcgenError),
 }
 
+impl Error {
+    /// Whether signing in again is the way out, however the server reported it.
+    pub fn is_session_expired(&self) -> bool {
+        matches!(
+            self,
+            Error::SessionExpired
+                | Error::DeveloperPortal {
+                    kind: developer::PortalError::SessionExpired,
+                    ..
+                }
+        )
+    }
+}
+
 pub fn client() -> Result<reqwest::Client, Error> {
     const APPLE_ROOT: &[u8] = include_bytes!("./apple_root.der");
     let client = reqwest::ClientBuilder::new()
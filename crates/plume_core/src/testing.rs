@@ -0,0 +1,414 @@
+//! Local stand-in for Apple's servers.
+//!
+//! [`MockServer`] replays recorded QH plist and v1 JSON responses over plain HTTP,
+//! so portal and auth code can be exercised without network access or an Apple ID.
+//! Responses recorded for the tests live in [`fixtures_dir`].
+
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use omnisette::AnisetteConfiguration;
+use plist::{Dictionary, Value};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use uuid::Uuid;
+
+use crate::auth::anisette_data::AnisetteData;
+use crate::developer::DeveloperSession;
+use crate::{Error, Transport};
+
+/// A canned HTTP response.
+#[derive(Debug, Clone)]
+pub struct Fixture {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Fixture {
+    pub fn new(status: u16, content_type: &str, body: impl Into<Vec<u8>>) -> Self {
+        Self {
+            status,
+            headers: vec![("Content-Type".to_string(), content_type.to_string())],
+            body: body.into(),
+        }
+    }
+
+    /// QH plist response, the metadata every QH response carries is filled in if missing.
+    pub fn qh(mut dict: Dictionary) -> Self {
+        let defaults = [
+            ("resultCode", Value::Integer(0.into())),
+            (
+                "creationTimestamp",
+                Value::String("2025-01-01T00:00:00Z".into()),
+            ),
+            ("userLocale", Value::String("en_US".into())),
+            ("protocolVersion", Value::String("QH65B2".into())),
+            (
+                "responseId",
+                Value::String(Uuid::new_v4().to_string().to_uppercase()),
+            ),
+        ];
+
+        for (key, value) in defaults {
+            if !dict.contains_key(key) {
+                dict.insert(key.to_string(), value);
+            }
+        }
+
+        let mut body = Vec::new();
+        // writing to a Vec can't fail
+        plist::to_writer_xml(&mut body, &dict).ok();
+
+        Self::new(200, "text/x-xml-plist", body)
+    }
+
+    /// QH response with a non-zero `resultCode`.
+    pub fn qh_error(result_code: i64, message: &str) -> Self {
+        let mut dict = Dictionary::new();
+        dict.insert("resultCode".into(), Value::Integer(result_code.into()));
+        dict.insert("userString".into(), Value::String(message.into()));
+        dict.insert("resultString".into(), Value::String(message.into()));
+        Self::qh(dict)
+    }
+
+    pub fn v1(json: serde_json::Value) -> Self {
+        Self::new(200, "application/vnd.api+json", json.to_string())
+    }
+
+    /// v1 response in the `{"errors": [...]}` shape.
+    pub fn v1_error(status: u16, result_code: i64, detail: &str) -> Self {
+        let json = serde_json::json!({
+            "errors": [{
+                "id": Uuid::new_v4().to_string(),
+                "status": status.to_string(),
+                "code": "ERROR",
+                "title": detail,
+                "detail": detail,
+                "resultCode": result_code,
+            }]
+        });
+
+        Self::new(status, "application/vnd.api+json", json.to_string())
+    }
+
+    /// Recorded response, `.plist` files are served as QH responses and `.json` as v1.
+    pub fn from_file(path: &Path) -> Result<Self, Error> {
+        let body = std::fs::read(path)?;
+
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("plist") => Ok(Self::new(200, "text/x-xml-plist", body)),
+            Some("json") => Ok(Self::new(200, "application/vnd.api+json", body)),
+            _ => Ok(Self::new(200, "application/octet-stream", body)),
+        }
+    }
+
+    /// Response recorded under [`fixtures_dir`], e.g. `qh/listTeams.plist`.
+    pub fn recorded(name: &str) -> Result<Self, Error> {
+        Self::from_file(&fixtures_dir().join(name))
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+/// Checked in responses: QH plists under `qh/`, v1 JSON under `v1/`, and under `keys/`
+/// the private key of the `AltStore` certificate the QH fixtures list.
+pub fn fixtures_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+}
+
+/// A request the mock server received.
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    /// Header names are lowercased.
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl RecordedRequest {
+    pub fn plist(&self) -> Result<Dictionary, Error> {
+        Ok(plist::from_bytes(&self.body)?)
+    }
+
+    pub fn json(&self) -> Result<serde_json::Value, Error> {
+        Ok(serde_json::from_slice(&self.body)?)
+    }
+}
+
+type Routes = Arc<Mutex<HashMap<String, VecDeque<Fixture>>>>;
+
+pub struct MockServer {
+    addr: SocketAddr,
+    routes: Routes,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl MockServer {
+    pub async fn start() -> Result<Self, Error> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+
+        let routes: Routes = Arc::default();
+        let requests: Arc<Mutex<Vec<RecordedRequest>>> = Arc::default();
+
+        let task = {
+            let routes = routes.clone();
+            let requests = requests.clone();
+
+            tokio::spawn(async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    tokio::spawn(handle_connection(stream, routes.clone(), requests.clone()));
+                }
+            })
+        };
+
+        Ok(Self {
+            addr,
+            routes,
+            requests,
+            task,
+        })
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Transport pointing both developer services (under `/services`) and GrandSlam here.
+    pub fn transport(&self) -> Transport {
+        Transport::new(
+            reqwest::Client::new(),
+            format!("{}/services", self.url()),
+            self.url(),
+        )
+    }
+
+    /// Queues a response for `path`, e.g. `/services/QH65B2/listTeams.action`.
+    /// Responses are served in order, the last one keeps being served.
+    pub fn mock(&self, path: &str, fixture: Fixture) {
+        if let Ok(mut routes) = self.routes.lock() {
+            routes
+                .entry(path.to_string())
+                .or_default()
+                .push_back(fixture);
+        }
+    }
+
+    /// Loads recorded responses from a directory, the file's path relative to it
+    /// (minus the extension) is the route it answers, so
+    /// `services/QH65B2/listTeams.action.plist` answers `/services/QH65B2/listTeams.action`.
+    pub fn load_fixtures(&self, dir: &Path) -> Result<(), Error> {
+        fn visit(server: &MockServer, root: &Path, dir: &Path) -> Result<(), Error> {
+            for entry in std::fs::read_dir(dir)? {
+                let path = entry?.path();
+
+                if path.is_dir() {
+                    visit(server, root, &path)?;
+                    continue;
+                }
+
+                let Ok(relative) = path.with_extension("").strip_prefix(root).map(|p| {
+                    p.components()
+                        .map(|c| c.as_os_str().to_string_lossy())
+                        .collect::<Vec<_>>()
+                        .join("/")
+                }) else {
+                    continue;
+                };
+
+                server.mock(&format!("/{}", relative), Fixture::from_file(&path)?);
+            }
+
+            Ok(())
+        }
+
+        visit(self, dir, dir)
+    }
+
+    /// Every request received so far, in order.
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().map(|r| r.clone()).unwrap_or_default()
+    }
+
+    /// Anisette data that doesn't need a provisioning server, valid for a minute
+    /// before the session tries to refresh it.
+    pub fn anisette() -> Arc<tokio::sync::Mutex<AnisetteData>> {
+        let base_headers = [
+            ("X-Apple-I-MD", "AAAAAA=="),
+            ("X-Apple-I-MD-M", "AAAAAA=="),
+            ("X-Apple-I-MD-RINFO", "17106176"),
+            ("X-Apple-I-MD-LU", "AAAAAA=="),
+            ("X-Apple-I-SRL-NO", "0"),
+            ("X-Mme-Device-Id", "00000000-0000-0000-0000-000000000000"),
+            (
+                "X-Mme-Client-Info",
+                "<MacBookPro13,2> <macOS;13.1;22C65> <com.apple.AuthKit/1 (com.apple.dt.Xcode/3594.4.19)>",
+            ),
+            ("X-Apple-I-Client-Time", "2025-01-01T00:00:00Z"),
+            ("X-Apple-Locale", "en_US"),
+            ("X-Apple-I-TimeZone", "UTC"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+
+        Arc::new(tokio::sync::Mutex::new(AnisetteData {
+            base_headers,
+            generated_at: SystemTime::now(),
+            config: AnisetteConfiguration::default(),
+            providers: Vec::new(),
+        }))
+    }
+
+    /// Developer session talking to this server, `listTeams` has to be mocked
+    /// since creating a session probes it.
+    pub async fn developer_session(&self) -> Result<DeveloperSession, Error> {
+        DeveloperSession::new_with_transport(
+            "000000-00-00000000-0000-0000-0000-000000000000".to_string(),
+            "mock-xcode-token".to_string(),
+            Self::anisette(),
+            self.transport(),
+        )
+        .await
+    }
+
+    /// [`MockServer::developer_session`] answering `listTeams` with the recorded
+    /// response, a single free team `TEAMID1234`.
+    pub async fn recorded_session(&self) -> Result<DeveloperSession, Error> {
+        self.mock(
+            "/services/QH65B2/listTeams.action",
+            Fixture::recorded("qh/listTeams.plist")?,
+        );
+        self.developer_session().await
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn handle_connection(
+    mut stream: TcpStream,
+    routes: Routes,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+) {
+    let Some(request) = read_request(&mut stream).await else {
+        return;
+    };
+
+    let route = request
+        .path
+        .split('?')
+        .next()
+        .unwrap_or_default()
+        .to_string();
+
+    let fixture = routes.lock().ok().and_then(|mut routes| {
+        let queue = routes.get_mut(&route)?;
+        if queue.len() > 1 {
+            queue.pop_front()
+        } else {
+            queue.front().cloned()
+        }
+    });
+
+    if let Ok(mut requests) = requests.lock() {
+        requests.push(request);
+    }
+
+    let fixture = fixture.unwrap_or_else(|| {
+        log::warn!("Mock server has no fixture for {}", route);
+        Fixture::new(404, "text/plain", format!("No fixture for {}", route))
+    });
+
+    let mut response = format!("HTTP/1.1 {} {}\r\n", fixture.status, reason(fixture.status));
+    for (name, value) in &fixture.headers {
+        response.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    response.push_str(&format!("Content-Length: {}\r\n", fixture.body.len()));
+    response.push_str("Connection: close\r\n\r\n");
+
+    let mut bytes = response.into_bytes();
+    bytes.extend_from_slice(&fixture.body);
+
+    stream.write_all(&bytes).await.ok();
+    stream.shutdown().await.ok();
+}
+
+async fn read_request(stream: &mut TcpStream) -> Option<RecordedRequest> {
+    let mut buffer = Vec::new();
+    let mut chunk = [0u8; 8192];
+
+    let header_end = loop {
+        let read = stream.read(&mut chunk).await.ok()?;
+        if read == 0 {
+            return None;
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+
+        if let Some(index) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+            break index;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+    let mut lines = head.split("\r\n");
+
+    let mut request_line = lines.next()?.split_whitespace();
+    let method = request_line.next()?.to_string();
+    let path = request_line.next()?.to_string();
+
+    let headers = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(k, v)| (k.trim().to_ascii_lowercase(), v.trim().to_string()))
+        .collect::<HashMap<_, _>>();
+
+    let content_length = headers
+        .get("content-length")
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(0);
+
+    let mut body = buffer[header_end + 4..].to_vec();
+    while body.len() < content_length {
+        let read = stream.read(&mut chunk).await.ok()?;
+        if read == 0 {
+            break;
+        }
+        body.extend_from_slice(&chunk[..read]);
+    }
+
+    Some(RecordedRequest {
+        method,
+        path,
+        headers,
+        body,
+    })
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        409 => "Conflict",
+        429 => "Too Many Requests",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        _ => "Unknown",
+    }
+}
@@ -0,0 +1,57 @@
+use reqwest::Client;
+
+use crate::Error;
+
+pub const DEVELOPER_SERVICES_URL: &str = "https://developerservices2.apple.com/services";
+pub const GSA_URL: &str = "https://gsa.apple.com";
+
+/// HTTP client and hosts used to talk to Apple.
+///
+/// [`Transport::apple`] pins Apple's root certificate and uses the real hosts,
+/// anything else (like a local mock server) can be plugged in with [`Transport::new`].
+#[derive(Debug, Clone)]
+pub struct Transport {
+    client: Client,
+    developer_services_url: String,
+    gsa_url: String,
+}
+
+impl Transport {
+    pub fn apple() -> Result<Self, Error> {
+        Ok(Self::new(crate::client()?, DEVELOPER_SERVICES_URL, GSA_URL))
+    }
+
+    pub fn new(
+        client: Client,
+        developer_services_url: impl Into<String>,
+        gsa_url: impl Into<String>,
+    ) -> Self {
+        Self {
+            client,
+            developer_services_url: developer_services_url.into(),
+            gsa_url: gsa_url.into(),
+        }
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    pub fn developer_services_url(&self) -> &str {
+        &self.developer_services_url
+    }
+
+    pub fn gsa_url(&self) -> &str {
+        &self.gsa_url
+    }
+
+    /// Full URL of a developer services endpoint, e.g. `/QH65B2/listTeams.action`.
+    pub fn developer_endpoint(&self, endpoint: &str) -> String {
+        format!("{}{}", self.developer_services_url, endpoint)
+    }
+
+    /// Full URL of a GrandSlam endpoint, e.g. `/auth/verify/trusteddevice`.
+    pub fn gsa_endpoint(&self, endpoint: &str) -> String {
+        format!("{}{}", self.gsa_url, endpoint)
+    }
+}
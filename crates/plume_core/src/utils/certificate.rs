@@ -17,9 +17,13 @@ use x509_certificate::{CapturedX509Certificate, X509Certificate};
 
 use crate::{
     Error,
-    developer::{DeveloperSession, qh::certs::Cert},
+    developer::{
+        DeveloperSession, PortalError, qh::certs::Cert, v1::certificates::CertificateKind,
+    },
 };
 
+use super::KeyStore;
+
 const MACHINE_NAME: &str = "AltStore";
 
 pub struct CertificateIdentity {
@@ -55,15 +59,14 @@ impl CertificateIdentity {
 
     pub async fn new_with_session(
         session: &DeveloperSession,
-        config_path: PathBuf,
+        keys: &KeyStore,
         machine_name: Option<String>,
         team_id: &String,
         is_export: bool,
+        auto_revoke: bool,
     ) -> Result<Self, Error> {
         let machine_name = machine_name.unwrap_or_else(|| MACHINE_NAME.to_string());
 
-        let key_path = Self::key_dir(config_path, &team_id)?.join("key.pem");
-
         let mut identity = Self {
             cert: None,
             key: None,
@@ -78,16 +81,12 @@ impl CertificateIdentity {
         // need to request certificates (after submitting a CSR, for example), they can do so
         let certs = session.qh_list_certs(&team_id).await?.certificates;
 
-        // Only the key will be written to disk, certificate can just be gotten via the request
+        // Only the key will be stored, certificate can just be gotten via the request
         // request we've made, by trying to match our public key with the requests public key
-        let key_pair: [Vec<u8>; 2] = if key_path.exists() {
-            let key_string = fs::read_to_string(&key_path)?;
+        let key_pair: [Vec<u8>; 2] = if let Some(key_string) = keys.read(team_id, "key.pem")? {
             let priv_key = RsaPrivateKey::from_pkcs8_pem(&key_string)?;
 
-            if let Some(certificate) = identity
-                .find_certificate(certs.clone(), &priv_key, &machine_name)
-                .await?
-            {
+            if let Some(certificate) = identity.find_certificate(certs.clone(), &priv_key).await? {
                 let cert_pem = encode_string(
                     "CERTIFICATE",
                     LineEnding::LF,
@@ -99,7 +98,7 @@ impl CertificateIdentity {
                 [cert_pem.into_bytes(), key_pem.into_bytes()]
             } else {
                 let (certificate, priv_key) = identity
-                    .request_new_certificate(session, team_id, &machine_name, certs)
+                    .request_new_certificate(session, team_id, &machine_name, certs, auto_revoke)
                     .await?;
                 let cert_pem = encode_string(
                     "CERTIFICATE",
@@ -109,19 +108,19 @@ impl CertificateIdentity {
                 .unwrap();
                 let key_pem = priv_key.to_pkcs8_pem(Default::default())?.to_string();
 
-                fs::write(&key_path, &key_pem)?;
+                keys.write(team_id, "key.pem", &key_pem)?;
                 identity.new = true;
                 [cert_pem.into_bytes(), key_pem.into_bytes()]
             }
         } else {
             let (cert, priv_key) = identity
-                .request_new_certificate(session, team_id, &machine_name, certs)
+                .request_new_certificate(session, team_id, &machine_name, certs, auto_revoke)
                 .await?;
             let cert_pem =
                 encode_string("CERTIFICATE", LineEnding::LF, cert.cert_content.as_ref()).unwrap();
             let key_pem = priv_key.to_pkcs8_pem(Default::default())?.to_string();
 
-            fs::write(&key_path, &key_pem)?;
+            keys.write(team_id, "key.pem", &key_pem)?;
             identity.new = true;
             [cert_pem.into_bytes(), key_pem.into_bytes()]
         };
@@ -138,13 +137,157 @@ impl CertificateIdentity {
         Ok(identity)
     }
 
-    // <config_path>/keys/<team_id>
-    fn key_dir(path: PathBuf, team_id: &String) -> Result<PathBuf, Error> {
-        let dir = path.join("keys").join(team_id);
+    /// Distribution identity for ad-hoc and in-house profiles, paid teams only.
+    ///
+    /// Kept apart from the development key, and never revoked to make room,
+    /// distribution certificates are usually shared across a team.
+    pub async fn new_distribution_with_session(
+        session: &DeveloperSession,
+        keys: &KeyStore,
+        team_id: &String,
+        is_export: bool,
+    ) -> Result<Self, Error> {
+        Self::new_with_v1_session(
+            session,
+            keys,
+            team_id,
+            CertificateKind::Distribution,
+            is_export,
+        )
+        .await
+    }
+
+    /// Identity managed through the `v1` certificate endpoints, the only ones
+    /// available to App Store Connect API key sessions.
+    pub async fn new_with_v1_session(
+        session: &DeveloperSession,
+        keys: &KeyStore,
+        team_id: &String,
+        kind: CertificateKind,
+        is_export: bool,
+    ) -> Result<Self, Error> {
+        // development certificates share the key with the Apple ID flow
+        let key_name = match kind {
+            CertificateKind::Development => "key.pem",
+            CertificateKind::Distribution => "distribution_key.pem",
+        };
+
+        let mut identity = Self {
+            cert: None,
+            key: None,
+            machine_id: None,
+            p12_data: None,
+            serial_number: None,
+            new: false,
+        };
 
-        fs::create_dir_all(&dir)?;
+        let certs = session
+            .v1_list_certificates(team_id, Some(kind))
+            .await?
+            .data;
 
-        Ok(dir)
+        let existing = if let Some(key_string) = keys.read(team_id, key_name)? {
+            let priv_key = RsaPrivateKey::from_pkcs8_pem(&key_string)?;
+            let mut found = None;
+            for cert in &certs {
+                let cert_der = cert.content_der()?;
+                if Self::matches_key(&cert_der, &priv_key)? {
+                    found = Some((cert.clone(), cert_der));
+                    break;
+                }
+            }
+            found.map(|found| (found, priv_key))
+        } else {
+            None
+        };
+
+        let ((certificate, cert_der), priv_key) = match existing {
+            Some(existing) => existing,
+            None => {
+                let priv_key = RsaPrivateKey::new(&mut OsRng, 2048)?;
+                let certificate = session
+                    .v1_create_certificate(team_id, Self::create_csr(&priv_key)?, kind)
+                    .await?
+                    .data;
+                let cert_der = certificate.content_der()?;
+
+                keys.write(
+                    team_id,
+                    key_name,
+                    &priv_key.to_pkcs8_pem(Default::default())?,
+                )?;
+                identity.new = true;
+                ((certificate, cert_der), priv_key)
+            }
+        };
+
+        identity.set_serial_number(certificate.attributes.serial_number.clone());
+
+        let cert_pem = encode_string("CERTIFICATE", LineEnding::LF, &cert_der).unwrap();
+        let key_pem = priv_key.to_pkcs8_pem(Default::default())?.to_string();
+        let key_pair = [cert_pem.into_bytes(), key_pem.into_bytes()];
+
+        if let Some(p12_data) = identity.create_pkcs12(&key_pair, is_export) {
+            identity.p12_data = Some(p12_data);
+        }
+
+        for pem in key_pair {
+            identity.resolve_certificate_from_contents(pem)?;
+        }
+
+        Ok(identity)
+    }
+
+    /// Adopts the key and certificate from a `.p12` as the team's identity,
+    /// so later signing reuses that certificate instead of requesting a new one.
+    ///
+    /// The certificate has to belong to the team, the previous key is kept as `key.pem.old`.
+    pub async fn import_p12(
+        session: &DeveloperSession,
+        keys: &KeyStore,
+        team_id: &String,
+        p12_data: &[u8],
+        password: &str,
+    ) -> Result<Cert, Error> {
+        let keystore = p12_keystore::KeyStore::from_pkcs12(p12_data, password)
+            .map_err(|e| Error::Certificate(format!("Failed to read p12: {}", e)))?;
+
+        let (_, key_chain) = keystore
+            .private_key_chain()
+            .ok_or_else(|| Error::Certificate("p12 has no private key".into()))?;
+
+        let priv_key = RsaPrivateKey::from_pkcs8_der(key_chain.key())?;
+        let cert_der = key_chain
+            .chain()
+            .first()
+            .ok_or(Error::CertificatePemMissing)?
+            .as_der()
+            .to_vec();
+
+        let certificate = session
+            .qh_list_certs(team_id)
+            .await?
+            .certificates
+            .into_iter()
+            .find(|c| c.cert_content.as_ref() == cert_der.as_slice())
+            .ok_or_else(|| {
+                Error::Certificate(format!(
+                    "Certificate is not a development certificate of team {}",
+                    team_id
+                ))
+            })?;
+
+        if let Some(previous) = keys.read(team_id, "key.pem")? {
+            keys.write(team_id, "key.pem.old", &previous)?;
+        }
+
+        keys.write(
+            team_id,
+            "key.pem",
+            &priv_key.to_pkcs8_pem(Default::default())?,
+        )?;
+
+        Ok(certificate)
     }
 
     fn set_machine_id(&mut self, machine_id: String) {
@@ -221,41 +364,37 @@ impl CertificateIdentity {
 }
 
 impl CertificateIdentity {
+    // The public key alone identifies our certificate, imported ones
+    // may have been requested under another machine name
     async fn find_certificate(
         &mut self,
         certs: Vec<Cert>,
         priv_key: &RsaPrivateKey,
-        machine_name: &str,
     ) -> Result<Option<Cert>, Error> {
-        let pub_key_der_obj = priv_key.to_public_key().to_pkcs1_der()?.as_bytes().to_vec();
-
         for cert in certs {
-            if cert.machine_name.as_deref() == Some(machine_name) {
-                let parsed_cert = X509Certificate::from_der(&cert.cert_content)?;
-                if pub_key_der_obj == parsed_cert.public_key_data().as_ref() {
-                    // We need to save the machine_id for our P12
-                    if let Some(ref machine_id) = cert.machine_id {
-                        self.set_machine_id(machine_id.clone());
-                    }
+            if Self::matches_key(cert.cert_content.as_ref(), priv_key)? {
+                // We need to save the machine_id for our P12
+                if let Some(ref machine_id) = cert.machine_id {
+                    self.set_machine_id(machine_id.clone());
+                }
 
-                    self.set_serial_number(cert.serial_number.clone());
+                self.set_serial_number(cert.serial_number.clone());
 
-                    return Ok(Some(cert));
-                }
+                return Ok(Some(cert));
             }
         }
 
         Ok(None)
     }
 
-    async fn request_new_certificate(
-        &mut self,
-        session: &DeveloperSession,
-        team_id: &String,
-        machine_name: &String,
-        certs: Vec<Cert>,
-    ) -> Result<(Cert, RsaPrivateKey), Error> {
-        let priv_key = RsaPrivateKey::new(&mut OsRng, 2048)?;
+    fn matches_key(cert_der: &[u8], priv_key: &RsaPrivateKey) -> Result<bool, Error> {
+        let pub_key_der_obj = priv_key.to_public_key().to_pkcs1_der()?.as_bytes().to_vec();
+        let parsed_cert = X509Certificate::from_der(cert_der)?;
+
+        Ok(pub_key_der_obj == parsed_cert.public_key_data().as_ref())
+    }
+
+    fn create_csr(priv_key: &RsaPrivateKey) -> Result<String, Error> {
         let priv_key_der = priv_key.to_pkcs8_der()?;
         let priv_key_pair = KeyPair::from_der(priv_key_der.as_bytes())?;
 
@@ -270,7 +409,19 @@ impl CertificateIdentity {
         dn.push(DnType::OrganizationName, "ORGNIZATION");
         dn.push(DnType::CommonName, "CN");
 
-        let cert_csr = rcgen::Certificate::from_params(params)?.serialize_request_pem()?;
+        Ok(rcgen::Certificate::from_params(params)?.serialize_request_pem()?)
+    }
+
+    async fn request_new_certificate(
+        &mut self,
+        session: &DeveloperSession,
+        team_id: &String,
+        machine_name: &String,
+        certs: Vec<Cert>,
+        auto_revoke: bool,
+    ) -> Result<(Cert, RsaPrivateKey), Error> {
+        let priv_key = RsaPrivateKey::new(&mut OsRng, 2048)?;
+        let cert_csr = Self::create_csr(&priv_key)?;
 
         let cert_serial_numbers = certs
             .iter()
@@ -282,7 +433,8 @@ impl CertificateIdentity {
         // fail, we also look through all of our existing certificates through
         // the api until we have a success on a single revokage, then we can
         // successfully submit our csr, but if we just cannot at all, return
-        // an error
+        // an error. With `auto_revoke` off the limit is left for the user to deal with,
+        // certificates may be shared with other machines on the team
         let cert_id = loop {
             match session
                 .qh_submit_cert_csr(&team_id, cert_csr.clone(), machine_name)
@@ -290,8 +442,14 @@ impl CertificateIdentity {
             {
                 Ok(id) => break id,
                 Err(e) => {
-                    // 7460 is for too many certificates (I think)
-                    if matches!(&e, Error::DeveloperApi { result_code, .. } if *result_code == 7460)
+                    if auto_revoke
+                        && matches!(
+                            &e,
+                            Error::DeveloperPortal {
+                                kind: PortalError::TooManyCertificates,
+                                ..
+                            }
+                        )
                     {
                         // Try to revoke certificates from the candidate list
                         let mut revoked_any = false;
@@ -353,3 +511,213 @@ impl CertificateIdentity {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::{CertificateIdentity, KeyStore};
+    use crate::testing::{Fixture, MockServer, fixtures_dir};
+
+    const LIST_CERTS: &str = "/services/QH65B2/ios/listAllDevelopmentCerts.action";
+    const SUBMIT_CSR: &str = "/services/QH65B2/ios/submitDevelopmentCSR.action";
+    const TEAM_ID: &str = "TEAMID1234";
+    // serial of the `AltStore` certificate in the fixtures, issued for `keys/key.pem`
+    const SERIAL: &str = "7921FF7BA27A365FBEA79A113BA632A215D95D34";
+
+    fn config_dir(with_key: bool) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("plume_identity_{}", uuid::Uuid::new_v4()));
+        let key_dir = dir.join("keys").join(TEAM_ID);
+        std::fs::create_dir_all(&key_dir).unwrap();
+        if with_key {
+            std::fs::copy(fixtures_dir().join("keys/key.pem"), key_dir.join("key.pem")).unwrap();
+        }
+        dir
+    }
+
+    #[tokio::test]
+    async fn reuses_certificate_matching_stored_key() {
+        let server = MockServer::start().await.unwrap();
+        let session = server.recorded_session().await.unwrap();
+        server.mock(
+            LIST_CERTS,
+            Fixture::recorded("qh/listAllDevelopmentCerts.plist").unwrap(),
+        );
+        let config = config_dir(true);
+
+        let identity = CertificateIdentity::new_with_session(
+            &session,
+            &KeyStore::new(config.clone(), None),
+            None,
+            &TEAM_ID.to_string(),
+            false,
+            true,
+        )
+        .await
+        .unwrap();
+
+        assert!(!identity.new);
+        assert_eq!(identity.serial_number.as_deref(), Some(SERIAL));
+        assert_eq!(
+            identity.machine_id.as_deref(),
+            Some("6F0E2C1B-3A5D-4E7F-8B9A-0C1D2E3F4A5B")
+        );
+        assert!(identity.cert.is_some() && identity.key.is_some());
+        assert!(identity.p12_data.is_some());
+        assert!(!server.requests().iter().any(|r| r.path == SUBMIT_CSR));
+
+        std::fs::remove_dir_all(config).ok();
+    }
+
+    #[tokio::test]
+    async fn requests_certificate_without_key() {
+        let server = MockServer::start().await.unwrap();
+        let session = server.recorded_session().await.unwrap();
+        // the new certificate only shows up once the CSR went through
+        server.mock(
+            LIST_CERTS,
+            Fixture::recorded("qh/listAllDevelopmentCerts-other.plist").unwrap(),
+        );
+        server.mock(
+            LIST_CERTS,
+            Fixture::recorded("qh/listAllDevelopmentCerts.plist").unwrap(),
+        );
+        server.mock(
+            SUBMIT_CSR,
+            Fixture::recorded("qh/submitDevelopmentCSR.plist").unwrap(),
+        );
+        let config = config_dir(false);
+
+        let identity = CertificateIdentity::new_with_session(
+            &session,
+            &KeyStore::new(config.clone(), None),
+            Some("Test Machine".to_string()),
+            &TEAM_ID.to_string(),
+            false,
+            true,
+        )
+        .await
+        .unwrap();
+
+        assert!(identity.new);
+        assert_eq!(identity.serial_number.as_deref(), Some(SERIAL));
+        assert!(config.join("keys").join(TEAM_ID).join("key.pem").exists());
+
+        let requests = server.requests();
+        let csr = requests.iter().find(|r| r.path == SUBMIT_CSR).unwrap();
+        let body = csr.plist().unwrap();
+        assert_eq!(
+            body.get("machineName").and_then(|v| v.as_string()),
+            Some("Test Machine")
+        );
+        assert!(
+            body.get("csrContent")
+                .and_then(|v| v.as_string())
+                .is_some_and(|csr| csr.contains("CERTIFICATE REQUEST"))
+        );
+
+        std::fs::remove_dir_all(config).ok();
+    }
+
+    #[tokio::test]
+    async fn revokes_to_make_room_when_allowed() {
+        let server = MockServer::start().await.unwrap();
+        let session = server.recorded_session().await.unwrap();
+        server.mock(
+            LIST_CERTS,
+            Fixture::recorded("qh/listAllDevelopmentCerts-other.plist").unwrap(),
+        );
+        server.mock(
+            LIST_CERTS,
+            Fixture::recorded("qh/listAllDevelopmentCerts.plist").unwrap(),
+        );
+        server.mock(
+            SUBMIT_CSR,
+            Fixture::qh_error(
+                7460,
+                "You already have a current iOS Development certificate",
+            ),
+        );
+        server.mock(
+            SUBMIT_CSR,
+            Fixture::recorded("qh/submitDevelopmentCSR.plist").unwrap(),
+        );
+        server.mock(
+            "/services/QH65B2/ios/revokeDevelopmentCert.action",
+            Fixture::qh(plist::Dictionary::new()),
+        );
+        let config = config_dir(false);
+
+        let identity = CertificateIdentity::new_with_session(
+            &session,
+            &KeyStore::new(config.clone(), None),
+            None,
+            &TEAM_ID.to_string(),
+            false,
+            true,
+        )
+        .await
+        .unwrap();
+
+        assert!(identity.new);
+        let requests = server.requests();
+        let revoke = requests
+            .iter()
+            .find(|r| r.path.ends_with("revokeDevelopmentCert.action"))
+            .unwrap();
+        assert_eq!(
+            revoke
+                .plist()
+                .unwrap()
+                .get("serialNumber")
+                .and_then(|v| v.as_string()),
+            Some("664FE0B989870867C0DC7DB5D9D4C702A6A271F5")
+        );
+
+        std::fs::remove_dir_all(config).ok();
+    }
+
+    #[tokio::test]
+    async fn leaves_certificates_alone_without_auto_revoke() {
+        let server = MockServer::start().await.unwrap();
+        let session = server.recorded_session().await.unwrap();
+        server.mock(
+            LIST_CERTS,
+            Fixture::recorded("qh/listAllDevelopmentCerts-other.plist").unwrap(),
+        );
+        server.mock(
+            SUBMIT_CSR,
+            Fixture::qh_error(
+                7460,
+                "You already have a current iOS Development certificate",
+            ),
+        );
+        let config = config_dir(false);
+
+        let result = CertificateIdentity::new_with_session(
+            &session,
+            &KeyStore::new(config.clone(), None),
+            None,
+            &TEAM_ID.to_string(),
+            false,
+            false,
+        )
+        .await;
+
+        assert!(matches!(
+            result,
+            Err(crate::Error::DeveloperPortal {
+                result_code: 7460,
+                ..
+            })
+        ));
+        assert!(
+            !server
+                .requests()
+                .iter()
+                .any(|r| r.path.ends_with("revokeDevelopmentCert.action"))
+        );
+
+        std::fs::remove_dir_all(config).ok();
+    }
+}
@@ -0,0 +1,233 @@
+use std::fmt::Debug;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use crate::Error;
+
+/// Key files a team can have: the development key, the one it replaced on import,
+/// and the distribution key.
+pub const KEY_NAMES: [&str; 3] = ["key.pem", "key.pem.old", "distribution_key.pem"];
+
+/// Somewhere to keep secrets outside of plain files, like the OS keyring or an encrypted vault.
+pub trait SecretBackend: Debug + Send + Sync {
+    fn name(&self) -> &'static str;
+    fn get(&self, key: &str) -> Result<Option<String>, Error>;
+    fn set(&self, key: &str, value: &str) -> Result<(), Error>;
+    fn delete(&self, key: &str) -> Result<(), Error>;
+}
+
+/// Private keys of each team, kept in `secrets` when there's a backend and in
+/// `<dir>/keys/<team>/` otherwise.
+///
+/// The team directory is created either way, it's how teams with keys are found again.
+#[derive(Debug, Clone)]
+pub struct KeyStore {
+    dir: PathBuf,
+    secrets: Option<Arc<dyn SecretBackend>>,
+}
+
+impl KeyStore {
+    pub fn new(dir: PathBuf, secrets: Option<Arc<dyn SecretBackend>>) -> Self {
+        Self { dir, secrets }
+    }
+
+    pub fn dir(&self) -> &PathBuf {
+        &self.dir
+    }
+
+    pub fn secret_backend(&self) -> Option<&Arc<dyn SecretBackend>> {
+        self.secrets.as_ref()
+    }
+
+    /// The PEM of a key, moving a plaintext file left by older versions into the backend.
+    pub fn read(&self, team_id: &str, name: &str) -> Result<Option<String>, Error> {
+        let path = self.key_dir(team_id)?.join(name);
+
+        let Some(secrets) = &self.secrets else {
+            return match path.exists() {
+                true => Ok(Some(fs::read_to_string(&path)?)),
+                false => Ok(None),
+            };
+        };
+
+        if let Some(pem) = secrets.get(&Self::secret_key(team_id, name))? {
+            return Ok(Some(pem));
+        }
+
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let pem = fs::read_to_string(&path)?;
+        secrets.set(&Self::secret_key(team_id, name), &pem)?;
+        fs::remove_file(&path)?;
+        Ok(Some(pem))
+    }
+
+    pub fn write(&self, team_id: &str, name: &str, pem: &str) -> Result<(), Error> {
+        let path = self.key_dir(team_id)?.join(name);
+
+        if let Some(secrets) = &self.secrets {
+            secrets.set(&Self::secret_key(team_id, name), pem)?;
+            if path.exists() {
+                fs::remove_file(&path)?;
+            }
+            return Ok(());
+        }
+
+        fs::write(&path, pem)?;
+
+        // private keys stay readable by the user only, the rest of the data dir isn't secret
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+        }
+
+        Ok(())
+    }
+
+    /// Teams that had a key stored, whichever way it's kept.
+    pub fn teams(&self) -> Result<Vec<String>, Error> {
+        let dir = self.dir.join("keys");
+        let mut teams = Vec::new();
+
+        if !dir.is_dir() {
+            return Ok(teams);
+        }
+
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            if entry.file_type()?.is_dir() {
+                teams.push(entry.file_name().to_string_lossy().to_string());
+            }
+        }
+
+        Ok(teams)
+    }
+
+    // <dir>/keys/<team_id>
+    fn key_dir(&self, team_id: &str) -> Result<PathBuf, Error> {
+        let dir = self.dir.join("keys").join(team_id);
+
+        fs::create_dir_all(&dir)?;
+
+        Ok(dir)
+    }
+
+    fn secret_key(team_id: &str, name: &str) -> String {
+        format!("key:{team_id}:{name}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    use super::*;
+
+    const TEAM_ID: &str = "TEAMID1234";
+
+    #[derive(Debug, Default)]
+    struct MemorySecrets(Mutex<HashMap<String, String>>);
+
+    impl SecretBackend for MemorySecrets {
+        fn name(&self) -> &'static str {
+            "memory"
+        }
+
+        fn get(&self, key: &str) -> Result<Option<String>, Error> {
+            Ok(self.0.lock().unwrap().get(key).cloned())
+        }
+
+        fn set(&self, key: &str, value: &str) -> Result<(), Error> {
+            self.0
+                .lock()
+                .unwrap()
+                .insert(key.to_string(), value.to_string());
+            Ok(())
+        }
+
+        fn delete(&self, key: &str) -> Result<(), Error> {
+            self.0.lock().unwrap().remove(key);
+            Ok(())
+        }
+    }
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("plume_keys_{}", uuid::Uuid::new_v4()))
+    }
+
+    #[test]
+    fn writes_plaintext_keys_for_the_user_only() {
+        let dir = temp_dir();
+        let keys = KeyStore::new(dir.clone(), None);
+
+        keys.write(TEAM_ID, "key.pem", "PEM").unwrap();
+
+        let path = dir.join("keys").join(TEAM_ID).join("key.pem");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "PEM");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(
+                fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+                0o600
+            );
+        }
+        assert_eq!(
+            keys.read(TEAM_ID, "key.pem").unwrap().as_deref(),
+            Some("PEM")
+        );
+
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn keeps_keys_out_of_the_data_dir_with_a_backend() {
+        let dir = temp_dir();
+        let secrets = Arc::new(MemorySecrets::default());
+        let keys = KeyStore::new(dir.clone(), Some(secrets.clone()));
+
+        keys.write(TEAM_ID, "key.pem", "PEM").unwrap();
+
+        assert!(!dir.join("keys").join(TEAM_ID).join("key.pem").exists());
+        assert_eq!(
+            secrets.get("key:TEAMID1234:key.pem").unwrap().as_deref(),
+            Some("PEM")
+        );
+        assert_eq!(
+            keys.read(TEAM_ID, "key.pem").unwrap().as_deref(),
+            Some("PEM")
+        );
+        assert_eq!(keys.teams().unwrap(), vec![TEAM_ID.to_string()]);
+
+        fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn moves_plaintext_keys_into_the_backend() {
+        let dir = temp_dir();
+        KeyStore::new(dir.clone(), None)
+            .write(TEAM_ID, "key.pem", "PEM")
+            .unwrap();
+
+        let secrets = Arc::new(MemorySecrets::default());
+        let keys = KeyStore::new(dir.clone(), Some(secrets.clone()));
+
+        assert_eq!(
+            keys.read(TEAM_ID, "key.pem").unwrap().as_deref(),
+            Some("PEM")
+        );
+        assert!(!dir.join("keys").join(TEAM_ID).join("key.pem").exists());
+        assert_eq!(
+            secrets.get("key:TEAMID1234:key.pem").unwrap().as_deref(),
+            Some("PEM")
+        );
+        assert_eq!(keys.read(TEAM_ID, "distribution_key.pem").unwrap(), None);
+
+        fs::remove_dir_all(dir).ok();
+    }
+}
@@ -1,11 +1,13 @@
 use plist::Value;
 
 mod certificate;
+mod key_store;
 #[cfg(feature = "tweaks")]
 mod macho;
 mod provision;
 
 pub use certificate::CertificateIdentity;
+pub use key_store::{KEY_NAMES, KeyStore, SecretBackend};
 #[cfg(feature = "tweaks")]
 pub use macho::{MachO, MachOExt};
 pub use provision::MobileProvision;
@@ -12,6 +12,11 @@ pub struct MobileProvision {
     pub data: Vec<u8>,
     entitlements: Dictionary,
     expiration_date: Date,
+    name: Option<String>,
+    uuid: Option<String>,
+    devices: Vec<String>,
+    provisions_all_devices: bool,
+    developer_certificates: Vec<Vec<u8>>,
 }
 
 impl MobileProvision {
@@ -23,12 +28,57 @@ impl MobileProvision {
     }
 
     pub fn load_with_bytes(data: Vec<u8>) -> Result<Self, Error> {
-        let (entitlements, expiration_date) = Self::extract_entitlements_from_prov(&data)?;
+        let plist = Self::extract_plist_from_prov(&data)?;
+
+        let expiration_date = plist
+            .get("ExpirationDate")
+            .and_then(|v| v.as_date())
+            .ok_or(Error::ProvisioningEntitlementsUnknown)?;
+
+        let entitlements = plist
+            .get("Entitlements")
+            .and_then(|v| v.as_dictionary())
+            .cloned()
+            .ok_or(Error::ProvisioningEntitlementsUnknown)?;
+
+        let devices = plist
+            .get("ProvisionedDevices")
+            .and_then(|v| v.as_array())
+            .map(|a| {
+                a.iter()
+                    .filter_map(|d| d.as_string().map(|s| s.to_owned()))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let developer_certificates = plist
+            .get("DeveloperCertificates")
+            .and_then(|v| v.as_array())
+            .map(|a| {
+                a.iter()
+                    .filter_map(|c| c.as_data().map(|d| d.to_vec()))
+                    .collect()
+            })
+            .unwrap_or_default();
 
         Ok(Self {
             data,
             entitlements,
             expiration_date,
+            name: plist
+                .get("Name")
+                .and_then(|v| v.as_string())
+                .map(|s| s.to_owned()),
+            uuid: plist
+                .get("UUID")
+                .and_then(|v| v.as_string())
+                .map(|s| s.to_owned()),
+            devices,
+            provisions_all_devices: plist
+                .get("ProvisionsAllDevices")
+                .and_then(|v| v.as_boolean())
+                .unwrap_or(false),
+            developer_certificates,
         })
     }
 
@@ -67,6 +117,32 @@ impl MobileProvision {
         &self.expiration_date
     }
 
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn uuid(&self) -> Option<&str> {
+        self.uuid.as_deref()
+    }
+
+    /// UDIDs the profile was generated for, empty for in-house profiles.
+    pub fn devices(&self) -> &[String] {
+        &self.devices
+    }
+
+    pub fn provisions_device(&self, udid: &str) -> bool {
+        self.provisions_all_devices || self.devices.iter().any(|d| d.eq_ignore_ascii_case(udid))
+    }
+
+    /// DER encoded certificates the profile allows signing with.
+    pub fn developer_certificates(&self) -> &[Vec<u8>] {
+        &self.developer_certificates
+    }
+
+    pub fn includes_certificate(&self, der: &[u8]) -> bool {
+        self.developer_certificates.iter().any(|c| c == der)
+    }
+
     pub fn entitlements_as_bytes(&self) -> Result<Vec<u8>, Error> {
         let mut buf = Vec::new();
         Value::Dictionary(self.entitlements.clone()).to_writer_xml(&mut buf)?;
@@ -85,7 +161,7 @@ impl MobileProvision {
         Some(bundle_id)
     }
 
-    fn extract_entitlements_from_prov(data: &[u8]) -> Result<(Dictionary, Date), Error> {
+    fn extract_plist_from_prov(data: &[u8]) -> Result<Dictionary, Error> {
         let start = data
             .windows(6)
             .position(|w| w == b"<plist")
@@ -98,21 +174,8 @@ impl MobileProvision {
         let plist_data = &data[start..end];
         let plist = plist::Value::from_reader_xml(plist_data)?;
 
-        let expiration_date = plist
-            .as_dictionary()
-            .and_then(|d| d.get("ExpirationDate"))
-            .and_then(|v| v.as_date());
-
-        let entitlements = plist
-            .as_dictionary()
-            .and_then(|d| d.get("Entitlements"))
-            .and_then(|v| v.as_dictionary())
-            .cloned()
-            .ok_or(Error::ProvisioningEntitlementsUnknown);
-
-        Ok((
-            entitlements?,
-            expiration_date.ok_or(Error::ProvisioningEntitlementsUnknown)?,
-        ))
+        plist
+            .into_dictionary()
+            .ok_or(Error::ProvisioningEntitlementsUnknown)
     }
 }
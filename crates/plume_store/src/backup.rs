@@ -0,0 +1,689 @@
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+
+use aes_gcm::{
+    Aes256Gcm, Nonce,
+    aead::{Aead, AeadCore, KeyInit, OsRng, rand_core::RngCore},
+};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use plume_core::{Error, KEY_NAMES, KeyStore};
+
+use crate::file::write_atomic_sync;
+use crate::migrations::migrate;
+use crate::vault::SALT_LEN;
+use crate::{AccountStore, FileVault};
+
+const MAGIC: &[u8; 8] = b"PLUMEBAK";
+const FORMAT_VERSION: u8 = 1;
+const NONCE_LEN: usize = 12;
+
+/// Signed app copies refreshes resign, only backed up when asked for since the whole
+/// archive is built in memory. Signing keys go through the [`KeyStore`].
+const APPS_DIR: &str = "refresh_store";
+/// Anisette provisioning state omnisette keeps in its configuration path,
+/// without it the new machine shows up as a new device to Apple.
+const ANISETTE_STATE: [&str; 2] = ["adi.pb", "device.json"];
+
+/// What went into, or came out of, a backup.
+#[derive(Debug, Clone, Default)]
+pub struct BackupSummary {
+    pub accounts: usize,
+    pub refresh_devices: usize,
+    pub files: usize,
+}
+
+// the decrypted archive, files are keyed by their `/` separated path under the data path
+#[derive(Serialize, Deserialize)]
+struct BackupContents {
+    created: DateTime<Utc>,
+    data_path: PathBuf,
+    store: Value,
+    files: BTreeMap<String, String>,
+}
+
+impl AccountStore {
+    fn key_store(&self, data_path: &Path) -> KeyStore {
+        KeyStore::new(data_path.to_path_buf(), self.secret_backend().cloned())
+    }
+
+    /// Writes everything needed to pick up on another machine to `archive`, encrypted
+    /// with a key derived from `passphrase` (Argon2id + AES-256-GCM).
+    ///
+    /// `data_path` is where keys, anisette state and `refresh_store` live. The stored app
+    /// copies are only included with `include_apps`, the archive is held in memory while
+    /// it's written and they can run into gigabytes. The archive holds the account tokens
+    /// in the clear once decrypted, whatever the secret backend.
+    pub fn create_backup(
+        &self,
+        data_path: &Path,
+        archive: &Path,
+        passphrase: &str,
+        include_apps: bool,
+    ) -> Result<BackupSummary, Error> {
+        let mut files = BTreeMap::new();
+        if include_apps {
+            collect_files(data_path, &data_path.join(APPS_DIR), &mut files)?;
+        }
+        let keys = self.key_store(data_path);
+        for team_id in keys.teams()? {
+            for name in KEY_NAMES {
+                if let Some(pem) = keys.read(&team_id, name)? {
+                    files.insert(format!("keys/{team_id}/{name}"), STANDARD.encode(pem));
+                }
+            }
+        }
+        for name in ANISETTE_STATE {
+            let path = data_path.join(name);
+            if path.is_file() {
+                files.insert(name.to_string(), STANDARD.encode(std::fs::read(&path)?));
+            }
+        }
+
+        let summary = BackupSummary {
+            accounts: self.accounts().len(),
+            refresh_devices: self.refreshes().len(),
+            files: files.len(),
+        };
+
+        let contents = BackupContents {
+            created: Utc::now(),
+            data_path: data_path.to_path_buf(),
+            store: serde_json::to_value(self)?,
+            files,
+        };
+
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let key = FileVault::derive_key(passphrase, &salt)?;
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = Aes256Gcm::new(&key)
+            .encrypt(&nonce, serde_json::to_vec(&contents)?.as_ref())
+            .map_err(|e| Error::Backup(e.to_string()))?;
+
+        let mut out = Vec::with_capacity(MAGIC.len() + 1 + SALT_LEN + NONCE_LEN + ciphertext.len());
+        out.extend_from_slice(MAGIC);
+        out.push(FORMAT_VERSION);
+        out.extend_from_slice(&salt);
+        out.extend_from_slice(&nonce);
+        out.extend_from_slice(&ciphertext);
+
+        write_atomic_sync(archive, &out)?;
+        Ok(summary)
+    }
+
+    /// Restores a backup made with [`AccountStore::create_backup`] into `data_path` and this store.
+    ///
+    /// Accounts and refresh devices from the backup replace ones with the same email or UDID,
+    /// others are kept. Refresh entries are pointed at the restored app copies, apps whose
+    /// copy is neither in the backup nor on this machine are dropped.
+    ///
+    /// Signing keys and anisette state that differ from the backup's are only replaced
+    /// with `force`, otherwise nothing is restored and [`Error::BackupWouldReplace`] lists them.
+    pub fn restore_backup(
+        &mut self,
+        data_path: &Path,
+        archive: &Path,
+        passphrase: &str,
+        force: bool,
+    ) -> Result<BackupSummary, Error> {
+        let data = std::fs::read(archive)?;
+        let header_len = MAGIC.len() + 1 + SALT_LEN + NONCE_LEN;
+        if data.len() < header_len || !data.starts_with(MAGIC) {
+            return Err(Error::Backup("Not a Plume backup".into()));
+        }
+        if data[MAGIC.len()] != FORMAT_VERSION {
+            return Err(Error::Backup(format!(
+                "Unsupported backup format {}",
+                data[MAGIC.len()]
+            )));
+        }
+
+        let (salt, rest) = data[MAGIC.len() + 1..].split_at(SALT_LEN);
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+        let key = FileVault::derive_key(passphrase, salt)?;
+        let plaintext = Aes256Gcm::new(&key)
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| Error::Backup("Wrong passphrase or corrupt backup".into()))?;
+
+        let mut contents: BackupContents = serde_json::from_slice(&plaintext)?;
+        migrate(&mut contents.store)?;
+        let backup: AccountStore = serde_json::from_value(contents.store)?;
+
+        let mut files = Vec::with_capacity(contents.files.len());
+        for (name, encoded) in std::mem::take(&mut contents.files) {
+            let path = restore_path(data_path, &name)?;
+            let bytes = STANDARD
+                .decode(encoded)
+                .map_err(|_| Error::Backup(format!("Invalid contents for {name}")))?;
+            files.push((name, path, bytes));
+        }
+
+        let keys = self.key_store(data_path);
+        if !force {
+            let replaced = replaced_files(&keys, &files)?;
+            if !replaced.is_empty() {
+                return Err(Error::BackupWouldReplace(replaced.join(", ")));
+            }
+        }
+
+        // keys land in the secret backend of this machine, whatever the old one used
+        let mut pems = Vec::new();
+        let mut staged = Vec::new();
+        for (name, path, bytes) in &files {
+            if let ["keys", team_id, key_name] = name.split('/').collect::<Vec<_>>().as_slice() {
+                let pem = String::from_utf8(bytes.clone())
+                    .map_err(|_| Error::Backup(format!("Invalid contents for {name}")))?;
+                pems.push((team_id.to_string(), key_name.to_string(), pem));
+            } else {
+                staged.push((name, path, bytes));
+            }
+        }
+
+        // nothing lands in the data path until the store took the backup, so a failed
+        // merge doesn't leave files behind that no account references
+        let staging = data_path.join(format!(".restore.{}", std::process::id()));
+        std::fs::remove_dir_all(&staging).ok();
+        let result = self.restore_staged(data_path, &staging, &contents.data_path, backup, &staged);
+        if result.is_err() {
+            std::fs::remove_dir_all(&staging).ok();
+        }
+        let summary = BackupSummary {
+            files: files.len(),
+            ..result?
+        };
+
+        for (name, path, _) in &staged {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::rename(staging.join(name), path)?;
+        }
+        std::fs::remove_dir_all(&staging).ok();
+
+        for (team_id, key_name, pem) in &pems {
+            keys.write(team_id, key_name, pem)?;
+        }
+
+        Ok(summary)
+    }
+
+    // writes the backup's files under `staging` and merges its store, pointing refresh
+    // entries at where the staged app copies will end up
+    fn restore_staged(
+        &mut self,
+        data_path: &Path,
+        staging: &Path,
+        backup_data_path: &Path,
+        mut backup: AccountStore,
+        files: &[(&String, &PathBuf, &Vec<u8>)],
+    ) -> Result<BackupSummary, Error> {
+        for (name, _, bytes) in files {
+            let path = staging.join(name.as_str());
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&path, bytes)?;
+            if !name.starts_with(&format!("{APPS_DIR}/")) {
+                restrict_permissions(&path)?;
+            }
+        }
+
+        // app copies were saved under the old machine's data path
+        for device in backup.refreshes_mut().values_mut() {
+            for app in device.apps.iter_mut() {
+                if let Ok(relative) = app.path.strip_prefix(backup_data_path) {
+                    app.path = data_path.join(relative);
+                }
+            }
+            // backups leave the app copies out unless asked, there's nothing to refresh then
+            device.apps.retain(|app| {
+                let staged = app
+                    .path
+                    .strip_prefix(data_path)
+                    .is_ok_and(|relative| staging.join(relative).exists());
+                let exists = staged || app.path.exists();
+                if !exists {
+                    log::warn!(
+                        "Dropping refresh of {} for {}, its app copy wasn't backed up",
+                        app.path.display(),
+                        device.udid
+                    );
+                }
+                exists
+            });
+        }
+
+        let summary = BackupSummary {
+            accounts: backup.accounts().len(),
+            refresh_devices: backup.refreshes().len(),
+            files: files.len(),
+        };
+
+        self.update_sync(|s| {
+            s.merge_backup(backup);
+            Ok(())
+        })?;
+
+        Ok(summary)
+    }
+}
+
+fn collect_files(
+    data_path: &Path,
+    dir: &Path,
+    files: &mut BTreeMap<String, String>,
+) -> Result<(), Error> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        // following links could loop forever, the framework links in Mac apps are left out
+        if file_type.is_symlink() {
+            log::warn!("Leaving link {} out of the backup", path.display());
+            continue;
+        }
+        if file_type.is_dir() {
+            collect_files(data_path, &path, files)?;
+            continue;
+        }
+
+        let name = path
+            .strip_prefix(data_path)
+            .map_err(|_| Error::Backup(format!("{} is outside the data path", path.display())))?
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        files.insert(name, STANDARD.encode(std::fs::read(&path)?));
+    }
+
+    Ok(())
+}
+
+// keys and anisette state on this machine that the backup has different contents for
+fn replaced_files(
+    keys: &KeyStore,
+    files: &[(String, PathBuf, Vec<u8>)],
+) -> Result<Vec<String>, Error> {
+    let teams = keys.teams()?;
+    let mut replaced = Vec::new();
+
+    for (name, path, bytes) in files {
+        let current = match name.split('/').collect::<Vec<_>>().as_slice() {
+            // reading a team that has no keys would create its directory
+            ["keys", team_id, key_name] if teams.iter().any(|t| t == team_id) => {
+                keys.read(team_id, key_name)?.map(String::into_bytes)
+            }
+            [file] if ANISETTE_STATE.contains(file) && path.is_file() => Some(std::fs::read(path)?),
+            _ => None,
+        };
+
+        if current.is_some_and(|current| &current != bytes) {
+            replaced.push(name.clone());
+        }
+    }
+
+    Ok(replaced)
+}
+
+// refuses names that would land outside the data path
+fn restore_path(data_path: &Path, name: &str) -> Result<PathBuf, Error> {
+    let relative = Path::new(name);
+    if name.is_empty()
+        || !relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+    {
+        return Err(Error::Backup(format!("Invalid file name {name}")));
+    }
+    Ok(data_path.join(relative))
+}
+
+// keys and anisette state are as sensitive as the tokens
+fn restrict_permissions(path: &Path) -> Result<(), Error> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::*;
+
+    const EMAIL: &str = "user@example.com";
+    const IPHONE: &str = "00008110-000000000000001E";
+    const TEAM_ID: &str = "TEAMID1234";
+    const PASSPHRASE: &str = "correct horse battery staple";
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("plume_backup_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn app_path(data_path: &Path) -> PathBuf {
+        data_path.join(APPS_DIR).join(IPHONE).join("Example.app")
+    }
+
+    // a data path with the v3 fixture's account and refresh entry, its app copy,
+    // a signing key and anisette state
+    fn source_data_path() -> (PathBuf, AccountStore) {
+        let dir = temp_dir();
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/accounts/v3.json");
+        let contents = std::fs::read_to_string(fixture)
+            .unwrap()
+            .replace("/home/user/.config/PlumeImpactor", &dir.to_string_lossy());
+        std::fs::write(dir.join("accounts.json"), contents).unwrap();
+
+        std::fs::create_dir_all(app_path(&dir)).unwrap();
+        std::fs::write(app_path(&dir).join("Info.plist"), "PLIST").unwrap();
+        KeyStore::new(dir.clone(), None)
+            .write(TEAM_ID, "key.pem", "OLD MACHINE KEY")
+            .unwrap();
+        std::fs::write(dir.join("adi.pb"), "OLD MACHINE ADI").unwrap();
+
+        let store =
+            AccountStore::load_with_secrets_sync(&Some(dir.join("accounts.json")), None).unwrap();
+        (dir, store)
+    }
+
+    fn empty_data_path() -> (PathBuf, AccountStore) {
+        let dir = temp_dir();
+        let store =
+            AccountStore::load_with_secrets_sync(&Some(dir.join("accounts.json")), None).unwrap();
+        (dir, store)
+    }
+
+    fn backup(include_apps: bool) -> (PathBuf, PathBuf) {
+        let (source, store) = source_data_path();
+        let archive = source.join("backup.plumebackup");
+        store
+            .create_backup(&source, &archive, PASSPHRASE, include_apps)
+            .unwrap();
+        (source, archive)
+    }
+
+    #[test]
+    fn restores_into_another_data_path() {
+        let (source, archive) = backup(true);
+        let (dest, mut store) = empty_data_path();
+
+        let summary = store
+            .restore_backup(&dest, &archive, PASSPHRASE, false)
+            .unwrap();
+
+        assert_eq!(summary.accounts, 1);
+        assert_eq!(summary.refresh_devices, 1);
+        assert_eq!(summary.files, 3);
+        assert!(store.get_account(EMAIL).is_some());
+
+        let app = &store.get_refresh_device(IPHONE).unwrap().apps[0];
+        assert_eq!(app.path, app_path(&dest));
+        assert_eq!(
+            std::fs::read_to_string(app.path.join("Info.plist")).unwrap(),
+            "PLIST"
+        );
+        assert_eq!(
+            KeyStore::new(dest.clone(), None)
+                .read(TEAM_ID, "key.pem")
+                .unwrap()
+                .as_deref(),
+            Some("OLD MACHINE KEY")
+        );
+        assert_eq!(
+            std::fs::read_to_string(dest.join("adi.pb")).unwrap(),
+            "OLD MACHINE ADI"
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(
+                std::fs::metadata(dest.join("adi.pb"))
+                    .unwrap()
+                    .permissions()
+                    .mode()
+                    & 0o777,
+                0o600
+            );
+        }
+
+        // the restored store is what's on disk
+        let reloaded =
+            AccountStore::load_with_secrets_sync(&Some(dest.join("accounts.json")), None).unwrap();
+        assert!(reloaded.get_account(EMAIL).is_some());
+        assert!(!staging_left(&dest));
+
+        std::fs::remove_dir_all(source).ok();
+        std::fs::remove_dir_all(dest).ok();
+    }
+
+    fn staging_left(data_path: &Path) -> bool {
+        std::fs::read_dir(data_path).unwrap().any(|entry| {
+            entry
+                .unwrap()
+                .file_name()
+                .to_string_lossy()
+                .starts_with(".restore.")
+        })
+    }
+
+    #[test]
+    fn failed_merge_leaves_the_data_path_alone() {
+        let (source, archive) = backup(true);
+        let (dest, mut store) = empty_data_path();
+        // the store can't be read back for the merge
+        std::fs::create_dir_all(dest.join("accounts.json")).unwrap();
+
+        assert!(
+            store
+                .restore_backup(&dest, &archive, PASSPHRASE, false)
+                .is_err()
+        );
+
+        assert!(!dest.join(APPS_DIR).exists());
+        assert!(!dest.join("adi.pb").exists());
+        assert!(
+            KeyStore::new(dest.clone(), None)
+                .read(TEAM_ID, "key.pem")
+                .unwrap()
+                .is_none()
+        );
+        assert!(!staging_left(&dest));
+
+        std::fs::remove_dir_all(source).ok();
+        std::fs::remove_dir_all(dest).ok();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn leaves_links_out() {
+        let (source, store) = source_data_path();
+        // a loop that would never finish if links were followed
+        std::os::unix::fs::symlink(&source, app_path(&source).join("Loop")).unwrap();
+        let archive = source.join("backup.plumebackup");
+
+        let summary = store
+            .create_backup(&source, &archive, PASSPHRASE, true)
+            .unwrap();
+
+        // Info.plist, the key and adi.pb
+        assert_eq!(summary.files, 3);
+
+        std::fs::remove_dir_all(source).ok();
+    }
+
+    #[test]
+    fn leaves_app_copies_out_by_default() {
+        let (source, archive) = backup(false);
+        let (dest, mut store) = empty_data_path();
+
+        let summary = store
+            .restore_backup(&dest, &archive, PASSPHRASE, false)
+            .unwrap();
+
+        // the key and adi.pb
+        assert_eq!(summary.files, 2);
+        assert!(!dest.join(APPS_DIR).exists());
+        // the device stays registered, there's just no copy of the app to refresh
+        assert!(store.get_refresh_device(IPHONE).unwrap().apps.is_empty());
+
+        std::fs::remove_dir_all(source).ok();
+        std::fs::remove_dir_all(dest).ok();
+    }
+
+    #[test]
+    fn refuses_to_replace_keys_and_anisette_state() {
+        let (source, archive) = backup(false);
+        let (dest, mut store) = empty_data_path();
+        let keys = KeyStore::new(dest.clone(), None);
+        keys.write(TEAM_ID, "key.pem", "NEW MACHINE KEY").unwrap();
+        std::fs::write(dest.join("adi.pb"), "NEW MACHINE ADI").unwrap();
+
+        let result = store.restore_backup(&dest, &archive, PASSPHRASE, false);
+
+        match result {
+            Err(Error::BackupWouldReplace(replaced)) => {
+                assert_eq!(replaced, "adi.pb, keys/TEAMID1234/key.pem");
+            }
+            result => panic!("expected BackupWouldReplace, got {result:?}"),
+        }
+        // nothing was restored
+        assert!(store.get_account(EMAIL).is_none());
+        assert_eq!(
+            keys.read(TEAM_ID, "key.pem").unwrap().as_deref(),
+            Some("NEW MACHINE KEY")
+        );
+        assert_eq!(
+            std::fs::read_to_string(dest.join("adi.pb")).unwrap(),
+            "NEW MACHINE ADI"
+        );
+
+        store
+            .restore_backup(&dest, &archive, PASSPHRASE, true)
+            .unwrap();
+
+        assert!(store.get_account(EMAIL).is_some());
+        assert_eq!(
+            keys.read(TEAM_ID, "key.pem").unwrap().as_deref(),
+            Some("OLD MACHINE KEY")
+        );
+        assert_eq!(
+            std::fs::read_to_string(dest.join("adi.pb")).unwrap(),
+            "OLD MACHINE ADI"
+        );
+
+        std::fs::remove_dir_all(source).ok();
+        std::fs::remove_dir_all(dest).ok();
+    }
+
+    #[test]
+    fn restores_over_identical_files() {
+        let (source, archive) = backup(true);
+        let mut store =
+            AccountStore::load_with_secrets_sync(&Some(source.join("accounts.json")), None)
+                .unwrap();
+
+        store
+            .restore_backup(&source, &archive, PASSPHRASE, false)
+            .unwrap();
+
+        std::fs::remove_dir_all(source).ok();
+    }
+
+    #[test]
+    fn rejects_wrong_passphrase() {
+        let (source, archive) = backup(false);
+        let (dest, mut store) = empty_data_path();
+
+        let result = store.restore_backup(&dest, &archive, "wrong passphrase", false);
+
+        assert!(matches!(result, Err(Error::Backup(_))), "{result:?}");
+        assert!(!dest.join("adi.pb").exists());
+        assert!(store.accounts().is_empty());
+
+        std::fs::remove_dir_all(source).ok();
+        std::fs::remove_dir_all(dest).ok();
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        let (source, archive) = backup(false);
+        let (dest, mut store) = empty_data_path();
+        let data = std::fs::read(&archive).unwrap();
+
+        let mut bad_magic = data.clone();
+        bad_magic[..MAGIC.len()].copy_from_slice(b"NOTPLUME");
+        let mut bad_version = data.clone();
+        bad_version[MAGIC.len()] = FORMAT_VERSION + 1;
+        let mut corrupt = data.clone();
+        let last = corrupt.len() - 1;
+        corrupt[last] ^= 0xff;
+
+        let cases = [
+            ("empty", Vec::new()),
+            ("bad magic", bad_magic),
+            ("bad version", bad_version),
+            (
+                "truncated header",
+                data[..MAGIC.len() + 1 + SALT_LEN].to_vec(),
+            ),
+            ("corrupt ciphertext", corrupt),
+        ];
+
+        for (name, contents) in cases {
+            let path = dest.join(format!("{name}.plumebackup"));
+            std::fs::write(&path, contents).unwrap();
+
+            let result = store.restore_backup(&dest, &path, PASSPHRASE, false);
+
+            assert!(
+                matches!(result, Err(Error::Backup(_))),
+                "{name}: {result:?}"
+            );
+        }
+        assert!(store.accounts().is_empty());
+
+        std::fs::remove_dir_all(source).ok();
+        std::fs::remove_dir_all(dest).ok();
+    }
+
+    #[test]
+    fn restore_path_stays_in_the_data_path() {
+        let data_path = Path::new("/data");
+
+        for name in [
+            "",
+            "..",
+            "../accounts.json",
+            "keys/../../etc/passwd",
+            "./adi.pb",
+            "/etc/passwd",
+        ] {
+            assert!(
+                matches!(restore_path(data_path, name), Err(Error::Backup(_))),
+                "{name:?}"
+            );
+        }
+
+        assert_eq!(
+            restore_path(data_path, "refresh_store/UDID/Example.app/Info.plist").unwrap(),
+            data_path.join("refresh_store/UDID/Example.app/Info.plist")
+        );
+    }
+}
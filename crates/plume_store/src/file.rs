@@ -0,0 +1,101 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use plume_core::Error;
+
+/// Advisory lock on `<path>.lock`, held until dropped.
+///
+/// The GUI, its refresh daemon and plumesign all write the same files, the lock
+/// keeps their read-modify-write cycles from interleaving.
+pub(crate) struct FileLock {
+    file: File,
+}
+
+impl FileLock {
+    pub(crate) fn acquire(path: &Path) -> Result<Self, Error> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(Self::lock_path(path))?;
+        file.lock()?;
+
+        Ok(Self { file })
+    }
+
+    /// [`FileLock::acquire`] off the runtime's workers, waiting on another process
+    /// holding the lock would block one otherwise.
+    pub(crate) async fn acquire_async(path: &Path) -> Result<Self, Error> {
+        let path = path.to_path_buf();
+        tokio::task::spawn_blocking(move || Self::acquire(&path))
+            .await
+            .map_err(|e| Error::Io(std::io::Error::other(e)))?
+    }
+
+    fn lock_path(path: &Path) -> PathBuf {
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(".lock");
+        path.with_file_name(name)
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+    }
+}
+
+/// Writes to a temporary file next to `path` and renames it over, so readers
+/// never see a half written file.
+pub(crate) fn write_atomic_sync(path: &Path, contents: &[u8]) -> Result<(), Error> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    let tmp_path = temp_path(path);
+    let result = (|| {
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(contents)?;
+        file.sync_all()?;
+        std::fs::rename(&tmp_path, path)
+    })();
+
+    if result.is_err() {
+        let _ = std::fs::remove_file(&tmp_path);
+    }
+
+    Ok(result?)
+}
+
+pub(crate) async fn write_atomic(path: &Path, contents: &[u8]) -> Result<(), Error> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent).await?;
+    }
+
+    let tmp_path = temp_path(path);
+    let result = async {
+        let mut file = tokio::fs::File::create(&tmp_path).await?;
+        tokio::io::AsyncWriteExt::write_all(&mut file, contents).await?;
+        file.sync_all().await?;
+        tokio::fs::rename(&tmp_path, path).await
+    }
+    .await;
+
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(&tmp_path).await;
+    }
+
+    Ok(result?)
+}
+
+// per process, so a crashed writer's leftover is never mistaken for another's
+fn temp_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{}.tmp", std::process::id()));
+    path.with_file_name(name)
+}
@@ -4,10 +4,20 @@ use serde::{Deserialize, Serialize};
 pub struct GsaAccount {
     email: String,
     first_name: String,
+    // kept in the secret backend when there is one, see `AccountStore`
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     adsid: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     xcode_gs_token: String,
     #[serde(default)]
     team_id: String,
+    /// Never revoke certificates to make room for a new one, for teams
+    /// that share their certificates with other machines.
+    #[serde(default)]
+    never_revoke: bool,
+    /// The stored token was rejected, set until the account signs in again.
+    #[serde(default)]
+    needs_relogin: bool,
 }
 
 impl GsaAccount {
@@ -24,6 +34,8 @@ impl GsaAccount {
             adsid,
             xcode_gs_token,
             team_id,
+            never_revoke: false,
+            needs_relogin: false,
         }
     }
     pub fn email(&self) -> &String {
@@ -44,13 +56,56 @@ impl GsaAccount {
     pub fn set_team_id(&mut self, team_id: String) {
         self.team_id = team_id;
     }
+    pub fn never_revoke(&self) -> bool {
+        self.never_revoke
+    }
+    pub fn set_never_revoke(&mut self, never_revoke: bool) {
+        self.never_revoke = never_revoke;
+    }
+    pub fn needs_relogin(&self) -> bool {
+        self.needs_relogin
+    }
+    pub fn set_needs_relogin(&mut self, needs_relogin: bool) {
+        self.needs_relogin = needs_relogin;
+    }
+    /// Carries over what the user chose for this account before signing in again.
+    pub(crate) fn keep_settings_from(&mut self, previous: &GsaAccount) {
+        if !previous.team_id.is_empty() {
+            self.team_id = previous.team_id.clone();
+        }
+        self.never_revoke = previous.never_revoke;
+    }
+    pub(crate) fn has_secrets(&self) -> bool {
+        !self.adsid.is_empty() || !self.xcode_gs_token.is_empty()
+    }
+    pub(crate) fn secrets(&self) -> AccountSecrets {
+        AccountSecrets {
+            adsid: self.adsid.clone(),
+            xcode_gs_token: self.xcode_gs_token.clone(),
+        }
+    }
+    pub(crate) fn set_secrets(&mut self, secrets: AccountSecrets) {
+        self.adsid = secrets.adsid;
+        self.xcode_gs_token = secrets.xcode_gs_token;
+    }
+    pub(crate) fn clear_secrets(&mut self) {
+        self.adsid.clear();
+        self.xcode_gs_token.clear();
+    }
+}
+
+/// What's stored in the secret backend for each account.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct AccountSecrets {
+    adsid: String,
+    xcode_gs_token: String,
 }
 
 pub async fn account_from_session(
     email: String,
     account: plume_core::auth::Account,
 ) -> Result<GsaAccount, plume_core::Error> {
-    let first_name = account.get_name().0;
+    let first_name = account.get_name()?.0;
     let s = plume_core::developer::DeveloperSession::using_account(account).await?;
     let teams_response = s.qh_list_teams().await?;
     let adsid = s.adsid().clone();
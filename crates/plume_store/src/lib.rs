@@ -1,6 +1,20 @@
+mod backup;
+mod file;
 mod gsa_account;
+mod migrations;
+mod profile_cache;
 mod refresh;
+mod secrets;
 mod store;
+mod vault;
+pub use backup::BackupSummary;
 pub use gsa_account::{GsaAccount, account_from_session};
+pub use migrations::SCHEMA_VERSION;
+pub use plume_core::SecretBackend;
+pub use profile_cache::{CachedProfile, ProfileCache};
 pub use refresh::{RefreshApp, RefreshDevice};
+pub use secrets::{
+    KeyringBackend, SECRET_BACKEND_ENV, VAULT_PASSPHRASE_ENV, default_backend, key_store,
+};
 pub use store::AccountStore;
+pub use vault::FileVault;
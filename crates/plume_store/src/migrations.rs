@@ -0,0 +1,256 @@
+use serde_json::{Map, Value};
+
+use plume_core::Error;
+
+/// Version of the `accounts.json` layout written by this build.
+pub const SCHEMA_VERSION: u64 = 4;
+
+type Migration = fn(&mut Map<String, Value>) -> Result<(), Error>;
+
+/// `MIGRATIONS[n]` upgrades a store from version `n` to `n + 1`.
+const MIGRATIONS: [Migration; SCHEMA_VERSION as usize] = [v0_to_v1, v1_to_v2, v2_to_v3, v3_to_v4];
+
+/// Upgrades a raw store to [`SCHEMA_VERSION`] one step at a time,
+/// returns the version it started at if anything was migrated.
+pub(crate) fn migrate(value: &mut Value) -> Result<Option<u64>, Error> {
+    let store = value.as_object_mut().ok_or(Error::Parse)?;
+
+    // files from before versioning have no field at all
+    let found = store
+        .get("schema_version")
+        .map(|v| v.as_u64().ok_or(Error::Parse))
+        .transpose()?
+        .unwrap_or(0);
+
+    if found > SCHEMA_VERSION {
+        return Err(Error::StoreSchemaUnsupported {
+            found,
+            supported: SCHEMA_VERSION,
+        });
+    }
+
+    if found == SCHEMA_VERSION {
+        return Ok(None);
+    }
+
+    for (version, migration) in MIGRATIONS.iter().enumerate().skip(found as usize) {
+        migration(store)?;
+        store.insert("schema_version".into(), Value::from(version as u64 + 1));
+    }
+
+    Ok(Some(found))
+}
+
+// Unversioned stores grew fields over time that serde filled in with defaults:
+// `team_id`/`never_revoke` on accounts, `refreshes`, and `name`/`bundle_id` on refresh apps.
+fn v0_to_v1(store: &mut Map<String, Value>) -> Result<(), Error> {
+    store.entry("selected_account").or_insert(Value::Null);
+
+    let accounts = store
+        .entry("accounts")
+        .or_insert_with(|| Value::Object(Map::new()))
+        .as_object_mut()
+        .ok_or(Error::Parse)?;
+    for account in accounts.values_mut() {
+        let account = account.as_object_mut().ok_or(Error::Parse)?;
+        account.entry("team_id").or_insert_with(|| Value::from(""));
+        account.entry("never_revoke").or_insert(Value::Bool(false));
+    }
+
+    let refreshes = store
+        .entry("refreshes")
+        .or_insert_with(|| Value::Object(Map::new()))
+        .as_object_mut()
+        .ok_or(Error::Parse)?;
+    for device in refreshes.values_mut() {
+        for app in refresh_apps(device)? {
+            let app = app.as_object_mut().ok_or(Error::Parse)?;
+            app.entry("name").or_insert(Value::Null);
+            app.entry("bundle_id").or_insert(Value::Null);
+        }
+    }
+
+    Ok(())
+}
+
+// accounts can be flagged for signing in again
+fn v1_to_v2(store: &mut Map<String, Value>) -> Result<(), Error> {
+    let accounts = store
+        .get_mut("accounts")
+        .and_then(Value::as_object_mut)
+        .ok_or(Error::Parse)?;
+    for account in accounts.values_mut() {
+        let account = account.as_object_mut().ok_or(Error::Parse)?;
+        account.entry("needs_relogin").or_insert(Value::Bool(false));
+    }
+
+    Ok(())
+}
+
+// anisette providers picked in Settings, none means omnisette's default
+fn v2_to_v3(store: &mut Map<String, Value>) -> Result<(), Error> {
+    store
+        .entry("anisette_providers")
+        .or_insert_with(|| Value::Array(Vec::new()));
+
+    Ok(())
+}
+
+// refresh apps remember the team they were signed with
+fn v3_to_v4(store: &mut Map<String, Value>) -> Result<(), Error> {
+    let refreshes = store
+        .get_mut("refreshes")
+        .and_then(Value::as_object_mut)
+        .ok_or(Error::Parse)?;
+    for device in refreshes.values_mut() {
+        for app in refresh_apps(device)? {
+            let app = app.as_object_mut().ok_or(Error::Parse)?;
+            app.entry("team_id").or_insert(Value::Null);
+        }
+    }
+
+    Ok(())
+}
+
+// devices could be saved before any app was added to them
+fn refresh_apps(device: &mut Value) -> Result<&mut Vec<Value>, Error> {
+    device
+        .as_object_mut()
+        .ok_or(Error::Parse)?
+        .entry("apps")
+        .or_insert_with(|| Value::Array(Vec::new()))
+        .as_array_mut()
+        .ok_or(Error::Parse)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use super::*;
+    use crate::AccountStore;
+
+    const EMAIL: &str = "user@example.com";
+    const IPHONE: &str = "00008110-000000000000001E";
+
+    // a copy of `accounts/v<version>.json` as the store of a fresh data dir
+    fn store_from_fixture(version: u64) -> (PathBuf, PathBuf, String) {
+        let fixture = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/fixtures/accounts")
+            .join(format!("v{version}.json"));
+        let dir = std::env::temp_dir().join(format!("plume_store_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let contents = std::fs::read_to_string(fixture).unwrap();
+        let path = dir.join("accounts.json");
+        std::fs::write(&path, &contents).unwrap();
+        (dir, path, contents)
+    }
+
+    #[tokio::test]
+    async fn migrates_every_fixture_to_the_current_schema() {
+        for version in 0..SCHEMA_VERSION {
+            let (dir, path, original) = store_from_fixture(version);
+
+            let store = AccountStore::load_with_secrets(&Some(path.clone()), None)
+                .await
+                .unwrap();
+
+            assert_eq!(store.schema_version(), SCHEMA_VERSION, "v{version}");
+            assert_eq!(
+                store.selected_account().map(|a| a.email().as_str()),
+                Some(EMAIL)
+            );
+            let account = store.get_account(EMAIL).unwrap();
+            assert!(!account.xcode_gs_token().is_empty(), "v{version}");
+            // fields added after the fixture's version come in with their defaults
+            assert_eq!(account.needs_relogin(), version == 2 || version == 3);
+            assert_eq!(account.never_revoke(), version > 0);
+            assert!(store.anisette_providers().is_empty());
+
+            let device = store.get_refresh_device(IPHONE).unwrap();
+            assert_eq!(device.apps.len(), 1);
+            assert_eq!(device.apps[0].team_id, None);
+            assert_eq!(
+                device.apps[0].bundle_id.as_deref(),
+                (version > 0).then_some("com.example.app")
+            );
+
+            let written: Value =
+                serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+            assert_eq!(written["schema_version"], Value::from(SCHEMA_VERSION));
+
+            // the pre-migration file is kept as is for downgrades
+            let backup = dir.join(format!("accounts.json.v{version}.bak"));
+            assert_eq!(std::fs::read_to_string(backup).unwrap(), original);
+
+            std::fs::remove_dir_all(dir).ok();
+        }
+    }
+
+    #[tokio::test]
+    async fn defaults_refresh_devices_without_apps() {
+        let (dir, path, _) = store_from_fixture(0);
+
+        let store = AccountStore::load_with_secrets(&Some(path), None)
+            .await
+            .unwrap();
+
+        let device = store
+            .get_refresh_device("00008120-000000000000002F")
+            .unwrap();
+        assert!(device.apps.is_empty());
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[tokio::test]
+    async fn keeps_current_stores_as_they_are() {
+        let (dir, path, _) = store_from_fixture(3);
+        AccountStore::load_with_secrets(&Some(path.clone()), None)
+            .await
+            .unwrap();
+        let migrated = std::fs::read_to_string(&path).unwrap();
+
+        AccountStore::load_with_secrets(&Some(path.clone()), None)
+            .await
+            .unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), migrated);
+        assert!(
+            !dir.join(format!("accounts.json.v{SCHEMA_VERSION}.bak"))
+                .exists()
+        );
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[tokio::test]
+    async fn rejects_stores_from_newer_versions() {
+        let dir = std::env::temp_dir().join(format!("plume_store_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("accounts.json");
+        let contents = format!(
+            r#"{{"schema_version": {}, "accounts": {{}}}}"#,
+            SCHEMA_VERSION + 1
+        );
+        std::fs::write(&path, &contents).unwrap();
+
+        let result = AccountStore::load_with_secrets(&Some(path.clone()), None).await;
+
+        assert!(matches!(
+            result,
+            Err(Error::StoreSchemaUnsupported { found, supported })
+                if found == SCHEMA_VERSION + 1 && supported == SCHEMA_VERSION
+        ));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), contents);
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn rejects_non_numeric_versions() {
+        let mut value = serde_json::json!({ "schema_version": "4" });
+        assert!(matches!(migrate(&mut value), Err(Error::Parse)));
+    }
+}
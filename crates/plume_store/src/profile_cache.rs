@@ -0,0 +1,413 @@
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+use plume_core::{Error, MobileProvision};
+use sha2::{Digest, Sha256};
+
+const PROFILE_FILE_NAME: &str = "embedded.mobileprovision";
+const DEVICES_FILE_NAME: &str = "devices";
+/// Profiles closer to expiry than this are fetched again, so refreshes a day before
+/// expiration never pick up the profile they're meant to replace.
+const MIN_REMAINING_VALIDITY: Duration = Duration::from_secs(2 * 24 * 60 * 60);
+
+/// Provisioning profiles downloaded from the portal, stored as `<dir>/<team>/<app id>/`.
+///
+/// A newer profile for an App ID covers every device the team had when it was
+/// generated, so only the latest one is kept per App ID, next to a digest of
+/// that device list so it's dropped once the team's devices change.
+#[derive(Debug, Clone)]
+pub struct ProfileCache {
+    dir: PathBuf,
+}
+
+#[derive(Clone)]
+pub struct CachedProfile {
+    pub team_id: String,
+    pub app_id_id: String,
+    pub path: PathBuf,
+    pub provision: MobileProvision,
+}
+
+impl CachedProfile {
+    pub fn expires(&self) -> SystemTime {
+        (*self.provision.expiration_date()).into()
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.expires() <= SystemTime::now()
+    }
+
+    fn is_reusable(&self) -> bool {
+        self.expires() > SystemTime::now() + MIN_REMAINING_VALIDITY
+    }
+}
+
+impl ProfileCache {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    pub fn dir(&self) -> &PathBuf {
+        &self.dir
+    }
+
+    /// Digest of a team's registered devices, independent of their order and case.
+    pub fn device_digest<S: AsRef<str>>(udids: impl IntoIterator<Item = S>) -> String {
+        let mut udids: Vec<String> = udids
+            .into_iter()
+            .map(|u| u.as_ref().to_ascii_lowercase())
+            .collect();
+        udids.sort();
+        udids.dedup();
+
+        let mut hasher = Sha256::new();
+        for udid in udids {
+            hasher.update(udid.as_bytes());
+            hasher.update(b"\n");
+        }
+        hex::encode(hasher.finalize())
+    }
+
+    /// A still valid profile for the App ID, generated while the team had the
+    /// devices in `device_digest` and provisioning `udid` if given.
+    pub async fn get(
+        &self,
+        team_id: &str,
+        app_id_id: &str,
+        udid: Option<&str>,
+        device_digest: &str,
+    ) -> Result<Option<MobileProvision>, Error> {
+        let Some(cached) = self.load_entry(team_id, app_id_id).await? else {
+            return Ok(None);
+        };
+
+        if !cached.is_reusable() || udid.is_some_and(|u| !cached.provision.provisions_device(u)) {
+            return Ok(None);
+        }
+
+        // entries written before the digest was stored count as stale
+        let stored_digest =
+            tokio::fs::read_to_string(cached.path.with_file_name(DEVICES_FILE_NAME))
+                .await
+                .ok();
+        if stored_digest.as_deref().map(str::trim) != Some(device_digest) {
+            return Ok(None);
+        }
+
+        Ok(Some(cached.provision))
+    }
+
+    pub async fn insert(
+        &self,
+        team_id: &str,
+        app_id_id: &str,
+        device_digest: &str,
+        provision: &MobileProvision,
+    ) -> Result<(), Error> {
+        let dir = self.dir.join(team_id).join(app_id_id);
+        tokio::fs::create_dir_all(&dir).await?;
+        tokio::fs::write(dir.join(PROFILE_FILE_NAME), &provision.data).await?;
+        tokio::fs::write(dir.join(DEVICES_FILE_NAME), device_digest).await?;
+        Ok(())
+    }
+
+    /// Drops every profile of the team, they only allow the certificates they were
+    /// generated with.
+    pub async fn remove_team(&self, team_id: &str) -> Result<(), Error> {
+        let dir = self.dir.join(team_id);
+        if dir.exists() {
+            tokio::fs::remove_dir_all(dir).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn remove(&self, team_id: &str, app_id_id: &str) -> Result<(), Error> {
+        let dir = self.dir.join(team_id).join(app_id_id);
+        if dir.exists() {
+            tokio::fs::remove_dir_all(dir).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn entries(&self) -> Result<Vec<CachedProfile>, Error> {
+        let mut entries = Vec::new();
+
+        for team_id in Self::read_dir_names(&self.dir).await? {
+            for app_id_id in Self::read_dir_names(&self.dir.join(&team_id)).await? {
+                if let Some(cached) = self.load_entry(&team_id, &app_id_id).await? {
+                    entries.push(cached);
+                }
+            }
+        }
+
+        Ok(entries)
+    }
+
+    /// Removes expired profiles, or every profile with `all`, returns what was removed.
+    pub async fn prune(&self, all: bool) -> Result<Vec<CachedProfile>, Error> {
+        let mut removed = Vec::new();
+
+        for cached in self.entries().await? {
+            if all || cached.is_expired() {
+                self.remove(&cached.team_id, &cached.app_id_id).await?;
+                removed.push(cached);
+            }
+        }
+
+        Ok(removed)
+    }
+
+    async fn load_entry(
+        &self,
+        team_id: &str,
+        app_id_id: &str,
+    ) -> Result<Option<CachedProfile>, Error> {
+        let path = self
+            .dir
+            .join(team_id)
+            .join(app_id_id)
+            .join(PROFILE_FILE_NAME);
+
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let data = tokio::fs::read(&path).await?;
+        // a corrupt profile is as good as a missing one, it'll be replaced on next download
+        let Ok(provision) = MobileProvision::load_with_bytes(data) else {
+            return Ok(None);
+        };
+
+        Ok(Some(CachedProfile {
+            team_id: team_id.to_string(),
+            app_id_id: app_id_id.to_string(),
+            path,
+            provision,
+        }))
+    }
+
+    async fn read_dir_names(dir: &PathBuf) -> Result<Vec<String>, Error> {
+        let mut names = Vec::new();
+
+        if !dir.exists() {
+            return Ok(names);
+        }
+
+        let mut read_dir = tokio::fs::read_dir(dir).await?;
+        while let Some(entry) = read_dir.next_entry().await? {
+            if entry.file_type().await?.is_dir() {
+                names.push(entry.file_name().to_string_lossy().to_string());
+            }
+        }
+
+        Ok(names)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use plist::Value;
+    use plume_core::testing::fixtures_dir;
+
+    use super::*;
+
+    const TEAM_ID: &str = "TEAMID1234";
+    const APP_ID_ID: &str = "NEWAPPID01";
+    const IPHONE: &str = "00008110-000000000000001E";
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    fn temp_cache() -> ProfileCache {
+        ProfileCache::new(
+            std::env::temp_dir().join(format!("plume_profiles_{}", uuid::Uuid::new_v4())),
+        )
+    }
+
+    // the recorded profile, expiring at `expires`
+    fn profile(expires: SystemTime) -> MobileProvision {
+        let response =
+            Value::from_file(fixtures_dir().join("qh/downloadTeamProvisioningProfile.plist"))
+                .unwrap();
+        let encoded = response
+            .as_dictionary()
+            .and_then(|r| r.get("provisioningProfile"))
+            .and_then(Value::as_dictionary)
+            .and_then(|p| p.get("encodedProfile"))
+            .and_then(Value::as_data)
+            .unwrap();
+
+        let mut profile = Value::from_reader_xml(encoded).unwrap();
+        profile
+            .as_dictionary_mut()
+            .unwrap()
+            .insert("ExpirationDate".into(), Value::Date(expires.into()));
+
+        let mut data = Vec::new();
+        profile.to_writer_xml(&mut data).unwrap();
+        MobileProvision::load_with_bytes(data).unwrap()
+    }
+
+    fn valid_profile() -> MobileProvision {
+        profile(SystemTime::now() + 30 * DAY)
+    }
+
+    fn digest() -> String {
+        ProfileCache::device_digest([IPHONE])
+    }
+
+    #[test]
+    fn device_digests_ignore_order_and_case() {
+        assert_eq!(
+            ProfileCache::device_digest(["B", "a"]),
+            ProfileCache::device_digest(["A", "b", "a"])
+        );
+        assert_ne!(
+            ProfileCache::device_digest(["a"]),
+            ProfileCache::device_digest(["a", "b"])
+        );
+    }
+
+    #[tokio::test]
+    async fn returns_profiles_for_the_same_devices() {
+        let cache = temp_cache();
+        cache
+            .insert(TEAM_ID, APP_ID_ID, &digest(), &valid_profile())
+            .await
+            .unwrap();
+
+        let cached = cache
+            .get(TEAM_ID, APP_ID_ID, Some(IPHONE), &digest())
+            .await
+            .unwrap();
+
+        assert!(cached.is_some());
+        std::fs::remove_dir_all(cache.dir()).ok();
+    }
+
+    #[tokio::test]
+    async fn misses_once_the_devices_change() {
+        let cache = temp_cache();
+        cache
+            .insert(TEAM_ID, APP_ID_ID, &digest(), &valid_profile())
+            .await
+            .unwrap();
+
+        let added = ProfileCache::device_digest([IPHONE, "00008120-000000000000002F"]);
+        let cached = cache
+            .get(TEAM_ID, APP_ID_ID, Some(IPHONE), &added)
+            .await
+            .unwrap();
+
+        assert!(cached.is_none());
+        std::fs::remove_dir_all(cache.dir()).ok();
+    }
+
+    #[tokio::test]
+    async fn misses_without_a_devices_file() {
+        let cache = temp_cache();
+        cache
+            .insert(TEAM_ID, APP_ID_ID, &digest(), &valid_profile())
+            .await
+            .unwrap();
+        std::fs::remove_file(
+            cache
+                .dir()
+                .join(TEAM_ID)
+                .join(APP_ID_ID)
+                .join(DEVICES_FILE_NAME),
+        )
+        .unwrap();
+
+        let cached = cache
+            .get(TEAM_ID, APP_ID_ID, Some(IPHONE), &digest())
+            .await
+            .unwrap();
+
+        assert!(cached.is_none());
+        std::fs::remove_dir_all(cache.dir()).ok();
+    }
+
+    #[tokio::test]
+    async fn misses_close_to_expiry() {
+        let cache = temp_cache();
+        cache
+            .insert(
+                TEAM_ID,
+                APP_ID_ID,
+                &digest(),
+                &profile(SystemTime::now() + MIN_REMAINING_VALIDITY - DAY),
+            )
+            .await
+            .unwrap();
+
+        let cached = cache
+            .get(TEAM_ID, APP_ID_ID, Some(IPHONE), &digest())
+            .await
+            .unwrap();
+
+        assert!(cached.is_none());
+        std::fs::remove_dir_all(cache.dir()).ok();
+    }
+
+    #[tokio::test]
+    async fn misses_devices_the_profile_doesnt_provision() {
+        let cache = temp_cache();
+        cache
+            .insert(TEAM_ID, APP_ID_ID, &digest(), &valid_profile())
+            .await
+            .unwrap();
+
+        let cached = cache
+            .get(
+                TEAM_ID,
+                APP_ID_ID,
+                Some("00008120-000000000000002F"),
+                &digest(),
+            )
+            .await
+            .unwrap();
+
+        assert!(cached.is_none());
+        std::fs::remove_dir_all(cache.dir()).ok();
+    }
+
+    #[tokio::test]
+    async fn prunes_only_expired_profiles() {
+        let cache = temp_cache();
+        cache
+            .insert(
+                TEAM_ID,
+                "EXPIRED001",
+                &digest(),
+                &profile(SystemTime::now() - DAY),
+            )
+            .await
+            .unwrap();
+        cache
+            .insert(TEAM_ID, APP_ID_ID, &digest(), &valid_profile())
+            .await
+            .unwrap();
+
+        let removed = cache.prune(false).await.unwrap();
+
+        assert_eq!(
+            removed
+                .iter()
+                .map(|c| c.app_id_id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["EXPIRED001"]
+        );
+        let remaining = cache.entries().await.unwrap();
+        assert_eq!(
+            remaining
+                .iter()
+                .map(|c| c.app_id_id.as_str())
+                .collect::<Vec<_>>(),
+            vec![APP_ID_ID]
+        );
+
+        assert_eq!(cache.prune(true).await.unwrap().len(), 1);
+        assert!(cache.entries().await.unwrap().is_empty());
+
+        std::fs::remove_dir_all(cache.dir()).ok();
+    }
+}
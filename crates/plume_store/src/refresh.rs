@@ -20,6 +20,9 @@ pub struct RefreshApp {
     pub name: Option<String>,
     #[serde(default)]
     pub bundle_id: Option<String>,
+    /// Team the app was signed with, the account's team for apps saved before this was kept.
+    #[serde(default)]
+    pub team_id: Option<String>,
     pub scheduled_refresh: DateTime<Utc>, // the scheduled refresh time will happen a day before expiration
 }
 
@@ -0,0 +1,115 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use plume_core::{Error, KeyStore, SecretBackend};
+
+use crate::FileVault;
+
+const KEYRING_SERVICE: &str = "PlumeImpactor";
+const VAULT_FILE_NAME: &str = "secrets.vault";
+
+/// Selects the backend: `keyring`, `vault` or `plaintext`, picked automatically when unset.
+pub const SECRET_BACKEND_ENV: &str = "PLUME_SECRET_BACKEND";
+/// Passphrase of the file vault, setting it alone is enough to use the vault.
+pub const VAULT_PASSPHRASE_ENV: &str = "PLUME_VAULT_PASSPHRASE";
+
+/// The OS credential store: Keychain, Credential Manager or the Secret Service.
+#[derive(Debug, Clone)]
+pub struct KeyringBackend {
+    service: String,
+}
+
+impl KeyringBackend {
+    pub fn new() -> Self {
+        Self {
+            service: KEYRING_SERVICE.to_string(),
+        }
+    }
+
+    /// Whether there's a credential store to talk to, headless Linux usually has none.
+    pub fn is_available(&self) -> bool {
+        self.get("probe").is_ok()
+    }
+
+    fn entry(&self, key: &str) -> Result<keyring::Entry, Error> {
+        keyring::Entry::new(&self.service, key).map_err(|e| Error::SecretStore(e.to_string()))
+    }
+}
+
+impl Default for KeyringBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SecretBackend for KeyringBackend {
+    fn name(&self) -> &'static str {
+        "keyring"
+    }
+
+    fn get(&self, key: &str) -> Result<Option<String>, Error> {
+        match self.entry(key)?.get_password() {
+            Ok(value) => Ok(Some(value)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(Error::SecretStore(e.to_string())),
+        }
+    }
+
+    fn set(&self, key: &str, value: &str) -> Result<(), Error> {
+        self.entry(key)?
+            .set_password(value)
+            .map_err(|e| Error::SecretStore(e.to_string()))
+    }
+
+    fn delete(&self, key: &str) -> Result<(), Error> {
+        match self.entry(key)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(Error::SecretStore(e.to_string())),
+        }
+    }
+}
+
+/// The backend for the store in `dir`, based on the environment.
+///
+/// `None` keeps tokens in `accounts.json` and keys in `keys/` like before, which only
+/// happens when asked for. Without a keyring or a vault passphrase this is an error.
+pub fn default_backend(dir: &Path) -> Result<Option<Arc<dyn SecretBackend>>, Error> {
+    let vault_path = dir.join(VAULT_FILE_NAME);
+    let passphrase = std::env::var(VAULT_PASSPHRASE_ENV).ok();
+
+    let open_vault = |passphrase: Option<String>| -> Result<Arc<dyn SecretBackend>, Error> {
+        let passphrase = passphrase.ok_or_else(|| {
+            Error::SecretStore(format!(
+                "{VAULT_PASSPHRASE_ENV} is required to open the vault"
+            ))
+        })?;
+        Ok(Arc::new(FileVault::open(vault_path.clone(), &passphrase)?))
+    };
+
+    match std::env::var(SECRET_BACKEND_ENV).ok().as_deref() {
+        Some("keyring") => Ok(Some(Arc::new(KeyringBackend::new()))),
+        Some("vault") => open_vault(passphrase).map(Some),
+        Some("plaintext") => Ok(None),
+        Some(other) => Err(Error::SecretStore(format!(
+            "Unknown secret backend '{other}', expected keyring, vault or plaintext"
+        ))),
+        None if passphrase.is_some() => open_vault(passphrase).map(Some),
+        // tokens were moved to the vault before, don't fall back to a backend without them
+        None if vault_path.exists() => open_vault(None).map(Some),
+        None => {
+            let keyring = KeyringBackend::new();
+            if keyring.is_available() {
+                Ok(Some(Arc::new(keyring)))
+            } else {
+                Err(Error::SecretStore(format!(
+                    "No keyring available for account tokens and signing keys. Set {VAULT_PASSPHRASE_ENV} to keep them in an encrypted vault, or {SECRET_BACKEND_ENV}=plaintext to keep them in plain files"
+                )))
+            }
+        }
+    }
+}
+
+/// Signing keys of the data path `dir`, kept in the same backend as the account tokens.
+pub fn key_store(dir: &Path) -> Result<KeyStore, Error> {
+    Ok(KeyStore::new(dir.to_path_buf(), default_backend(dir)?))
+}
@@ -1,71 +1,258 @@
 use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 use serde::{Deserialize, Serialize};
 
-use plume_core::Error;
+use plume_core::auth::anisette_data::AnisetteData;
+use plume_core::auth::anisette_provider::AnisetteProvider;
+use plume_core::{AnisetteConfiguration, Error};
 
-use crate::{GsaAccount, RefreshDevice};
+use crate::file::{FileLock, write_atomic, write_atomic_sync};
+use crate::migrations::{SCHEMA_VERSION, migrate};
+use crate::{GsaAccount, RefreshDevice, SecretBackend, default_backend};
 
-#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct AccountStore {
+    schema_version: u64,
     selected_account: Option<String>,      // Email
     accounts: HashMap<String, GsaAccount>, // Email -> GsaAccount
     #[serde(default)]
     refreshes: HashMap<String, RefreshDevice>, // UDID -> RefreshDevice (apps?)
+    #[serde(default)]
+    anisette_providers: Vec<AnisetteProvider>,
     #[serde(skip)]
     path: Option<PathBuf>,
+    #[serde(skip)]
+    secrets: Option<Arc<dyn SecretBackend>>,
+}
+
+impl Default for AccountStore {
+    fn default() -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            selected_account: None,
+            accounts: HashMap::new(),
+            refreshes: HashMap::new(),
+            anisette_providers: Vec::new(),
+            path: None,
+            secrets: None,
+        }
+    }
 }
 
 impl AccountStore {
+    /// Loads the store with the secret backend picked by [`default_backend`].
     pub async fn load(path: &Option<PathBuf>) -> Result<Self, Error> {
+        Self::load_with_secrets(path, Self::default_secrets(path)?).await
+    }
+
+    pub fn load_sync(path: &Option<PathBuf>) -> Result<Self, Error> {
+        Self::load_with_secrets_sync(path, Self::default_secrets(path)?)
+    }
+
+    /// Loads the store keeping account tokens in `secrets`, or in the file itself with `None`.
+    ///
+    /// Tokens still in the file from older versions are moved into `secrets` right away.
+    pub async fn load_with_secrets(
+        path: &Option<PathBuf>,
+        secrets: Option<Arc<dyn SecretBackend>>,
+    ) -> Result<Self, Error> {
         if let Some(path) = path {
-            let mut settings = if !path.exists() {
-                Self::default()
-            } else {
-                let contents = tokio::fs::read_to_string(path).await?;
-                serde_json::from_str(&contents)?
-            };
-            settings.path = Some(path.clone());
+            let (mut settings, migrate) = Self::read(path, secrets).await?;
+            if migrate {
+                settings.update(|_| Ok(())).await?;
+            }
             Ok(settings)
         } else {
             Ok(Self::default())
         }
     }
 
-    pub fn load_sync(path: &Option<PathBuf>) -> Result<Self, Error> {
+    pub fn load_with_secrets_sync(
+        path: &Option<PathBuf>,
+        secrets: Option<Arc<dyn SecretBackend>>,
+    ) -> Result<Self, Error> {
         if let Some(path) = path {
-            let mut settings = if !path.exists() {
-                Self::default()
-            } else {
-                let contents = std::fs::read_to_string(path)?;
-                serde_json::from_str(&contents)?
-            };
-            settings.path = Some(path.clone());
+            let (mut settings, migrate) = Self::read_sync(path, secrets)?;
+            if migrate {
+                settings.update_sync(|_| Ok(()))?;
+            }
             Ok(settings)
         } else {
             Ok(Self::default())
         }
     }
 
+    /// Reloads the store under the lock, applies `f` and writes the result back.
+    ///
+    /// `self` is replaced by the reloaded store, so whatever the GUI, the refresh daemon
+    /// or plumesign wrote since it was loaded is kept. Nothing is written when `f` fails.
+    pub async fn update<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let Some(path) = self.path.clone() else {
+            return f(self);
+        };
+
+        // the lock is only held for a few small reads and writes, those stay sync
+        let _lock = FileLock::acquire_async(&path).await?;
+        let (mut store, _) = Self::read_sync(&path, self.secrets.clone())?;
+        let value = f(&mut store)?;
+        write_atomic_sync(&path, store.contents()?.as_bytes())?;
+
+        *self = store;
+        Ok(value)
+    }
+
+    pub fn update_sync<T>(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let Some(path) = self.path.clone() else {
+            return f(self);
+        };
+
+        let _lock = FileLock::acquire(&path)?;
+        let (mut store, _) = Self::read_sync(&path, self.secrets.clone())?;
+        let value = f(&mut store)?;
+        write_atomic_sync(&path, store.contents()?.as_bytes())?;
+
+        *self = store;
+        Ok(value)
+    }
+
+    /// Writes the store as it is in memory, replacing anything written since it was loaded,
+    /// use [`AccountStore::update`] to change it instead.
     pub async fn save(&self) -> Result<(), Error> {
         if let Some(path) = &self.path {
-            if let Some(parent) = path.parent() {
-                tokio::fs::create_dir_all(parent).await?;
-            }
-
-            tokio::fs::write(path, serde_json::to_string_pretty(self)?).await?;
+            let _lock = FileLock::acquire_async(path).await?;
+            write_atomic(path, self.contents()?.as_bytes()).await?;
         }
         Ok(())
     }
 
     pub fn save_sync(&self) -> Result<(), Error> {
         if let Some(path) = &self.path {
-            if let Some(parent) = path.parent() {
-                std::fs::create_dir_all(parent)?;
+            let _lock = FileLock::acquire(path)?;
+            write_atomic_sync(path, self.contents()?.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Reads the store, upgrading older layouts, returns whether it has to be written back.
+    async fn read(
+        path: &PathBuf,
+        secrets: Option<Arc<dyn SecretBackend>>,
+    ) -> Result<(Self, bool), Error> {
+        let (mut settings, upgraded): (Self, _) = if !path.exists() {
+            (Self::default(), false)
+        } else {
+            let mut value = serde_json::from_str(&tokio::fs::read_to_string(path).await?)?;
+            let upgraded_from = migrate(&mut value)?;
+            if let Some(version) = upgraded_from {
+                let backup = Self::backup_path(path, version);
+                if !backup.exists() {
+                    tokio::fs::copy(path, backup).await?;
+                }
+            }
+            (serde_json::from_value(value)?, upgraded_from.is_some())
+        };
+        settings.path = Some(path.clone());
+        let migrate = settings.attach_secrets(secrets)?;
+        Ok((settings, upgraded || migrate))
+    }
+
+    fn read_sync(
+        path: &PathBuf,
+        secrets: Option<Arc<dyn SecretBackend>>,
+    ) -> Result<(Self, bool), Error> {
+        let (mut settings, upgraded): (Self, _) = if !path.exists() {
+            (Self::default(), false)
+        } else {
+            let mut value = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+            let upgraded_from = migrate(&mut value)?;
+            if let Some(version) = upgraded_from {
+                let backup = Self::backup_path(path, version);
+                if !backup.exists() {
+                    std::fs::copy(path, backup)?;
+                }
             }
+            (serde_json::from_value(value)?, upgraded_from.is_some())
+        };
+        settings.path = Some(path.clone());
+        let migrate = settings.attach_secrets(secrets)?;
+        Ok((settings, upgraded || migrate))
+    }
+
+    // accounts.json.v0.bak, kept so a downgrade can go back to the old file
+    fn backup_path(path: &PathBuf, version: u64) -> PathBuf {
+        let mut name = path.file_name().unwrap_or_default().to_os_string();
+        name.push(format!(".v{version}.bak"));
+        path.with_file_name(name)
+    }
 
-            std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+    pub fn schema_version(&self) -> u64 {
+        self.schema_version
+    }
+
+    pub fn secret_backend(&self) -> Option<&Arc<dyn SecretBackend>> {
+        self.secrets.as_ref()
+    }
+
+    fn default_secrets(path: &Option<PathBuf>) -> Result<Option<Arc<dyn SecretBackend>>, Error> {
+        match path.as_ref().and_then(|p| p.parent()) {
+            Some(dir) => default_backend(dir),
+            None => Ok(None),
+        }
+    }
+
+    fn secret_key(email: &str) -> String {
+        format!("account:{email}")
+    }
+
+    /// Fills in the tokens from `secrets`, returns whether the file still has tokens to move.
+    fn attach_secrets(&mut self, secrets: Option<Arc<dyn SecretBackend>>) -> Result<bool, Error> {
+        let mut migrate = false;
+
+        if let Some(backend) = &secrets {
+            for (email, account) in self.accounts.iter_mut() {
+                if account.has_secrets() {
+                    migrate = true;
+                } else if let Some(stored) = backend.get(&Self::secret_key(email))? {
+                    account.set_secrets(serde_json::from_str(&stored)?);
+                }
+            }
+        }
+
+        self.secrets = secrets;
+        Ok(migrate)
+    }
+
+    /// The file contents, with tokens written to the secret backend instead when there is one.
+    fn contents(&self) -> Result<String, Error> {
+        let Some(secrets) = &self.secrets else {
+            return Ok(serde_json::to_string_pretty(self)?);
+        };
+
+        let mut stripped = self.clone();
+        for (email, account) in stripped.accounts.iter_mut() {
+            if account.has_secrets() {
+                secrets.set(
+                    &Self::secret_key(email),
+                    &serde_json::to_string(&account.secrets())?,
+                )?;
+            }
+            account.clear_secrets();
+        }
+
+        Ok(serde_json::to_string_pretty(&stripped)?)
+    }
+
+    fn forget_secrets(&self, email: &str) -> Result<(), Error> {
+        if let Some(secrets) = &self.secrets {
+            secrets.delete(&Self::secret_key(email))?;
         }
         Ok(())
     }
@@ -83,51 +270,27 @@ impl AccountStore {
     }
 
     pub async fn accounts_add(&mut self, account: GsaAccount) -> Result<(), Error> {
-        let email = account.email().clone();
-        self.accounts.insert(email.clone(), account);
-        self.selected_account = Some(email);
-        self.save().await
+        self.update(|s| s.insert_account(account)).await
     }
 
     pub fn accounts_add_sync(&mut self, account: GsaAccount) -> Result<(), Error> {
-        let email = account.email().clone();
-        self.accounts.insert(email.clone(), account);
-        self.selected_account = Some(email);
-        self.save_sync()
+        self.update_sync(|s| s.insert_account(account))
     }
 
     pub async fn accounts_remove(&mut self, email: &str) -> Result<(), Error> {
-        self.accounts.remove(email);
-        if self.selected_account.as_ref() == Some(&email.to_string()) {
-            self.selected_account = None;
-        }
-        self.save().await
+        self.update(|s| s.remove_account(email)).await
     }
 
     pub fn accounts_remove_sync(&mut self, email: &str) -> Result<(), Error> {
-        self.accounts.remove(email);
-        if self.selected_account.as_ref() == Some(&email.to_string()) {
-            self.selected_account = None;
-        }
-        self.save_sync()
+        self.update_sync(|s| s.remove_account(email))
     }
 
     pub async fn account_select(&mut self, email: &str) -> Result<(), Error> {
-        if self.accounts.contains_key(email) {
-            self.selected_account = Some(email.to_string());
-            self.save().await
-        } else {
-            Err(Error::Parse) // we need better errors
-        }
+        self.update(|s| s.select_account(email)).await
     }
 
     pub fn account_select_sync(&mut self, email: &str) -> Result<(), Error> {
-        if self.accounts.contains_key(email) {
-            self.selected_account = Some(email.to_string());
-            self.save_sync()
-        } else {
-            Err(Error::Parse) // we need better errors
-        }
+        self.update_sync(|s| s.select_account(email))
     }
 
     pub fn selected_account(&self) -> Option<&GsaAccount> {
@@ -143,7 +306,7 @@ impl AccountStore {
         email: String,
         account: plume_core::auth::Account,
     ) -> Result<(), Error> {
-        let first_name = account.get_name().0;
+        let first_name = account.get_name()?.0;
         let s = plume_core::developer::DeveloperSession::using_account(account).await?;
         let teams_response = s.qh_list_teams().await?;
         let adsid = s.adsid().clone();
@@ -163,27 +326,57 @@ impl AccountStore {
     }
 
     pub async fn update_account_team(&mut self, email: &str, team_id: String) -> Result<(), Error> {
-        if let Some(account) = self.accounts.get_mut(email) {
-            account.set_team_id(team_id);
-            self.save().await
-        } else {
-            Err(Error::Parse)
-        }
+        self.update(|s| s.account_mut(email).map(|a| a.set_team_id(team_id)))
+            .await
     }
 
     pub fn update_account_team_sync(&mut self, email: &str, team_id: String) -> Result<(), Error> {
-        if let Some(account) = self.accounts.get_mut(email) {
-            account.set_team_id(team_id);
-            self.save_sync()
-        } else {
-            Err(Error::Parse)
-        }
+        self.update_sync(|s| s.account_mut(email).map(|a| a.set_team_id(team_id)))
+    }
+
+    pub async fn update_account_never_revoke(
+        &mut self,
+        email: &str,
+        never_revoke: bool,
+    ) -> Result<(), Error> {
+        self.update(|s| {
+            s.account_mut(email)
+                .map(|a| a.set_never_revoke(never_revoke))
+        })
+        .await
+    }
+
+    pub fn update_account_never_revoke_sync(
+        &mut self,
+        email: &str,
+        never_revoke: bool,
+    ) -> Result<(), Error> {
+        self.update_sync(|s| {
+            s.account_mut(email)
+                .map(|a| a.set_never_revoke(never_revoke))
+        })
+    }
+
+    /// Flags the account after its token was rejected, signing in again clears it.
+    pub async fn mark_needs_relogin(&mut self, email: &str) -> Result<(), Error> {
+        self.update(|s| s.account_mut(email).map(|a| a.set_needs_relogin(true)))
+            .await
+    }
+
+    pub fn mark_needs_relogin_sync(&mut self, email: &str) -> Result<(), Error> {
+        self.update_sync(|s| s.account_mut(email).map(|a| a.set_needs_relogin(true)))
     }
 
     pub fn refreshes(&self) -> &HashMap<String, RefreshDevice> {
         &self.refreshes
     }
 
+    /// For changing single apps inside [`AccountStore::update`], rather than
+    /// replacing a whole device that may be out of date.
+    pub fn refreshes_mut(&mut self) -> &mut HashMap<String, RefreshDevice> {
+        &mut self.refreshes
+    }
+
     pub fn get_refresh_device(&self, udid: &str) -> Option<&RefreshDevice> {
         self.refreshes.get(udid)
     }
@@ -192,25 +385,146 @@ impl AccountStore {
         &mut self,
         device: RefreshDevice,
     ) -> Result<(), Error> {
-        self.refreshes.insert(device.udid.clone(), device);
-        self.save().await
+        self.update(|s| {
+            s.refreshes.insert(device.udid.clone(), device);
+            Ok(())
+        })
+        .await
     }
 
     pub fn add_or_update_refresh_device_sync(
         &mut self,
         device: RefreshDevice,
     ) -> Result<(), Error> {
-        self.refreshes.insert(device.udid.clone(), device);
-        self.save_sync()
+        self.update_sync(|s| {
+            s.refreshes.insert(device.udid.clone(), device);
+            Ok(())
+        })
     }
 
     pub async fn remove_refresh_device(&mut self, udid: &str) -> Result<(), Error> {
-        self.refreshes.remove(udid);
-        self.save().await
+        self.update(|s| {
+            s.refreshes.remove(udid);
+            Ok(())
+        })
+        .await
     }
 
     pub fn remove_refresh_device_sync(&mut self, udid: &str) -> Result<(), Error> {
-        self.refreshes.remove(udid);
-        self.save_sync()
+        self.update_sync(|s| {
+            s.refreshes.remove(udid);
+            Ok(())
+        })
+    }
+
+    /// Anisette providers in the order they're tried, empty uses omnisette's default.
+    pub fn anisette_providers(&self) -> &[AnisetteProvider] {
+        &self.anisette_providers
+    }
+
+    pub async fn set_anisette_providers(
+        &mut self,
+        providers: Vec<AnisetteProvider>,
+    ) -> Result<(), Error> {
+        self.update(|s| {
+            s.anisette_providers = providers;
+            Ok(())
+        })
+        .await
+    }
+
+    pub fn set_anisette_providers_sync(
+        &mut self,
+        providers: Vec<AnisetteProvider>,
+    ) -> Result<(), Error> {
+        self.update_sync(|s| {
+            s.anisette_providers = providers;
+            Ok(())
+        })
+    }
+
+    /// Anisette data from the configured providers, provisioning state is kept in `configuration_path`.
+    pub async fn anisette_data(&self, configuration_path: PathBuf) -> Result<AnisetteData, Error> {
+        let config = AnisetteConfiguration::default().set_configuration_path(configuration_path);
+        AnisetteData::with_providers(config, self.anisette_providers.clone()).await
+    }
+
+    fn insert_account(&mut self, mut account: GsaAccount) -> Result<(), Error> {
+        let email = account.email().clone();
+        if let Some(previous) = self.accounts.get(&email) {
+            account.keep_settings_from(previous);
+        }
+        self.accounts.insert(email.clone(), account);
+        self.selected_account = Some(email);
+        Ok(())
+    }
+
+    // the backup wins for the same account or device, providers only fill in an empty list
+    pub(crate) fn merge_backup(&mut self, backup: Self) {
+        self.accounts.extend(backup.accounts);
+        self.refreshes.extend(backup.refreshes);
+        if self.anisette_providers.is_empty() {
+            self.anisette_providers = backup.anisette_providers;
+        }
+        if self.selected_account.is_none() {
+            self.selected_account = backup.selected_account;
+        }
+    }
+
+    fn remove_account(&mut self, email: &str) -> Result<(), Error> {
+        self.forget_secrets(email)?;
+        self.accounts.remove(email);
+        if self.selected_account.as_ref() == Some(&email.to_string()) {
+            self.selected_account = None;
+        }
+        Ok(())
+    }
+
+    fn select_account(&mut self, email: &str) -> Result<(), Error> {
+        if self.accounts.contains_key(email) {
+            self.selected_account = Some(email.to_string());
+            Ok(())
+        } else {
+            Err(Error::Parse) // we need better errors
+        }
+    }
+
+    fn account_mut(&mut self, email: &str) -> Result<&mut GsaAccount, Error> {
+        self.accounts.get_mut(email).ok_or(Error::Parse)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    // on a single threaded runtime, blocking on the lock would keep it from ever being released
+    #[tokio::test]
+    async fn update_waits_for_the_lock_without_blocking_the_runtime() {
+        let dir = std::env::temp_dir().join(format!("plume_store_{}", uuid::Uuid::new_v4()));
+        let path = dir.join("accounts.json");
+        let mut store = AccountStore::load_with_secrets(&Some(path.clone()), None)
+            .await
+            .unwrap();
+
+        let lock = FileLock::acquire(&path).unwrap();
+        let release = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            drop(lock);
+        });
+
+        store
+            .update(|s| {
+                s.refreshes.clear();
+                Ok(())
+            })
+            .await
+            .unwrap();
+
+        release.await.unwrap();
+        assert!(path.exists());
+        std::fs::remove_dir_all(dir).ok();
     }
 }
@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use aes_gcm::{
+    Aes256Gcm, Key, Nonce,
+    aead::{Aead, AeadCore, KeyInit, OsRng, rand_core::RngCore},
+};
+use argon2::Argon2;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use serde::{Deserialize, Serialize};
+
+use plume_core::Error;
+
+use crate::SecretBackend;
+use crate::file::{FileLock, write_atomic_sync};
+
+pub(crate) const SALT_LEN: usize = 16;
+
+/// Secrets encrypted with a key derived from a passphrase (Argon2id + AES-256-GCM),
+/// for machines without a keyring.
+///
+/// The whole map is re-encrypted with a fresh nonce on every change. Changes are made
+/// to the file as it is under its lock, so the GUI, the refresh daemon and plumesign
+/// don't drop each other's secrets.
+pub struct FileVault {
+    path: PathBuf,
+    salt: [u8; SALT_LEN],
+    key: Key<Aes256Gcm>,
+    entries: Mutex<HashMap<String, String>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct VaultFile {
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+impl FileVault {
+    /// Opens the vault at `path`, or creates an empty one.
+    pub fn open(path: PathBuf, passphrase: &str) -> Result<Self, Error> {
+        let _lock = FileLock::acquire(&path)?;
+
+        if !path.exists() {
+            let mut salt = [0u8; SALT_LEN];
+            OsRng.fill_bytes(&mut salt);
+
+            // written right away, every handle has to encrypt with the same salt
+            let vault = Self {
+                key: Self::derive_key(passphrase, &salt)?,
+                path,
+                salt,
+                entries: Mutex::new(HashMap::new()),
+            };
+            vault.write(&HashMap::new())?;
+            return Ok(vault);
+        }
+
+        let file = Self::read_file(&path)?;
+        let salt: [u8; SALT_LEN] = Self::decode(&file.salt)?
+            .try_into()
+            .map_err(|_| Error::SecretStore("Invalid vault salt".into()))?;
+        let key = Self::derive_key(passphrase, &salt)?;
+        let entries = Self::decrypt(&key, &file)?;
+
+        Ok(Self {
+            path,
+            salt,
+            key,
+            entries: Mutex::new(entries),
+        })
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    pub(crate) fn derive_key(passphrase: &str, salt: &[u8]) -> Result<Key<Aes256Gcm>, Error> {
+        let mut key = Key::<Aes256Gcm>::default();
+        Argon2::default()
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|e| Error::SecretStore(e.to_string()))?;
+        Ok(key)
+    }
+
+    fn decode(value: &str) -> Result<Vec<u8>, Error> {
+        STANDARD
+            .decode(value)
+            .map_err(|_| Error::SecretStore("Invalid vault encoding".into()))
+    }
+
+    fn read_file(path: &PathBuf) -> Result<VaultFile, Error> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    fn decrypt(key: &Key<Aes256Gcm>, file: &VaultFile) -> Result<HashMap<String, String>, Error> {
+        let nonce = Self::decode(&file.nonce)?;
+        if nonce.len() != 12 {
+            return Err(Error::SecretStore("Invalid vault nonce".into()));
+        }
+
+        let plaintext = Aes256Gcm::new(key)
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                Self::decode(&file.ciphertext)?.as_ref(),
+            )
+            .map_err(|_| Error::SecretStore("Wrong vault passphrase or corrupt vault".into()))?;
+
+        Ok(serde_json::from_slice(&plaintext)?)
+    }
+
+    /// Applies `f` to the entries on disk under the vault's lock, writing them back
+    /// when it changed something, and keeps the result as the entries of this handle.
+    fn modify(&self, f: impl FnOnce(&mut HashMap<String, String>) -> bool) -> Result<(), Error> {
+        let mut entries = self.entries();
+        let _lock = FileLock::acquire(&self.path)?;
+
+        let file = Self::read_file(&self.path)?;
+        if Self::decode(&file.salt)? != self.salt {
+            return Err(Error::SecretStore(
+                "The vault was replaced since it was opened".into(),
+            ));
+        }
+        let mut current = Self::decrypt(&self.key, &file)?;
+
+        if f(&mut current) {
+            self.write(&current)?;
+        }
+        *entries = current;
+        Ok(())
+    }
+
+    fn write(&self, entries: &HashMap<String, String>) -> Result<(), Error> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = Aes256Gcm::new(&self.key)
+            .encrypt(&nonce, serde_json::to_vec(entries)?.as_ref())
+            .map_err(|e| Error::SecretStore(e.to_string()))?;
+
+        let file = VaultFile {
+            salt: STANDARD.encode(self.salt),
+            nonce: STANDARD.encode(nonce),
+            ciphertext: STANDARD.encode(ciphertext),
+        };
+
+        write_atomic_sync(&self.path, serde_json::to_string_pretty(&file)?.as_bytes())
+    }
+
+    fn entries(&self) -> std::sync::MutexGuard<'_, HashMap<String, String>> {
+        // the map is only swapped in whole, a poisoned lock still holds a consistent one
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl std::fmt::Debug for FileVault {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FileVault")
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SecretBackend for FileVault {
+    fn name(&self) -> &'static str {
+        "vault"
+    }
+
+    fn get(&self, key: &str) -> Result<Option<String>, Error> {
+        Ok(self.entries().get(key).cloned())
+    }
+
+    fn set(&self, key: &str, value: &str) -> Result<(), Error> {
+        self.modify(|entries| {
+            entries
+                .insert(key.to_string(), value.to_string())
+                .as_deref()
+                != Some(value)
+        })
+    }
+
+    fn delete(&self, key: &str) -> Result<(), Error> {
+        self.modify(|entries| entries.remove(key).is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PASSPHRASE: &str = "correct horse battery staple";
+
+    fn temp_path() -> PathBuf {
+        std::env::temp_dir()
+            .join(format!("plume_vault_{}", uuid::Uuid::new_v4()))
+            .join("secrets.vault")
+    }
+
+    #[test]
+    fn keeps_changes_made_through_other_handles() {
+        let path = temp_path();
+        // the GUI and plumesign each open the vault
+        let gui = FileVault::open(path.clone(), PASSPHRASE).unwrap();
+        let cli = FileVault::open(path.clone(), PASSPHRASE).unwrap();
+
+        gui.set("token:gui@example.com", "GUI").unwrap();
+        cli.set("token:cli@example.com", "CLI").unwrap();
+
+        let reopened = FileVault::open(path.clone(), PASSPHRASE).unwrap();
+        assert_eq!(
+            reopened.get("token:gui@example.com").unwrap().as_deref(),
+            Some("GUI")
+        );
+        assert_eq!(
+            reopened.get("token:cli@example.com").unwrap().as_deref(),
+            Some("CLI")
+        );
+        // the writer picks up what the other handle stored
+        assert_eq!(
+            cli.get("token:gui@example.com").unwrap().as_deref(),
+            Some("GUI")
+        );
+
+        gui.delete("token:gui@example.com").unwrap();
+        cli.set("token:cli@example.com", "CLI 2").unwrap();
+
+        let reopened = FileVault::open(path.clone(), PASSPHRASE).unwrap();
+        assert_eq!(reopened.get("token:gui@example.com").unwrap(), None);
+        assert_eq!(
+            reopened.get("token:cli@example.com").unwrap().as_deref(),
+            Some("CLI 2")
+        );
+
+        std::fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+
+    #[test]
+    fn rejects_the_wrong_passphrase() {
+        let path = temp_path();
+        FileVault::open(path.clone(), PASSPHRASE)
+            .unwrap()
+            .set("key", "value")
+            .unwrap();
+
+        assert!(matches!(
+            FileVault::open(path.clone(), "wrong passphrase"),
+            Err(Error::SecretStore(_))
+        ));
+
+        std::fs::remove_dir_all(path.parent().unwrap()).ok();
+    }
+}
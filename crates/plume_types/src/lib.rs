@@ -12,15 +12,33 @@ pub use device::{Device, get_device_for_id, install_app_mac}; // Device helper
 pub use options::{
     SignerApp, // Supported app types
     SignerAppReal,
-    SignerEmbedding,   // Embedding options
-    SignerFeatures,    // Feature support options
-    SignerInstallMode, // Installation mode
-    SignerMode,        // Signing mode
-    SignerOptions,     // Main
+    SignerDistribution,   // Provisioning profile kind
+    SignerEmbedding,      // Embedding options
+    SignerFeatures,       // Feature support options
+    SignerHookingRuntime, // Hooking runtime for tweaks
+    SignerInstallMode,    // Installation mode
+    SignerMode,           // Signing mode
+    SignerOptions,        // Main
 };
 pub use package::Package; // Package helper
 pub use signer::Signer; // Signer
-pub use tweak::Tweak; // Tweak helper
+pub use tweak::{
+    InstalledLoadCommand,
+    InstalledTweak,
+    TWEAK_MANIFEST_PATH,
+    Tweak, // Tweak helper
+    TweakConflict,
+    TweakDependency,
+    TweakFilter,          // MobileSubstrate filter plist
+    TweakInstallManifest, // Record of injected tweaks inside a bundle
+    TweakLoadPath,
+    TweakManifest, // Parsed .deb control file
+    TweakMissingDependency,
+    TweakReport, // Load path rewrites after applying
+    TweakResolution,
+    TweakTarget,
+    TweakWarnings,
+};
 
 use thiserror::Error as ThisError;
 #[derive(Debug, ThisError)]
@@ -35,6 +53,15 @@ pub enum Error {
     TweakInvalidPath,
     #[error("Tweak extraction failed: {0}")]
     TweakExtractionFailed(String),
+    #[error("Tweak is not installed: {0}")]
+    TweakNotInstalled(String),
+    #[error("Selected tweaks conflict: {0}")]
+    TweakConflicts(String),
+    #[error("{0} is not provided by the bundled hooking runtime {1}")]
+    TweakIncompatibleHookingRuntime(String, String),
+    // Signer
+    #[error("Not enough App IDs left this week: {needed} needed, {remaining} available")]
+    AppIdQuotaExceeded { needed: usize, remaining: usize },
     #[error("Unsupported file type: {0}")]
     UnsupportedFileType(String),
 
@@ -46,6 +73,8 @@ pub enum Error {
     Io(#[from] std::io::Error),
     #[error("Plist error: {0}")]
     Plist(#[from] plist::Error),
+    #[error("Serde JSON error: {0}")]
+    SerdeJson(#[from] serde_json::Error),
     #[error("Core error: {0}")]
     Core(#[from] plume_core::Error),
     #[error("Idevice error: {0}")]
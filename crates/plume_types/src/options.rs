@@ -15,12 +15,22 @@ pub struct SignerOptions {
     pub mode: SignerMode,
     /// Installation mode.
     pub install_mode: SignerInstallMode,
+    /// Kind of provisioning profile to register, for `SignerMode::Pem`.
+    pub distribution: SignerDistribution,
     /// Tweaks to apply before signing.
     pub tweaks: Option<Vec<std::path::PathBuf>>,
+    /// Previously injected tweaks to remove, by their id in the bundle's tweak manifest.
+    pub remove_tweaks: Vec<String>,
+    /// Hooking runtime injected alongside tweaks.
+    pub hooking_runtime: SignerHookingRuntime,
+    /// Apply tweaks even when they declare conflicts with each other.
+    pub allow_tweak_conflicts: bool,
     /// App type.
     pub app: SignerApp,
     /// Apply autorefresh
     pub refresh: bool,
+    /// Team to sign with for `SignerMode::Pem`, the account's stored team when `None`.
+    pub team_id: Option<String>,
 }
 
 impl Default for SignerOptions {
@@ -33,9 +43,14 @@ impl Default for SignerOptions {
             embedding: SignerEmbedding::default(),
             mode: SignerMode::default(),
             install_mode: SignerInstallMode::default(),
+            distribution: SignerDistribution::default(),
             tweaks: None,
+            remove_tweaks: Vec::new(),
+            hooking_runtime: SignerHookingRuntime::default(),
+            allow_tweak_conflicts: false,
             app: SignerApp::Default,
             refresh: false,
+            team_id: None,
         }
     }
 }
@@ -76,6 +91,64 @@ pub struct SignerEmbedding {
     pub single_profile: bool,
 }
 
+/// Hooking runtime to inject for tweaks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignerHookingRuntime {
+    /// ElleKit bundled with Impactor.
+    ElleKit,
+    /// User supplied `.deb` or `.framework`, e.g. Substitute or libhooker.
+    Custom(std::path::PathBuf),
+    /// Nothing, for tweaks that hook without a runtime.
+    None,
+}
+
+impl Default for SignerHookingRuntime {
+    fn default() -> Self {
+        SignerHookingRuntime::ElleKit
+    }
+}
+
+impl std::fmt::Display for SignerHookingRuntime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SignerHookingRuntime::ElleKit => write!(f, "ElleKit"),
+            SignerHookingRuntime::Custom(path) if path.as_os_str().is_empty() => {
+                write!(f, "Custom")
+            }
+            SignerHookingRuntime::Custom(path) => write!(
+                f,
+                "Custom ({})",
+                path.file_name()
+                    .map(|n| n.to_string_lossy())
+                    .unwrap_or_default()
+            ),
+            SignerHookingRuntime::None => write!(f, "None"),
+        }
+    }
+}
+
+impl std::str::FromStr for SignerHookingRuntime {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "ellekit" => Ok(SignerHookingRuntime::ElleKit),
+            "none" => Ok(SignerHookingRuntime::None),
+            _ => {
+                let path = std::path::PathBuf::from(s);
+                if path.exists() {
+                    Ok(SignerHookingRuntime::Custom(path))
+                } else {
+                    Err(format!(
+                        "expected `ellekit`, `none` or a path to a .deb/.framework, got `{}`",
+                        s
+                    ))
+                }
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SignerInstallMode {
     Install,
@@ -97,6 +170,61 @@ impl std::fmt::Display for SignerInstallMode {
     }
 }
 
+/// Provisioning profile used when signing with a team's certificate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignerDistribution {
+    /// Development profile for the selected device, works with free teams.
+    Development,
+    /// Ad-hoc profile covering every enabled device of a paid team.
+    AdHoc,
+    /// In-house profile installable on any device, enterprise teams only.
+    InHouse,
+}
+
+impl Default for SignerDistribution {
+    fn default() -> Self {
+        SignerDistribution::Development
+    }
+}
+
+impl SignerDistribution {
+    pub fn profile_type(&self) -> Option<plume_core::developer::v1::profiles::ProfileType> {
+        use plume_core::developer::v1::profiles::ProfileType;
+
+        match self {
+            SignerDistribution::Development => None,
+            SignerDistribution::AdHoc => Some(ProfileType::AdHoc),
+            SignerDistribution::InHouse => Some(ProfileType::InHouse),
+        }
+    }
+}
+
+impl std::fmt::Display for SignerDistribution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SignerDistribution::Development => write!(f, "Development"),
+            SignerDistribution::AdHoc => write!(f, "Ad-hoc"),
+            SignerDistribution::InHouse => write!(f, "In-house"),
+        }
+    }
+}
+
+impl std::str::FromStr for SignerDistribution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "development" | "dev" => Ok(SignerDistribution::Development),
+            "adhoc" | "ad-hoc" => Ok(SignerDistribution::AdHoc),
+            "inhouse" | "in-house" | "enterprise" => Ok(SignerDistribution::InHouse),
+            _ => Err(format!(
+                "expected `development`, `adhoc` or `inhouse`, got `{}`",
+                s
+            )),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SignerMode {
     Pem,
@@ -1,5 +1,5 @@
 use super::{Bundle, PlistInfoTrait};
-use crate::{Error, SignerApp, SignerOptions};
+use crate::{Error, InstalledTweak, SignerApp, SignerOptions, TWEAK_MANIFEST_PATH};
 use plist::Dictionary;
 use std::path::PathBuf;
 use std::{env, fs, io::Read};
@@ -135,6 +135,45 @@ impl Package {
         Ok(zip_file_path)
     }
 
+    /// Tweaks a previous signing injected into this package.
+    pub fn installed_tweaks(&self) -> Vec<InstalledTweak> {
+        let Some(entry) = self.archive_entries.iter().find(|entry| {
+            entry.starts_with("Payload/")
+                && entry.ends_with(TWEAK_MANIFEST_PATH)
+                && entry.matches('/').count() == 3
+        }) else {
+            return Vec::new();
+        };
+
+        let read = || -> Result<Vec<InstalledTweak>, Error> {
+            let file = fs::File::open(&self.package_file)?;
+            let mut archive = ZipArchive::new(file)?;
+            let mut manifest_file = archive.by_name(entry)?;
+            let mut data = Vec::new();
+            manifest_file.read_to_end(&mut data)?;
+
+            Ok(crate::TweakInstallManifest::from_slice(&data)?.tweaks)
+        };
+
+        read().unwrap_or_default()
+    }
+
+    /// App IDs signing this package registers, the app and each of its extensions.
+    pub fn app_id_count(&self) -> usize {
+        let extensions = self
+            .archive_entries
+            .iter()
+            .filter(|entry| {
+                entry.starts_with("Payload/")
+                    && entry
+                        .strip_suffix("/Info.plist")
+                        .is_some_and(|dir| dir.ends_with(".appex"))
+            })
+            .count();
+
+        1 + extensions
+    }
+
     pub fn remove_package_stage(self) {
         fs::remove_dir_all(&self.stage_dir).ok();
     }
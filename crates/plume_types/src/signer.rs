@@ -5,15 +5,22 @@ use tokio::fs;
 
 use plume_core::{
     CertificateIdentity, MobileProvision, SettingsScope, SigningSettings, UnifiedSigner,
-    developer::DeveloperSession,
+    developer::{DeveloperSession, v1::profiles::ProfileType},
 };
+use plume_store::ProfileCache;
 
-use crate::{Bundle, BundleType, Error, PlistInfoTrait, SignerApp, SignerMode, SignerOptions};
+use crate::{
+    Bundle, BundleType, Error, PlistInfoTrait, SignerApp, SignerHookingRuntime, SignerMode,
+    SignerOptions, TweakWarnings,
+};
 
 pub struct Signer {
     certificate: Option<CertificateIdentity>,
     pub options: SignerOptions,
     pub provisioning_files: Vec<MobileProvision>,
+    profile_cache: Option<ProfileCache>,
+    target_udid: Option<String>,
+    free_team: bool,
 }
 
 impl Signer {
@@ -22,16 +29,35 @@ impl Signer {
             certificate,
             options,
             provisioning_files: Vec::new(),
+            profile_cache: None,
+            target_udid: None,
+            free_team: false,
         }
     }
 
+    /// Reuses cached profiles that still provision `udid` instead of downloading new ones.
+    pub fn with_profile_cache(mut self, cache: ProfileCache, udid: Option<String>) -> Self {
+        self.profile_cache = Some(cache);
+        self.target_udid = udid;
+        self
+    }
+
+    /// Marks the team as a free one, whose App ID quota is checked before registering.
+    pub fn with_free_team(mut self, free_team: bool) -> Self {
+        self.free_team = free_team;
+        self
+    }
+
+    /// Applies the options to the bundle, returning tweak problems the user should hear about.
     pub async fn modify_bundle(
         &mut self,
         bundle: &Bundle,
         team_id: &Option<String>,
-    ) -> Result<(), Error> {
+    ) -> Result<TweakWarnings, Error> {
+        let mut warnings = TweakWarnings::default();
+
         if self.options.mode == SignerMode::None {
-            return Ok(());
+            return Ok(warnings);
         }
 
         let bundles = bundle
@@ -69,6 +95,54 @@ impl Signer {
             bundle.set_info_plist_key("CADisableMinimumFrameDurationOnPhone", true)?;
         }
 
+        for id in &self.options.remove_tweaks {
+            crate::Tweak::uninstall(bundle, id).await?;
+        }
+
+        // Tweaks go in before identifiers are changed so substrate filters
+        // match against the original bundle ids
+        let has_tweaks = self.options.tweaks.as_ref().is_some_and(|t| !t.is_empty());
+
+        if self.options.features.support_ellekit || has_tweaks {
+            crate::Tweak::install_hooking_runtime(bundle, &self.options.hooking_runtime).await?;
+        }
+
+        if let Some(tweak_files) = self.options.tweaks.as_ref() {
+            let mut tweaks = Vec::with_capacity(tweak_files.len());
+            for tweak_file in tweak_files {
+                tweaks.push(crate::Tweak::new(tweak_file, bundle).await?);
+            }
+
+            let resolution = crate::Tweak::resolve_dependencies(
+                tweaks,
+                self.options.hooking_runtime != SignerHookingRuntime::None,
+            );
+
+            if !resolution.conflicts.is_empty() && !self.options.allow_tweak_conflicts {
+                return Err(Error::TweakConflicts(
+                    resolution
+                        .conflicts
+                        .iter()
+                        .map(|c| c.to_string())
+                        .collect::<Vec<_>>()
+                        .join(", "),
+                ));
+            }
+
+            warnings.missing = resolution.missing;
+            warnings.conflicts = resolution.conflicts;
+
+            for tweak in &resolution.ordered {
+                let report = tweak.apply().await?;
+
+                for (load_path, target) in &report.rewritten {
+                    log::debug!("Rewrote {} to {}", load_path, target);
+                }
+
+                warnings.unresolved.extend(report.unresolved);
+            }
+        }
+
         let identifier = bundle.get_bundle_identifier();
 
         if self.options.mode != SignerMode::Adhoc && self.options.custom_identifier.is_none() {
@@ -124,19 +198,6 @@ impl Signer {
             }
         }
 
-        let has_tweaks = self.options.tweaks.as_ref().is_some_and(|t| !t.is_empty());
-
-        if self.options.features.support_ellekit || has_tweaks {
-            crate::Tweak::install_ellekit(&bundle).await?;
-        }
-
-        if let Some(tweak_files) = self.options.tweaks.as_ref() {
-            for tweak_file in tweak_files {
-                let tweak = crate::Tweak::new(tweak_file, bundle).await?;
-                tweak.apply().await?;
-            }
-        }
-
         if self.options.features.support_liquid_glass {
             bundle.set_info_plist_key("UIDesignRequiresCompatibility", false)?;
 
@@ -153,7 +214,7 @@ impl Signer {
             macho.replace_sdk_version("26.0.0")?;
         }
 
-        Ok(())
+        Ok(warnings)
     }
 
     pub async fn register_bundle(
@@ -167,36 +228,78 @@ impl Signer {
             return Ok(());
         }
 
-        let bundles = bundle
-            .collect_bundles_sorted()?
-            .into_iter()
-            .filter(|b| b.bundle_type().should_have_entitlements())
-            .collect::<Vec<_>>();
+        let bundles = self.bundles_to_register(bundle)?;
+
+        // free teams only get a handful of App IDs a week, better to find out now
+        // than halfway through registering extensions. Api keys only exist for paid teams
+        if self.free_team && !session.uses_api_key() {
+            let identifiers = bundles
+                .iter()
+                .filter_map(|b| b.get_bundle_identifier())
+                .collect::<Vec<_>>();
+
+            match session.qh_team_app_id_quota(team_id, true).await {
+                Ok(quota) if !quota.fits(&identifiers) => {
+                    return Err(Error::AppIdQuotaExceeded {
+                        needed: quota.missing(&identifiers).len(),
+                        remaining: quota.remaining().unwrap_or_default(),
+                    });
+                }
+                Ok(_) => {}
+                // the portal still refuses new App IDs itself once the limit is hit
+                Err(e) => log::warn!("Couldn't check the App ID quota, continuing: {e}"),
+            }
+        }
+
+        // profiles only allow the certificates they were generated with, a new one
+        // makes every cached profile of the team useless
+        if let (Some(cache), Some(identity)) = (&self.profile_cache, &self.certificate) {
+            if identity.new {
+                cache.remove_team(team_id).await?;
+            }
+        }
+
+        // cached profiles only cover the devices the team had when they were
+        // generated, without the current list there's nothing to compare against
+        let device_digest = match &self.profile_cache {
+            Some(_) if !session.uses_api_key() => match session.qh_list_devices(team_id).await {
+                Ok(response) => Some(ProfileCache::device_digest(
+                    response.devices.iter().map(|d| d.device_number.as_str()),
+                )),
+                Err(e) => {
+                    log::warn!("Couldn't list the team's devices, not using cached profiles: {e}");
+                    None
+                }
+            },
+            _ => None,
+        };
+
         let signer_settings = &self.options;
 
         let bundle_arc = Arc::new(bundle.clone());
         let session_arc = Arc::new(session);
         let team_id_arc = Arc::new(team_id.clone());
 
-        let futures = bundles.iter().filter_map(|sub_bundle| {
+        let futures = bundles.iter().map(|sub_bundle| {
             let sub_bundle = sub_bundle.clone();
             let bundle = bundle_arc.clone();
             let session = session_arc.clone();
             let team_id = team_id_arc.clone();
             let signer_settings = signer_settings.clone();
-
-            if signer_settings.embedding.single_profile
-                && sub_bundle.bundle_dir() != bundle.bundle_dir()
-            {
-                return None;
-            }
-            if *sub_bundle.bundle_type() != BundleType::AppExtension
-                && *sub_bundle.bundle_type() != BundleType::App
-            {
-                return None;
-            }
-
-            Some(async move {
+            let profile_cache = self.profile_cache.clone();
+            let target_udid = self.target_udid.clone();
+            let device_digest = device_digest.clone();
+            let certificate_serial = self
+                .certificate
+                .as_ref()
+                .and_then(|c| c.serial_number.clone());
+            let certificate_der = self
+                .certificate
+                .as_ref()
+                .and_then(|c| c.cert.as_ref())
+                .map(|c| c.constructed_data().to_vec());
+
+            async move {
                 let bundle_executable_name = sub_bundle
                     .get_executable()
                     .ok_or_else(|| Error::Other("Failed to get bundle executable name.".into()))?;
@@ -210,12 +313,17 @@ impl Signer {
 
                 let name = sub_bundle.get_bundle_name().unwrap_or_else(|| id.clone());
 
-                session.qh_ensure_app_id(&team_id, &name, &id).await?;
+                let app_id_id = if session.uses_api_key() {
+                    session.v1_ensure_app_id(&team_id, &name, &id).await?.id
+                } else {
+                    session.qh_ensure_app_id(&team_id, &name, &id).await?;
 
-                let app_id_id = session
-                    .qh_get_app_id(&team_id, &id)
-                    .await?
-                    .ok_or_else(|| Error::Other("Failed to get ensured app ID.".into()))?;
+                    session
+                        .qh_get_app_id(&team_id, &id)
+                        .await?
+                        .ok_or_else(|| Error::Other("Failed to get ensured app ID.".into()))?
+                        .app_id_id
+                };
 
                 if let Some(e) = macho.entitlements().as_ref() {
                     session
@@ -223,7 +331,15 @@ impl Signer {
                         .await?;
                 }
 
-                if let Some(app_groups) = macho.app_groups_for_entitlements() {
+                if session.uses_api_key() {
+                    if let Some(app_groups) = macho.app_groups_for_entitlements() {
+                        // the public api can enable the capability, but not create or assign groups
+                        log::warn!(
+                            "App groups {:?} of {id} have to be set up on the Developer Portal when signing with an API key",
+                            app_groups
+                        );
+                    }
+                } else if let Some(app_groups) = macho.app_groups_for_entitlements() {
                     let mut app_group_ids: Vec<String> = Vec::new();
                     for group in &app_groups {
                         let mut group_name = format!("{group}.{team_id}");
@@ -253,24 +369,101 @@ impl Signer {
                     }
 
                     session
-                        .qh_assign_app_group(&team_id, &app_id_id.app_id_id, &app_group_ids)
+                        .qh_assign_app_group(&team_id, &app_id_id, &app_group_ids)
                         .await?;
                 }
 
-                let profiles = session
-                    .qh_get_profile(&team_id, &app_id_id.app_id_id)
+                let profile_type = signer_settings.distribution.profile_type().or_else(|| {
+                    session
+                        .uses_api_key()
+                        .then_some(ProfileType::Development)
+                });
+
+                if let Some(profile_type) = profile_type {
+                    let serial = certificate_serial.as_deref().ok_or_else(|| {
+                        Error::Other("Signing certificate has no serial number.".into())
+                    })?;
+                    let profile = session
+                        .v1_ensure_profile(&team_id, &id, profile_type, serial)
+                        .await?;
+                    let mobile_provision = MobileProvision::load_with_bytes(profile.content()?)?;
+
+                    tokio::fs::write(
+                        sub_bundle.bundle_dir().join("embedded.mobileprovision"),
+                        &mobile_provision.data,
+                    )
                     .await?;
-                let profile_data = profiles.provisioning_profile.encoded_profile;
+                    return Ok::<_, Error>(mobile_provision);
+                }
+
+                let cached = match (&profile_cache, &device_digest) {
+                    (Some(cache), Some(digest)) => {
+                        cache
+                            .get(&team_id, &app_id_id, target_udid.as_deref(), digest)
+                            .await?
+                    }
+                    _ => None,
+                };
+
+                // the signing certificate may have been replaced outside of this signer
+                let cached = cached.filter(|p| {
+                    certificate_der
+                        .as_deref()
+                        .is_none_or(|der| p.includes_certificate(der))
+                });
+
+                // a cached profile predating an app group assignment would lack the group
+                let cached = cached.filter(|p| {
+                    macho.app_groups_for_entitlements().is_none_or(|groups| {
+                        let granted = p
+                            .entitlements()
+                            .get("com.apple.security.application-groups")
+                            .and_then(Value::as_array);
+                        groups.iter().all(|g| {
+                            granted.is_some_and(|a| {
+                                a.iter().any(|v| {
+                                    v.as_string()
+                                        .is_some_and(|v| v == g || v == format!("{g}.{team_id}"))
+                                })
+                            })
+                        })
+                    })
+                });
+
+                let mobile_provision = match cached {
+                    Some(provision) => {
+                        log::info!("Using cached provisioning profile for {id}");
+                        provision
+                    }
+                    None => {
+                        let profiles = session
+                            .qh_get_profile(&team_id, &app_id_id)
+                            .await?;
+                        let provision = MobileProvision::load_with_bytes(
+                            profiles
+                                .provisioning_profile
+                                .encoded_profile
+                                .as_ref()
+                                .to_vec(),
+                        )?;
+
+                        if let (Some(cache), Some(digest)) = (&profile_cache, &device_digest) {
+                            cache
+                                .insert(&team_id, &app_id_id, digest, &provision)
+                                .await?;
+                        }
+
+                        provision
+                    }
+                };
 
                 tokio::fs::write(
                     sub_bundle.bundle_dir().join("embedded.mobileprovision"),
-                    &profile_data,
+                    &mobile_provision.data,
                 )
                 .await?;
-                let mobile_provision =
-                    MobileProvision::load_with_bytes(profile_data.as_ref().to_vec())?;
                 Ok::<_, Error>(mobile_provision)
-            })
+            }
         });
 
         let provisionings: Vec<MobileProvision> = try_join_all(futures).await?;
@@ -279,6 +472,19 @@ impl Signer {
         Ok(())
     }
 
+    /// Bundles that get their own App ID and provisioning profile.
+    pub fn bundles_to_register(&self, bundle: &Bundle) -> Result<Vec<Bundle>, Error> {
+        Ok(bundle
+            .collect_bundles_sorted()?
+            .into_iter()
+            .filter(|b| b.bundle_type().should_have_entitlements())
+            .filter(|b| {
+                !self.options.embedding.single_profile || b.bundle_dir() == bundle.bundle_dir()
+            })
+            .filter(|b| matches!(b.bundle_type(), BundleType::AppExtension | BundleType::App))
+            .collect())
+    }
+
     pub async fn sign_bundle(&self, bundle: &Bundle) -> Result<(), Error> {
         if self.options.mode == SignerMode::None {
             return Ok(());
@@ -392,3 +598,424 @@ impl Signer {
         Ok(settings)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::{Path, PathBuf};
+
+    use plist::Dictionary;
+    use plume_core::KeyStore;
+    use plume_core::testing::{Fixture, MockServer, fixtures_dir};
+
+    use super::*;
+
+    const TEAM_ID: &str = "TEAMID1234";
+    const UDID: &str = "00008110-000000000000001E";
+    const LIST_APP_IDS: &str = "/services/QH65B2/ios/listAppIds.action";
+    const ADD_APP_ID: &str = "/services/QH65B2/ios/addAppId.action";
+    const LIST_CERTS: &str = "/services/QH65B2/ios/listAllDevelopmentCerts.action";
+    const DOWNLOAD_PROFILE: &str = "/services/QH65B2/ios/downloadTeamProvisioningProfile.action";
+    const LIST_DEVICES: &str = "/services/QH65B2/ios/listDevices.action";
+
+    // arm64 executable header without load commands, enough to look for entitlements in
+    const EXECUTABLE: [u8; 32] = [
+        0xcf, 0xfa, 0xed, 0xfe, 0x0c, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00,
+    ];
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("plume_signer_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn app_bundle(dir: &Path) -> Bundle {
+        let app = dir.join("Example.app");
+        std::fs::create_dir_all(&app).unwrap();
+
+        let mut info = plist::Dictionary::new();
+        info.insert("CFBundleIdentifier".into(), "com.example.app".into());
+        info.insert("CFBundleExecutable".into(), "Example".into());
+        info.insert("CFBundleName".into(), "Example".into());
+        Value::Dictionary(info)
+            .to_file_xml(app.join("Info.plist"))
+            .unwrap();
+        std::fs::write(app.join("Example"), EXECUTABLE).unwrap();
+
+        Bundle::new(app).unwrap()
+    }
+
+    fn pem_options() -> SignerOptions {
+        SignerOptions {
+            mode: SignerMode::Pem,
+            team_id: Some(TEAM_ID.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn count(server: &MockServer, path: &str) -> usize {
+        server.requests().iter().filter(|r| r.path == path).count()
+    }
+
+    #[tokio::test]
+    async fn register_bundle_adds_app_id_and_embeds_profile() {
+        let server = MockServer::start().await.unwrap();
+        let session = server.recorded_session().await.unwrap();
+        // the App ID only shows up in listings once it was added
+        for fixture in [
+            "qh/listAppIds.plist",
+            "qh/listAppIds.plist",
+            "qh/listAppIds-added.plist",
+        ] {
+            server.mock(LIST_APP_IDS, Fixture::recorded(fixture).unwrap());
+        }
+        server.mock(ADD_APP_ID, Fixture::recorded("qh/addAppId.plist").unwrap());
+        server.mock(
+            DOWNLOAD_PROFILE,
+            Fixture::recorded("qh/downloadTeamProvisioningProfile.plist").unwrap(),
+        );
+
+        let dir = temp_dir();
+        let bundle = app_bundle(&dir);
+        let mut signer = Signer::new(None, pem_options()).with_free_team(true);
+
+        signer
+            .register_bundle(&bundle, &session, &TEAM_ID.to_string(), false)
+            .await
+            .unwrap();
+
+        assert_eq!(count(&server, ADD_APP_ID), 1);
+        let requests = server.requests();
+        let download = requests
+            .iter()
+            .find(|r| r.path == DOWNLOAD_PROFILE)
+            .unwrap();
+        assert_eq!(
+            download
+                .plist()
+                .unwrap()
+                .get("appIdId")
+                .and_then(Value::as_string),
+            Some("NEWAPPID01")
+        );
+
+        assert_eq!(signer.provisioning_files.len(), 1);
+        assert!(signer.provisioning_files[0].provisions_device(UDID));
+        assert!(
+            bundle
+                .bundle_dir()
+                .join("embedded.mobileprovision")
+                .exists()
+        );
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[tokio::test]
+    async fn register_bundle_stops_before_exceeding_app_id_limit() {
+        let server = MockServer::start().await.unwrap();
+        let session = server.recorded_session().await.unwrap();
+
+        // a free team with all ten App IDs in use
+        let mut listing =
+            plist::from_file::<_, plist::Dictionary>(fixtures_dir().join("qh/listAppIds.plist"))
+                .unwrap();
+        let existing = listing.get("appIds").and_then(Value::as_array).unwrap()[0].clone();
+        let app_ids = (0..10)
+            .map(|i| {
+                let mut app_id = existing.as_dictionary().unwrap().clone();
+                app_id.insert("appIdId".into(), format!("USED{i:06}").into());
+                app_id.insert("identifier".into(), format!("com.example.used{i}").into());
+                Value::Dictionary(app_id)
+            })
+            .collect();
+        listing.insert("appIds".into(), Value::Array(app_ids));
+        server.mock(LIST_APP_IDS, Fixture::qh(listing));
+
+        let dir = temp_dir();
+        let bundle = app_bundle(&dir);
+        let mut signer = Signer::new(None, pem_options()).with_free_team(true);
+
+        let result = signer
+            .register_bundle(&bundle, &session, &TEAM_ID.to_string(), false)
+            .await;
+
+        assert!(matches!(
+            result,
+            Err(Error::AppIdQuotaExceeded {
+                needed: 1,
+                remaining: 0
+            })
+        ));
+        assert_eq!(count(&server, ADD_APP_ID), 0);
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[tokio::test]
+    async fn register_bundle_skips_quota_for_paid_teams() {
+        let server = MockServer::start().await.unwrap();
+        let session = server.recorded_session().await.unwrap();
+        server.mock(
+            LIST_APP_IDS,
+            Fixture::recorded("qh/listAppIds.plist").unwrap(),
+        );
+        server.mock(
+            LIST_APP_IDS,
+            Fixture::recorded("qh/listAppIds-added.plist").unwrap(),
+        );
+        server.mock(ADD_APP_ID, Fixture::recorded("qh/addAppId.plist").unwrap());
+        server.mock(
+            DOWNLOAD_PROFILE,
+            Fixture::recorded("qh/downloadTeamProvisioningProfile.plist").unwrap(),
+        );
+
+        let dir = temp_dir();
+        let bundle = app_bundle(&dir);
+        let mut signer = Signer::new(None, pem_options());
+
+        signer
+            .register_bundle(&bundle, &session, &TEAM_ID.to_string(), false)
+            .await
+            .unwrap();
+
+        // one listing to look the App ID up, one to find it after adding it
+        assert_eq!(count(&server, LIST_APP_IDS), 2);
+        assert_eq!(count(&server, "/services/QH65B2/listTeams.action"), 1);
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[tokio::test]
+    async fn register_bundle_continues_when_quota_is_unknown() {
+        let server = MockServer::start().await.unwrap();
+        let session = server.recorded_session().await.unwrap();
+        server.mock(
+            LIST_APP_IDS,
+            Fixture::qh_error(35, "Temporarily unavailable"),
+        );
+        server.mock(
+            LIST_APP_IDS,
+            Fixture::recorded("qh/listAppIds.plist").unwrap(),
+        );
+        server.mock(
+            LIST_APP_IDS,
+            Fixture::recorded("qh/listAppIds-added.plist").unwrap(),
+        );
+        server.mock(ADD_APP_ID, Fixture::recorded("qh/addAppId.plist").unwrap());
+        server.mock(
+            DOWNLOAD_PROFILE,
+            Fixture::recorded("qh/downloadTeamProvisioningProfile.plist").unwrap(),
+        );
+
+        let dir = temp_dir();
+        let bundle = app_bundle(&dir);
+        let mut signer = Signer::new(None, pem_options()).with_free_team(true);
+
+        signer
+            .register_bundle(&bundle, &session, &TEAM_ID.to_string(), false)
+            .await
+            .unwrap();
+
+        assert_eq!(count(&server, ADD_APP_ID), 1);
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    // the daemon's setup: the stored key finds its certificate on the portal
+    fn refresh_server(server: &MockServer) {
+        server.mock(
+            LIST_APP_IDS,
+            Fixture::recorded("qh/listAppIds-added.plist").unwrap(),
+        );
+        server.mock(
+            LIST_CERTS,
+            Fixture::recorded("qh/listAllDevelopmentCerts.plist").unwrap(),
+        );
+        server.mock(
+            LIST_DEVICES,
+            Fixture::recorded("qh/listDevices.plist").unwrap(),
+        );
+        server.mock(
+            DOWNLOAD_PROFILE,
+            Fixture::recorded("qh/downloadTeamProvisioningProfile.plist").unwrap(),
+        );
+    }
+
+    fn refresh_data_dir(dir: &Path) -> PathBuf {
+        let data_dir = dir.join("data");
+        let key_dir = data_dir.join("keys").join(TEAM_ID);
+        std::fs::create_dir_all(&key_dir).unwrap();
+        std::fs::copy(fixtures_dir().join("keys/key.pem"), key_dir.join("key.pem")).unwrap();
+        data_dir
+    }
+
+    async fn refresh(
+        session: &DeveloperSession,
+        identity: CertificateIdentity,
+        bundle: &Bundle,
+        cache: &ProfileCache,
+    ) {
+        let mut signer = Signer::new(Some(identity), pem_options())
+            .with_profile_cache(cache.clone(), Some(UDID.to_string()))
+            .with_free_team(true);
+        signer
+            .register_bundle(bundle, session, &TEAM_ID.to_string(), true)
+            .await
+            .unwrap();
+
+        assert_eq!(signer.provisioning_files.len(), 1);
+    }
+
+    async fn stored_identity(session: &DeveloperSession, data_dir: &Path) -> CertificateIdentity {
+        let identity = CertificateIdentity::new_with_session(
+            session,
+            &KeyStore::new(data_dir.to_path_buf(), None),
+            None,
+            &TEAM_ID.to_string(),
+            false,
+            true,
+        )
+        .await
+        .unwrap();
+        assert!(!identity.new);
+        identity
+    }
+
+    // what the refresh daemon does for an app that's still installed: profiles are
+    // only downloaded while the cache has none
+    #[tokio::test]
+    async fn refresh_reuses_identity_and_cached_profile() {
+        let server = MockServer::start().await.unwrap();
+        let session = server.recorded_session().await.unwrap();
+        refresh_server(&server);
+
+        let dir = temp_dir();
+        let data_dir = refresh_data_dir(&dir);
+        let bundle = app_bundle(&dir);
+        let cache = ProfileCache::new(dir.join("profiles"));
+
+        for _ in 0..2 {
+            let identity = stored_identity(&session, &data_dir).await;
+            refresh(&session, identity, &bundle, &cache).await;
+        }
+
+        assert_eq!(count(&server, ADD_APP_ID), 0);
+        assert_eq!(count(&server, DOWNLOAD_PROFILE), 1);
+        assert!(
+            !server
+                .requests()
+                .iter()
+                .any(|r| r.path.ends_with("submitDevelopmentCSR.action"))
+        );
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[tokio::test]
+    async fn refresh_downloads_again_after_team_devices_change() {
+        let server = MockServer::start().await.unwrap();
+        let session = server.recorded_session().await.unwrap();
+        refresh_server(&server);
+        server.mock(
+            LIST_DEVICES,
+            Fixture::recorded("qh/listDevices-added.plist").unwrap(),
+        );
+
+        let dir = temp_dir();
+        let data_dir = refresh_data_dir(&dir);
+        let bundle = app_bundle(&dir);
+        let cache = ProfileCache::new(dir.join("profiles"));
+
+        for _ in 0..3 {
+            let identity = stored_identity(&session, &data_dir).await;
+            refresh(&session, identity, &bundle, &cache).await;
+        }
+
+        // the second refresh sees the added device, the third reuses its profile
+        assert_eq!(count(&server, DOWNLOAD_PROFILE), 2);
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[tokio::test]
+    async fn refresh_skips_cached_profile_without_the_certificate() {
+        let server = MockServer::start().await.unwrap();
+        let session = server.recorded_session().await.unwrap();
+        refresh_server(&server);
+
+        let dir = temp_dir();
+        let data_dir = refresh_data_dir(&dir);
+        let bundle = app_bundle(&dir);
+        let cache = ProfileCache::new(dir.join("profiles"));
+
+        let identity = stored_identity(&session, &data_dir).await;
+        refresh(&session, identity, &bundle, &cache).await;
+
+        // swap the cached profile's certificate for one of another machine
+        let other: Value =
+            plist::from_file(fixtures_dir().join("qh/listAllDevelopmentCerts-other.plist"))
+                .unwrap();
+        let other_der = other
+            .as_dictionary()
+            .and_then(|d| d.get("certificates"))
+            .and_then(Value::as_array)
+            .and_then(|a| a.first())
+            .and_then(Value::as_dictionary)
+            .and_then(|c| c.get("certContent"))
+            .and_then(Value::as_data)
+            .unwrap()
+            .to_vec();
+        let cached_path = dir
+            .join("profiles")
+            .join(TEAM_ID)
+            .join("NEWAPPID01")
+            .join("embedded.mobileprovision");
+        let mut cached: Dictionary = plist::from_file(&cached_path).unwrap();
+        cached.insert(
+            "DeveloperCertificates".to_string(),
+            Value::Array(vec![Value::Data(other_der)]),
+        );
+        plist::to_file_xml(&cached_path, &cached).unwrap();
+
+        let identity = stored_identity(&session, &data_dir).await;
+        refresh(&session, identity, &bundle, &cache).await;
+
+        assert_eq!(count(&server, DOWNLOAD_PROFILE), 2);
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[tokio::test]
+    async fn new_identity_drops_cached_profiles_of_the_team() {
+        let server = MockServer::start().await.unwrap();
+        let session = server.recorded_session().await.unwrap();
+        refresh_server(&server);
+
+        let dir = temp_dir();
+        let data_dir = refresh_data_dir(&dir);
+        let bundle = app_bundle(&dir);
+        let cache = ProfileCache::new(dir.join("profiles"));
+        let other_app = dir.join("profiles").join(TEAM_ID).join("OTHERAPP01");
+        std::fs::create_dir_all(&other_app).unwrap();
+
+        let identity = stored_identity(&session, &data_dir).await;
+        refresh(&session, identity, &bundle, &cache).await;
+
+        let identity = CertificateIdentity {
+            cert: None,
+            key: None,
+            machine_id: None,
+            serial_number: None,
+            p12_data: None,
+            new: true,
+        };
+        refresh(&session, identity, &bundle, &cache).await;
+
+        assert_eq!(count(&server, DOWNLOAD_PROFILE), 2);
+        assert!(!other_app.exists());
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
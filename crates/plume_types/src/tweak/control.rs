@@ -0,0 +1,435 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+// Packages we never expect to be bundled with an app, these are either
+// virtual packages provided by the jailbreak itself or device checks
+const IGNORED_DEPENDENCY_PREFIXES: &[&str] = &["firmware", "cy+", "gsc."];
+const IGNORED_DEPENDENCIES: &[&str] = &[
+    "dpkg", "apt", "apt7", "apt7-lib", "apt7-key", "apt-key", "apt-lib",
+];
+
+// Hooking runtimes that are satisfied by the ElleKit we embed ourselves
+pub(crate) const HOOKING_RUNTIME_PACKAGES: &[&str] = &[
+    "mobilesubstrate",
+    "cydiasubstrate",
+    "ellekit",
+    "com.ex.substitute",
+    "org.coolstar.libhooker",
+];
+
+/// Parsed `control` file of a `.deb` tweak.
+#[derive(Debug, Clone, Default)]
+pub struct TweakManifest {
+    pub package: String,
+    pub name: Option<String>,
+    pub version: Option<String>,
+    pub architecture: Option<String>,
+    pub author: Option<String>,
+    pub description: Option<String>,
+    /// Each entry is a group of alternatives, `a | b` is satisfied by either.
+    pub depends: Vec<Vec<TweakDependency>>,
+    pub pre_depends: Vec<Vec<TweakDependency>>,
+    pub conflicts: Vec<TweakDependency>,
+    pub provides: Vec<TweakDependency>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TweakDependency {
+    pub package: String,
+    /// Version constraint, e.g. `>= 0.9.5000`.
+    pub version: Option<String>,
+}
+
+impl TweakManifest {
+    pub fn parse(contents: &str) -> Option<Self> {
+        let mut fields: HashMap<String, String> = HashMap::new();
+        let mut last_key: Option<String> = None;
+
+        for line in contents.lines() {
+            // control files may contain multiple stanzas, we only care about the first
+            if line.trim().is_empty() {
+                if !fields.is_empty() {
+                    break;
+                }
+                continue;
+            }
+
+            // continuation lines (mostly used by Description)
+            if line.starts_with(' ') || line.starts_with('\t') {
+                if let Some(value) = last_key.as_ref().and_then(|k| fields.get_mut(k)) {
+                    value.push('\n');
+                    value.push_str(line.trim());
+                }
+                continue;
+            }
+
+            if let Some((key, value)) = line.split_once(':') {
+                let key = key.trim().to_ascii_lowercase();
+                fields.insert(key.clone(), value.trim().to_string());
+                last_key = Some(key);
+            }
+        }
+
+        let package = fields.remove("package")?.to_ascii_lowercase();
+        if package.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            package,
+            name: fields.remove("name"),
+            version: fields.remove("version"),
+            architecture: fields.remove("architecture"),
+            author: fields
+                .remove("author")
+                .or_else(|| fields.remove("maintainer")),
+            description: fields.remove("description"),
+            depends: parse_relationships(fields.get("depends")),
+            pre_depends: parse_relationships(fields.get("pre-depends")),
+            conflicts: parse_relationships(fields.get("conflicts"))
+                .into_iter()
+                .flatten()
+                .collect(),
+            provides: parse_relationships(fields.get("provides"))
+                .into_iter()
+                .flatten()
+                .collect(),
+        })
+    }
+
+    pub fn display_name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.package)
+    }
+
+    /// All dependency groups, including `Pre-Depends`, minus the ones we can never bundle.
+    pub fn required_dependencies(&self) -> impl Iterator<Item = &Vec<TweakDependency>> {
+        self.pre_depends
+            .iter()
+            .chain(self.depends.iter())
+            .filter(|group| !group.iter().any(|d| d.is_ignored()))
+    }
+
+    /// Whether this package satisfies `dependency`, either directly or through `Provides`,
+    /// including its version constraint.
+    pub fn satisfies(&self, dependency: &TweakDependency) -> bool {
+        if self.package == dependency.package && dependency.accepts(self.version.as_deref()) {
+            return true;
+        }
+
+        self.provides
+            .iter()
+            .filter(|p| p.package == dependency.package)
+            .any(|p| dependency.accepts(p.provided_version()))
+    }
+}
+
+impl TweakDependency {
+    fn parse(raw: &str) -> Option<Self> {
+        let raw = raw.trim();
+        if raw.is_empty() {
+            return None;
+        }
+
+        let (name, rest) = match raw.find(|c: char| c == '(' || c == '[' || c.is_whitespace()) {
+            Some(index) => (&raw[..index], &raw[index..]),
+            None => (raw, ""),
+        };
+
+        // strip architecture qualifiers like `libfoo:any`
+        let package = name.split(':').next()?.to_ascii_lowercase();
+
+        let version = rest
+            .split_once('(')
+            .and_then(|(_, v)| v.split_once(')'))
+            .map(|(v, _)| v.split_whitespace().collect::<Vec<_>>().join(" "))
+            .filter(|v| !v.is_empty());
+
+        Some(Self { package, version })
+    }
+
+    pub fn is_ignored(&self) -> bool {
+        IGNORED_DEPENDENCIES.contains(&self.package.as_str())
+            || IGNORED_DEPENDENCY_PREFIXES
+                .iter()
+                .any(|prefix| self.package.starts_with(prefix))
+    }
+
+    /// Whether a package at `version` meets the version constraint. Packages that
+    /// don't declare a version are given the benefit of the doubt.
+    pub fn accepts(&self, version: Option<&str>) -> bool {
+        let (Some(constraint), Some(version)) = (self.version.as_deref(), version) else {
+            return true;
+        };
+
+        let constraint = constraint.trim();
+        let split = constraint
+            .find(|c: char| !matches!(c, '<' | '>' | '='))
+            .unwrap_or(constraint.len());
+        let (op, wanted) = constraint.split_at(split);
+        let ordering = compare_versions(version, wanted.trim());
+
+        match op {
+            "<<" => ordering == Ordering::Less,
+            // `<` and `>` are the deprecated spellings of `<=` and `>=`
+            "<=" | "<" => ordering != Ordering::Greater,
+            "=" | "" => ordering == Ordering::Equal,
+            ">=" | ">" => ordering != Ordering::Less,
+            ">>" => ordering == Ordering::Greater,
+            _ => true,
+        }
+    }
+
+    // `Provides: foo (= 1.0)` is the only form with a version
+    fn provided_version(&self) -> Option<&str> {
+        self.version
+            .as_deref()
+            .map(|v| v.trim_start_matches('=').trim())
+    }
+
+    pub fn is_hooking_runtime(&self) -> bool {
+        HOOKING_RUNTIME_PACKAGES.contains(&self.package.as_str())
+    }
+}
+
+impl std::fmt::Display for TweakDependency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.version {
+            Some(version) => write!(f, "{} ({})", self.package, version),
+            None => write!(f, "{}", self.package),
+        }
+    }
+}
+
+/// Compares two Debian package versions (`[epoch:]upstream[-revision]`) like dpkg does.
+pub fn compare_versions(a: &str, b: &str) -> Ordering {
+    fn split(version: &str) -> (u64, &str, &str) {
+        let (epoch, rest) = match version.split_once(':') {
+            Some((epoch, rest)) => (epoch.parse().unwrap_or(0), rest),
+            None => (0, version),
+        };
+        match rest.rsplit_once('-') {
+            Some((upstream, revision)) => (epoch, upstream, revision),
+            None => (epoch, rest, ""),
+        }
+    }
+
+    let (epoch_a, upstream_a, revision_a) = split(a.trim());
+    let (epoch_b, upstream_b, revision_b) = split(b.trim());
+
+    epoch_a
+        .cmp(&epoch_b)
+        .then_with(|| compare_fragment(upstream_a, upstream_b))
+        .then_with(|| compare_fragment(revision_a, revision_b))
+}
+
+// alternating non-digit and digit runs, `~` sorts before anything, even the end
+fn compare_fragment(a: &str, b: &str) -> Ordering {
+    fn order(c: Option<char>) -> i32 {
+        match c {
+            Some('~') => -1,
+            None => 0,
+            Some(c) if c.is_ascii_alphabetic() => c as i32,
+            Some(c) => c as i32 + 256,
+        }
+    }
+
+    let (mut a, mut b) = (a, b);
+    while !a.is_empty() || !b.is_empty() {
+        let a_text = a.find(|c: char| c.is_ascii_digit()).unwrap_or(a.len());
+        let b_text = b.find(|c: char| c.is_ascii_digit()).unwrap_or(b.len());
+        let (mut a_chars, mut b_chars) = (a[..a_text].chars(), b[..b_text].chars());
+        loop {
+            let (x, y) = (a_chars.next(), b_chars.next());
+            if x.is_none() && y.is_none() {
+                break;
+            }
+            match order(x).cmp(&order(y)) {
+                Ordering::Equal => {}
+                other => return other,
+            }
+        }
+        a = &a[a_text..];
+        b = &b[b_text..];
+
+        let a_digits = a.find(|c: char| !c.is_ascii_digit()).unwrap_or(a.len());
+        let b_digits = b.find(|c: char| !c.is_ascii_digit()).unwrap_or(b.len());
+        let x = a[..a_digits].trim_start_matches('0');
+        let y = b[..b_digits].trim_start_matches('0');
+        match x.len().cmp(&y.len()).then_with(|| x.cmp(y)) {
+            Ordering::Equal => {}
+            other => return other,
+        }
+        a = &a[a_digits..];
+        b = &b[b_digits..];
+    }
+
+    Ordering::Equal
+}
+
+fn parse_relationships(value: Option<&String>) -> Vec<Vec<TweakDependency>> {
+    let Some(value) = value else {
+        return Vec::new();
+    };
+
+    value
+        .split(',')
+        .map(|group| {
+            group
+                .split('|')
+                .filter_map(TweakDependency::parse)
+                .collect::<Vec<_>>()
+        })
+        .filter(|group| !group.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTROL: &str = "Package: com.example.Tweak
+Name: Example
+Version: 1.2-3
+Architecture: iphoneos-arm64
+Maintainer: someone
+Description: first line
+ second line
+Depends: mobilesubstrate (>= 0.9.5000), firmware (>= 14.0), preferenceloader | com.example.prefs, libfoo:any ( >=  2.0 )
+Pre-Depends: dpkg (>= 1.19.1)
+Conflicts: com.example.old, com.example.other (<< 2.0)
+Provides: example-api (= 1.5)
+
+Package: com.example.second
+Version: 9.9
+";
+
+    fn dependency(package: &str, version: Option<&str>) -> TweakDependency {
+        TweakDependency {
+            package: package.to_string(),
+            version: version.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn parses_first_stanza() {
+        let manifest = TweakManifest::parse(CONTROL).unwrap();
+
+        assert_eq!(manifest.package, "com.example.tweak");
+        assert_eq!(manifest.display_name(), "Example");
+        assert_eq!(manifest.version.as_deref(), Some("1.2-3"));
+        assert_eq!(manifest.author.as_deref(), Some("someone"));
+        assert_eq!(
+            manifest.description.as_deref(),
+            Some("first line\nsecond line")
+        );
+        assert_eq!(manifest.depends.len(), 4);
+        assert_eq!(
+            manifest.depends[2],
+            vec![
+                dependency("preferenceloader", None),
+                dependency("com.example.prefs", None)
+            ]
+        );
+        assert_eq!(
+            manifest.depends[3],
+            vec![dependency("libfoo", Some(">= 2.0"))]
+        );
+        assert_eq!(
+            manifest.conflicts,
+            vec![
+                dependency("com.example.old", None),
+                dependency("com.example.other", Some("<< 2.0"))
+            ]
+        );
+        assert_eq!(
+            manifest.provides,
+            vec![dependency("example-api", Some("= 1.5"))]
+        );
+    }
+
+    #[test]
+    fn rejects_stanza_without_package() {
+        assert!(TweakManifest::parse("Name: Nothing\nVersion: 1.0\n").is_none());
+        assert!(TweakManifest::parse("Package: \n").is_none());
+        assert!(TweakManifest::parse("").is_none());
+    }
+
+    #[test]
+    fn skips_jailbreak_dependencies() {
+        let manifest = TweakManifest::parse(CONTROL).unwrap();
+        let required = manifest
+            .required_dependencies()
+            .map(|group| group[0].package.as_str())
+            .collect::<Vec<_>>();
+
+        assert_eq!(required, ["mobilesubstrate", "preferenceloader", "libfoo"]);
+    }
+
+    #[test]
+    fn ignores_apt_by_exact_name() {
+        assert!(dependency("apt", None).is_ignored());
+        assert!(dependency("apt7-lib", None).is_ignored());
+        assert!(dependency("firmware-sbin", None).is_ignored());
+        assert!(!dependency("aptitude-helper", None).is_ignored());
+        assert!(!dependency("apt-transport-example", None).is_ignored());
+        assert!(!dependency("dpkg-example", None).is_ignored());
+    }
+
+    #[test]
+    fn compares_debian_versions() {
+        let cases = [
+            ("1.0", "1.0", Ordering::Equal),
+            ("1.0", "1.00", Ordering::Equal),
+            ("1.10", "1.9", Ordering::Greater),
+            ("1.0~beta1", "1.0", Ordering::Less),
+            ("1.0", "1.0a", Ordering::Less),
+            ("1.0-1", "1.0-2", Ordering::Less),
+            ("1:0.1", "9.9", Ordering::Greater),
+            ("0.9.5000", "0.9.5001", Ordering::Less),
+            ("2.0+git", "2.0.1", Ordering::Less),
+        ];
+
+        for (a, b, expected) in cases {
+            assert_eq!(compare_versions(a, b), expected, "{a} vs {b}");
+            assert_eq!(compare_versions(b, a), expected.reverse(), "{b} vs {a}");
+        }
+    }
+
+    #[test]
+    fn checks_version_constraints() {
+        let cases = [
+            (">= 2.0", "2.0", true),
+            (">= 2.0", "1.9", false),
+            ("<< 2.0", "2.0", false),
+            ("<< 2.0", "1.9", true),
+            ("<= 2.0", "2.0", true),
+            (">> 2.0", "2.0.1", true),
+            ("= 1.5", "1.5", true),
+            ("= 1.5", "1.6", false),
+            (">=2.0", "2.1", true),
+            ("> 2.0", "2.0", true),
+        ];
+
+        for (constraint, version, expected) in cases {
+            assert_eq!(
+                dependency("foo", Some(constraint)).accepts(Some(version)),
+                expected,
+                "{version} {constraint}"
+            );
+        }
+
+        assert!(dependency("foo", Some(">= 2.0")).accepts(None));
+        assert!(dependency("foo", None).accepts(Some("0.1")));
+    }
+
+    #[test]
+    fn satisfies_through_provides() {
+        let manifest = TweakManifest::parse(CONTROL).unwrap();
+
+        assert!(manifest.satisfies(&dependency("com.example.tweak", Some(">= 1.0"))));
+        assert!(!manifest.satisfies(&dependency("com.example.tweak", Some(">= 2.0"))));
+        assert!(manifest.satisfies(&dependency("example-api", Some(">= 1.0"))));
+        assert!(!manifest.satisfies(&dependency("example-api", Some(">> 1.5"))));
+        assert!(!manifest.satisfies(&dependency("something-else", None)));
+    }
+}
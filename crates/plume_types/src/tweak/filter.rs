@@ -0,0 +1,259 @@
+use std::path::{Path, PathBuf};
+
+use plist::Value;
+
+/// MobileSubstrate filter plist (`<name>.plist` next to `<name>.dylib`).
+#[derive(Debug, Clone, Default)]
+pub struct TweakFilter {
+    pub bundles: Vec<String>,
+    pub executables: Vec<String>,
+    pub classes: Vec<String>,
+    /// `Mode = Any`, only one of the criteria needs to match instead of all of them.
+    pub match_any: bool,
+}
+
+/// Executable a tweak may be injected into, the main app or one of its extensions.
+#[derive(Debug, Clone)]
+pub struct TweakTarget {
+    pub bundle_id: Option<String>,
+    pub executable_name: String,
+    pub executable_path: PathBuf,
+    pub is_main: bool,
+    pub(crate) load_paths: Vec<String>,
+}
+
+impl TweakFilter {
+    /// Loads the filter for a dylib, `None` if there isn't one or it has no `Filter` key.
+    pub fn for_dylib(dylib_path: &Path) -> Option<Self> {
+        let plist_path = dylib_path.with_extension("plist");
+        if !plist_path.exists() {
+            return None;
+        }
+
+        let plist = Value::from_file(&plist_path).ok()?;
+        let filter = plist.as_dictionary()?.get("Filter")?.as_dictionary()?;
+
+        let strings = |key: &str| -> Vec<String> {
+            filter
+                .get(key)
+                .and_then(Value::as_array)
+                .map(|arr| {
+                    arr.iter()
+                        .filter_map(Value::as_string)
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+
+        Some(Self {
+            bundles: strings("Bundles"),
+            executables: strings("Executables"),
+            classes: strings("Classes"),
+            match_any: filter
+                .get("Mode")
+                .and_then(Value::as_string)
+                .is_some_and(|m| m.eq_ignore_ascii_case("any")),
+        })
+    }
+
+    pub fn matches(&self, target: &TweakTarget) -> bool {
+        let mut results = Vec::with_capacity(3);
+
+        if !self.bundles.is_empty() {
+            results.push(self.bundles.iter().any(|b| Self::bundle_matches(b, target)));
+        }
+
+        if !self.executables.is_empty() {
+            results.push(
+                self.executables
+                    .iter()
+                    .any(|e| e == &target.executable_name),
+            );
+        }
+
+        // Classes can only be checked at runtime, we assume they're present in
+        // the main app since that's what tweaks filtering on classes usually target
+        if !self.classes.is_empty() {
+            results.push(target.is_main);
+        }
+
+        if results.is_empty() {
+            return target.is_main;
+        }
+
+        if self.match_any {
+            results.iter().any(|r| *r)
+        } else {
+            results.iter().all(|r| *r)
+        }
+    }
+
+    fn bundle_matches(filter_bundle: &str, target: &TweakTarget) -> bool {
+        if target
+            .bundle_id
+            .as_deref()
+            .is_some_and(|id| id.eq_ignore_ascii_case(filter_bundle))
+        {
+            return true;
+        }
+
+        // System framework identifiers (com.apple.UIKit) match anything linking that framework
+        if let Some(framework) = filter_bundle.strip_prefix("com.apple.") {
+            let needle = format!("/{}.framework/", framework);
+            return target.load_paths.iter().any(|p| p.contains(&needle));
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UIKIT: &str = "/System/Library/Frameworks/UIKit.framework/UIKit";
+
+    fn target(
+        bundle_id: &str,
+        executable: &str,
+        is_main: bool,
+        load_paths: &[&str],
+    ) -> TweakTarget {
+        TweakTarget {
+            bundle_id: Some(bundle_id.to_string()),
+            executable_name: executable.to_string(),
+            executable_path: PathBuf::from(format!("/Payload/{executable}")),
+            is_main,
+            load_paths: load_paths.iter().map(|p| p.to_string()).collect(),
+        }
+    }
+
+    fn main_app() -> TweakTarget {
+        target("com.example.app", "Example", true, &[UIKIT])
+    }
+
+    fn extension() -> TweakTarget {
+        target("com.example.app.widget", "Widget", false, &[])
+    }
+
+    fn filter(bundles: &[&str], executables: &[&str], classes: &[&str]) -> TweakFilter {
+        let strings = |v: &[&str]| v.iter().map(|s| s.to_string()).collect();
+        TweakFilter {
+            bundles: strings(bundles),
+            executables: strings(executables),
+            classes: strings(classes),
+            match_any: false,
+        }
+    }
+
+    #[test]
+    fn matches_bundles() {
+        let app = filter(&["COM.example.APP"], &[], &[]);
+        assert!(app.matches(&main_app()));
+        assert!(!app.matches(&extension()));
+
+        let widget = filter(&["com.example.app.widget"], &[], &[]);
+        assert!(!widget.matches(&main_app()));
+        assert!(widget.matches(&extension()));
+    }
+
+    #[test]
+    fn matches_executables() {
+        let widget = filter(&[], &["Widget"], &[]);
+
+        assert!(!widget.matches(&main_app()));
+        assert!(widget.matches(&extension()));
+    }
+
+    #[test]
+    fn matches_all_criteria_unless_mode_is_any() {
+        let mut both = filter(&["com.example.app"], &["Widget"], &[]);
+        assert!(!both.matches(&main_app()));
+        assert!(!both.matches(&extension()));
+
+        both.match_any = true;
+        assert!(both.matches(&main_app()));
+        assert!(both.matches(&extension()));
+    }
+
+    #[test]
+    fn assumes_classes_are_in_the_main_app() {
+        let classes = filter(&[], &[], &["UIViewController"]);
+
+        assert!(classes.matches(&main_app()));
+        assert!(!classes.matches(&extension()));
+    }
+
+    #[test]
+    fn matches_system_frameworks_by_linked_framework() {
+        let uikit = filter(&["com.apple.UIKit"], &[], &[]);
+
+        assert!(uikit.matches(&main_app()));
+        assert!(!uikit.matches(&extension()));
+        // only the framework itself, not one whose name starts the same
+        assert!(!uikit.matches(&target(
+            "com.example.app.kit",
+            "Kit",
+            false,
+            &["/System/Library/Frameworks/UIKitCore.framework/UIKitCore"],
+        )));
+    }
+
+    #[test]
+    fn empty_filters_match_the_main_app() {
+        let empty = TweakFilter::default();
+
+        assert!(empty.matches(&main_app()));
+        assert!(!empty.matches(&extension()));
+    }
+
+    #[test]
+    fn reads_filters_next_to_dylibs() {
+        let dir = std::env::temp_dir().join(format!("plume_filter_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // no plist
+        assert!(TweakFilter::for_dylib(&dir.join("Missing.dylib")).is_none());
+
+        // a plist without a filter
+        let mut plist = plist::Dictionary::new();
+        plist.insert("Other".into(), Value::Boolean(true));
+        Value::Dictionary(plist)
+            .to_file_xml(dir.join("NoFilter.plist"))
+            .unwrap();
+        assert!(TweakFilter::for_dylib(&dir.join("NoFilter.dylib")).is_none());
+
+        let mut filter = plist::Dictionary::new();
+        filter.insert(
+            "Bundles".into(),
+            Value::Array(vec!["com.apple.UIKit".into(), Value::Integer(1.into())]),
+        );
+        filter.insert("Executables".into(), Value::Array(vec!["Widget".into()]));
+        filter.insert("Mode".into(), "ANY".into());
+        let mut plist = plist::Dictionary::new();
+        plist.insert("Filter".into(), Value::Dictionary(filter));
+        Value::Dictionary(plist)
+            .to_file_xml(dir.join("Tweak.plist"))
+            .unwrap();
+
+        let filter = TweakFilter::for_dylib(&dir.join("Tweak.dylib")).unwrap();
+        assert_eq!(filter.bundles, vec!["com.apple.UIKit"]);
+        assert_eq!(filter.executables, vec!["Widget"]);
+        assert!(filter.classes.is_empty());
+        assert!(filter.match_any);
+
+        // an empty filter is kept and only matches the main app
+        let mut plist = plist::Dictionary::new();
+        plist.insert("Filter".into(), Value::Dictionary(plist::Dictionary::new()));
+        Value::Dictionary(plist)
+            .to_file_xml(dir.join("Empty.plist"))
+            .unwrap();
+
+        let empty = TweakFilter::for_dylib(&dir.join("Empty.dylib")).unwrap();
+        assert!(empty.matches(&main_app()));
+        assert!(!empty.matches(&extension()));
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
@@ -0,0 +1,77 @@
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+/// Where the record of injected tweaks lives, relative to the app bundle.
+pub const TWEAK_MANIFEST_PATH: &str = "Plume/tweaks.json";
+
+/// Everything Impactor injected into an app, so it can be undone later.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TweakInstallManifest {
+    pub tweaks: Vec<InstalledTweak>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstalledTweak {
+    /// Package id for `.deb` tweaks, file name for everything else.
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub version: Option<String>,
+    /// Files and directories copied into the app, relative to the bundle.
+    pub files: Vec<PathBuf>,
+    /// Load commands added to existing binaries.
+    pub load_commands: Vec<InstalledLoadCommand>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstalledLoadCommand {
+    /// Binary the command was added to, relative to the bundle.
+    pub binary: PathBuf,
+    pub path: String,
+}
+
+impl TweakInstallManifest {
+    /// Loads the manifest from an app bundle, empty if nothing was injected yet.
+    pub fn load(app_dir: &Path) -> Result<Self, Error> {
+        let path = app_dir.join(TWEAK_MANIFEST_PATH);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+    }
+
+    pub fn from_slice(data: &[u8]) -> Result<Self, Error> {
+        Ok(serde_json::from_slice(data)?)
+    }
+
+    pub fn save(&self, app_dir: &Path) -> Result<(), Error> {
+        let path = app_dir.join(TWEAK_MANIFEST_PATH);
+
+        // leave no trace once everything has been removed again
+        if self.tweaks.is_empty() {
+            if path.exists() {
+                std::fs::remove_file(&path)?;
+            }
+            if let Some(dir) = path.parent() {
+                std::fs::remove_dir(dir).ok();
+            }
+            return Ok(());
+        }
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+
+        Ok(())
+    }
+
+    pub fn get(&self, id: &str) -> Option<&InstalledTweak> {
+        self.tweaks.iter().find(|t| t.id == id)
+    }
+}
@@ -0,0 +1,1070 @@
+mod control;
+mod filter;
+mod installed;
+mod paths;
+
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use plume_core::{MachO, MachOExt};
+use uuid::Uuid;
+
+use crate::{
+    Bundle, BundleType, Error, PlistInfoTrait, SignerHookingRuntime, copy_dir_recursively,
+};
+
+pub use control::{TweakDependency, TweakManifest};
+pub use filter::{TweakFilter, TweakTarget};
+pub use installed::{
+    InstalledLoadCommand, InstalledTweak, TWEAK_MANIFEST_PATH, TweakInstallManifest,
+};
+
+const ELLEKIT_BYTES: &[u8] = include_bytes!("./ellekit.deb");
+
+pub struct Tweak {
+    path: PathBuf,
+    app_bundle: PathBuf,
+    stage_dir: PathBuf,
+    manifest: Option<TweakManifest>,
+    installed: Mutex<InstallState>,
+}
+
+/// What a tweak put into the app while being applied.
+#[derive(Default)]
+struct InstallState {
+    files: Vec<PathBuf>,
+    /// Mach-O binaries copied into the app, their load paths get rewritten once installed.
+    binaries: Vec<PathBuf>,
+    load_commands: Vec<(PathBuf, String)>,
+}
+
+/// Load path rewrites done while applying a tweak.
+#[derive(Debug, Clone, Default)]
+pub struct TweakReport {
+    /// Jailbreak paths that now point at the bundled copy, with their new path.
+    pub rewritten: Vec<(TweakLoadPath, String)>,
+    /// Paths that won't resolve inside the app, the tweak will most likely fail to load.
+    pub unresolved: Vec<TweakLoadPath>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TweakLoadPath {
+    pub binary: PathBuf,
+    pub load_path: String,
+}
+
+impl std::fmt::Display for TweakLoadPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let binary = self
+            .binary
+            .file_name()
+            .map(|n| n.to_string_lossy())
+            .unwrap_or_default();
+
+        write!(f, "{} -> {}", binary, self.load_path)
+    }
+}
+
+/// Tweaks sorted in the order they should be installed, along with
+/// anything we couldn't satisfy from the selected tweaks.
+pub struct TweakResolution {
+    pub ordered: Vec<Tweak>,
+    pub missing: Vec<TweakMissingDependency>,
+    pub conflicts: Vec<TweakConflict>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TweakMissingDependency {
+    /// Package that declared the dependency.
+    pub package: String,
+    /// Alternatives, any of these would have satisfied it.
+    pub dependency: Vec<TweakDependency>,
+}
+
+impl TweakMissingDependency {
+    /// Short explanation of what breaks without the dependency, for the well known ones.
+    pub fn hint(&self) -> Option<&'static str> {
+        self.dependency
+            .iter()
+            .find_map(|d| match d.package.as_str() {
+                "preferenceloader" => Some("its settings pane will not show up in the app"),
+                "com.opa334.altlist" | "altlist" => Some("add the AltList .deb alongside it"),
+                "ws.hbang.common" | "cephei" => Some("add the Cephei .deb alongside it"),
+                "mobilesubstrate" | "ellekit" => Some("no hooking runtime will be injected"),
+                _ => None,
+            })
+    }
+}
+
+impl std::fmt::Display for TweakMissingDependency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let alternatives = self
+            .dependency
+            .iter()
+            .map(|d| d.to_string())
+            .collect::<Vec<_>>()
+            .join(" | ");
+
+        write!(f, "{} depends on {}", self.package, alternatives)?;
+        if let Some(hint) = self.hint() {
+            write!(f, " ({})", hint)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TweakConflict {
+    pub package: String,
+    pub conflicts_with: String,
+}
+
+impl std::fmt::Display for TweakConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} conflicts with {}", self.package, self.conflicts_with)
+    }
+}
+
+/// Problems with the selected tweaks that didn't stop signing, for showing to the user.
+#[derive(Debug, Clone, Default)]
+pub struct TweakWarnings {
+    pub missing: Vec<TweakMissingDependency>,
+    /// Only reported when [`SignerOptions::allow_tweak_conflicts`](crate::SignerOptions) is set,
+    /// otherwise conflicts fail signing.
+    pub conflicts: Vec<TweakConflict>,
+    pub unresolved: Vec<TweakLoadPath>,
+}
+
+impl TweakWarnings {
+    pub fn is_empty(&self) -> bool {
+        self.missing.is_empty() && self.conflicts.is_empty() && self.unresolved.is_empty()
+    }
+
+    /// One line per problem.
+    pub fn messages(&self) -> Vec<String> {
+        self.missing
+            .iter()
+            .map(|m| format!("Missing dependency: {}", m))
+            .chain(self.conflicts.iter().map(|c| format!("Conflict: {}", c)))
+            .chain(
+                self.unresolved
+                    .iter()
+                    .map(|p| format!("Unresolved load path: {}", p)),
+            )
+            .collect()
+    }
+}
+
+impl Tweak {
+    /// Injects the selected hooking runtime, unless the app already bundles one of its own.
+    /// A runtime we injected on a previous signing is replaced.
+    pub async fn install_hooking_runtime(
+        app_bundle: &Bundle,
+        runtime: &SignerHookingRuntime,
+    ) -> Result<(), Error> {
+        if *runtime == SignerHookingRuntime::None {
+            return Ok(());
+        }
+
+        let frameworks_dir = Path::new("Frameworks");
+        if let Some(existing) =
+            paths::bundled_hooking_runtime(&app_bundle.bundle_dir().join(frameworks_dir))
+        {
+            let existing = frameworks_dir.join(existing);
+            let manifest = TweakInstallManifest::load(app_bundle.bundle_dir())?;
+
+            let previous = manifest
+                .tweaks
+                .iter()
+                .find(|t| t.files.iter().any(|f| existing.starts_with(f)))
+                .map(|t| t.id.clone());
+
+            match previous {
+                Some(id) => Self::uninstall(app_bundle, &id).await?,
+                None => {
+                    log::info!(
+                        "App already bundles {}, not injecting another hooking runtime",
+                        existing.display()
+                    );
+                    return Ok(());
+                }
+            }
+        }
+
+        match runtime {
+            SignerHookingRuntime::ElleKit => Self::install_ellekit(app_bundle).await,
+            SignerHookingRuntime::Custom(path) => {
+                let tweak = Tweak::new(path, app_bundle).await?;
+                tweak.apply().await?;
+                Ok(())
+            }
+            SignerHookingRuntime::None => Ok(()),
+        }
+    }
+
+    pub async fn install_ellekit(app_bundle: &Bundle) -> Result<(), Error> {
+        let stage_dir = env::temp_dir().join(format!("plume_ellekit_{}", Uuid::new_v4()));
+        tokio::fs::create_dir_all(&stage_dir).await?;
+
+        let deb_path = stage_dir.join("ellekit.deb");
+        tokio::fs::write(&deb_path, ELLEKIT_BYTES).await?;
+
+        let tweak = Tweak::new(&deb_path, app_bundle).await?;
+        tweak.install_deb().await?;
+        tweak.record_install(Some("ElleKit"))?;
+
+        tokio::fs::remove_dir_all(&stage_dir).await.ok();
+
+        Ok(())
+    }
+
+    pub async fn new<P: AsRef<Path>>(tweak_path: P, app_bundle: &Bundle) -> Result<Self, Error> {
+        let path = tweak_path.as_ref();
+        if !path.exists() {
+            return Err(Error::TweakInvalidPath);
+        }
+
+        let file_name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .ok_or(Error::TweakInvalidPath)?;
+
+        if !file_name.ends_with(".deb")
+            && !file_name.ends_with(".dylib")
+            && !file_name.ends_with(".framework")
+            && !file_name.ends_with(".bundle")
+            && !file_name.ends_with(".appex")
+        {
+            return Err(Error::UnsupportedFileType(file_name.to_string()));
+        }
+
+        let stage_dir = env::temp_dir().join(format!("plume_tweak_{}", Uuid::new_v4()));
+        tokio::fs::create_dir_all(&stage_dir).await?;
+
+        let mut tweak = Self {
+            path: path.to_path_buf(),
+            app_bundle: app_bundle.bundle_dir().clone(),
+            stage_dir,
+            manifest: None,
+            installed: Mutex::new(InstallState::default()),
+        };
+
+        if file_name.ends_with(".deb") {
+            tweak.extract_ar().await?;
+            tweak.manifest = tweak.read_control().await?;
+        }
+
+        Ok(tweak)
+    }
+
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+
+    pub fn manifest(&self) -> Option<&TweakManifest> {
+        self.manifest.as_ref()
+    }
+
+    /// Id the tweak is recorded under in the bundle's tweak manifest.
+    pub fn id(&self) -> String {
+        match &self.manifest {
+            Some(manifest) => manifest.package.clone(),
+            None => self
+                .path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Tweaks previously injected into an app bundle.
+    pub fn installed(app_bundle: &Bundle) -> Result<Vec<InstalledTweak>, Error> {
+        Ok(TweakInstallManifest::load(app_bundle.bundle_dir())?.tweaks)
+    }
+
+    /// Removes a previously injected tweak, its files and the load commands it added.
+    /// Anything another installed tweak also relies on is left in place.
+    pub async fn uninstall(app_bundle: &Bundle, id: &str) -> Result<(), Error> {
+        let app_dir = app_bundle.bundle_dir();
+        let mut manifest = TweakInstallManifest::load(app_dir)?;
+
+        let index = manifest
+            .tweaks
+            .iter()
+            .position(|t| t.id == id)
+            .ok_or_else(|| Error::TweakNotInstalled(id.to_string()))?;
+        let tweak = manifest.tweaks.remove(index);
+
+        for load_command in &tweak.load_commands {
+            if manifest
+                .tweaks
+                .iter()
+                .any(|t| t.load_commands.contains(load_command))
+            {
+                continue;
+            }
+
+            let binary = app_dir.join(&load_command.binary);
+            if binary.exists() {
+                let mut macho = MachO::new(&binary)?;
+                macho.remove_dylib(&load_command.path)?;
+            }
+        }
+
+        for file in &tweak.files {
+            if manifest.tweaks.iter().any(|t| t.files.contains(file)) {
+                continue;
+            }
+
+            let path = app_dir.join(file);
+            if path.is_dir() {
+                tokio::fs::remove_dir_all(&path).await?;
+            } else if path.exists() {
+                tokio::fs::remove_file(&path).await?;
+            }
+        }
+
+        manifest.save(app_dir)
+    }
+
+    /// Sorts tweaks so dependencies are installed before their dependents, and reports
+    /// declared dependencies that aren't among the selected tweaks or the hooking runtime.
+    pub fn resolve_dependencies(tweaks: Vec<Tweak>, has_hooking_runtime: bool) -> TweakResolution {
+        let manifests = tweaks
+            .iter()
+            .map(|t| t.manifest.as_ref())
+            .collect::<Vec<_>>();
+        let (order, missing, conflicts) = resolve_order(&manifests, has_hooking_runtime);
+
+        let mut slots: Vec<Option<Tweak>> = tweaks.into_iter().map(Some).collect();
+        let ordered = order.into_iter().filter_map(|i| slots[i].take()).collect();
+
+        TweakResolution {
+            ordered,
+            missing,
+            conflicts,
+        }
+    }
+
+    pub async fn apply(&self) -> Result<TweakReport, Error> {
+        let file_name = self
+            .path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .ok_or(Error::TweakInvalidPath)?;
+
+        if file_name.ends_with(".deb") {
+            self.install_deb().await?;
+        } else if file_name.ends_with(".framework") {
+            self.install_framework(&self.path).await?;
+        } else if file_name.ends_with(".bundle") {
+            self.install_bundle(&self.path).await?;
+        } else if file_name.ends_with(".appex") {
+            self.install_appex(&self.path).await?;
+        } else if file_name.ends_with(".dylib") {
+            self.install_dylib(&self.path).await?;
+        }
+
+        tokio::fs::remove_dir_all(&self.stage_dir).await.ok();
+
+        let report = self.rewrite_load_paths()?;
+        self.record_install(None)?;
+
+        Ok(report)
+    }
+
+    async fn extract_ar(&self) -> Result<(), Error> {
+        let ar_extract_dir = self.stage_dir.join("ar_contents");
+        tokio::fs::create_dir_all(&ar_extract_dir).await?;
+
+        extract_archive(self.path.clone(), ar_extract_dir)
+            .await
+            .map_err(|_| Error::TweakExtractionFailed("Failed to extract .ar archive".to_string()))
+    }
+
+    async fn read_control(&self) -> Result<Option<TweakManifest>, Error> {
+        let ar_extract_dir = self.stage_dir.join("ar_contents");
+        let control_dir = self.stage_dir.join("control_contents");
+        tokio::fs::create_dir_all(&control_dir).await?;
+
+        for archive_name in [
+            "control.tar.gz",
+            "control.tar.xz",
+            "control.tar.lzma",
+            "control.tar.zst",
+            "control.tar",
+        ] {
+            let control_path = ar_extract_dir.join(archive_name);
+            if control_path.exists() {
+                extract_archive(control_path, control_dir.clone())
+                    .await
+                    .map_err(|e| {
+                        Error::TweakExtractionFailed(format!(
+                            "Failed to extract control.tar: {}",
+                            e
+                        ))
+                    })?;
+
+                let control_file = control_dir.join("control");
+                if !control_file.exists() {
+                    return Ok(None);
+                }
+
+                let contents = tokio::fs::read_to_string(&control_file).await?;
+                return Ok(TweakManifest::parse(&contents));
+            }
+        }
+
+        Ok(None)
+    }
+
+    async fn install_deb(&self) -> Result<(), Error> {
+        let extract_dir = self.stage_dir.join("deb_contents");
+        tokio::fs::create_dir_all(&extract_dir).await?;
+
+        let ar_extract_dir = self.stage_dir.join("ar_contents");
+
+        for archive_name in [
+            "data.tar.lzma",
+            "data.tar.gz",
+            "data.tar.xz",
+            "data.tar.bz2",
+            "data.tar",
+        ] {
+            let data_path = ar_extract_dir.join(archive_name);
+            if data_path.exists() {
+                extract_archive(data_path, extract_dir.clone())
+                    .await
+                    .map_err(|e| {
+                        Error::TweakExtractionFailed(format!("Failed to extract data.tar: {}", e))
+                    })?;
+
+                break;
+            }
+        }
+
+        self.scan_and_install(&extract_dir).await
+    }
+
+    async fn scan_and_install(&self, root: &Path) -> Result<(), Error> {
+        let search_paths = [
+            "Library/MobileSubstrate/DynamicLibraries",
+            "usr/lib",
+            "Library/Frameworks",
+            "Library/Application Support",
+            "var/jb/Library/MobileSubstrate/DynamicLibraries",
+            "var/jb/usr/lib",
+            "var/jb/Library/Frameworks",
+            "var/jb/Library/Application Support",
+        ];
+
+        for search_path in search_paths {
+            let dir = root.join(search_path);
+            if dir.exists() {
+                self.scan_directory(&dir).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn scan_directory(&self, dir: &Path) -> Result<(), Error> {
+        use futures::future::BoxFuture;
+
+        fn scan_recursive<'a>(tweak: &'a Tweak, dir: &'a Path) -> BoxFuture<'a, Result<(), Error>> {
+            Box::pin(async move {
+                let mut entries = tokio::fs::read_dir(dir).await?;
+
+                while let Some(entry) = entries.next_entry().await? {
+                    let path = entry.path();
+                    let metadata = tokio::fs::symlink_metadata(&path).await?;
+
+                    if metadata.is_symlink() {
+                        continue;
+                    }
+
+                    if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
+                        if path.is_file() && name.ends_with(".dylib") {
+                            tweak.install_dylib(&path).await?;
+                        } else if path.is_dir() {
+                            if name.ends_with(".framework") {
+                                tweak.install_framework(&path).await?;
+                            } else if name.ends_with(".bundle") {
+                                tweak.install_bundle(&path).await?;
+                            } else if name.ends_with(".appex") {
+                                tweak.install_appex(&path).await?;
+                            } else {
+                                // Recursively scan subdirectories
+                                scan_recursive(tweak, &path).await?;
+                            }
+                        }
+                    }
+                }
+
+                Ok(())
+            })
+        }
+
+        scan_recursive(self, dir).await
+    }
+
+    async fn install_dylib(&self, dylib_path: &Path) -> Result<(), Error> {
+        let targets = match TweakFilter::for_dylib(dylib_path) {
+            Some(filter) => {
+                let targets = self
+                    .injection_targets()?
+                    .into_iter()
+                    .filter(|t| filter.matches(t))
+                    .collect::<Vec<_>>();
+
+                if targets.is_empty() {
+                    log::info!(
+                        "Skipping {}, its filter does not match this app",
+                        dylib_path.display()
+                    );
+                    return Ok(());
+                }
+
+                targets
+            }
+            None => vec![self.main_target()?],
+        };
+
+        let frameworks_dir = self.app_bundle.join("Frameworks");
+        tokio::fs::create_dir_all(&frameworks_dir).await?;
+
+        let dylib_name = dylib_path.file_name().ok_or(Error::TweakInvalidPath)?;
+        let dest = frameworks_dir.join(dylib_name);
+
+        tokio::fs::copy(dylib_path, &dest).await?;
+
+        self.track_file(&dest);
+        self.track_binary(&dest);
+        self.inject_dylib(&dest, false, &targets).await
+    }
+
+    async fn install_framework(&self, framework_path: &Path) -> Result<(), Error> {
+        let frameworks_dir = self.app_bundle.join("Frameworks");
+        tokio::fs::create_dir_all(&frameworks_dir).await?;
+
+        let framework_name = framework_path.file_name().ok_or(Error::TweakInvalidPath)?;
+        let dest = frameworks_dir.join(framework_name);
+
+        copy_dir_recursively(&framework_path, &dest).await?;
+        self.track_file(&dest);
+
+        if let Ok(bundle) = Bundle::new(&dest) {
+            if let Some(exec_name) = bundle.get_executable() {
+                let exec_path = dest.join(exec_name);
+                if exec_path.exists() {
+                    self.track_binary(&exec_path);
+                    self.inject_dylib(&exec_path, true, &[self.main_target()?])
+                        .await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn install_bundle(&self, bundle_path: &Path) -> Result<(), Error> {
+        let bundle_name = bundle_path.file_name().ok_or(Error::TweakInvalidPath)?;
+        let dest = self.app_bundle.join(bundle_name);
+
+        copy_dir_recursively(bundle_path, &dest).await?;
+        self.track_file(&dest);
+        self.track_bundle_executable(&dest);
+
+        Ok(())
+    }
+
+    async fn install_appex(&self, appex_path: &Path) -> Result<(), Error> {
+        let plugins_dir = self.app_bundle.join("PlugIns");
+        tokio::fs::create_dir_all(&plugins_dir).await?;
+
+        let appex_name = appex_path.file_name().ok_or(Error::TweakInvalidPath)?;
+        let dest = plugins_dir.join(appex_name);
+
+        copy_dir_recursively(appex_path, &dest).await?;
+        self.track_file(&dest);
+        self.track_bundle_executable(&dest);
+
+        Ok(())
+    }
+
+    /// The main executable, plus every app extension's executable.
+    fn injection_targets(&self) -> Result<Vec<TweakTarget>, Error> {
+        let bundle = Bundle::new(&self.app_bundle)?;
+        let mut targets = vec![Self::target_for_bundle(&bundle, true)?];
+
+        for nested in bundle.collect_nested_bundles()? {
+            if *nested.bundle_type() != BundleType::AppExtension {
+                continue;
+            }
+
+            match Self::target_for_bundle(&nested, false) {
+                Ok(target) => targets.push(target),
+                Err(e) => log::warn!(
+                    "Skipping extension {} for tweak injection: {}",
+                    nested.bundle_dir().display(),
+                    e
+                ),
+            }
+        }
+
+        Ok(targets)
+    }
+
+    fn main_target(&self) -> Result<TweakTarget, Error> {
+        Self::target_for_bundle(&Bundle::new(&self.app_bundle)?, true)
+    }
+
+    fn target_for_bundle(bundle: &Bundle, is_main: bool) -> Result<TweakTarget, Error> {
+        let executable_name = bundle
+            .get_executable()
+            .ok_or(Error::BundleInfoPlistMissing)?;
+
+        let executable_path = bundle.bundle_dir().join(&executable_name);
+        if !executable_path.exists() {
+            return Err(Error::BundleInfoPlistMissing);
+        }
+
+        // only needed for matching framework bundle ids, so failures aren't fatal
+        let load_paths = MachO::new(&executable_path)
+            .ok()
+            .and_then(|m| m.macho_file().nth_macho(0).ok()?.dylib_load_paths().ok())
+            .unwrap_or_default();
+
+        Ok(TweakTarget {
+            bundle_id: bundle.get_bundle_identifier(),
+            executable_name,
+            executable_path,
+            is_main,
+            load_paths,
+        })
+    }
+
+    async fn inject_dylib(
+        &self,
+        dylib_path: &Path,
+        is_framework: bool,
+        targets: &[TweakTarget],
+    ) -> Result<(), Error> {
+        let inject_path = if is_framework {
+            let components: Vec<_> = dylib_path.components().rev().take(2).collect();
+            format!(
+                "@rpath/{}/{}",
+                components[1]
+                    .as_os_str()
+                    .to_str()
+                    .ok_or(Error::TweakInvalidPath)?,
+                components[0]
+                    .as_os_str()
+                    .to_str()
+                    .ok_or(Error::TweakInvalidPath)?
+            )
+        } else {
+            let file_name = dylib_path
+                .file_name()
+                .and_then(|f| f.to_str())
+                .ok_or(Error::TweakInvalidPath)?;
+            format!("@rpath/{}", file_name)
+        };
+
+        // load commands a previous install of this tweak added, reinstalling keeps them ours
+        let previous = TweakInstallManifest::load(&self.app_bundle)?
+            .get(&self.id())
+            .map(|t| t.load_commands.clone())
+            .unwrap_or_default();
+
+        for target in targets {
+            let mut macho = MachO::new(&target.executable_path)?;
+
+            // don't claim load commands the app already had, uninstalling would break it
+            let existing = macho.macho_file().nth_macho(0)?.dylib_load_paths()?;
+            if existing.contains(&inject_path) {
+                let ours = InstalledLoadCommand {
+                    binary: self.relative_path(&target.executable_path),
+                    path: inject_path.clone(),
+                };
+                if !previous.contains(&ours) {
+                    continue;
+                }
+            } else {
+                macho.add_dylib(&inject_path)?;
+            }
+
+            if let Ok(mut installed) = self.installed.lock() {
+                installed
+                    .load_commands
+                    .push((target.executable_path.clone(), inject_path.clone()));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn relative_path(&self, path: &Path) -> PathBuf {
+        path.strip_prefix(&self.app_bundle)
+            .map(Path::to_path_buf)
+            .unwrap_or_else(|_| path.to_path_buf())
+    }
+
+    fn track_file(&self, path: &Path) {
+        if let Ok(mut installed) = self.installed.lock() {
+            installed.files.push(path.to_path_buf());
+        }
+    }
+
+    fn track_binary(&self, binary_path: &Path) {
+        if let Ok(mut installed) = self.installed.lock() {
+            installed.binaries.push(binary_path.to_path_buf());
+        }
+    }
+
+    // Preference bundles and extensions carry their own executable
+    fn track_bundle_executable(&self, bundle_dir: &Path) {
+        if let Some(exec_name) = Bundle::new(bundle_dir)
+            .ok()
+            .and_then(|b| b.get_executable())
+        {
+            let exec_path = bundle_dir.join(exec_name);
+            if exec_path.exists() {
+                self.track_binary(&exec_path);
+            }
+        }
+    }
+
+    /// Points jailbreak load paths in every installed binary at their bundled
+    /// location, and reports the ones that still won't resolve.
+    fn rewrite_load_paths(&self) -> Result<TweakReport, Error> {
+        let frameworks_dir = self.app_bundle.join("Frameworks");
+        let binaries = self
+            .installed
+            .lock()
+            .map(|i| i.binaries.clone())
+            .unwrap_or_default();
+
+        let mut report = TweakReport::default();
+
+        for binary in binaries {
+            let Ok(mut macho) = MachO::new(&binary) else {
+                log::warn!(
+                    "Failed to parse {}, skipping path rewrite",
+                    binary.display()
+                );
+                continue;
+            };
+
+            let load_paths = macho.macho_file().nth_macho(0)?.dylib_load_paths()?;
+
+            for load_path in load_paths {
+                let current = match paths::rewrite_target(&load_path, &frameworks_dir)? {
+                    Some(target) => {
+                        macho.replace_dylib(&load_path, &target)?;
+                        report.rewritten.push((
+                            TweakLoadPath {
+                                binary: binary.clone(),
+                                load_path,
+                            },
+                            target.clone(),
+                        ));
+                        target
+                    }
+                    None => load_path,
+                };
+
+                if paths::is_unresolved(&current, &frameworks_dir) {
+                    report.unresolved.push(TweakLoadPath {
+                        binary: binary.clone(),
+                        load_path: current,
+                    });
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Adds what this tweak installed to the bundle's tweak manifest, replacing any
+    /// previous record of the same tweak.
+    fn record_install(&self, name: Option<&str>) -> Result<(), Error> {
+        let (files, load_commands) = match self.installed.lock() {
+            Ok(installed) => (
+                installed
+                    .files
+                    .iter()
+                    .map(|f| self.relative_path(f))
+                    .collect(),
+                installed
+                    .load_commands
+                    .iter()
+                    .map(|(binary, path)| InstalledLoadCommand {
+                        binary: self.relative_path(binary),
+                        path: path.clone(),
+                    })
+                    .collect(),
+            ),
+            Err(_) => (Vec::new(), Vec::new()),
+        };
+
+        let id = self.id();
+        let name = name
+            .map(str::to_string)
+            .or_else(|| self.manifest.as_ref().map(|m| m.display_name().to_string()))
+            .unwrap_or_else(|| id.clone());
+
+        let mut manifest = TweakInstallManifest::load(&self.app_bundle)?;
+        manifest.tweaks.retain(|t| t.id != id);
+        manifest.tweaks.push(InstalledTweak {
+            id,
+            name,
+            version: self.manifest.as_ref().and_then(|m| m.version.clone()),
+            files,
+            load_commands,
+        });
+
+        manifest.save(&self.app_bundle)
+    }
+}
+
+async fn extract_archive(archive_path: PathBuf, extract_dir: PathBuf) -> Result<(), String> {
+    use decompress::ExtractOpts;
+
+    tokio::task::spawn_blocking(move || {
+        decompress::decompress(
+            &archive_path,
+            &extract_dir,
+            &ExtractOpts {
+                strip: 0,
+                detect_content: false,
+                filter: Box::new(|_: &std::path::Path| true),
+                map: Box::new(|p| std::borrow::Cow::Borrowed(p)),
+            },
+        )
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map(|_| ())
+    .map_err(|e| e.to_string())
+}
+
+// Indices in install order, stable so tweaks keep the order they were selected in
+// unless a dependency needs to go first
+fn resolve_order(
+    manifests: &[Option<&TweakManifest>],
+    has_hooking_runtime: bool,
+) -> (Vec<usize>, Vec<TweakMissingDependency>, Vec<TweakConflict>) {
+    let mut missing = Vec::new();
+    let mut conflicts = Vec::new();
+
+    let satisfied_by = |dependency: &TweakDependency| -> Option<usize> {
+        manifests
+            .iter()
+            .position(|m| m.is_some_and(|m| m.satisfies(dependency)))
+    };
+
+    // index -> indices it depends on
+    let mut edges: HashMap<usize, HashSet<usize>> = HashMap::new();
+
+    for (index, manifest) in manifests.iter().enumerate() {
+        let Some(manifest) = manifest else {
+            continue;
+        };
+
+        for group in manifest.required_dependencies() {
+            if let Some(provider) = group.iter().find_map(&satisfied_by) {
+                if provider != index {
+                    edges.entry(index).or_default().insert(provider);
+                }
+                continue;
+            }
+
+            if has_hooking_runtime && group.iter().any(|d| d.is_hooking_runtime()) {
+                continue;
+            }
+
+            missing.push(TweakMissingDependency {
+                package: manifest.package.clone(),
+                dependency: group.clone(),
+            });
+        }
+
+        for conflict in &manifest.conflicts {
+            if let Some(other) = satisfied_by(conflict).filter(|o| *o != index) {
+                if let Some(other) = manifests[other] {
+                    conflicts.push(TweakConflict {
+                        package: manifest.package.clone(),
+                        conflicts_with: other.package.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    let mut order: Vec<usize> = Vec::with_capacity(manifests.len());
+    let mut placed: HashSet<usize> = HashSet::new();
+
+    while order.len() < manifests.len() {
+        let next = (0..manifests.len()).find(|i| {
+            !placed.contains(i)
+                && edges
+                    .get(i)
+                    .is_none_or(|deps| deps.iter().all(|d| placed.contains(d)))
+        });
+
+        let next = match next {
+            Some(next) => next,
+            None => {
+                // cycle, just take whatever is left in the original order
+                let next = (0..manifests.len()).find(|i| !placed.contains(i)).unwrap();
+                log::warn!(
+                    "Dependency cycle detected while ordering tweaks, installing {} as-is",
+                    manifests[next].map_or("tweak", |m| m.package.as_str())
+                );
+                next
+            }
+        };
+
+        placed.insert(next);
+        order.push(next);
+    }
+
+    (order, missing, conflicts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(control: &str) -> TweakManifest {
+        TweakManifest::parse(control).unwrap()
+    }
+
+    fn resolve(
+        manifests: &[TweakManifest],
+        has_hooking_runtime: bool,
+    ) -> (Vec<usize>, Vec<TweakMissingDependency>, Vec<TweakConflict>) {
+        let refs = manifests.iter().map(Some).collect::<Vec<_>>();
+        resolve_order(&refs, has_hooking_runtime)
+    }
+
+    #[test]
+    fn keeps_selection_order_without_dependencies() {
+        let manifests = [
+            manifest("Package: a"),
+            manifest("Package: b"),
+            manifest("Package: c"),
+        ];
+
+        let (order, missing, conflicts) = resolve(&manifests, true);
+        assert_eq!(order, [0, 1, 2]);
+        assert!(missing.is_empty());
+        assert!(conflicts.is_empty());
+    }
+
+    #[test]
+    fn installs_dependencies_first() {
+        let manifests = [
+            manifest("Package: app-tweak\nDepends: cephei, altlist"),
+            manifest("Package: other"),
+            manifest("Package: altlist\nDepends: cephei"),
+            manifest("Package: cephei"),
+        ];
+
+        let (order, missing, _) = resolve(&manifests, true);
+        assert_eq!(order, [1, 3, 2, 0]);
+        assert!(missing.is_empty());
+    }
+
+    #[test]
+    fn depends_through_provides_and_alternatives() {
+        let manifests = [
+            manifest("Package: tweak\nDepends: missing-lib | prefs-api (>= 1.0)"),
+            manifest("Package: prefs\nVersion: 2.0\nProvides: prefs-api (= 1.2)"),
+        ];
+
+        let (order, missing, _) = resolve(&manifests, true);
+        assert_eq!(order, [1, 0]);
+        assert!(missing.is_empty());
+    }
+
+    #[test]
+    fn reports_missing_dependencies() {
+        let manifests = [manifest(
+            "Package: tweak\nDepends: mobilesubstrate, preferenceloader, firmware (>= 14.0)",
+        )];
+
+        let (_, missing, _) = resolve(&manifests, true);
+        assert_eq!(missing.len(), 1);
+        assert_eq!(missing[0].package, "tweak");
+        assert_eq!(missing[0].dependency[0].package, "preferenceloader");
+        assert!(missing[0].hint().is_some());
+
+        // without a hooking runtime substrate is missing too
+        let (_, missing, _) = resolve(&manifests, false);
+        assert_eq!(missing.len(), 2);
+    }
+
+    #[test]
+    fn version_mismatch_is_missing() {
+        let manifests = [
+            manifest("Package: tweak\nDepends: lib (>= 2.0)"),
+            manifest("Package: lib\nVersion: 1.4"),
+        ];
+
+        let (order, missing, _) = resolve(&manifests, true);
+        assert_eq!(order, [0, 1]);
+        assert_eq!(missing.len(), 1);
+        assert_eq!(missing[0].dependency[0].to_string(), "lib (>= 2.0)");
+    }
+
+    #[test]
+    fn reports_conflicts() {
+        let manifests = [
+            manifest("Package: new\nConflicts: old (<< 2.0), unrelated"),
+            manifest("Package: old\nVersion: 1.0"),
+        ];
+
+        let (_, _, conflicts) = resolve(&manifests, true);
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].to_string(), "new conflicts with old");
+
+        let manifests = [
+            manifest("Package: new\nConflicts: old (<< 2.0)"),
+            manifest("Package: old\nVersion: 2.1"),
+        ];
+        let (_, _, conflicts) = resolve(&manifests, true);
+        assert!(conflicts.is_empty());
+    }
+
+    #[test]
+    fn breaks_cycles_in_selection_order() {
+        let manifests = [
+            manifest("Package: first"),
+            manifest("Package: a\nDepends: b"),
+            manifest("Package: b\nDepends: a"),
+            manifest("Package: c\nDepends: a"),
+        ];
+
+        let (order, missing, _) = resolve(&manifests, true);
+        assert_eq!(order, [0, 1, 2, 3]);
+        assert!(missing.is_empty());
+    }
+
+    #[test]
+    fn tweaks_without_manifest_keep_their_place() {
+        let tweak = manifest("Package: tweak\nDepends: lib");
+        let lib = manifest("Package: lib");
+        let refs = [None, Some(&tweak), Some(&lib)];
+
+        let (order, missing, _) = resolve_order(&refs, true);
+        assert_eq!(order, [0, 2, 1]);
+        assert!(missing.is_empty());
+    }
+}
@@ -0,0 +1,275 @@
+use std::path::Path;
+
+use crate::Error;
+
+// Hooking libraries tweaks link against (without the rootless/roothide prefix),
+// these all resolve to whichever hooking runtime ended up in the app
+const HOOKING_LIBRARIES: &[&str] = &[
+    "/Library/Frameworks/CydiaSubstrate.framework/CydiaSubstrate",
+    "/usr/lib/libsubstrate.dylib",
+    "/usr/lib/libellekit.dylib",
+    "/usr/lib/libhooker.dylib",
+    "/usr/lib/libsubstitute.dylib",
+    "/usr/lib/libsubstitute.0.dylib",
+];
+
+// Where a hooking runtime may be bundled, relative to `Frameworks`, in order of preference
+const HOOKING_RUNTIME_BINARIES: &[&str] = &[
+    "CydiaSubstrate.framework/CydiaSubstrate",
+    "libellekit.dylib",
+    "libsubstrate.dylib",
+    "libsubstitute.dylib",
+    "libhooker.dylib",
+];
+
+// Where other known jailbreak libraries live and where they end up once bundled
+const KNOWN_PATHS: &[(&str, &str)] = &[
+    (
+        "/Library/Frameworks/Cephei.framework/Cephei",
+        "@rpath/Cephei.framework/Cephei",
+    ),
+    (
+        "/Library/Frameworks/CepheiUI.framework/CepheiUI",
+        "@rpath/CepheiUI.framework/CepheiUI",
+    ),
+    (
+        "/Library/Frameworks/CepheiPrefs.framework/CepheiPrefs",
+        "@rpath/CepheiPrefs.framework/CepheiPrefs",
+    ),
+    (
+        "/Library/Frameworks/AltList.framework/AltList",
+        "@rpath/AltList.framework/AltList",
+    ),
+];
+
+// rootless installs under /var/jb, roothide resolves relative to a hidden jbroot
+const JAILBREAK_PREFIXES: &[&str] = &[
+    "/var/jb",
+    "@loader_path/.jbroot",
+    "@executable_path/.jbroot",
+];
+
+/// Hooking runtime bundled in the app, relative to its `Frameworks` directory.
+pub(crate) fn bundled_hooking_runtime(frameworks_dir: &Path) -> Option<&'static str> {
+    HOOKING_RUNTIME_BINARIES
+        .iter()
+        .find(|binary| frameworks_dir.join(binary).exists())
+        .copied()
+}
+
+/// Where a tweak load path should point inside the app, `None` if it should be left alone.
+///
+/// Hooking libraries are only pointed at a bundled runtime that exports the same API,
+/// linking a substrate tweak against libhooker would fail to load at launch.
+pub(crate) fn rewrite_target(
+    load_path: &str,
+    frameworks_dir: &Path,
+) -> Result<Option<String>, Error> {
+    let stripped = strip_jailbreak_prefix(load_path);
+
+    if is_hooking_library(stripped) {
+        let runtime = compatible_runtimes(stripped)
+            .iter()
+            .find(|binary| frameworks_dir.join(binary).exists());
+
+        return match (runtime, bundled_hooking_runtime(frameworks_dir)) {
+            (Some(runtime), _) => {
+                let runtime = format!("@rpath/{}", runtime);
+                Ok((runtime != load_path).then_some(runtime))
+            }
+            (None, Some(bundled)) => Err(Error::TweakIncompatibleHookingRuntime(
+                stripped.to_string(),
+                bundled.to_string(),
+            )),
+            (None, None) => Ok(None),
+        };
+    }
+
+    let target = known_target(stripped).map(str::to_string).or_else(|| {
+        let relative = bundled_relative_path(stripped)?;
+        frameworks_dir
+            .join(&relative)
+            .exists()
+            .then(|| format!("@rpath/{}", relative))
+    });
+
+    Ok(target.filter(|target| target != load_path))
+}
+
+/// Whether a load path (after rewriting) won't resolve inside a sideloaded app.
+pub(crate) fn is_unresolved(load_path: &str, frameworks_dir: &Path) -> bool {
+    if strip_jailbreak_prefix(load_path) != load_path {
+        return true;
+    }
+
+    if let Some(relative) = load_path.strip_prefix("@rpath/") {
+        // swift runtime comes from the OS
+        if relative.starts_with("libswift") {
+            return false;
+        }
+
+        return !frameworks_dir.join(relative).exists();
+    }
+
+    if is_hooking_library(load_path) || known_target(load_path).is_some() {
+        return true;
+    }
+
+    // nothing from the OS lives in /Library, only jailbreak packages do
+    load_path.starts_with("/Library/")
+}
+
+fn is_hooking_library(load_path: &str) -> bool {
+    match load_path.strip_prefix("@rpath/") {
+        Some(relative) => HOOKING_RUNTIME_BINARIES.contains(&relative),
+        None => HOOKING_LIBRARIES.contains(&load_path),
+    }
+}
+
+// Bundled runtimes exporting the API of a hooking library, ElleKit and Substitute
+// both carry a substrate shim while libhooker only has its own `LH*` functions
+fn compatible_runtimes(load_path: &str) -> &'static [&'static str] {
+    match load_path.rsplit('/').next().unwrap_or(load_path) {
+        "CydiaSubstrate" | "libsubstrate.dylib" => &[
+            "CydiaSubstrate.framework/CydiaSubstrate",
+            "libellekit.dylib",
+            "libsubstrate.dylib",
+            "libsubstitute.dylib",
+        ],
+        "libellekit.dylib" => &["libellekit.dylib"],
+        "libhooker.dylib" => &["libhooker.dylib", "libellekit.dylib"],
+        "libsubstitute.dylib" | "libsubstitute.0.dylib" => &["libsubstitute.dylib"],
+        _ => &[],
+    }
+}
+
+fn strip_jailbreak_prefix(load_path: &str) -> &str {
+    JAILBREAK_PREFIXES
+        .iter()
+        .find_map(|prefix| load_path.strip_prefix(prefix))
+        .filter(|rest| rest.starts_with('/'))
+        .unwrap_or(load_path)
+}
+
+fn known_target(load_path: &str) -> Option<&'static str> {
+    if let Some(relative) = load_path.strip_prefix("@rpath/") {
+        let suffix = format!("/{}", relative);
+        return KNOWN_PATHS
+            .iter()
+            .find(|(path, _)| path.ends_with(&suffix))
+            .map(|(_, target)| *target);
+    }
+
+    KNOWN_PATHS
+        .iter()
+        .find(|(path, _)| *path == load_path)
+        .map(|(_, target)| *target)
+}
+
+/// Path relative to the app's `Frameworks` directory a jailbreak library would be copied to.
+fn bundled_relative_path(load_path: &str) -> Option<String> {
+    if let Some(framework) = load_path.strip_prefix("/Library/Frameworks/") {
+        return Some(framework.to_string());
+    }
+
+    for dir in ["/usr/lib/", "/Library/MobileSubstrate/DynamicLibraries/"] {
+        if let Some(name) = load_path.strip_prefix(dir) {
+            // we flatten dylibs into Frameworks, nested ones keep just the file name
+            return name.rsplit('/').next().map(str::to_string);
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frameworks(binaries: &[&str]) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("plume_paths_{}", uuid::Uuid::new_v4()));
+        for binary in binaries {
+            let path = dir.join(binary);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, b"").unwrap();
+        }
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn rewrites_substrate_to_compatible_runtime() {
+        let dir = frameworks(&["CydiaSubstrate.framework/CydiaSubstrate"]);
+        let cases = [
+            "/Library/Frameworks/CydiaSubstrate.framework/CydiaSubstrate",
+            "/var/jb/Library/Frameworks/CydiaSubstrate.framework/CydiaSubstrate",
+            "/var/jb/usr/lib/libsubstrate.dylib",
+        ];
+
+        for load_path in cases {
+            assert_eq!(
+                rewrite_target(load_path, &dir).unwrap().as_deref(),
+                Some("@rpath/CydiaSubstrate.framework/CydiaSubstrate"),
+                "{load_path}"
+            );
+        }
+        assert_eq!(
+            rewrite_target("@rpath/CydiaSubstrate.framework/CydiaSubstrate", &dir).unwrap(),
+            None
+        );
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn prefers_a_runtime_with_the_same_api() {
+        let dir = frameworks(&["CydiaSubstrate.framework/CydiaSubstrate", "libhooker.dylib"]);
+
+        assert_eq!(
+            rewrite_target("/usr/lib/libhooker.dylib", &dir)
+                .unwrap()
+                .as_deref(),
+            Some("@rpath/libhooker.dylib")
+        );
+        assert_eq!(
+            rewrite_target("/usr/lib/libsubstrate.dylib", &dir)
+                .unwrap()
+                .as_deref(),
+            Some("@rpath/CydiaSubstrate.framework/CydiaSubstrate")
+        );
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn refuses_incompatible_runtime() {
+        let dir = frameworks(&["libhooker.dylib"]);
+
+        assert!(matches!(
+            rewrite_target(
+                "/Library/Frameworks/CydiaSubstrate.framework/CydiaSubstrate",
+                &dir
+            ),
+            Err(Error::TweakIncompatibleHookingRuntime(_, _))
+        ));
+        assert!(matches!(
+            rewrite_target("/usr/lib/libsubstitute.dylib", &dir),
+            Err(Error::TweakIncompatibleHookingRuntime(_, _))
+        ));
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn leaves_hooking_library_without_runtime() {
+        let dir = frameworks(&[]);
+
+        assert_eq!(
+            rewrite_target("/usr/lib/libsubstrate.dylib", &dir).unwrap(),
+            None
+        );
+        assert!(is_unresolved("/usr/lib/libsubstrate.dylib", &dir));
+
+        std::fs::remove_dir_all(dir).ok();
+    }
+}